@@ -0,0 +1,119 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+
+/// Allowed extensions for uploaded avatar images.
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "webp"];
+
+/// Flat on-disk store for binary artifacts (avatars, attachments).
+///
+/// Files are named `<prefix>-<uuid>.<ext>` under a single root directory
+/// inside the app data dir; the database stores only the relative path.
+pub struct ArtifactStore {
+    root: PathBuf,
+}
+
+impl ArtifactStore {
+    pub fn new(root: PathBuf) -> AppResult<Self> {
+        fs::create_dir_all(&root)?;
+        Ok(Self { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Write `bytes` as a new artifact and return its path relative to
+    /// the store root.
+    pub fn store_bytes(&self, prefix: &str, extension: &str, bytes: &[u8]) -> AppResult<String> {
+        let extension = extension.trim_start_matches('.').to_ascii_lowercase();
+        let name = format!("{prefix}-{}.{extension}", Uuid::new_v4());
+        fs::write(self.root.join(&name), bytes)?;
+        Ok(name)
+    }
+
+    /// Like [`store_bytes`](Self::store_bytes) but restricted to image
+    /// extensions, for avatar uploads.
+    pub fn store_image(&self, prefix: &str, extension: &str, bytes: &[u8]) -> AppResult<String> {
+        let ext = extension.trim_start_matches('.').to_ascii_lowercase();
+        if !IMAGE_EXTENSIONS.contains(&ext.as_str()) {
+            return Err(AppError::InvalidArgument(format!(
+                "unsupported image extension: {extension}"
+            )));
+        }
+        self.store_bytes(prefix, &ext, bytes)
+    }
+
+    pub fn read(&self, name: &str) -> AppResult<Vec<u8>> {
+        fs::read(self.root.join(name)).map_err(Into::into)
+    }
+
+    pub fn remove(&self, name: &str) -> AppResult<()> {
+        fs::remove_file(self.root.join(name)).map_err(Into::into)
+    }
+}
+
+/// Longest extracted-text excerpt included in an executor prompt.
+pub const MAX_EXTRACTED_CHARS: usize = 16_000;
+
+/// Best-effort text extraction from an attachment, returning the text
+/// and whether it was truncated to [`MAX_EXTRACTED_CHARS`].
+///
+/// UTF-8 content is used as-is; binary formats get a placeholder note
+/// until dedicated extractors (PDF, etc.) exist, so the model at least
+/// knows an attachment was present.
+pub fn extract_text(filename: &str, bytes: &[u8]) -> (String, bool) {
+    let text = match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => format!(
+            "[binary attachment {filename}, {} bytes; no text extractor available]",
+            bytes.len()
+        ),
+    };
+    if text.chars().count() > MAX_EXTRACTED_CHARS {
+        let truncated: String = text.chars().take(MAX_EXTRACTED_CHARS).collect();
+        (truncated, true)
+    } else {
+        (text, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stores_and_reads_back_artifacts() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ArtifactStore::new(dir.path().to_path_buf()).unwrap();
+        let name = store.store_image("avatar", "png", b"fake-png").unwrap();
+        assert!(name.starts_with("avatar-") && name.ends_with(".png"));
+        assert_eq!(store.read(&name).unwrap(), b"fake-png");
+    }
+
+    #[test]
+    fn extracts_utf8_and_truncates_long_text() {
+        let (text, truncated) = extract_text("notes.txt", b"hello");
+        assert_eq!(text, "hello");
+        assert!(!truncated);
+
+        let long = "x".repeat(MAX_EXTRACTED_CHARS + 10);
+        let (text, truncated) = extract_text("big.txt", long.as_bytes());
+        assert_eq!(text.chars().count(), MAX_EXTRACTED_CHARS);
+        assert!(truncated);
+
+        let (text, truncated) = extract_text("doc.pdf", &[0xff, 0xfe, 0x00]);
+        assert!(text.contains("binary attachment doc.pdf"));
+        assert!(!truncated);
+    }
+
+    #[test]
+    fn rejects_non_image_extensions_for_avatars() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = ArtifactStore::new(dir.path().to_path_buf()).unwrap();
+        assert!(store.store_image("avatar", "exe", b"nope").is_err());
+    }
+}
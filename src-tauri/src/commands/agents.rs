@@ -0,0 +1,421 @@
+use serde_json::json;
+use tauri::State;
+
+use crate::discovery::{self, DiscoveredAgent};
+use crate::error::AppResult;
+use crate::framework_config::{self, FieldSpec};
+use crate::metrics;
+use crate::models::{Agent, AgentHistoryEntry, AgentStats, AgentStatus, TaskPriority};
+use crate::pricing;
+use crate::state::AppState;
+use crate::windows;
+
+#[tauri::command]
+pub fn create_agent(
+    state: State<'_, AppState>,
+    name: String,
+    model: String,
+    default_priority: Option<TaskPriority>,
+    framework: Option<String>,
+    framework_config: Option<serde_json::Value>,
+    timezone: Option<String>,
+) -> AppResult<Agent> {
+    metrics::timed(
+        &state.storage,
+        "create_agent",
+        json!({ "name": name, "model": model, "default_priority": default_priority }),
+        || {
+            let mut agent = Agent::new(name.clone(), model.clone());
+            agent.default_priority = default_priority.unwrap_or_default();
+            agent.framework = framework.clone();
+            if let Some(config) = &framework_config {
+                framework_config::validate(agent.framework.as_deref(), config)?;
+                agent.framework_config = config.clone();
+            }
+            if let Some(tz) = &timezone {
+                crate::scheduler::validate_timezone(tz)?;
+                agent.timezone = Some(tz.clone());
+            }
+            state.storage.create_agent(&agent)?;
+            Ok(agent)
+        },
+    )
+}
+
+/// Bulk-import a fleet of agents from a CSV file, returning the
+/// per-row report.
+#[tauri::command]
+pub fn import_agents_csv(
+    window: tauri::Window,
+    state: State<'_, AppState>,
+    path: String,
+) -> AppResult<Vec<crate::settings_io::CsvImportRow>> {
+    metrics::timed(
+        &state.storage,
+        "import_agents_csv",
+        json!({ "path": path }),
+        || {
+            let report = crate::settings_io::import_agents_csv(&state.storage, &path)?;
+            windows::broadcast(&window, &state.windows, "agents", json!({ "imported": true }));
+            Ok(report)
+        },
+    )
+}
+
+/// Scan this machine for running agent runtimes (Ollama, LangServe,
+/// MCP servers) and describe each as a ready-to-register agent.
+#[tauri::command]
+pub fn discover_local_agents(state: State<'_, AppState>) -> AppResult<Vec<DiscoveredAgent>> {
+    metrics::timed(&state.storage, "discover_local_agents", json!({}), || {
+        discovery::discover_local_agents(&state.storage)
+    })
+}
+
+/// One-click registration of a discovery result as a real agent.
+#[tauri::command]
+pub fn register_discovered_agent(
+    state: State<'_, AppState>,
+    discovered: DiscoveredAgent,
+) -> AppResult<Agent> {
+    metrics::timed(
+        &state.storage,
+        "register_discovered_agent",
+        json!({ "name": discovered.name, "framework": discovered.framework }),
+        || discovery::register(&state.storage, &discovered),
+    )
+}
+
+/// The config fields `framework` accepts, for rendering its form.
+#[tauri::command]
+pub fn get_framework_schema(framework: String) -> Vec<FieldSpec> {
+    framework_config::schema_for(&framework).to_vec()
+}
+
+/// Store an uploaded avatar image in the artifact store and attach it to
+/// the agent; passing no data clears the avatar back to the generated
+/// color identity.
+#[tauri::command]
+pub fn set_agent_avatar(
+    state: State<'_, AppState>,
+    agent_id: String,
+    data: Option<Vec<u8>>,
+    extension: Option<String>,
+) -> AppResult<Agent> {
+    metrics::timed(
+        &state.storage,
+        "set_agent_avatar",
+        json!({ "agent_id": agent_id, "bytes": data.as_ref().map(Vec::len) }),
+        || {
+            let agent = state.storage.get_agent(&agent_id)?;
+            let new_path = match (&data, &extension) {
+                (Some(bytes), Some(ext)) => {
+                    Some(state.artifacts.store_image("avatar", ext, bytes)?)
+                }
+                (None, _) => None,
+                (Some(_), None) => {
+                    return Err(crate::error::AppError::InvalidArgument(
+                        "avatar upload requires a file extension".into(),
+                    ))
+                }
+            };
+            if let Some(old) = &agent.avatar_path {
+                // Best-effort cleanup of the replaced image.
+                let _ = state.artifacts.remove(old);
+            }
+            state
+                .storage
+                .set_agent_avatar(&agent_id, new_path.as_deref())?;
+            state.storage.append_agent_history(
+                &agent_id,
+                "config_changed",
+                Some(&json!({ "field": "avatar", "set": new_path.is_some() })),
+            )?;
+            state.storage.get_agent(&agent_id)
+        },
+    )
+}
+
+/// Hard ceiling on agents returned in one IPC response.
+const MAX_AGENT_PAGE: u32 = 500;
+const DEFAULT_AGENT_PAGE: u32 = 200;
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AgentPage {
+    pub agents: Vec<Agent>,
+    /// Total agents in storage, so the UI can paginate responsibly.
+    pub total: i64,
+}
+
+/// Offset-paged agent roster in creation order.
+#[tauri::command]
+pub fn get_all_agents(
+    state: State<'_, AppState>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+) -> AppResult<AgentPage> {
+    metrics::timed(
+        &state.storage,
+        "get_all_agents",
+        json!({ "limit": limit, "offset": offset }),
+        || {
+            let limit = limit.unwrap_or(DEFAULT_AGENT_PAGE).min(MAX_AGENT_PAGE);
+            let (agents, total) = state
+                .storage
+                .get_agents_page(i64::from(limit), i64::from(offset.unwrap_or(0)))?;
+            Ok(AgentPage { agents, total })
+        },
+    )
+}
+
+#[tauri::command]
+pub fn pause_agent(
+    window: tauri::Window,
+    state: State<'_, AppState>,
+    agent_id: String,
+) -> AppResult<Agent> {
+    metrics::timed(
+        &state.storage,
+        "pause_agent",
+        json!({ "agent_id": agent_id }),
+        || {
+            state
+                .storage
+                .set_agent_status(&agent_id, AgentStatus::Paused)?;
+            state.storage.append_agent_history(&agent_id, "paused", None)?;
+            let agent = state.storage.get_agent(&agent_id)?;
+            windows::broadcast(&window, &state.windows, "agents", json!({ "agent": agent }));
+            Ok(agent)
+        },
+    )
+}
+
+#[tauri::command]
+pub fn resume_agent(
+    window: tauri::Window,
+    state: State<'_, AppState>,
+    agent_id: String,
+) -> AppResult<Agent> {
+    metrics::timed(
+        &state.storage,
+        "resume_agent",
+        json!({ "agent_id": agent_id }),
+        || {
+            state
+                .storage
+                .set_agent_status(&agent_id, AgentStatus::Idle)?;
+            state.storage.append_agent_history(&agent_id, "resumed", None)?;
+            let agent = state.storage.get_agent(&agent_id)?;
+            windows::broadcast(&window, &state.windows, "agents", json!({ "agent": agent }));
+            Ok(agent)
+        },
+    )
+}
+
+/// Move an agent to the trash. Soft delete only: its tasks, events and
+/// history survive, and [`restore_agent`] undoes it completely.
+#[tauri::command]
+pub fn delete_agent(
+    window: tauri::Window,
+    state: State<'_, AppState>,
+    agent_id: String,
+) -> AppResult<()> {
+    metrics::timed(
+        &state.storage,
+        "delete_agent",
+        json!({ "agent_id": agent_id }),
+        || {
+            state.storage.append_agent_history(&agent_id, "deleted", None)?;
+            state.storage.soft_delete_agent(&agent_id)?;
+            windows::broadcast(
+                &window,
+                &state.windows,
+                "agents",
+                json!({ "deleted": agent_id }),
+            );
+            Ok(())
+        },
+    )
+}
+
+/// A trashed agent and when it was trashed.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DeletedAgent {
+    pub agent: Agent,
+    pub deleted_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// The trash: soft-deleted agents, newest first.
+#[tauri::command]
+pub fn list_deleted_agents(state: State<'_, AppState>) -> AppResult<Vec<DeletedAgent>> {
+    metrics::timed(&state.storage, "list_deleted_agents", json!({}), || {
+        Ok(state
+            .storage
+            .list_deleted_agents()?
+            .into_iter()
+            .map(|(agent, deleted_at)| DeletedAgent { agent, deleted_at })
+            .collect())
+    })
+}
+
+/// Bring a trashed agent back, activity history and all.
+#[tauri::command]
+pub fn restore_agent(
+    window: tauri::Window,
+    state: State<'_, AppState>,
+    agent_id: String,
+) -> AppResult<Agent> {
+    metrics::timed(
+        &state.storage,
+        "restore_agent",
+        json!({ "agent_id": agent_id }),
+        || {
+            state.storage.restore_agent(&agent_id)?;
+            state.storage.append_agent_history(&agent_id, "restored", None)?;
+            let agent = state.storage.get_agent(&agent_id)?;
+            windows::broadcast(&window, &state.windows, "agents", json!({ "agent": agent }));
+            Ok(agent)
+        },
+    )
+}
+
+/// Permanently remove a trashed agent together with its tasks, events
+/// and history. Only works from the trash; there is no undo.
+#[tauri::command]
+pub fn purge_agent(
+    window: tauri::Window,
+    state: State<'_, AppState>,
+    agent_id: String,
+) -> AppResult<()> {
+    metrics::timed(
+        &state.storage,
+        "purge_agent",
+        json!({ "agent_id": agent_id }),
+        || {
+            state.storage.purge_agent(&agent_id)?;
+            windows::broadcast(
+                &window,
+                &state.windows,
+                "agents",
+                json!({ "purged": agent_id }),
+            );
+            Ok(())
+        },
+    )
+}
+
+/// Chronological "what has happened to this agent" feed: creation,
+/// pauses/resumes, config changes and incidents.
+#[tauri::command]
+pub fn get_agent_history(
+    state: State<'_, AppState>,
+    agent_id: String,
+) -> AppResult<Vec<AgentHistoryEntry>> {
+    metrics::timed(
+        &state.storage,
+        "get_agent_history",
+        json!({ "agent_id": agent_id }),
+        || state.storage.get_agent_history(&agent_id),
+    )
+}
+
+/// Provider-reported token usage summed over an agent's tasks.
+#[tauri::command]
+pub fn get_agent_stats(state: State<'_, AppState>, agent_id: String) -> AppResult<AgentStats> {
+    metrics::timed(
+        &state.storage,
+        "get_agent_stats",
+        json!({ "agent_id": agent_id }),
+        || state.storage.get_agent_stats(&agent_id),
+    )
+}
+
+/// Spend per task for an agent over the last `period_days` (default
+/// 30), at the recorded charges -- not re-priced retroactively.
+#[tauri::command]
+pub fn get_cost_breakdown(
+    state: State<'_, AppState>,
+    agent_id: String,
+    period_days: Option<u32>,
+) -> AppResult<pricing::CostBreakdown> {
+    metrics::timed(
+        &state.storage,
+        "get_cost_breakdown",
+        json!({ "agent_id": agent_id, "period_days": period_days }),
+        || {
+            state.storage.get_agent(&agent_id)?;
+            pricing::cost_breakdown(&state.storage, &agent_id, period_days.unwrap_or(30))
+        },
+    )
+}
+
+/// Re-discover the tools on every MCP server this agent declares and
+/// store them for execution-time use.
+#[tauri::command]
+pub fn refresh_mcp_tools(
+    state: State<'_, AppState>,
+    agent_id: String,
+) -> AppResult<Vec<crate::mcp::McpTool>> {
+    metrics::timed(
+        &state.storage,
+        "refresh_mcp_tools",
+        json!({ "agent_id": agent_id }),
+        || {
+            let agent = state.storage.get_agent(&agent_id)?;
+            crate::mcp::refresh_agent_tools(&state.storage, &agent)
+        },
+    )
+}
+
+/// The stored (last-discovered) tools available to an agent.
+#[tauri::command]
+pub fn get_mcp_tools(
+    state: State<'_, AppState>,
+    agent_id: String,
+) -> AppResult<Vec<crate::mcp::McpTool>> {
+    metrics::timed(
+        &state.storage,
+        "get_mcp_tools",
+        json!({ "agent_id": agent_id }),
+        || {
+            let agent = state.storage.get_agent(&agent_id)?;
+            state.storage.get_mcp_tools(&agent.mcp_servers)
+        },
+    )
+}
+
+/// Set (or clear, with `None`) the minimum severity of events stored
+/// for this agent's tasks; below-threshold chatter is dropped at write
+/// time.
+#[tauri::command]
+pub fn set_agent_min_severity(
+    window: tauri::Window,
+    state: State<'_, AppState>,
+    agent_id: String,
+    severity: Option<String>,
+) -> AppResult<()> {
+    metrics::timed(
+        &state.storage,
+        "set_agent_min_severity",
+        json!({ "agent_id": agent_id, "severity": severity }),
+        || {
+            state.storage.get_agent(&agent_id)?;
+            let key = format!(
+                "{}{agent_id}",
+                crate::storage::MIN_SEVERITY_SETTING_PREFIX
+            );
+            match severity {
+                Some(raw) => {
+                    let parsed = crate::models::EventSeverity::parse(&raw).ok_or_else(|| {
+                        crate::error::AppError::InvalidArgument(format!(
+                            "unknown severity {raw:?}; expected debug, info, warn or error"
+                        ))
+                    })?;
+                    state.storage.set_setting(&key, parsed.as_str())?;
+                }
+                None => state.storage.delete_setting(&key)?,
+            }
+            windows::broadcast(&window, &state.windows, "agents", json!({ "agent_id": agent_id }));
+            Ok(())
+        },
+    )
+}
@@ -0,0 +1,4 @@
+pub mod agents;
+pub mod settings;
+pub mod tasks;
+pub mod workspace;
@@ -0,0 +1,220 @@
+use serde_json::json;
+use tauri::State;
+
+use crate::api_tokens::{self, ApiToken, IssuedToken, TokenScope};
+use crate::email::{self, SmtpConfig};
+use crate::error::AppResult;
+use crate::metrics;
+use crate::models::SecretUsage;
+use crate::policy::Constitution;
+use crate::secrets;
+use crate::settings_io::{
+    self, AgentImportConflict, DuplicateResolution, ImportSummary, SettingsExport,
+};
+use crate::state::{AppState, BackendHealth};
+use crate::telemetry::{self, TelemetryPayload};
+
+#[tauri::command]
+pub fn export_settings(state: State<'_, AppState>) -> AppResult<SettingsExport> {
+    metrics::timed(&state.storage, "export_settings", json!({}), || {
+        settings_io::export_settings(&state.storage)
+    })
+}
+
+/// Imported agents that collide with existing ones (matched by
+/// name+framework), for the pre-import merge/duplicate prompt.
+#[tauri::command]
+pub fn plan_import(
+    state: State<'_, AppState>,
+    export: SettingsExport,
+) -> AppResult<Vec<AgentImportConflict>> {
+    metrics::timed(
+        &state.storage,
+        "plan_import",
+        json!({ "version": export.version, "agents": export.agents.len() }),
+        || settings_io::plan_agent_import(&state.storage, &export.agents),
+    )
+}
+
+#[tauri::command]
+pub fn import_settings(
+    state: State<'_, AppState>,
+    health: State<'_, BackendHealth>,
+    export: SettingsExport,
+    resolutions: Option<std::collections::BTreeMap<String, DuplicateResolution>>,
+) -> AppResult<()> {
+    metrics::timed(
+        &state.storage,
+        "import_settings",
+        json!({ "version": export.version, "keys": export.settings.len() }),
+        || {
+            // Imports overwrite live data, so snapshot first; a failed
+            // snapshot is logged but does not block the import.
+            if let Some(data_dir) = health.data_dir() {
+                if let Err(err) = crate::backup::create_backup(&state.storage, &data_dir) {
+                    tracing::warn!(%err, "pre-import backup failed");
+                }
+            }
+            settings_io::import_settings(
+                &state.storage,
+                &export,
+                &resolutions.unwrap_or_default(),
+            )
+        },
+    )
+}
+
+/// Validated, transactional import of an exported bundle. With
+/// `dry_run` nothing is written and the summary previews what would
+/// change; otherwise the bundle applies atomically and any error rolls
+/// the whole import back.
+#[tauri::command]
+pub fn import_data(
+    state: State<'_, AppState>,
+    health: State<'_, BackendHealth>,
+    export: SettingsExport,
+    resolutions: Option<std::collections::BTreeMap<String, DuplicateResolution>>,
+    dry_run: Option<bool>,
+) -> AppResult<ImportSummary> {
+    let dry_run = dry_run.unwrap_or(false);
+    metrics::timed(
+        &state.storage,
+        "import_data",
+        json!({ "version": export.version, "dry_run": dry_run }),
+        || {
+            if !dry_run {
+                if let Some(data_dir) = health.data_dir() {
+                    if let Err(err) = crate::backup::create_backup(&state.storage, &data_dir) {
+                        tracing::warn!(%err, "pre-import backup failed");
+                    }
+                }
+            }
+            settings_io::import_data(
+                &state.storage,
+                &export,
+                &resolutions.unwrap_or_default(),
+                dry_run,
+            )
+        },
+    )
+}
+
+/// Who accessed a secret name and when, so operators can confirm a key
+/// can be rotated safely. Values are never part of the audit.
+#[tauri::command]
+pub fn get_secret_usage(state: State<'_, AppState>, name: String) -> AppResult<Vec<SecretUsage>> {
+    metrics::timed(
+        &state.storage,
+        "get_secret_usage",
+        json!({ "name": name }),
+        || state.storage.get_secret_usage(&name),
+    )
+}
+
+/// Validate and atomically swap a secret's value, smoke-testing the
+/// provider when enabled. The value never reaches the log; only the
+/// name is recorded.
+#[tauri::command]
+pub fn rotate_secret(
+    state: State<'_, AppState>,
+    name: String,
+    new_value: String,
+) -> AppResult<()> {
+    metrics::timed(
+        &state.storage,
+        "rotate_secret",
+        json!({ "name": name }),
+        || secrets::rotate_secret(&state.storage, &name, &new_value),
+    )
+}
+
+/// The current workspace constitution (guard prompt), if one is set.
+#[tauri::command]
+pub fn get_constitution(state: State<'_, AppState>) -> AppResult<Option<Constitution>> {
+    metrics::timed(&state.storage, "get_constitution", json!({}), || {
+        Constitution::load(&state.storage)
+    })
+}
+
+/// Replace the workspace constitution, bumping its version.
+#[tauri::command]
+pub fn set_constitution(state: State<'_, AppState>, text: String) -> AppResult<Constitution> {
+    metrics::timed(
+        &state.storage,
+        "set_constitution",
+        json!({ "chars": text.len() }),
+        || Constitution::save(&state.storage, &text),
+    )
+}
+
+/// Issue a scoped API token for the local REST server. The response is
+/// the only time the plaintext exists outside the caller; storage and
+/// the metrics log only ever see the name and scopes.
+#[tauri::command]
+pub fn create_api_token(
+    state: State<'_, AppState>,
+    name: String,
+    scopes: Vec<TokenScope>,
+) -> AppResult<IssuedToken> {
+    metrics::timed(
+        &state.storage,
+        "create_api_token",
+        json!({ "name": name, "scopes": scopes }),
+        || api_tokens::create(&state.storage, &name, &scopes),
+    )
+}
+
+#[tauri::command]
+pub fn list_api_tokens(state: State<'_, AppState>) -> AppResult<Vec<ApiToken>> {
+    metrics::timed(&state.storage, "list_api_tokens", json!({}), || {
+        state.storage.get_api_tokens()
+    })
+}
+
+#[tauri::command]
+pub fn revoke_api_token(state: State<'_, AppState>, token_id: String) -> AppResult<()> {
+    metrics::timed(
+        &state.storage,
+        "revoke_api_token",
+        json!({ "token_id": token_id }),
+        || state.storage.revoke_api_token(&token_id),
+    )
+}
+
+/// Send a test message through the configured SMTP settings so the
+/// operator can confirm the channel before relying on alerts.
+#[tauri::command]
+pub fn send_test_email(state: State<'_, AppState>) -> AppResult<()> {
+    metrics::timed(&state.storage, "send_test_email", json!({}), || {
+        let config = SmtpConfig::load(&state.storage)?.ok_or_else(|| {
+            crate::error::AppError::InvalidArgument(
+                "email is not configured: set email.smtp_host, email.from and email.to".into(),
+            )
+        })?;
+        email::send(
+            &state.storage,
+            &config,
+            "Workspace agent test email",
+            "If you can read this, the email notification channel works.",
+        )
+    })
+}
+
+/// The exact telemetry payload that would be sent, so the opt-in
+/// decision can be made with the data in front of you.
+#[tauri::command]
+pub fn preview_telemetry_payload(state: State<'_, AppState>) -> AppResult<TelemetryPayload> {
+    metrics::timed(&state.storage, "preview_telemetry_payload", json!({}), || {
+        telemetry::preview_payload(&state.storage)
+    })
+}
+
+#[tauri::command]
+pub fn set_telemetry_enabled(state: State<'_, AppState>, enabled: bool) -> AppResult<()> {
+    metrics::timed(
+        &state.storage,
+        "set_telemetry_enabled",
+        json!({ "enabled": enabled }),
+        || telemetry::set_enabled(&state.storage, enabled),
+    )
+}
@@ -0,0 +1,812 @@
+use serde_json::json;
+use tauri::State;
+
+use crate::error::AppResult;
+use crate::metrics;
+use crate::models::{Agent, Approval, Schedule, Task, TaskEvent, TaskPriority, TaskTemplate};
+use crate::state::AppState;
+use crate::task_dispatch;
+use crate::templates;
+use crate::windows;
+
+#[tauri::command]
+pub fn dispatch(
+    window: tauri::Window,
+    state: State<'_, AppState>,
+    request: task_dispatch::DispatchRequest,
+) -> AppResult<Task> {
+    metrics::timed(
+        &state.storage,
+        "dispatch",
+        json!({
+            "agent_id": request.agent_id,
+            "title": request.title,
+            "prompt": request.prompt,
+            "priority": request.priority,
+            "tags": request.tags,
+        }),
+        || {
+            let task = task_dispatch::dispatch(&state.storage, &request)?;
+            windows::broadcast(&window, &state.windows, "tasks", json!({ "task": task }));
+            Ok(task)
+        },
+    )
+}
+
+/// Dispatch many tasks in one IPC call, with per-item results; see
+/// [`task_dispatch::dispatch_batch`].
+#[tauri::command]
+pub fn dispatch_tasks_batch(
+    window: tauri::Window,
+    state: State<'_, AppState>,
+    requests: Vec<task_dispatch::DispatchRequest>,
+) -> AppResult<Vec<task_dispatch::BatchDispatchResult>> {
+    metrics::timed(
+        &state.storage,
+        "dispatch_tasks_batch",
+        json!({ "count": requests.len() }),
+        || {
+            let results = task_dispatch::dispatch_batch(&state.storage, &requests);
+            for result in &results {
+                if let Some(task) = &result.task {
+                    windows::broadcast(&window, &state.windows, "tasks", json!({ "task": task }));
+                }
+            }
+            Ok(results)
+        },
+    )
+}
+
+/// Re-dispatch a failed or cancelled task without re-entering its
+/// instruction, optionally editing the prompt first.
+#[tauri::command]
+pub fn retry_task(
+    window: tauri::Window,
+    state: State<'_, AppState>,
+    task_id: String,
+    prompt: Option<String>,
+) -> AppResult<Task> {
+    metrics::timed(
+        &state.storage,
+        "retry_task",
+        json!({ "task_id": task_id, "prompt_edited": prompt.is_some() }),
+        || {
+            let task = task_dispatch::retry(&state.storage, &task_id, prompt.as_deref())?;
+            windows::broadcast(&window, &state.windows, "tasks", json!({ "task": task }));
+            Ok(task)
+        },
+    )
+}
+
+/// Hand the task to the worker pool and return immediately; progress
+/// arrives through events rather than the IPC response.
+#[tauri::command]
+pub fn execute_task(
+    window: tauri::Window,
+    state: State<'_, AppState>,
+    task_id: String,
+) -> AppResult<()> {
+    metrics::timed(
+        &state.storage,
+        "execute_task",
+        json!({ "task_id": task_id }),
+        || {
+            // Surface obvious mistakes synchronously before queueing.
+            state.storage.get_task(&task_id)?;
+            state.workers.enqueue(&task_id);
+            windows::broadcast(&window, &state.windows, "tasks", json!({ "queued": task_id }));
+            Ok(())
+        },
+    )
+}
+
+#[tauri::command]
+pub fn cancel_task(
+    window: tauri::Window,
+    state: State<'_, AppState>,
+    task_id: String,
+    mutation_token: Option<String>,
+) -> AppResult<windows::Mutation<Task>> {
+    metrics::timed(
+        &state.storage,
+        "cancel_task",
+        json!({ "task_id": task_id }),
+        || {
+            windows::reconcile(&window, &state.windows, mutation_token, || {
+                let task = task_dispatch::cancel(&state.storage, &task_id)?;
+                windows::broadcast(&window, &state.windows, "tasks", json!({ "task": task }));
+                Ok(task)
+            })
+        },
+    )
+}
+
+/// Release a step-mode task paused at its current thought or tool
+/// call.
+#[tauri::command]
+pub fn continue_task(state: State<'_, AppState>, task_id: String) -> AppResult<()> {
+    metrics::timed(
+        &state.storage,
+        "continue_task",
+        json!({ "task_id": task_id }),
+        || task_dispatch::continue_task(&state.storage, &task_id),
+    )
+}
+
+/// Approve the submitted plan of a plan-mode run so execution
+/// proceeds.
+#[tauri::command]
+pub fn approve_plan(state: State<'_, AppState>, task_id: String) -> AppResult<()> {
+    metrics::timed(
+        &state.storage,
+        "approve_plan",
+        json!({ "task_id": task_id }),
+        || task_dispatch::approve_plan(&state.storage, &task_id),
+    )
+}
+
+/// Accept a NeedsReview result, promoting the task to Completed.
+#[tauri::command]
+pub fn accept_result(
+    window: tauri::Window,
+    state: State<'_, AppState>,
+    task_id: String,
+) -> AppResult<Task> {
+    metrics::timed(
+        &state.storage,
+        "accept_result",
+        json!({ "task_id": task_id }),
+        || {
+            let task = task_dispatch::accept_result(&state.storage, &task_id)?;
+            // Acceptance is the review-gated equivalent of completion:
+            // dependents waiting on this task become runnable now.
+            for dependent in state.storage.ready_dependents(&task_id)? {
+                state.workers.enqueue(&dependent.id);
+            }
+            windows::broadcast(&window, &state.windows, "tasks", json!({ "task": task }));
+            Ok(task)
+        },
+    )
+}
+
+/// Send a NeedsReview result back with feedback; the task is
+/// re-dispatched with the feedback appended to its instruction.
+#[tauri::command]
+pub fn request_changes(
+    window: tauri::Window,
+    state: State<'_, AppState>,
+    task_id: String,
+    feedback: String,
+) -> AppResult<Task> {
+    metrics::timed(
+        &state.storage,
+        "request_changes",
+        json!({ "task_id": task_id }),
+        || {
+            let task = task_dispatch::request_changes(&state.storage, &task_id, &feedback)?;
+            state.workers.enqueue(&task.id);
+            windows::broadcast(&window, &state.windows, "tasks", json!({ "task": task }));
+            Ok(task)
+        },
+    )
+}
+
+/// Queue an operator message for a running task; the executor applies
+/// it at its next turn.
+#[tauri::command]
+pub fn send_task_message(
+    state: State<'_, AppState>,
+    task_id: String,
+    message: String,
+) -> AppResult<()> {
+    metrics::timed(
+        &state.storage,
+        "send_task_message",
+        json!({ "task_id": task_id }),
+        || task_dispatch::send_message(&state.storage, &task_id, &message),
+    )
+}
+
+/// Tool calls across running tasks awaiting operator approval.
+#[tauri::command]
+pub fn get_pending_tool_calls(
+    state: State<'_, AppState>,
+) -> AppResult<Vec<task_dispatch::PendingToolCall>> {
+    metrics::timed(&state.storage, "get_pending_tool_calls", json!({}), || {
+        task_dispatch::pending_tool_calls(&state.storage)
+    })
+}
+
+/// Approve or deny a task's pending tool call, recording the decision
+/// on the approvals audit trail.
+#[tauri::command]
+pub fn resolve_tool_call(
+    state: State<'_, AppState>,
+    task_id: String,
+    approved: bool,
+    approver: String,
+    comment: Option<String>,
+) -> AppResult<()> {
+    metrics::timed(
+        &state.storage,
+        "resolve_tool_call",
+        json!({ "task_id": task_id, "approved": approved, "approver": approver }),
+        || {
+            task_dispatch::resolve_tool_call(
+                &state.storage,
+                &task_id,
+                approved,
+                &approver,
+                comment.as_deref(),
+            )
+        },
+    )
+}
+
+/// The approvals audit trail for one task, oldest first.
+#[tauri::command]
+pub fn get_task_approvals(
+    state: State<'_, AppState>,
+    task_id: String,
+) -> AppResult<Vec<crate::models::Approval>> {
+    metrics::timed(
+        &state.storage,
+        "get_task_approvals",
+        json!({ "task_id": task_id }),
+        || state.storage.get_task_approvals(&task_id),
+    )
+}
+
+/// Answer a task's pending `ask_user` question so execution resumes.
+#[tauri::command]
+pub fn provide_task_input(
+    state: State<'_, AppState>,
+    task_id: String,
+    answer: String,
+) -> AppResult<()> {
+    metrics::timed(
+        &state.storage,
+        "provide_task_input",
+        json!({ "task_id": task_id }),
+        || task_dispatch::provide_input(&state.storage, &task_id, &answer),
+    )
+}
+
+/// Hard ceiling on tasks returned in one IPC response.
+const MAX_TASK_PAGE: u32 = 500;
+const DEFAULT_TASK_PAGE: u32 = 200;
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TaskPage {
+    pub tasks: Vec<Task>,
+    /// Total tasks in storage, so the UI can paginate responsibly.
+    pub total: i64,
+}
+
+/// Offset-paged task listing, newest first. With thousands of
+/// historical tasks the old return-everything shape made every board
+/// refresh sluggish.
+#[tauri::command]
+pub fn get_all_tasks(
+    state: State<'_, AppState>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+) -> AppResult<TaskPage> {
+    metrics::timed(
+        &state.storage,
+        "get_all_tasks",
+        json!({ "limit": limit, "offset": offset }),
+        || {
+            let limit = limit.unwrap_or(DEFAULT_TASK_PAGE).min(MAX_TASK_PAGE);
+            let (tasks, total) = state
+                .storage
+                .get_tasks_page(i64::from(limit), i64::from(offset.unwrap_or(0)))?;
+            Ok(TaskPage { tasks, total })
+        },
+    )
+}
+
+/// Hard ceiling on events returned in one IPC response; larger reads
+/// must paginate or use [`stream_task_events`].
+const MAX_EVENT_PAGE: u32 = 500;
+const DEFAULT_EVENT_PAGE: u32 = 100;
+/// Batch size for the chunked streaming path.
+const EVENT_STREAM_CHUNK: usize = 200;
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct EventPage {
+    pub events: Vec<TaskEvent>,
+    /// Total events on the task, so the UI can paginate responsibly.
+    pub total: i64,
+}
+
+/// Memory-bounded event read: the limit is clamped to a hard cap and
+/// the response carries total-count metadata.
+#[tauri::command]
+pub fn get_task_events(
+    state: State<'_, AppState>,
+    task_id: String,
+    limit: Option<u32>,
+    offset: Option<u32>,
+) -> AppResult<EventPage> {
+    metrics::timed(
+        &state.storage,
+        "get_task_events",
+        json!({ "task_id": task_id, "limit": limit, "offset": offset }),
+        || {
+            let limit = limit.unwrap_or(DEFAULT_EVENT_PAGE).min(MAX_EVENT_PAGE);
+            let (events, total) = state.storage.get_task_events_bounded(
+                &task_id,
+                i64::from(limit),
+                i64::from(offset.unwrap_or(0)),
+            )?;
+            Ok(EventPage { events, total })
+        },
+    )
+}
+
+/// Filtered event query: kind, task, agent and a `[start, end)` time
+/// window, all optional and pushed down into SQL.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn query_events(
+    state: State<'_, AppState>,
+    event_type: Option<String>,
+    task_id: Option<String>,
+    agent_id: Option<String>,
+    start: Option<chrono::DateTime<chrono::Utc>>,
+    end: Option<chrono::DateTime<chrono::Utc>>,
+    limit: Option<u32>,
+) -> AppResult<Vec<TaskEvent>> {
+    state
+        .storage
+        .run(move |storage| {
+            metrics::timed(
+                storage,
+                "query_events",
+                json!({
+                    "event_type": event_type,
+                    "task_id": task_id,
+                    "agent_id": agent_id,
+                    "limit": limit,
+                }),
+                || {
+                    let limit = limit.unwrap_or(DEFAULT_EVENT_PAGE).min(MAX_EVENT_PAGE);
+                    storage.query_events(
+                        event_type.as_deref(),
+                        task_id.as_deref(),
+                        agent_id.as_deref(),
+                        start,
+                        end,
+                        i64::from(limit),
+                    )
+                },
+            )
+        })
+        .await
+}
+
+/// Everything the task detail view renders, aggregated so one IPC call
+/// suffices: the task, its agent, the ordered event log, the approval
+/// audit trail and any tool call still waiting on a decision.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct TaskTimeline {
+    pub task: Task,
+    /// `None` when the agent has since been deleted.
+    pub agent: Option<Agent>,
+    pub events: Vec<TaskEvent>,
+    pub approvals: Vec<Approval>,
+    pub pending_tool_calls: Vec<task_dispatch::PendingToolCall>,
+}
+
+#[tauri::command]
+pub async fn get_task_timeline(
+    state: State<'_, AppState>,
+    task_id: String,
+) -> AppResult<TaskTimeline> {
+    state
+        .storage
+        .run(move |storage| {
+            metrics::timed(
+                storage,
+                "get_task_timeline",
+                json!({ "task_id": task_id }),
+                || {
+                    let task = storage.get_task(&task_id)?;
+                    let agent = storage.get_agent(&task.agent_id).ok();
+                    let pending_tool_calls = task_dispatch::pending_tool_calls(storage)?
+                        .into_iter()
+                        .filter(|call| call.task_id == task_id)
+                        .collect();
+                    Ok(TaskTimeline {
+                        events: storage.get_task_events(&task_id)?,
+                        approvals: storage.get_task_approvals(&task_id)?,
+                        pending_tool_calls,
+                        task,
+                        agent,
+                    })
+                },
+            )
+        })
+        .await
+}
+
+/// One cursor page of a task's events, newest first, for infinite
+/// scroll.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct EventCursorPage {
+    pub events: Vec<TaskEvent>,
+    /// Pass back as the next call's `before_id`; `None` when the
+    /// history is exhausted.
+    pub next_cursor: Option<i64>,
+}
+
+/// Cursor-paged event read for infinite scroll: `before_id` /
+/// `before_timestamp` are exclusive upper bounds from the previous
+/// page, so scrolling through millions of events never loads them all.
+#[tauri::command]
+pub async fn get_task_events_page(
+    state: State<'_, AppState>,
+    task_id: String,
+    before_id: Option<i64>,
+    before_timestamp: Option<chrono::DateTime<chrono::Utc>>,
+    page_size: Option<u32>,
+) -> AppResult<EventCursorPage> {
+    state.storage.run(move |storage| metrics::timed(
+        storage,
+        "get_task_events_page",
+        json!({ "task_id": task_id, "before_id": before_id, "page_size": page_size }),
+        || {
+            let limit = page_size.unwrap_or(DEFAULT_EVENT_PAGE).min(MAX_EVENT_PAGE);
+            // Over-fetch one row to detect whether another page exists.
+            let mut events = storage.get_task_events_page(
+                &task_id,
+                before_id,
+                before_timestamp,
+                i64::from(limit) + 1,
+            )?;
+            let next_cursor = if events.len() as i64 > i64::from(limit) {
+                events.truncate(limit as usize);
+                events.last().map(|e| e.id)
+            } else {
+                None
+            };
+            Ok(EventCursorPage { events, next_cursor })
+        },
+    ))
+    .await
+}
+
+/// Stream a task's full event history to the calling window in bounded
+/// chunks on `workspace://event-chunk`, instead of materializing one
+/// giant IPC response. Returns the total number of events streamed.
+#[tauri::command]
+pub fn stream_task_events(
+    window: tauri::Window,
+    state: State<'_, AppState>,
+    task_id: String,
+) -> AppResult<i64> {
+    metrics::timed(
+        &state.storage,
+        "stream_task_events",
+        json!({ "task_id": task_id, "window": window.label() }),
+        || {
+            use tauri::Emitter;
+            let mut offset = 0i64;
+            let mut total;
+            loop {
+                let (events, t) = state.storage.get_task_events_bounded(
+                    &task_id,
+                    EVENT_STREAM_CHUNK as i64,
+                    offset,
+                )?;
+                total = t;
+                let count = events.len() as i64;
+                if count == 0 {
+                    break;
+                }
+                window
+                    .emit(
+                        "workspace://event-chunk",
+                        json!({ "task_id": task_id, "events": events, "total": total }),
+                    )
+                    .map_err(|err| {
+                        crate::error::AppError::InvalidArgument(format!(
+                            "failed to emit event chunk: {err}"
+                        ))
+                    })?;
+                offset += count;
+                if offset >= total {
+                    break;
+                }
+            }
+            Ok(offset)
+        },
+    )
+}
+
+/// Persist a kanban drag: place the task at `position` in `column`.
+///
+/// `expected_revision` is the board revision the window last saw for
+/// this task; a concurrent move from another window wins and the stale
+/// drag is rejected with EditConflict.
+///
+/// Drags are applied optimistically by the frontend; the response and
+/// the follow-up `reconcile` broadcast confirm or roll back the token.
+#[tauri::command]
+pub fn move_task(
+    window: tauri::Window,
+    state: State<'_, AppState>,
+    task_id: String,
+    column: String,
+    position: i64,
+    expected_revision: Option<u64>,
+    mutation_token: Option<String>,
+) -> AppResult<windows::Mutation<Task>> {
+    metrics::timed(
+        &state.storage,
+        "move_task",
+        json!({ "task_id": task_id, "column": column, "position": position }),
+        || {
+            windows::reconcile(&window, &state.windows, mutation_token, || {
+                let revision = state
+                    .windows
+                    .check_and_bump(&format!("board:{task_id}"), expected_revision)?;
+                let task = state.storage.move_task(&task_id, &column, position)?;
+                windows::broadcast(
+                    &window,
+                    &state.windows,
+                    "board",
+                    json!({ "task": task, "revision": revision }),
+                );
+                Ok(task)
+            })
+        },
+    )
+}
+
+/// All tasks in board order (column, then position) for the kanban view.
+#[tauri::command]
+pub fn get_board(state: State<'_, AppState>) -> AppResult<Vec<Task>> {
+    metrics::timed(&state.storage, "get_board", json!({}), || {
+        state.storage.get_board()
+    })
+}
+
+/// Store an attachment in the artifact store, extract its text for
+/// prompt inclusion, and return the artifact name to pass to dispatch.
+#[tauri::command]
+pub fn upload_attachment(
+    state: State<'_, AppState>,
+    filename: String,
+    data: Vec<u8>,
+) -> AppResult<String> {
+    metrics::timed(
+        &state.storage,
+        "upload_attachment",
+        json!({ "filename": filename, "bytes": data.len() }),
+        || {
+            let extension = filename.rsplit('.').next().unwrap_or("bin");
+            let artifact = state.artifacts.store_bytes("attachment", extension, &data)?;
+            let (text, truncated) = crate::artifacts::extract_text(&filename, &data);
+            state
+                .storage
+                .register_attachment(&artifact, &filename, &text, truncated)?;
+            Ok(artifact)
+        },
+    )
+}
+
+/// Invoke a discovered MCP tool for a running task. The call and its
+/// outcome land in the task's event stream.
+#[tauri::command]
+pub fn invoke_mcp_tool(
+    state: State<'_, AppState>,
+    task_id: String,
+    server: String,
+    tool: String,
+    arguments: serde_json::Value,
+) -> AppResult<serde_json::Value> {
+    metrics::timed(
+        &state.storage,
+        "invoke_mcp_tool",
+        json!({ "task_id": task_id, "server": server, "tool": tool }),
+        || crate::mcp::invoke_tool(&state.storage, &task_id, &server, &tool, arguments.clone()),
+    )
+}
+
+/// The agent config that was in effect when this task last ran.
+#[tauri::command]
+pub fn get_task_config_snapshot(
+    state: State<'_, AppState>,
+    task_id: String,
+) -> AppResult<Option<serde_json::Value>> {
+    metrics::timed(
+        &state.storage,
+        "get_task_config_snapshot",
+        json!({ "task_id": task_id }),
+        || state.storage.get_task_config_snapshot(&task_id),
+    )
+}
+
+/// Maximum bytes one read_result_range call may return.
+const MAX_RESULT_RANGE: u64 = 1024 * 1024;
+
+/// Read a byte range of an offloaded task result, so the UI can page
+/// through multi-MB outputs without one giant IPC payload. Returns the
+/// (lossily decoded) text of the range.
+#[tauri::command]
+pub fn read_result_range(
+    state: State<'_, AppState>,
+    task_id: String,
+    offset: u64,
+    len: u64,
+) -> AppResult<String> {
+    metrics::timed(
+        &state.storage,
+        "read_result_range",
+        json!({ "task_id": task_id, "offset": offset, "len": len }),
+        || {
+            use std::io::{Read, Seek, SeekFrom};
+            let task = state.storage.get_task(&task_id)?;
+            let artifact = task.result_artifact.ok_or_else(|| {
+                crate::error::AppError::InvalidArgument(format!(
+                    "task {task_id} has no offloaded result"
+                ))
+            })?;
+            let path = state.storage.result_artifact_path(&artifact)?;
+            let mut file = std::fs::File::open(path)?;
+            file.seek(SeekFrom::Start(offset))?;
+            let mut buf = vec![0u8; len.min(MAX_RESULT_RANGE) as usize];
+            let mut read = 0;
+            while read < buf.len() {
+                let n = file.read(&mut buf[read..])?;
+                if n == 0 {
+                    break;
+                }
+                read += n;
+            }
+            buf.truncate(read);
+            Ok(String::from_utf8_lossy(&buf).into_owned())
+        },
+    )
+}
+
+/// Register a recurring dispatch of the given task template. The cron
+/// expression is validated before anything is stored.
+#[tauri::command]
+pub fn create_schedule(
+    state: State<'_, AppState>,
+    agent_id: String,
+    title: String,
+    prompt: String,
+    cron: String,
+) -> AppResult<Schedule> {
+    metrics::timed(
+        &state.storage,
+        "create_schedule",
+        json!({ "agent_id": agent_id, "cron": cron }),
+        || {
+            crate::scheduler::validate_cron(&cron)?;
+            state.storage.create_schedule(&agent_id, &title, &prompt, &cron)
+        },
+    )
+}
+
+#[tauri::command]
+pub fn list_schedules(state: State<'_, AppState>) -> AppResult<Vec<Schedule>> {
+    metrics::timed(&state.storage, "list_schedules", json!({}), || {
+        state.storage.get_schedules()
+    })
+}
+
+#[tauri::command]
+pub fn pause_schedule(
+    state: State<'_, AppState>,
+    schedule_id: String,
+    paused: bool,
+) -> AppResult<()> {
+    metrics::timed(
+        &state.storage,
+        "pause_schedule",
+        json!({ "schedule_id": schedule_id, "paused": paused }),
+        || state.storage.set_schedule_paused(&schedule_id, paused),
+    )
+}
+
+#[tauri::command]
+pub fn delete_schedule(state: State<'_, AppState>, schedule_id: String) -> AppResult<()> {
+    metrics::timed(
+        &state.storage,
+        "delete_schedule",
+        json!({ "schedule_id": schedule_id }),
+        || state.storage.delete_schedule(&schedule_id),
+    )
+}
+
+/// Save a reusable instruction. The prompt may carry `{{date}}`-style
+/// parameters filled at dispatch time.
+#[tauri::command]
+pub fn create_task_template(
+    state: State<'_, AppState>,
+    name: String,
+    title: String,
+    prompt: String,
+    agent_id: Option<String>,
+    priority: Option<TaskPriority>,
+    tags: Option<Vec<String>>,
+) -> AppResult<TaskTemplate> {
+    metrics::timed(
+        &state.storage,
+        "create_task_template",
+        json!({ "name": name }),
+        || {
+            let template = TaskTemplate {
+                id: uuid::Uuid::new_v4().to_string(),
+                name: name.clone(),
+                title,
+                prompt,
+                agent_id,
+                priority,
+                tags: tags.unwrap_or_default(),
+                created_at: chrono::Utc::now(),
+            };
+            state.storage.create_task_template(&template)?;
+            Ok(template)
+        },
+    )
+}
+
+#[tauri::command]
+pub fn list_task_templates(state: State<'_, AppState>) -> AppResult<Vec<TaskTemplate>> {
+    metrics::timed(&state.storage, "list_task_templates", json!({}), || {
+        state.storage.get_task_templates()
+    })
+}
+
+#[tauri::command]
+pub fn delete_task_template(state: State<'_, AppState>, template_id: String) -> AppResult<()> {
+    metrics::timed(
+        &state.storage,
+        "delete_task_template",
+        json!({ "template_id": template_id }),
+        || state.storage.delete_task_template(&template_id),
+    )
+}
+
+/// Dispatch a saved template in one call: parameters are substituted
+/// into title and prompt, and the template's defaults apply unless the
+/// caller overrides the target agent.
+#[tauri::command]
+pub fn dispatch_from_template(
+    window: tauri::Window,
+    state: State<'_, AppState>,
+    template_id: String,
+    agent_id: Option<String>,
+    params: Option<std::collections::BTreeMap<String, String>>,
+) -> AppResult<Task> {
+    metrics::timed(
+        &state.storage,
+        "dispatch_from_template",
+        json!({ "template_id": template_id, "agent_id": agent_id }),
+        || {
+            let template = state.storage.get_task_template(&template_id)?;
+            let agent_id = agent_id.or(template.agent_id).ok_or_else(|| {
+                crate::error::AppError::InvalidArgument(
+                    "template has no default agent; pass one explicitly".into(),
+                )
+            })?;
+            let params = params.unwrap_or_default();
+            let mut request = task_dispatch::DispatchRequest::new(
+                agent_id,
+                templates::substitute_params(&template.title, &params),
+                templates::substitute_params(&template.prompt, &params),
+            );
+            request.priority = template.priority;
+            request.tags = template.tags;
+            let task = task_dispatch::dispatch(&state.storage, &request)?;
+            windows::broadcast(&window, &state.windows, "tasks", json!({ "task": task }));
+            Ok(task)
+        },
+    )
+}
@@ -0,0 +1,539 @@
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use tauri::State;
+
+use crate::backup;
+use crate::diagnostics::{self, DiagnosticCheck};
+use crate::digest::{self, ActivityDigest};
+use crate::error::{AppError, AppResult};
+use crate::feed::{self, FeedPage, FeedQuery};
+use crate::maintenance::{self, MaintenanceSummary};
+use crate::status_page::{self, StatusSnapshot};
+use crate::metrics::{self, MetricsQuery, MetricsSeries};
+use crate::state::{AppState, BackendHealth};
+use crate::windows;
+
+#[tauri::command]
+pub fn generate_digest(
+    state: State<'_, AppState>,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    post_to_slack: Option<bool>,
+) -> AppResult<ActivityDigest> {
+    metrics::timed(
+        &state.storage,
+        "generate_digest",
+        json!({ "start": start, "end": end, "post_to_slack": post_to_slack }),
+        || {
+            let digest = digest::generate(&state.storage, &state.artifacts, start, end)?;
+            if post_to_slack.unwrap_or(false) {
+                digest::post_to_slack(&state.storage, &digest)?;
+            }
+            Ok(digest)
+        },
+    )
+}
+
+/// One page of the merged, display-ready activity feed for the home
+/// screen: events across all agents, grouped and collapsed server-side.
+#[tauri::command]
+pub async fn get_activity_feed(
+    state: State<'_, AppState>,
+    query: FeedQuery,
+) -> AppResult<FeedPage> {
+    state
+        .storage
+        .run(move |storage| {
+            metrics::timed(
+                storage,
+                "get_activity_feed",
+                json!({ "cursor": query.cursor, "limit": query.limit, "agent_id": query.agent_id }),
+                || feed::get_activity_feed(storage, &query),
+            )
+        })
+        .await
+}
+
+/// The stored self-diagnostics report from the most recent startup.
+#[tauri::command]
+pub fn get_startup_diagnostics(state: State<'_, AppState>) -> AppResult<Vec<DiagnosticCheck>> {
+    metrics::timed(&state.storage, "get_startup_diagnostics", json!({}), || {
+        diagnostics::get_startup_diagnostics(&state.storage)
+    })
+}
+
+/// Register the calling window's update subscriptions; mutations made in
+/// other windows are then pushed to it, scoped to these topics.
+#[tauri::command]
+pub fn subscribe_window(
+    window: tauri::Window,
+    state: State<'_, AppState>,
+    topics: Vec<String>,
+) -> AppResult<()> {
+    metrics::timed(
+        &state.storage,
+        "subscribe_window",
+        json!({ "window": window.label(), "topics": topics }),
+        || {
+            state.windows.subscribe(window.label(), topics.clone());
+            Ok(())
+        },
+    )
+}
+
+/// Stable JSON query endpoint over the metrics table for dashboards.
+#[tauri::command]
+pub async fn query_metrics(
+    state: State<'_, AppState>,
+    query: MetricsQuery,
+) -> AppResult<MetricsSeries> {
+    state
+        .storage
+        .run(move |storage| {
+            metrics::timed(
+                storage,
+                "query_metrics",
+                json!({ "expr": query.expr, "step_seconds": query.step_seconds }),
+                || metrics::query_metrics(storage, &query),
+            )
+        })
+        .await
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthReport {
+    pub degraded: bool,
+    /// Deferred subsystem -> whether its background init has finished.
+    pub subsystems: std::collections::HashMap<String, bool>,
+}
+
+/// Backend health plus per-subsystem readiness of deferred init. Safe
+/// to call from the splash screen: works even before (or without)
+/// [`AppState`] existing.
+#[tauri::command]
+pub fn get_health(app: tauri::AppHandle, health: State<'_, BackendHealth>) -> HealthReport {
+    use tauri::Manager;
+    HealthReport {
+        degraded: health.error().is_some(),
+        subsystems: app
+            .try_state::<AppState>()
+            .map(|state| state.readiness.snapshot())
+            .unwrap_or_default(),
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BackendStatus {
+    pub degraded: bool,
+    pub error: Option<String>,
+}
+
+/// Whether the backend came up; polled by the frontend before routing
+/// to the normal UI or the degraded-startup screen. Deliberately does
+/// not touch [`AppState`], which may not exist.
+#[tauri::command]
+pub fn get_backend_status(health: State<'_, BackendHealth>) -> BackendStatus {
+    let error = health.error();
+    BackendStatus {
+        degraded: error.is_some(),
+        error,
+    }
+}
+
+/// Move the database to a new location (external drive, synced folder).
+/// The copy is a consistent snapshot taken while the old database is
+/// still live; the old file is left behind untouched as a fallback.
+/// Takes effect on the next launch via the same override file the
+/// recovery flow writes -- the running app keeps using the old path,
+/// so the frontend must prompt for a restart (as with encryption).
+#[tauri::command]
+pub fn migrate_storage(
+    state: State<'_, AppState>,
+    health: State<'_, BackendHealth>,
+    new_path: String,
+) -> AppResult<()> {
+    let data_dir = health
+        .data_dir()
+        .ok_or_else(|| AppError::InvalidArgument("data dir unknown".into()))?;
+    let new_path = PathBuf::from(new_path);
+    if new_path == crate::resolve_db_path(&data_dir) {
+        return Err(AppError::InvalidArgument(
+            "database already lives at that path".into(),
+        ));
+    }
+    if new_path.exists() {
+        return Err(AppError::InvalidArgument(
+            "refusing to overwrite an existing file; pick a fresh path".into(),
+        ));
+    }
+    if let Some(parent) = new_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    state.storage.snapshot_to(&new_path)?;
+    crate::write_db_path_override(&data_dir, &new_path)?;
+    Ok(())
+}
+
+/// Recovery action: retry initialization against a different database
+/// path (e.g. on a healthy disk) and persist the choice for future
+/// startups.
+#[tauri::command]
+pub fn recover_with_db_path(
+    app: tauri::AppHandle,
+    health: State<'_, BackendHealth>,
+    db_path: String,
+) -> AppResult<()> {
+    let data_dir = health
+        .data_dir()
+        .ok_or_else(|| AppError::InvalidArgument("data dir unknown".into()))?;
+    crate::init_backend(&app, &data_dir, &PathBuf::from(&db_path))?;
+    crate::write_db_path_override(&data_dir, &PathBuf::from(&db_path))?;
+    health.clear_error();
+    Ok(())
+}
+
+/// Recovery action: replace the broken database with a backup copy and
+/// retry initialization.
+#[tauri::command]
+pub fn recover_restore_backup(
+    app: tauri::AppHandle,
+    health: State<'_, BackendHealth>,
+    backup_path: String,
+) -> AppResult<()> {
+    let data_dir = health
+        .data_dir()
+        .ok_or_else(|| AppError::InvalidArgument("data dir unknown".into()))?;
+    let db_path = crate::resolve_db_path(&data_dir);
+    std::fs::copy(&backup_path, &db_path)?;
+    crate::init_backend(&app, &data_dir, &db_path)?;
+    health.clear_error();
+    Ok(())
+}
+
+/// Every database snapshot in the rotation, newest first.
+#[tauri::command]
+pub fn list_backups(
+    state: State<'_, AppState>,
+    health: State<'_, BackendHealth>,
+) -> AppResult<Vec<backup::BackupInfo>> {
+    metrics::timed(&state.storage, "list_backups", json!({}), || {
+        let data_dir = health
+            .data_dir()
+            .ok_or_else(|| AppError::InvalidArgument("data dir unknown".into()))?;
+        backup::list_backups(&data_dir)
+    })
+}
+
+/// Replace the live database with a snapshot from the rotation. The
+/// pre-restore state is snapshotted first so a mistaken restore is
+/// itself recoverable. The live file is never overwritten while its
+/// connection is open: the backup is copied to a fresh path and the
+/// override points the next launch at it, so the frontend must prompt
+/// for a restart (as with encryption and migration).
+#[tauri::command]
+pub fn restore_backup(
+    state: State<'_, AppState>,
+    health: State<'_, BackendHealth>,
+    id: String,
+) -> AppResult<()> {
+    let data_dir = health
+        .data_dir()
+        .ok_or_else(|| AppError::InvalidArgument("data dir unknown".into()))?;
+    let source = backup::backup_path(&data_dir, &id)?;
+    backup::create_backup(&state.storage, &data_dir)?;
+    let restored = data_dir.join(format!(
+        "workspace-restored-{}.db",
+        Utc::now().format("%Y%m%d-%H%M%S")
+    ));
+    std::fs::copy(&source, &restored)?;
+    crate::write_db_path_override(&data_dir, &restored)?;
+    Ok(())
+}
+
+/// Occasional lookups against cold-storage archived tasks.
+#[tauri::command]
+pub async fn query_archive(
+    state: State<'_, AppState>,
+    title_contains: Option<String>,
+    limit: Option<u32>,
+) -> AppResult<Vec<crate::models::Task>> {
+    state
+        .storage
+        .run(move |storage| {
+            metrics::timed(storage, "query_archive", json!({}), || {
+                storage.query_archive(title_contains.as_deref(), limit.unwrap_or(50).min(500))
+            })
+        })
+        .await
+}
+
+/// Incident groups of correlated failures, newest first.
+#[tauri::command]
+pub fn list_incidents(
+    state: State<'_, AppState>,
+) -> AppResult<Vec<crate::incidents::Incident>> {
+    metrics::timed(&state.storage, "list_incidents", json!({}), || {
+        state.storage.list_incidents()
+    })
+}
+
+/// Close an incident with a resolution note.
+#[tauri::command]
+pub fn resolve_incident(
+    state: State<'_, AppState>,
+    incident_id: String,
+    resolution: String,
+) -> AppResult<()> {
+    metrics::timed(
+        &state.storage,
+        "resolve_incident",
+        json!({ "incident_id": incident_id }),
+        || state.storage.resolve_incident(&incident_id, &resolution),
+    )
+}
+
+/// Kick off a maintenance pass immediately (the nightly scheduler runs
+/// the same code).
+#[tauri::command]
+pub fn run_maintenance(state: State<'_, AppState>) -> AppResult<MaintenanceSummary> {
+    metrics::timed(&state.storage, "run_maintenance", json!({}), || {
+        maintenance::run_maintenance(&state.storage)
+    })
+}
+
+/// Save (or replace) a named composite command for the palette.
+#[tauri::command]
+pub fn save_composite(
+    state: State<'_, AppState>,
+    composite: crate::composites::CompositeCommand,
+) -> AppResult<()> {
+    metrics::timed(
+        &state.storage,
+        "save_composite",
+        json!({ "name": composite.name, "steps": composite.steps.len() }),
+        || crate::composites::save_composite(&state.storage, &composite),
+    )
+}
+
+/// Every stored composite command, sorted by name.
+#[tauri::command]
+pub fn list_composites(
+    state: State<'_, AppState>,
+) -> AppResult<Vec<crate::composites::CompositeCommand>> {
+    metrics::timed(&state.storage, "list_composites", json!({}), || {
+        crate::composites::list_composites(&state.storage)
+    })
+}
+
+#[tauri::command]
+pub fn delete_composite(state: State<'_, AppState>, name: String) -> AppResult<()> {
+    metrics::timed(
+        &state.storage,
+        "delete_composite",
+        json!({ "name": name }),
+        || crate::composites::delete_composite(&state.storage, &name),
+    )
+}
+
+/// Run a stored composite with palette-supplied parameters, returning
+/// one outcome per step.
+#[tauri::command]
+pub fn run_composite(
+    window: tauri::Window,
+    state: State<'_, AppState>,
+    name: String,
+    params: std::collections::BTreeMap<String, String>,
+) -> AppResult<Vec<crate::composites::CompositeOutcome>> {
+    metrics::timed(
+        &state.storage,
+        "run_composite",
+        json!({ "name": name }),
+        || {
+            let outcomes = crate::composites::run_composite(&state.storage, &name, &params)?;
+            windows::broadcast(&window, &state.windows, "tasks", json!({ "composite": name }));
+            Ok(outcomes)
+        },
+    )
+}
+
+/// Export matching events to a newline-delimited JSON file on disk,
+/// returning how many were written.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn export_events_jsonl(
+    state: State<'_, AppState>,
+    path: String,
+    event_type: Option<String>,
+    task_id: Option<String>,
+    agent_id: Option<String>,
+    start: Option<chrono::DateTime<chrono::Utc>>,
+    end: Option<chrono::DateTime<chrono::Utc>>,
+) -> AppResult<u64> {
+    state
+        .storage
+        .run(move |storage| {
+            metrics::timed(
+                storage,
+                "export_events_jsonl",
+                json!({
+                    "path": path,
+                    "event_type": event_type,
+                    "task_id": task_id,
+                    "agent_id": agent_id,
+                }),
+                || {
+                    feed::export_events_jsonl(
+                        storage,
+                        event_type.as_deref(),
+                        task_id.as_deref(),
+                        agent_id.as_deref(),
+                        start,
+                        end,
+                        std::path::Path::new(&path),
+                    )
+                },
+            )
+        })
+        .await
+}
+
+/// Open a live-tail subscription: events matching the filter are
+/// pushed as `subscription://<id>` Tauri events until unsubscribed.
+#[tauri::command]
+pub fn subscribe_events(
+    state: State<'_, AppState>,
+    filter: Option<windows::EventFilter>,
+) -> AppResult<String> {
+    metrics::timed(&state.storage, "subscribe_events", json!({}), || {
+        Ok(state.subscriptions.subscribe(filter.unwrap_or_default()))
+    })
+}
+
+#[tauri::command]
+pub fn unsubscribe(state: State<'_, AppState>, sub_id: String) -> AppResult<()> {
+    metrics::timed(
+        &state.storage,
+        "unsubscribe",
+        json!({ "sub_id": sub_id }),
+        || state.subscriptions.unsubscribe(&sub_id),
+    )
+}
+
+/// Full-text search across the whole activity history, best matches
+/// first.
+#[tauri::command]
+pub async fn search_events(
+    state: State<'_, AppState>,
+    query: String,
+    limit: Option<u32>,
+) -> AppResult<Vec<crate::models::TaskEvent>> {
+    state
+        .storage
+        .run(move |storage| {
+            metrics::timed(
+                storage,
+                "search_events",
+                json!({ "query": query, "limit": limit }),
+                || {
+                    let limit = limit.unwrap_or(50).min(200);
+                    storage.search_events(&query, i64::from(limit))
+                },
+            )
+        })
+        .await
+}
+
+/// Compact the database (VACUUM + ANALYZE) and run an integrity check,
+/// returning sizes and any corruption found. Heavy on large databases,
+/// so it runs off the IPC runtime.
+#[tauri::command]
+pub async fn maintain_storage(
+    state: State<'_, AppState>,
+) -> AppResult<maintenance::StorageCheckReport> {
+    state
+        .storage
+        .run(move |storage| {
+            metrics::timed(storage, "maintain_storage", json!({}), || {
+                maintenance::maintain_storage(storage)
+            })
+        })
+        .await
+}
+
+/// Find and fix dangling references in storage, reporting every fix.
+#[tauri::command]
+pub fn repair_storage(
+    window: tauri::Window,
+    state: State<'_, AppState>,
+) -> AppResult<maintenance::RepairReport> {
+    metrics::timed(&state.storage, "repair_storage", json!({}), || {
+        let report = maintenance::repair_storage(&state.storage)?;
+        if report.total_fixes() > 0 {
+            windows::broadcast(
+                &window,
+                &state.windows,
+                "tasks",
+                json!({ "repaired": report.total_fixes() }),
+            );
+        }
+        Ok(report)
+    })
+}
+
+/// Encrypt the workspace database with SQLCipher, keying it from the
+/// macOS Keychain. One-way from the app's point of view: after this
+/// succeeds the app must restart to reopen the database with the key.
+#[tauri::command]
+pub fn enable_storage_encryption(
+    state: State<'_, AppState>,
+    health: State<'_, BackendHealth>,
+) -> AppResult<()> {
+    metrics::timed(&state.storage, "enable_storage_encryption", json!({}), || {
+        let data_dir = health
+            .data_dir()
+            .ok_or_else(|| AppError::InvalidArgument("data dir unknown".into()))?;
+        let db_path = crate::resolve_db_path(&data_dir);
+        crate::encryption::enable(&state.storage, &data_dir, &db_path)
+    })
+}
+
+/// Structured quick status for the command palette: per-status task
+/// counts, busiest agents, and the sorted roster.
+#[tauri::command]
+pub fn get_quick_status(state: State<'_, AppState>) -> AppResult<status_page::QuickStatus> {
+    metrics::timed(&state.storage, "get_quick_status", json!({}), || {
+        status_page::quick_status(&state.storage)
+    })
+}
+
+/// Drain-and-upgrade the storage schema: maintenance mode, drain,
+/// checkpoint, migrate, resume. Phase progress is broadcast to every
+/// window subscribed to the `maintenance` topic.
+#[tauri::command]
+pub fn upgrade_storage(
+    window: tauri::Window,
+    state: State<'_, AppState>,
+) -> AppResult<maintenance::UpgradeSummary> {
+    metrics::timed(&state.storage, "upgrade_storage", json!({}), || {
+        maintenance::upgrade_storage(&state.storage, |phase, detail| {
+            windows::broadcast(
+                &window,
+                &state.windows,
+                "maintenance",
+                json!({ "phase": phase, "detail": detail }),
+            );
+        })
+    })
+}
+
+/// Render and publish a status snapshot immediately; the interval job
+/// runs the same code.
+#[tauri::command]
+pub fn publish_status_page(state: State<'_, AppState>) -> AppResult<StatusSnapshot> {
+    metrics::timed(&state.storage, "publish_status_page", json!({}), || {
+        status_page::publish(&state.storage)
+    })
+}
@@ -0,0 +1,294 @@
+//! Named composite commands for the palette.
+//!
+//! A composite is a keyboard-macro style sequence of quick commands
+//! ("standup" = dispatch a report task, then notify the channel) stored
+//! in the backend. Every step is resolved and validated before anything
+//! runs, so a typo in step three does not leave steps one and two
+//! half-applied.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::error::{AppError, AppResult};
+use crate::models::AgentStatus;
+use crate::storage::Storage;
+use crate::task_dispatch::{self, DispatchRequest};
+use crate::templates;
+
+/// Settings key the composite library is stored under.
+pub const COMPOSITES_SETTING: &str = "composite_commands";
+
+/// One step of a composite: a quick command name plus its arguments.
+/// String arguments may carry `{{placeholder}}` parameters substituted
+/// at run time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompositeStep {
+    pub command: String,
+    #[serde(default)]
+    pub args: Value,
+}
+
+/// A named sequence of steps, executable from the palette in one call.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompositeCommand {
+    pub name: String,
+    pub steps: Vec<CompositeStep>,
+}
+
+/// What one executed step did, for the palette's result rendering.
+#[derive(Debug, Clone, Serialize)]
+pub struct CompositeOutcome {
+    pub command: String,
+    pub detail: Value,
+}
+
+/// A step with placeholders substituted and arguments deserialized,
+/// ready to execute.
+enum ResolvedStep {
+    Dispatch(DispatchRequest),
+    PauseAgent(String),
+    ResumeAgent(String),
+    Notify { title: String, body: String },
+}
+
+fn library(storage: &Storage) -> AppResult<BTreeMap<String, Vec<CompositeStep>>> {
+    Ok(storage
+        .get_setting(COMPOSITES_SETTING)?
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default())
+}
+
+fn save_library(storage: &Storage, library: &BTreeMap<String, Vec<CompositeStep>>) -> AppResult<()> {
+    let raw = serde_json::to_string(library).expect("composites serialize");
+    storage.set_setting(COMPOSITES_SETTING, &raw)
+}
+
+/// Save (or replace) a composite. Steps are resolved against empty
+/// parameters at save time so unknown commands and malformed arguments
+/// are rejected before they can break a palette run.
+pub fn save_composite(storage: &Storage, composite: &CompositeCommand) -> AppResult<()> {
+    if composite.name.trim().is_empty() {
+        return Err(AppError::InvalidArgument("composite name is required".into()));
+    }
+    if composite.steps.is_empty() {
+        return Err(AppError::InvalidArgument(format!(
+            "composite \"{}\" has no steps",
+            composite.name
+        )));
+    }
+    for step in &composite.steps {
+        resolve_step(step, &BTreeMap::new())?;
+    }
+    let mut all = library(storage)?;
+    all.insert(composite.name.clone(), composite.steps.clone());
+    save_library(storage, &all)
+}
+
+/// Every stored composite, sorted by name.
+pub fn list_composites(storage: &Storage) -> AppResult<Vec<CompositeCommand>> {
+    Ok(library(storage)?
+        .into_iter()
+        .map(|(name, steps)| CompositeCommand { name, steps })
+        .collect())
+}
+
+pub fn delete_composite(storage: &Storage, name: &str) -> AppResult<()> {
+    let mut all = library(storage)?;
+    if all.remove(name).is_none() {
+        return Err(AppError::not_found("composite", name));
+    }
+    save_library(storage, &all)
+}
+
+/// Run a stored composite with the given parameters. All steps are
+/// resolved and validated first -- unknown agents, bad priorities and
+/// unparseable arguments abort before anything executes -- then each
+/// step runs in order, returning one outcome per step.
+pub fn run_composite(
+    storage: &Storage,
+    name: &str,
+    params: &BTreeMap<String, String>,
+) -> AppResult<Vec<CompositeOutcome>> {
+    let steps = library(storage)?
+        .remove(name)
+        .ok_or_else(|| AppError::not_found("composite", name))?;
+    let resolved = steps
+        .iter()
+        .map(|step| {
+            let resolved = resolve_step(step, params)?;
+            // Existence checks up front, so execution cannot stop
+            // halfway through the sequence.
+            match &resolved {
+                ResolvedStep::Dispatch(request) => {
+                    storage.get_agent(&request.agent_id)?;
+                    templates::validate(storage, &request.prompt)?;
+                }
+                ResolvedStep::PauseAgent(agent_id) | ResolvedStep::ResumeAgent(agent_id) => {
+                    storage.get_agent(agent_id)?;
+                }
+                ResolvedStep::Notify { .. } => {}
+            }
+            Ok(resolved)
+        })
+        .collect::<AppResult<Vec<_>>>()?;
+
+    let mut outcomes = Vec::new();
+    for (step, resolved) in steps.iter().zip(resolved) {
+        let detail = match resolved {
+            ResolvedStep::Dispatch(request) => {
+                let task = task_dispatch::dispatch(storage, &request)?;
+                json!({ "task_id": task.id, "title": task.title })
+            }
+            ResolvedStep::PauseAgent(agent_id) => {
+                storage.set_agent_status(&agent_id, AgentStatus::Paused)?;
+                storage.append_agent_history(&agent_id, "paused", None)?;
+                json!({ "agent_id": agent_id })
+            }
+            ResolvedStep::ResumeAgent(agent_id) => {
+                storage.set_agent_status(&agent_id, AgentStatus::Idle)?;
+                storage.append_agent_history(&agent_id, "resumed", None)?;
+                json!({ "agent_id": agent_id })
+            }
+            ResolvedStep::Notify { title, body } => {
+                let id = storage.add_notification(&title, &body)?;
+                json!({ "notification_id": id })
+            }
+        };
+        outcomes.push(CompositeOutcome {
+            command: step.command.clone(),
+            detail,
+        });
+    }
+    Ok(outcomes)
+}
+
+/// Substitute parameters into a step's arguments and deserialize them
+/// for the named command.
+fn resolve_step(step: &CompositeStep, params: &BTreeMap<String, String>) -> AppResult<ResolvedStep> {
+    let args = substitute_value(&step.args, params);
+    let str_arg = |key: &str| {
+        args[key]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| {
+                AppError::InvalidArgument(format!(
+                    "composite step \"{}\" is missing \"{key}\"",
+                    step.command
+                ))
+            })
+    };
+    match step.command.as_str() {
+        "dispatch" => serde_json::from_value(args.clone())
+            .map(ResolvedStep::Dispatch)
+            .map_err(|err| {
+                AppError::InvalidArgument(format!("invalid dispatch step arguments: {err}"))
+            }),
+        "pause_agent" => Ok(ResolvedStep::PauseAgent(str_arg("agent_id")?)),
+        "resume_agent" => Ok(ResolvedStep::ResumeAgent(str_arg("agent_id")?)),
+        "notify" => Ok(ResolvedStep::Notify {
+            title: str_arg("title")?,
+            body: str_arg("body")?,
+        }),
+        other => Err(AppError::InvalidArgument(format!(
+            "unknown composite command \"{other}\""
+        ))),
+    }
+}
+
+/// Apply `{{placeholder}}` substitution to every string in a JSON
+/// argument tree. Settings/secrets references pass through untouched,
+/// exactly as they do in task templates.
+fn substitute_value(value: &Value, params: &BTreeMap<String, String>) -> Value {
+    match value {
+        Value::String(text) => Value::String(templates::substitute_params(text, params)),
+        Value::Array(items) => {
+            Value::Array(items.iter().map(|v| substitute_value(v, params)).collect())
+        }
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| (k.clone(), substitute_value(v, params)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Agent;
+
+    #[test]
+    fn composites_validate_at_save_and_run_with_parameters() {
+        let storage = Storage::open_in_memory().unwrap();
+        let agent = Agent::new("reporter", "mock");
+        storage.create_agent(&agent).unwrap();
+
+        // Unknown commands never make it into the library.
+        assert!(save_composite(
+            &storage,
+            &CompositeCommand {
+                name: "bad".into(),
+                steps: vec![CompositeStep { command: "reboot".into(), args: json!({}) }],
+            },
+        )
+        .is_err());
+
+        save_composite(
+            &storage,
+            &CompositeCommand {
+                name: "standup".into(),
+                steps: vec![
+                    CompositeStep {
+                        command: "dispatch".into(),
+                        args: json!({
+                            "agent_id": agent.id,
+                            "title": "Standup {{date}}",
+                            "prompt": "Summarize {{team}} activity",
+                        }),
+                    },
+                    CompositeStep {
+                        command: "notify".into(),
+                        args: json!({ "title": "Standup queued", "body": "for {{team}}" }),
+                    },
+                ],
+            },
+        )
+        .unwrap();
+        assert_eq!(list_composites(&storage).unwrap().len(), 1);
+
+        let params = BTreeMap::from([("team".to_string(), "platform".to_string())]);
+        let outcomes = run_composite(&storage, "standup", &params).unwrap();
+        assert_eq!(outcomes.len(), 2);
+        let task_id = outcomes[0].detail["task_id"].as_str().unwrap();
+        let task = storage.get_task(task_id).unwrap();
+        assert_eq!(task.prompt, "Summarize platform activity");
+
+        // A step referencing a missing agent aborts before anything runs.
+        save_composite(
+            &storage,
+            &CompositeCommand {
+                name: "broken".into(),
+                steps: vec![
+                    CompositeStep {
+                        command: "notify".into(),
+                        args: json!({ "title": "t", "body": "b" }),
+                    },
+                    CompositeStep {
+                        command: "pause_agent".into(),
+                        args: json!({ "agent_id": "ghost" }),
+                    },
+                ],
+            },
+        )
+        .unwrap();
+        let before = storage.get_task_events(task_id).unwrap().len();
+        assert!(run_composite(&storage, "broken", &BTreeMap::new()).is_err());
+        assert_eq!(storage.get_task_events(task_id).unwrap().len(), before);
+
+        delete_composite(&storage, "standup").unwrap();
+        assert!(run_composite(&storage, "standup", &BTreeMap::new()).is_err());
+    }
+}
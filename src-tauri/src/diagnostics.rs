@@ -0,0 +1,174 @@
+use std::path::Path;
+
+use chrono::{TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppResult;
+use crate::storage::Storage;
+
+/// Settings key holding the most recent startup diagnostics run.
+pub const DIAGNOSTICS_KEY: &str = "startup_diagnostics";
+
+/// Free space below this triggers a warning (the db and artifacts grow).
+const MIN_FREE_BYTES: u64 = 500 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticStatus {
+    Pass,
+    Warning,
+    Fail,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiagnosticCheck {
+    pub name: String,
+    pub status: DiagnosticStatus,
+    pub detail: String,
+}
+
+impl DiagnosticCheck {
+    fn pass(name: &str, detail: impl Into<String>) -> Self {
+        Self { name: name.into(), status: DiagnosticStatus::Pass, detail: detail.into() }
+    }
+
+    fn warning(name: &str, detail: impl Into<String>) -> Self {
+        Self { name: name.into(), status: DiagnosticStatus::Warning, detail: detail.into() }
+    }
+
+    fn fail(name: &str, detail: impl Into<String>) -> Self {
+        Self { name: name.into(), status: DiagnosticStatus::Fail, detail: detail.into() }
+    }
+}
+
+/// Run the startup self-diagnostics and persist the results.
+///
+/// Non-fatal issues become warnings in the report (and a notification),
+/// never a panic: a workspace with low disk space should still open.
+pub fn run_startup(storage: &Storage, data_dir: &Path) -> AppResult<Vec<DiagnosticCheck>> {
+    let checks = vec![
+        check_db_writable(storage),
+        check_schema_version(storage),
+        check_disk_space(data_dir),
+        check_secrets_reachable(storage),
+        check_clock(),
+    ];
+
+    for check in &checks {
+        if check.status != DiagnosticStatus::Pass {
+            tracing::warn!(check = %check.name, detail = %check.detail, "startup diagnostic");
+            storage.add_notification(
+                &format!("Startup check {}: {:?}", check.name, check.status),
+                &check.detail,
+            )?;
+        }
+    }
+    storage.set_setting(
+        DIAGNOSTICS_KEY,
+        &serde_json::to_string(&checks).expect("diagnostics serialize"),
+    )?;
+    Ok(checks)
+}
+
+/// The stored report from the last startup, for the diagnostics screen.
+pub fn get_startup_diagnostics(storage: &Storage) -> AppResult<Vec<DiagnosticCheck>> {
+    Ok(storage
+        .get_setting(DIAGNOSTICS_KEY)?
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default())
+}
+
+fn check_db_writable(storage: &Storage) -> DiagnosticCheck {
+    match storage.set_setting("diagnostics.last_probe", &Utc::now().to_rfc3339()) {
+        Ok(()) => DiagnosticCheck::pass("db_writable", "write probe succeeded"),
+        Err(err) => DiagnosticCheck::fail("db_writable", format!("write probe failed: {err}")),
+    }
+}
+
+fn check_schema_version(storage: &Storage) -> DiagnosticCheck {
+    match storage.schema_version() {
+        Ok(version) => DiagnosticCheck::pass("schema_version", format!("user_version {version}")),
+        Err(err) => DiagnosticCheck::fail("schema_version", format!("unreadable: {err}")),
+    }
+}
+
+fn check_disk_space(data_dir: &Path) -> DiagnosticCheck {
+    match free_bytes(data_dir) {
+        Some(free) if free < MIN_FREE_BYTES => DiagnosticCheck::warning(
+            "disk_space",
+            format!("{} MB free in data dir", free / (1024 * 1024)),
+        ),
+        Some(free) => DiagnosticCheck::pass(
+            "disk_space",
+            format!("{} MB free in data dir", free / (1024 * 1024)),
+        ),
+        None => DiagnosticCheck::warning("disk_space", "free space unavailable on this platform"),
+    }
+}
+
+#[cfg(unix)]
+fn free_bytes(path: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+    let cpath = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(cpath.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+fn free_bytes(_path: &Path) -> Option<u64> {
+    None
+}
+
+/// Secrets live in storage rather than the OS keychain, but the check
+/// keeps its own name so a future keychain backend slots in.
+fn check_secrets_reachable(storage: &Storage) -> DiagnosticCheck {
+    match storage.list_secret_names() {
+        Ok(names) => {
+            DiagnosticCheck::pass("keychain", format!("{} secret names registered", names.len()))
+        }
+        Err(err) => DiagnosticCheck::fail("keychain", format!("unreachable: {err}")),
+    }
+}
+
+/// A badly wrong clock corrupts runtime accrual and schedule math, so
+/// flag it early rather than debugging nonsense timestamps later.
+fn check_clock() -> DiagnosticCheck {
+    let now = Utc::now();
+    let floor = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+    let ceiling = Utc.with_ymd_and_hms(2100, 1, 1, 0, 0, 0).unwrap();
+    if now < floor || now > ceiling {
+        DiagnosticCheck::warning("clock", format!("system clock reads {now}; check NTP"))
+    } else {
+        DiagnosticCheck::pass("clock", format!("system clock reads {now}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_stores_a_queryable_report() {
+        let storage = Storage::open_in_memory().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let checks = run_startup(&storage, dir.path()).unwrap();
+        assert!(checks.iter().any(|c| c.name == "db_writable"));
+
+        let stored = get_startup_diagnostics(&storage).unwrap();
+        assert_eq!(stored.len(), checks.len());
+    }
+
+    #[test]
+    fn healthy_workspace_passes_core_checks() {
+        let storage = Storage::open_in_memory().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let checks = run_startup(&storage, dir.path()).unwrap();
+        for name in ["db_writable", "schema_version", "keychain", "clock"] {
+            let check = checks.iter().find(|c| c.name == name).unwrap();
+            assert_eq!(check.status, DiagnosticStatus::Pass, "{name}: {}", check.detail);
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::artifacts::ArtifactStore;
+use crate::error::{AppError, AppResult};
+use crate::models::TaskStatus;
+use crate::storage::Storage;
+
+/// Settings key holding the Slack incoming-webhook URL digests post to.
+pub const SLACK_WEBHOOK_KEY: &str = "slack_webhook_url";
+
+/// How many agents make the "top agents" list.
+const TOP_AGENTS: usize = 5;
+/// How many failures are called out individually.
+const NOTABLE_FAILURES: usize = 5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentActivity {
+    pub agent_id: String,
+    pub name: String,
+    pub completed: usize,
+    pub failed: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotableFailure {
+    pub task_id: String,
+    pub title: String,
+    pub error: Option<String>,
+}
+
+/// Summary of workspace activity over a time range.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityDigest {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub generated_at: DateTime<Utc>,
+    pub total_tasks: usize,
+    pub completed: usize,
+    pub failed: usize,
+    pub cancelled: usize,
+    pub top_agents: Vec<AgentActivity>,
+    pub notable_failures: Vec<NotableFailure>,
+    /// Rendered markdown summary of the above.
+    pub prose: String,
+    /// Name of the saved digest artifact.
+    pub artifact: String,
+}
+
+/// Build a digest for `[start, end)`, render it to markdown and save the
+/// rendering in the artifact store.
+pub fn generate(
+    storage: &Storage,
+    artifacts: &ArtifactStore,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> AppResult<ActivityDigest> {
+    if end <= start {
+        return Err(AppError::InvalidArgument(
+            "digest range end must be after start".into(),
+        ));
+    }
+
+    let tasks = storage.get_tasks_updated_between(&start, &end)?;
+    let completed = tasks
+        .iter()
+        .filter(|t| t.status == TaskStatus::Completed)
+        .count();
+    let failed = tasks
+        .iter()
+        .filter(|t| t.status == TaskStatus::Failed)
+        .count();
+    let cancelled = tasks
+        .iter()
+        .filter(|t| t.status == TaskStatus::Cancelled)
+        .count();
+
+    let mut per_agent: std::collections::HashMap<String, AgentActivity> = Default::default();
+    for task in &tasks {
+        let entry = per_agent
+            .entry(task.agent_id.clone())
+            .or_insert_with(|| AgentActivity {
+                agent_id: task.agent_id.clone(),
+                name: storage
+                    .get_agent(&task.agent_id)
+                    .map(|a| a.name)
+                    .unwrap_or_else(|_| task.agent_id.clone()),
+                completed: 0,
+                failed: 0,
+            });
+        match task.status {
+            TaskStatus::Completed => entry.completed += 1,
+            TaskStatus::Failed => entry.failed += 1,
+            _ => {}
+        }
+    }
+    let mut top_agents: Vec<_> = per_agent.into_values().collect();
+    top_agents.sort_by(|a, b| b.completed.cmp(&a.completed).then(a.name.cmp(&b.name)));
+    top_agents.truncate(TOP_AGENTS);
+
+    let notable_failures: Vec<_> = tasks
+        .iter()
+        .filter(|t| t.status == TaskStatus::Failed)
+        .take(NOTABLE_FAILURES)
+        .map(|t| NotableFailure {
+            task_id: t.id.clone(),
+            title: t.title.clone(),
+            error: t.error.clone(),
+        })
+        .collect();
+
+    let mut digest = ActivityDigest {
+        start,
+        end,
+        generated_at: Utc::now(),
+        total_tasks: tasks.len(),
+        completed,
+        failed,
+        cancelled,
+        top_agents,
+        notable_failures,
+        prose: String::new(),
+        artifact: String::new(),
+    };
+    digest.prose = render_prose(&digest);
+    digest.artifact = artifacts.store_bytes("digest", "md", digest.prose.as_bytes())?;
+    Ok(digest)
+}
+
+/// Deterministic markdown rendering. When an LLM provider is configured
+/// this is the fallback and the prompt context.
+fn render_prose(digest: &ActivityDigest) -> String {
+    let mut out = format!(
+        "# Workspace digest {} — {}\n\n{} tasks touched: {} completed, {} failed, {} cancelled.\n",
+        digest.start.format("%Y-%m-%d %H:%M"),
+        digest.end.format("%Y-%m-%d %H:%M"),
+        digest.total_tasks,
+        digest.completed,
+        digest.failed,
+        digest.cancelled,
+    );
+    if !digest.top_agents.is_empty() {
+        out.push_str("\n## Top agents\n");
+        for agent in &digest.top_agents {
+            out.push_str(&format!(
+                "- {}: {} completed, {} failed\n",
+                agent.name, agent.completed, agent.failed
+            ));
+        }
+    }
+    if !digest.notable_failures.is_empty() {
+        out.push_str("\n## Notable failures\n");
+        for failure in &digest.notable_failures {
+            out.push_str(&format!(
+                "- {} ({}): {}\n",
+                failure.title,
+                failure.task_id,
+                failure.error.as_deref().unwrap_or("no error recorded")
+            ));
+        }
+    }
+    out
+}
+
+/// Post the digest prose to the configured Slack webhook, if any.
+/// Returns whether a post was made.
+pub fn post_to_slack(storage: &Storage, digest: &ActivityDigest) -> AppResult<bool> {
+    let Some(webhook) = storage.get_setting(SLACK_WEBHOOK_KEY)? else {
+        return Ok(false);
+    };
+    let client = reqwest::blocking::Client::new();
+    client
+        .post(&webhook)
+        .json(&serde_json::json!({ "text": digest.prose }))
+        .send()
+        .and_then(|resp| resp.error_for_status())
+        .map_err(|err| AppError::InvalidArgument(format!("slack webhook post failed: {err}")))?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Agent;
+    use crate::task_dispatch::{self, DispatchRequest};
+    use chrono::Duration;
+
+    #[test]
+    fn digest_counts_statuses_and_saves_artifact() {
+        let storage = Storage::open_in_memory().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let artifacts = ArtifactStore::new(dir.path().to_path_buf()).unwrap();
+        let agent = Agent::new("digester", "mock");
+        storage.create_agent(&agent).unwrap();
+
+        let done = task_dispatch::dispatch(&storage, &DispatchRequest::new(&agent.id, "a", "p"))
+            .unwrap();
+        task_dispatch::execute(&storage, &done.id).unwrap();
+        task_dispatch::dispatch(&storage, &DispatchRequest::new(&agent.id, "b", "p")).unwrap();
+
+        let start = Utc::now() - Duration::hours(1);
+        let end = Utc::now() + Duration::hours(1);
+        let digest = generate(&storage, &artifacts, start, end).unwrap();
+
+        assert_eq!(digest.total_tasks, 2);
+        assert_eq!(digest.completed, 1);
+        assert_eq!(digest.top_agents[0].name, "digester");
+        assert!(digest.prose.contains("digester"));
+        assert!(artifacts.read(&digest.artifact).is_ok());
+    }
+
+    #[test]
+    fn rejects_inverted_range() {
+        let storage = Storage::open_in_memory().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let artifacts = ArtifactStore::new(dir.path().to_path_buf()).unwrap();
+        let now = Utc::now();
+        assert!(generate(&storage, &artifacts, now, now).is_err());
+    }
+}
@@ -0,0 +1,176 @@
+//! Discovery of agent runtimes already running on this machine.
+//!
+//! Scans the usual local suspects -- an Ollama daemon, LangServe apps,
+//! MCP servers -- and describes each find as a pre-filled agent config
+//! the user can register with one click.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::AppResult;
+use crate::mcp::McpClient;
+use crate::models::Agent;
+use crate::storage::Storage;
+
+/// Settings keys listing extra endpoints to scan, as JSON string
+/// arrays; localhost defaults are always tried.
+pub const LANGSERVE_CANDIDATES_SETTING: &str = "discovery.langserve_endpoints";
+pub const MCP_CANDIDATES_SETTING: &str = "discovery.mcp_endpoints";
+
+const OLLAMA_DEFAULT: &str = "http://localhost:11434";
+const LANGSERVE_DEFAULT: &str = "http://localhost:8000";
+const PROBE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(2);
+
+/// A runtime found on this machine, shaped as the agent it would become.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscoveredAgent {
+    /// Suggested display name, e.g. "Ollama: llama3".
+    pub name: String,
+    pub model: String,
+    pub framework: String,
+    pub endpoint: Option<String>,
+    #[serde(default)]
+    pub mcp_servers: Vec<String>,
+}
+
+/// Scan local runtimes. Probes are short-timeout and best-effort: an
+/// absent daemon is simply not in the result, never an error.
+pub fn discover_local_agents(storage: &Storage) -> AppResult<Vec<DiscoveredAgent>> {
+    let mut found = Vec::new();
+    found.extend(discover_ollama());
+    for endpoint in candidates(storage, LANGSERVE_CANDIDATES_SETTING, LANGSERVE_DEFAULT)? {
+        found.extend(probe_langserve(&endpoint));
+    }
+    for endpoint in candidates(storage, MCP_CANDIDATES_SETTING, "")? {
+        found.extend(probe_mcp(&endpoint));
+    }
+    Ok(found)
+}
+
+/// Register a discovered runtime as a real agent.
+pub fn register(storage: &Storage, discovered: &DiscoveredAgent) -> AppResult<Agent> {
+    let mut agent = Agent::new(&discovered.name, &discovered.model);
+    agent.framework = Some(discovered.framework.clone());
+    agent.endpoint = discovered.endpoint.clone();
+    agent.mcp_servers = discovered.mcp_servers.clone();
+    storage.create_agent(&agent)?;
+    Ok(agent)
+}
+
+fn candidates(storage: &Storage, setting: &str, default: &str) -> AppResult<Vec<String>> {
+    let mut list: Vec<String> = storage
+        .get_setting(setting)?
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default();
+    if !default.is_empty() && !list.iter().any(|e| e == default) {
+        list.push(default.to_string());
+    }
+    Ok(list)
+}
+
+fn probe_client() -> reqwest::blocking::Client {
+    reqwest::blocking::Client::builder()
+        .timeout(PROBE_TIMEOUT)
+        .build()
+        .unwrap_or_default()
+}
+
+/// Every model the local Ollama daemon has pulled becomes a candidate.
+fn discover_ollama() -> Vec<DiscoveredAgent> {
+    let Ok(response) = probe_client()
+        .get(format!("{OLLAMA_DEFAULT}/api/tags"))
+        .send()
+        .and_then(|r| r.json::<Value>())
+    else {
+        return Vec::new();
+    };
+    response["models"]
+        .as_array()
+        .map(|models| {
+            models
+                .iter()
+                .filter_map(|m| m["name"].as_str())
+                .map(|name| DiscoveredAgent {
+                    name: format!("Ollama: {name}"),
+                    model: format!("ollama/{name}"),
+                    framework: "ollama".into(),
+                    endpoint: Some(OLLAMA_DEFAULT.into()),
+                    mcp_servers: Vec::new(),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// A LangServe app advertises its runnables through the OpenAPI spec;
+/// any path exposing `/invoke` is a registrable chain.
+fn probe_langserve(endpoint: &str) -> Vec<DiscoveredAgent> {
+    let Ok(spec) = probe_client()
+        .get(format!("{endpoint}/openapi.json"))
+        .send()
+        .and_then(|r| r.json::<Value>())
+    else {
+        return Vec::new();
+    };
+    let Some(paths) = spec["paths"].as_object() else {
+        return Vec::new();
+    };
+    paths
+        .keys()
+        .filter_map(|path| path.strip_suffix("/invoke"))
+        .map(|chain| DiscoveredAgent {
+            name: format!("LangServe: {}", chain.trim_start_matches('/')),
+            model: chain.trim_start_matches('/').to_string(),
+            framework: "langserve".into(),
+            endpoint: Some(format!("{endpoint}{chain}")),
+            mcp_servers: Vec::new(),
+        })
+        .collect()
+}
+
+/// A responsive MCP server becomes a mock-backed agent wired to its
+/// tools, ready to pair with a real model later.
+fn probe_mcp(endpoint: &str) -> Vec<DiscoveredAgent> {
+    match McpClient::new(endpoint).list_tools() {
+        Ok(tools) if !tools.is_empty() => vec![DiscoveredAgent {
+            name: format!("MCP: {endpoint}"),
+            model: "mock".into(),
+            framework: "mock".into(),
+            endpoint: None,
+            mcp_servers: vec![endpoint.to_string()],
+        }],
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registration_prefills_the_discovered_config() {
+        let storage = Storage::open_in_memory().unwrap();
+        let discovered = DiscoveredAgent {
+            name: "Ollama: llama3".into(),
+            model: "ollama/llama3".into(),
+            framework: "ollama".into(),
+            endpoint: Some("http://localhost:11434".into()),
+            mcp_servers: Vec::new(),
+        };
+        let agent = register(&storage, &discovered).unwrap();
+        let stored = storage.get_agent(&agent.id).unwrap();
+        assert_eq!(stored.framework.as_deref(), Some("ollama"));
+        assert_eq!(stored.endpoint.as_deref(), Some("http://localhost:11434"));
+    }
+
+    #[test]
+    fn candidate_lists_merge_settings_with_the_default() {
+        let storage = Storage::open_in_memory().unwrap();
+        storage
+            .set_setting(LANGSERVE_CANDIDATES_SETTING, r#"["http://localhost:9001"]"#)
+            .unwrap();
+        let list = candidates(&storage, LANGSERVE_CANDIDATES_SETTING, LANGSERVE_DEFAULT).unwrap();
+        assert_eq!(list.len(), 2);
+        assert!(list.contains(&LANGSERVE_DEFAULT.to_string()));
+    }
+}
@@ -0,0 +1,223 @@
+//! SMTP email notifications.
+//!
+//! Alert-style subsystems call [`notify`] to mail the operator. The
+//! server settings live in ordinary settings; the password lives in
+//! the secrets store and is read through the audited path at send
+//! time, never persisted in config or logs. Delivery failures degrade
+//! to a Warning event (when tied to a task) or a notification, so a
+//! broken relay cannot take a run down with it.
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use serde_json::json;
+
+use crate::error::{AppError, AppResult};
+use crate::storage::Storage;
+
+pub const HOST_SETTING: &str = "email.smtp_host";
+pub const PORT_SETTING: &str = "email.smtp_port";
+pub const FROM_SETTING: &str = "email.from";
+pub const TO_SETTING: &str = "email.to";
+pub const USER_SETTING: &str = "email.smtp_user";
+/// Name of the registered secret holding the SMTP password.
+pub const PASSWORD_SECRET_SETTING: &str = "email.password_secret";
+
+const SMTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Resolved SMTP configuration; `None` when email is not set up.
+#[derive(Debug, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub from: String,
+    pub to: String,
+    pub user: Option<String>,
+    pub password_secret: Option<String>,
+}
+
+impl SmtpConfig {
+    pub fn load(storage: &Storage) -> AppResult<Option<Self>> {
+        let (Some(host), Some(from), Some(to)) = (
+            storage.get_setting(HOST_SETTING)?,
+            storage.get_setting(FROM_SETTING)?,
+            storage.get_setting(TO_SETTING)?,
+        ) else {
+            return Ok(None);
+        };
+        Ok(Some(Self {
+            host,
+            port: storage
+                .get_setting(PORT_SETTING)?
+                .and_then(|raw| raw.parse().ok())
+                .unwrap_or(25),
+            from,
+            to,
+            user: storage.get_setting(USER_SETTING)?,
+            password_secret: storage.get_setting(PASSWORD_SECRET_SETTING)?,
+        }))
+    }
+}
+
+/// RFC 5322 message with dot-stuffing applied for the DATA phase.
+pub fn format_message(config: &SmtpConfig, subject: &str, body: &str) -> String {
+    let stuffed = body
+        .lines()
+        .map(|line| {
+            if line.starts_with('.') {
+                format!(".{line}")
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\r\n");
+    format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\nMIME-Version: 1.0\r\n\
+         Content-Type: text/plain; charset=utf-8\r\n\r\n{}\r\n",
+        config.from, config.to, subject, stuffed
+    )
+}
+
+/// Base64 for AUTH LOGIN; small enough to not warrant a dependency.
+pub(crate) fn base64(input: &[u8]) -> String {
+    const TABLE: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in input.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        out.push(TABLE[(n >> 18) as usize & 63] as char);
+        out.push(TABLE[(n >> 12) as usize & 63] as char);
+        out.push(if chunk.len() > 1 { TABLE[(n >> 6) as usize & 63] as char } else { '=' });
+        out.push(if chunk.len() > 2 { TABLE[n as usize & 63] as char } else { '=' });
+    }
+    out
+}
+
+/// Send one message now, erroring on any SMTP rejection. Plain SMTP
+/// (optionally with AUTH LOGIN) -- meant for a localhost or trusted
+/// LAN relay; hosted providers should be bridged through one.
+pub fn send(storage: &Storage, config: &SmtpConfig, subject: &str, body: &str) -> AppResult<()> {
+    let stream = TcpStream::connect((config.host.as_str(), config.port))?;
+    stream.set_read_timeout(Some(SMTP_TIMEOUT))?;
+    stream.set_write_timeout(Some(SMTP_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+
+    expect(&mut reader, "220")?;
+    command(&mut writer, &mut reader, "EHLO oz-workspace-agent", "250")?;
+    if let (Some(user), Some(secret_name)) = (&config.user, &config.password_secret) {
+        let password = storage.get_secret_audited(secret_name, None, None)?;
+        command(&mut writer, &mut reader, "AUTH LOGIN", "334")?;
+        command(&mut writer, &mut reader, &base64(user.as_bytes()), "334")?;
+        command(&mut writer, &mut reader, &base64(password.as_bytes()), "235")?;
+    }
+    command(&mut writer, &mut reader, &format!("MAIL FROM:<{}>", config.from), "250")?;
+    command(&mut writer, &mut reader, &format!("RCPT TO:<{}>", config.to), "250")?;
+    command(&mut writer, &mut reader, "DATA", "354")?;
+    write!(writer, "{}.\r\n", format_message(config, subject, body))?;
+    expect(&mut reader, "250")?;
+    command(&mut writer, &mut reader, "QUIT", "221")?;
+    Ok(())
+}
+
+fn command(
+    writer: &mut TcpStream,
+    reader: &mut BufReader<TcpStream>,
+    line: &str,
+    expected: &str,
+) -> AppResult<()> {
+    write!(writer, "{line}\r\n")?;
+    expect(reader, expected)
+}
+
+fn expect(reader: &mut BufReader<TcpStream>, code: &str) -> AppResult<()> {
+    // Multi-line replies repeat the code with a dash until the last.
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        if !line.starts_with(code) {
+            return Err(AppError::Provider(format!(
+                "smtp: expected {code}, got {}",
+                line.trim_end()
+            )));
+        }
+        if line.as_bytes().get(3) != Some(&b'-') {
+            return Ok(());
+        }
+    }
+}
+
+/// Mail the operator if email is configured; failures never propagate.
+/// With a `task_id` the failure lands as a Warning event on the task,
+/// otherwise as a notification.
+pub fn notify(storage: &Storage, task_id: Option<&str>, subject: &str, body: &str) {
+    let config = match SmtpConfig::load(storage) {
+        Ok(Some(config)) => config,
+        Ok(None) => return,
+        Err(err) => {
+            tracing::warn!(%err, "email config could not be loaded");
+            return;
+        }
+    };
+    if let Err(err) = send(storage, &config, subject, body) {
+        tracing::warn!(%err, "email delivery failed");
+        let result = match task_id {
+            Some(task_id) => storage
+                .append_event(
+                    task_id,
+                    "warning",
+                    Some(&json!({ "reason": "email_delivery_failed", "error": err.to_string() })),
+                )
+                .map(|_| ()),
+            None => storage
+                .add_notification("Email delivery failed", &err.to_string())
+                .map(|_| ()),
+        };
+        if let Err(err) = result {
+            tracing::warn!(%err, "could not record email failure");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_requires_host_from_and_to() {
+        let storage = Storage::open_in_memory().unwrap();
+        assert!(SmtpConfig::load(&storage).unwrap().is_none());
+        storage.set_setting(HOST_SETTING, "localhost").unwrap();
+        storage.set_setting(FROM_SETTING, "agent@example.com").unwrap();
+        storage.set_setting(TO_SETTING, "ops@example.com").unwrap();
+        let config = SmtpConfig::load(&storage).unwrap().unwrap();
+        assert_eq!(config.port, 25);
+        assert!(config.user.is_none());
+    }
+
+    #[test]
+    fn messages_are_dot_stuffed_and_carry_headers() {
+        let config = SmtpConfig {
+            host: "localhost".into(),
+            port: 25,
+            from: "a@example.com".into(),
+            to: "b@example.com".into(),
+            user: None,
+            password_secret: None,
+        };
+        let message = format_message(&config, "Alert", "line one\n.hidden terminator");
+        assert!(message.starts_with("From: a@example.com\r\n"));
+        assert!(message.contains("Subject: Alert"));
+        assert!(message.contains("\r\n..hidden terminator"));
+    }
+
+    #[test]
+    fn base64_matches_known_vectors() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+}
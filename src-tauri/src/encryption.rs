@@ -0,0 +1,125 @@
+//! Opt-in at-rest encryption for the workspace database.
+//!
+//! Task prompts and results routinely contain proprietary code, so the
+//! database can be run through SQLCipher (enable the `sqlcipher` cargo
+//! feature) with a randomly generated key held in the macOS Keychain --
+//! never in a file next to the database. A marker file in the data dir
+//! records that the database is encrypted, since that fact cannot be
+//! read from the encrypted database itself.
+
+use std::path::Path;
+#[cfg(target_os = "macos")]
+use std::process::Command;
+
+use crate::error::{AppError, AppResult};
+use crate::storage::Storage;
+
+/// Marker file in the data dir recording that the database is
+/// encrypted; its presence makes startup fetch the key first.
+pub const MARKER_FILE: &str = "db_encrypted";
+
+/// Keychain service/account the key is stored under.
+#[cfg(target_os = "macos")]
+const KEYCHAIN_SERVICE: &str = "oz-workspace-agent";
+#[cfg(target_os = "macos")]
+const KEYCHAIN_ACCOUNT: &str = "storage-key";
+
+/// Whether the database in `data_dir` is marked as encrypted.
+pub fn is_enabled(data_dir: &Path) -> bool {
+    data_dir.join(MARKER_FILE).exists()
+}
+
+/// The key startup must apply before touching the database: `None`
+/// when encryption is not enabled, the Keychain-held key when it is.
+pub fn database_key(data_dir: &Path) -> AppResult<Option<String>> {
+    if !is_enabled(data_dir) {
+        return Ok(None);
+    }
+    keychain_read().map(Some)
+}
+
+/// Encrypt the live database in place: generate a key, store it in the
+/// Keychain, rewrite the database through SQLCipher's export, and drop
+/// the marker file. The app must restart to reopen with the key; the
+/// caller surfaces that to the operator.
+pub fn enable(storage: &Storage, data_dir: &Path, db_path: &Path) -> AppResult<()> {
+    if is_enabled(data_dir) {
+        return Err(AppError::InvalidArgument(
+            "storage is already encrypted".into(),
+        ));
+    }
+    let key = generate_key();
+    keychain_store(&key)?;
+
+    let encrypted = db_path.with_extension("encrypted.db");
+    storage.export_encrypted(&encrypted, &key)?;
+    std::fs::rename(&encrypted, db_path)?;
+    std::fs::write(data_dir.join(MARKER_FILE), b"")?;
+    storage.add_notification(
+        "Storage encryption enabled",
+        "The database is now encrypted; restart the app to reopen it.",
+    )?;
+    Ok(())
+}
+
+/// 32 random bytes, hex-encoded. Never logged and never written to
+/// disk; the Keychain is its only home.
+fn generate_key() -> String {
+    let a = uuid::Uuid::new_v4();
+    let b = uuid::Uuid::new_v4();
+    format!("{}{}", a.simple(), b.simple())
+}
+
+#[cfg(target_os = "macos")]
+fn keychain_store(key: &str) -> AppResult<()> {
+    let status = Command::new("security")
+        .args([
+            "add-generic-password",
+            "-U",
+            "-s",
+            KEYCHAIN_SERVICE,
+            "-a",
+            KEYCHAIN_ACCOUNT,
+            "-w",
+            key,
+        ])
+        .status()?;
+    if !status.success() {
+        return Err(AppError::Provider("keychain write failed".into()));
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn keychain_read() -> AppResult<String> {
+    let output = Command::new("security")
+        .args([
+            "find-generic-password",
+            "-s",
+            KEYCHAIN_SERVICE,
+            "-a",
+            KEYCHAIN_ACCOUNT,
+            "-w",
+        ])
+        .output()?;
+    if !output.status.success() {
+        return Err(AppError::Provider(
+            "database is marked encrypted but its key is not in the Keychain".into(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(not(target_os = "macos"))]
+fn keychain_store(_key: &str) -> AppResult<()> {
+    Err(AppError::InvalidArgument(
+        "encrypted storage requires the macOS Keychain".into(),
+    ))
+}
+
+#[cfg(not(target_os = "macos"))]
+fn keychain_read() -> AppResult<String> {
+    Err(AppError::InvalidArgument(
+        "encrypted storage requires the macOS Keychain".into(),
+    ))
+}
@@ -0,0 +1,107 @@
+use serde::{Serialize, Serializer};
+
+/// Unified error type returned by every IPC command.
+///
+/// Tauri serializes command errors to the frontend, so this implements
+/// `Serialize` by rendering the display form.
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error("storage error: {0}")]
+    Storage(rusqlite::Error),
+
+    /// The database was locked past the busy timeout. Transient by
+    /// definition: callers may retry.
+    #[error("storage is busy; retry shortly")]
+    Busy,
+
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("{kind} not found: {id}")]
+    NotFound { kind: &'static str, id: String },
+
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+
+    #[error("secret {0} is registered but has no value")]
+    SecretNotSet(String),
+
+    #[error("conflicting edit on {entity}: expected revision {expected}, current {current}")]
+    EditConflict {
+        entity: String,
+        expected: u64,
+        current: u64,
+    },
+
+    #[error("agent {0} is already running a task")]
+    AgentBusy(String),
+
+    #[error("agent {0} is paused")]
+    AgentPaused(String),
+
+    #[error("agent {agent_id} dependency {service} is down: {reason}")]
+    DependencyDown {
+        agent_id: String,
+        service: String,
+        reason: String,
+    },
+
+    #[error("provider error: {0}")]
+    Provider(String),
+
+    #[error("internal error: {0}")]
+    Internal(String),
+
+    #[error("task {task_id} exceeded its max cost: spent ${spent:.4} of ${limit:.4}")]
+    BudgetExceeded {
+        task_id: String,
+        spent: f64,
+        limit: f64,
+    },
+
+    #[error("task {task_id} timed out after {seconds}s")]
+    Timeout { task_id: String, seconds: u64 },
+
+    #[error("tool {tool} was denied by the operator for task {task_id}")]
+    ApprovalDenied { task_id: String, tool: String },
+
+    #[error("dispatch requires confirmation (matched rule {rule:?})")]
+    ConfirmationRequired { rule: String },
+
+    #[error("storage is in maintenance mode ({0}); try again shortly")]
+    MaintenanceMode(String),
+
+    #[error("task {task_id} is in state {status} and cannot transition to {requested}")]
+    InvalidTransition {
+        task_id: String,
+        status: String,
+        requested: String,
+    },
+}
+
+impl AppError {
+    pub fn not_found(kind: &'static str, id: impl Into<String>) -> Self {
+        Self::NotFound {
+            kind,
+            id: id.into(),
+        }
+    }
+}
+
+impl From<rusqlite::Error> for AppError {
+    fn from(err: rusqlite::Error) -> Self {
+        match err.sqlite_error_code() {
+            Some(rusqlite::ErrorCode::DatabaseBusy)
+            | Some(rusqlite::ErrorCode::DatabaseLocked) => Self::Busy,
+            _ => Self::Storage(err),
+        }
+    }
+}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+pub type AppResult<T> = Result<T, AppError>;
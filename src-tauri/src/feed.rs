@@ -0,0 +1,401 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppResult;
+use crate::storage::Storage;
+
+/// Settings key for the Atom export target; unset disables the export.
+pub const ATOM_PATH_SETTING: &str = "feed.atom_path";
+/// Settings key for the export cadence in seconds.
+pub const ATOM_INTERVAL_SETTING: &str = "feed.atom_interval_seconds";
+const DEFAULT_ATOM_INTERVAL_SECONDS: u64 = 60;
+/// Event kinds worth a feed reader's attention: terminal outcomes and
+/// warnings, not per-token noise.
+const ATOM_KINDS: &[&str] = &["completed", "failed", "cancelled", "blocked", "warning"];
+const ATOM_ENTRIES: u32 = 50;
+
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 200;
+const SUMMARY_CHARS: usize = 140;
+
+/// Filters and cursor for one activity feed page. The cursor is the
+/// last event id of the previous page; items are returned newest-first.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FeedQuery {
+    pub cursor: Option<i64>,
+    pub limit: Option<u32>,
+    pub agent_id: Option<String>,
+    pub kinds: Option<Vec<String>>,
+}
+
+/// One display-ready feed entry. Consecutive events of the same kind on
+/// the same task are collapsed into a single item with a count.
+#[derive(Debug, Clone, Serialize)]
+pub struct FeedItem {
+    pub task_id: String,
+    pub task_title: String,
+    pub agent_id: String,
+    pub agent_name: String,
+    pub kind: String,
+    pub summary: String,
+    /// How many raw events this item collapses.
+    pub count: u32,
+    pub last_event_id: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FeedPage {
+    pub items: Vec<FeedItem>,
+    /// Pass back as the next query's cursor; `None` when exhausted.
+    pub next_cursor: Option<i64>,
+}
+
+/// A raw event row joined with its task and agent, as read by storage.
+pub struct FeedEventRow {
+    pub event_id: i64,
+    pub task_id: String,
+    pub task_title: String,
+    pub agent_id: String,
+    pub agent_name: String,
+    pub kind: String,
+    pub payload: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Build one page of the merged activity feed. Shaping (grouping by
+/// task, duplicate collapsing, summaries) happens here so every surface
+/// renders the same feed.
+pub fn get_activity_feed(storage: &Storage, query: &FeedQuery) -> AppResult<FeedPage> {
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+    let rows = storage.query_feed_events(
+        query.cursor,
+        // Over-fetch one row to detect whether another page exists.
+        i64::from(limit) + 1,
+        query.agent_id.as_deref(),
+        query.kinds.as_deref(),
+    )?;
+    let has_more = rows.len() as i64 > i64::from(limit);
+
+    let mut items: Vec<FeedItem> = Vec::new();
+    let mut last_raw_id = None;
+    for row in rows.into_iter().take(limit as usize) {
+        last_raw_id = Some(row.event_id);
+        // Collapse into the previous item when task and kind repeat.
+        if let Some(prev) = items.last_mut() {
+            if prev.task_id == row.task_id && prev.kind == row.kind {
+                prev.count += 1;
+                continue;
+            }
+        }
+        items.push(FeedItem {
+            summary: summarize(&row),
+            task_id: row.task_id,
+            task_title: row.task_title,
+            agent_id: row.agent_id,
+            agent_name: row.agent_name,
+            kind: row.kind,
+            count: 1,
+            last_event_id: row.event_id,
+            created_at: row.created_at,
+        });
+    }
+
+    Ok(FeedPage {
+        items,
+        next_cursor: if has_more { last_raw_id } else { None },
+    })
+}
+
+/// One-line human summary per event kind, for direct display.
+fn summarize(row: &FeedEventRow) -> String {
+    let payload = row.payload.as_ref();
+    let text = match row.kind.as_str() {
+        "dispatched" => format!("{} queued \"{}\"", row.agent_name, row.task_title),
+        "api_call" => format!(
+            "Called {}",
+            payload
+                .and_then(|p| p["provider"].as_str())
+                .unwrap_or("the model")
+        ),
+        "usage" => format!(
+            "Used {} prompt + {} completion tokens",
+            payload.and_then(|p| p["prompt_tokens"].as_u64()).unwrap_or(0),
+            payload
+                .and_then(|p| p["completion_tokens"].as_u64())
+                .unwrap_or(0)
+        ),
+        "output" => payload
+            .and_then(|p| p["text"].as_str())
+            .unwrap_or("Produced output")
+            .to_string(),
+        other => payload
+            .and_then(|p| p["message"].as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| other.replace('_', " ")),
+    };
+    if text.chars().count() > SUMMARY_CHARS {
+        let cut: String = text.chars().take(SUMMARY_CHARS).collect();
+        format!("{cut}\u{2026}")
+    } else {
+        text
+    }
+}
+
+/// Render recent high-severity activity (completed tasks, failures,
+/// warnings) as an Atom feed, so users can follow agent activity from
+/// a feed reader without opening the app.
+pub fn render_atom(storage: &Storage) -> AppResult<String> {
+    let page = get_activity_feed(
+        storage,
+        &FeedQuery {
+            limit: Some(ATOM_ENTRIES),
+            kinds: Some(ATOM_KINDS.iter().map(|k| k.to_string()).collect()),
+            ..FeedQuery::default()
+        },
+    )?;
+    let updated = page
+        .items
+        .first()
+        .map(|item| item.created_at)
+        .unwrap_or_else(Utc::now);
+
+    let mut xml = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n",
+        "<feed xmlns=\"http://www.w3.org/2005/Atom\">\n",
+        "  <title>Workspace agent activity</title>\n",
+        "  <id>urn:oz-workspace-agent:activity</id>\n",
+    ));
+    xml.push_str(&format!("  <updated>{}</updated>\n", updated.to_rfc3339()));
+    for item in &page.items {
+        xml.push_str("  <entry>\n");
+        xml.push_str(&format!(
+            "    <title>{}: {} \u{2014} {}</title>\n",
+            escape(&item.agent_name),
+            escape(&item.kind),
+            escape(&item.task_title),
+        ));
+        xml.push_str(&format!(
+            "    <id>urn:oz-workspace-agent:event:{}</id>\n",
+            item.last_event_id
+        ));
+        xml.push_str(&format!(
+            "    <updated>{}</updated>\n",
+            item.created_at.to_rfc3339()
+        ));
+        xml.push_str(&format!(
+            "    <summary>{}</summary>\n",
+            escape(&item.summary)
+        ));
+        xml.push_str("  </entry>\n");
+    }
+    xml.push_str("</feed>\n");
+    Ok(xml)
+}
+
+/// Write the Atom feed to the configured path; a no-op when the export
+/// is not enabled. Returns the path written, if any.
+pub fn export_atom(storage: &Storage) -> AppResult<Option<std::path::PathBuf>> {
+    let Some(path) = storage.get_setting(ATOM_PATH_SETTING)? else {
+        return Ok(None);
+    };
+    let path = std::path::PathBuf::from(path);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, render_atom(storage)?)?;
+    Ok(Some(path))
+}
+
+/// Background loop keeping the exported feed fresh; idles cheaply while
+/// the export is unconfigured.
+pub fn export_loop(storage: &Storage) {
+    loop {
+        if let Err(err) = export_atom(storage) {
+            tracing::warn!(%err, "atom feed export failed");
+        }
+        let interval = storage
+            .get_setting(ATOM_INTERVAL_SETTING)
+            .ok()
+            .flatten()
+            .and_then(|raw| raw.parse().ok())
+            .unwrap_or(DEFAULT_ATOM_INTERVAL_SECONDS);
+        std::thread::sleep(std::time::Duration::from_secs(interval.max(30)));
+    }
+}
+
+/// Stream events matching the filters to a newline-delimited JSON file
+/// at `path`, one event per line, returning how many were written.
+/// Events are written as they are read, so exporting a large history
+/// never builds the whole payload in memory.
+#[allow(clippy::too_many_arguments)]
+pub fn export_events_jsonl(
+    storage: &Storage,
+    kind: Option<&str>,
+    task_id: Option<&str>,
+    agent_id: Option<&str>,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+    path: &std::path::Path,
+) -> AppResult<u64> {
+    use std::io::Write;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+    let written = storage.stream_events(kind, task_id, agent_id, start, end, |event| {
+        serde_json::to_writer(&mut writer, event)
+            .map_err(|err| crate::error::AppError::InvalidArgument(err.to_string()))?;
+        writer.write_all(b"\n")?;
+        Ok(())
+    })?;
+    writer.flush()?;
+    Ok(written)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Agent;
+    use crate::task_dispatch::{self, DispatchRequest};
+    use serde_json::json;
+
+    fn feed_fixture() -> (Storage, String) {
+        let storage = Storage::open_in_memory().unwrap();
+        let agent = Agent::new("feeder", "mock");
+        storage.create_agent(&agent).unwrap();
+        let task = task_dispatch::dispatch(
+            &storage,
+            &DispatchRequest::new(&agent.id, "demo", "p"),
+        )
+        .unwrap();
+        (storage, task.id)
+    }
+
+    #[test]
+    fn jsonl_export_streams_matching_events_line_by_line() {
+        let (storage, task_id) = feed_fixture();
+        storage
+            .append_event(&task_id, "output", Some(&json!({ "text": "hello" })))
+            .unwrap();
+        storage.append_event(&task_id, "progress", None).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("events.jsonl");
+        let written = export_events_jsonl(
+            &storage,
+            Some("output"),
+            Some(&task_id),
+            None,
+            None,
+            None,
+            &path,
+        )
+        .unwrap();
+        assert_eq!(written, 1);
+
+        let raw = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = raw.lines().collect();
+        assert_eq!(lines.len(), 1);
+        let event: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(event["kind"], "output");
+        assert_eq!(event["task_id"], task_id.as_str());
+    }
+
+    #[test]
+    fn collapses_consecutive_duplicates_per_task() {
+        let (storage, task_id) = feed_fixture();
+        for _ in 0..5 {
+            storage.append_event(&task_id, "progress", None).unwrap();
+        }
+        storage
+            .append_event(&task_id, "output", Some(&json!({ "text": "done" })))
+            .unwrap();
+
+        let page = get_activity_feed(&storage, &FeedQuery::default()).unwrap();
+        let progress = page.items.iter().find(|i| i.kind == "progress").unwrap();
+        assert_eq!(progress.count, 5);
+        assert_eq!(page.items[0].summary, "done");
+        assert!(page.next_cursor.is_none());
+    }
+
+    #[test]
+    fn paginates_with_a_stable_cursor() {
+        let (storage, task_id) = feed_fixture();
+        for i in 0..6 {
+            storage
+                .append_event(&task_id, &format!("kind_{i}"), None)
+                .unwrap();
+        }
+
+        let first = get_activity_feed(
+            &storage,
+            &FeedQuery { limit: Some(3), ..Default::default() },
+        )
+        .unwrap();
+        assert_eq!(first.items.len(), 3);
+        let cursor = first.next_cursor.expect("more pages");
+
+        let second = get_activity_feed(
+            &storage,
+            &FeedQuery { cursor: Some(cursor), limit: Some(10), ..Default::default() },
+        )
+        .unwrap();
+        assert!(second.items.iter().all(|i| i.last_event_id < cursor));
+        assert!(second.next_cursor.is_none());
+    }
+
+    #[test]
+    fn filters_by_agent_and_kind() {
+        let (storage, task_id) = feed_fixture();
+        storage.append_event(&task_id, "progress", None).unwrap();
+        let page = get_activity_feed(
+            &storage,
+            &FeedQuery { kinds: Some(vec!["progress".into()]), ..Default::default() },
+        )
+        .unwrap();
+        assert!(page.items.iter().all(|i| i.kind == "progress"));
+
+        let page = get_activity_feed(
+            &storage,
+            &FeedQuery { agent_id: Some("nobody".into()), ..Default::default() },
+        )
+        .unwrap();
+        assert!(page.items.is_empty());
+    }
+
+    #[test]
+    fn atom_export_renders_high_severity_items_and_escapes_markup() {
+        let (storage, task_id) = feed_fixture();
+        storage
+            .append_event(&task_id, "warning", Some(&json!({ "reason": "<b>timeout</b>" })))
+            .unwrap();
+        task_dispatch::execute(&storage, &task_id).unwrap();
+
+        let xml = render_atom(&storage).unwrap();
+        assert!(xml.starts_with("<?xml"));
+        assert!(xml.contains("<entry>"));
+        assert!(xml.contains("completed"));
+        // Token chunks are below the severity bar for a feed reader.
+        assert!(!xml.contains("token_chunk"));
+        assert!(!xml.contains("<b>"));
+
+        // Unconfigured export is a no-op; configured writes the file.
+        assert!(export_atom(&storage).unwrap().is_none());
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("activity.atom");
+        storage
+            .set_setting(ATOM_PATH_SETTING, &path.to_string_lossy())
+            .unwrap();
+        assert_eq!(export_atom(&storage).unwrap(), Some(path.clone()));
+        assert!(std::fs::read_to_string(path).unwrap().contains("<feed"));
+    }
+}
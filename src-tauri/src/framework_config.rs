@@ -0,0 +1,156 @@
+//! Per-framework agent config sections.
+//!
+//! Each execution framework needs its own extra fields (CrewAI crew
+//! roles, LangServe chain paths, OpenAI assistant ids). The sections
+//! are typed here, validated on save, and described by a field schema
+//! the frontend fetches to render dynamic forms.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{AppError, AppResult};
+
+/// Role definition for a CrewAI agent.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CrewAiConfig {
+    pub role: String,
+    pub goal: String,
+    #[serde(default)]
+    pub backstory: Option<String>,
+}
+
+/// Chain selection for LangChain/LangServe agents.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LangChainConfig {
+    /// Path of the runnable on the LangServe server, e.g. `/chain`.
+    pub chain: String,
+    /// Extra `config` object forwarded with each invocation.
+    #[serde(default)]
+    pub config: Option<Value>,
+}
+
+/// Assistant binding for OpenAI agents.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OpenAiConfig {
+    #[serde(default)]
+    pub assistant_id: Option<String>,
+    #[serde(default)]
+    pub organization: Option<String>,
+}
+
+/// The parsed, typed form of an agent's `framework_config`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum FrameworkConfig {
+    CrewAi(CrewAiConfig),
+    LangChain(LangChainConfig),
+    OpenAi(OpenAiConfig),
+}
+
+/// One field in a framework's config form.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldSpec {
+    pub key: &'static str,
+    pub label: &'static str,
+    /// "string" | "json"; the only kinds frameworks need so far.
+    pub kind: &'static str,
+    pub required: bool,
+}
+
+const fn field(key: &'static str, label: &'static str, kind: &'static str, required: bool) -> FieldSpec {
+    FieldSpec { key, label, kind, required }
+}
+
+const CREWAI_SCHEMA: &[FieldSpec] = &[
+    field("role", "Role", "string", true),
+    field("goal", "Goal", "string", true),
+    field("backstory", "Backstory", "string", false),
+];
+
+const LANGCHAIN_SCHEMA: &[FieldSpec] = &[
+    field("chain", "Chain path", "string", true),
+    field("config", "Invocation config", "json", false),
+];
+
+const OPENAI_SCHEMA: &[FieldSpec] = &[
+    field("assistant_id", "Assistant id", "string", false),
+    field("organization", "Organization", "string", false),
+];
+
+/// The config fields a framework accepts, for dynamic forms. Frameworks
+/// without extra config (mock, subprocess, scripted, ollama, anthropic)
+/// return an empty schema.
+pub fn schema_for(framework: &str) -> &'static [FieldSpec] {
+    match framework {
+        "crewai" => CREWAI_SCHEMA,
+        "langchain" | "langserve" => LANGCHAIN_SCHEMA,
+        "openai" => OPENAI_SCHEMA,
+        _ => &[],
+    }
+}
+
+/// Parse and validate a raw `framework_config` value against the
+/// agent's framework. Unknown keys and missing required fields are
+/// rejected so typos surface at save time, not mid-run.
+pub fn parse(framework: Option<&str>, raw: &Value) -> AppResult<Option<FrameworkConfig>> {
+    if raw.is_null() {
+        return Ok(None);
+    }
+    let err = |e: serde_json::Error, framework: &str| {
+        AppError::InvalidArgument(format!("invalid {framework} config: {e}"))
+    };
+    match framework {
+        Some("crewai") => Ok(Some(FrameworkConfig::CrewAi(
+            serde_json::from_value(raw.clone()).map_err(|e| err(e, "crewai"))?,
+        ))),
+        Some(fw @ ("langchain" | "langserve")) => Ok(Some(FrameworkConfig::LangChain(
+            serde_json::from_value(raw.clone()).map_err(|e| err(e, fw))?,
+        ))),
+        Some("openai") => Ok(Some(FrameworkConfig::OpenAi(
+            serde_json::from_value(raw.clone()).map_err(|e| err(e, "openai"))?,
+        ))),
+        Some(other) => Err(AppError::InvalidArgument(format!(
+            "framework {other} takes no extra config"
+        ))),
+        None => Err(AppError::InvalidArgument(
+            "framework_config requires an explicit framework".into(),
+        )),
+    }
+}
+
+/// Validate without keeping the parsed form, for the save path.
+pub fn validate(framework: Option<&str>, raw: &Value) -> AppResult<()> {
+    parse(framework, raw).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn crewai_config_requires_role_and_goal_and_rejects_unknown_keys() {
+        assert!(validate(Some("crewai"), &json!({ "role": "r", "goal": "g" })).is_ok());
+        assert!(validate(Some("crewai"), &json!({ "role": "r" })).is_err());
+        assert!(
+            validate(Some("crewai"), &json!({ "role": "r", "goal": "g", "rol": "typo" }))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn frameworks_without_config_reject_payloads_but_accept_null() {
+        assert!(validate(Some("mock"), &json!({ "anything": 1 })).is_err());
+        assert!(validate(Some("mock"), &Value::Null).is_ok());
+        assert!(validate(None, &Value::Null).is_ok());
+    }
+
+    #[test]
+    fn schema_lists_fields_for_dynamic_forms() {
+        let fields = schema_for("langserve");
+        assert!(fields.iter().any(|f| f.key == "chain" && f.required));
+        assert!(schema_for("subprocess").is_empty());
+    }
+}
@@ -0,0 +1,122 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::error::AppResult;
+use crate::models::{FailureKind, Task};
+use crate::storage::Storage;
+
+/// Settings key for the correlation window in seconds; failures with
+/// the same cause inside the window fold into one incident.
+pub const WINDOW_SETTING: &str = "incidents.window_seconds";
+const DEFAULT_WINDOW_SECONDS: i64 = 900;
+
+/// A group of correlated failures (e.g. a provider outage): several
+/// agents failing with the same classified cause inside a time window.
+#[derive(Debug, Clone, Serialize)]
+pub struct Incident {
+    pub id: String,
+    pub cause: FailureKind,
+    pub started_at: DateTime<Utc>,
+    /// Time of the most recent failure folded in.
+    pub last_seen_at: DateTime<Utc>,
+    /// Set when an operator closed the incident.
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub agent_ids: Vec<String>,
+    pub task_count: i64,
+    pub resolution: Option<String>,
+}
+
+fn window(storage: &Storage) -> Duration {
+    let seconds = storage
+        .get_setting(WINDOW_SETTING)
+        .ok()
+        .flatten()
+        .and_then(|raw| raw.parse().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(DEFAULT_WINDOW_SECONDS);
+    Duration::seconds(seconds)
+}
+
+/// Fold a just-failed task into the incident log: join the open
+/// incident with the same cause inside the correlation window, or open
+/// a new one. Cancellations are operator actions, not outages, and are
+/// not tracked.
+pub fn record_failure(storage: &Storage, task: &Task) -> AppResult<()> {
+    let Some(cause) = task.failure_kind else {
+        return Ok(());
+    };
+    if cause == FailureKind::Cancelled {
+        return Ok(());
+    }
+    let now = Utc::now();
+    match storage.find_open_incident(cause, now - window(storage))? {
+        Some(incident) => storage.attach_to_incident(&incident.id, &task.agent_id, now),
+        None => storage.create_incident(&Incident {
+            id: Uuid::new_v4().to_string(),
+            cause,
+            started_at: now,
+            last_seen_at: now,
+            resolved_at: None,
+            agent_ids: vec![task.agent_id.clone()],
+            task_count: 1,
+            resolution: None,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::AppError;
+    use crate::models::{Agent, TaskStatus};
+    use crate::task_dispatch::{self, DispatchRequest};
+
+    fn fail_task(storage: &Storage, agent_id: &str, kind: FailureKind) -> Task {
+        let task =
+            task_dispatch::dispatch(storage, &DispatchRequest::new(agent_id, "t", "p")).unwrap();
+        storage.claim_task(&task.id).unwrap();
+        storage
+            .finish_task(&task.id, TaskStatus::Failed, None, Some("boom"), Some(kind))
+            .unwrap();
+        storage.get_task(&task.id).unwrap()
+    }
+
+    #[test]
+    fn same_cause_failures_group_into_one_incident() {
+        let storage = Storage::open_in_memory().unwrap();
+        let first = Agent::new("a", "mock");
+        let second = Agent::new("b", "mock");
+        storage.create_agent(&first).unwrap();
+        storage.create_agent(&second).unwrap();
+
+        let failed = fail_task(&storage, &first.id, FailureKind::Provider);
+        record_failure(&storage, &failed).unwrap();
+        let failed = fail_task(&storage, &second.id, FailureKind::Provider);
+        record_failure(&storage, &failed).unwrap();
+        // A different cause opens its own incident.
+        let failed = fail_task(&storage, &first.id, FailureKind::Timeout);
+        record_failure(&storage, &failed).unwrap();
+
+        let incidents = storage.list_incidents().unwrap();
+        assert_eq!(incidents.len(), 2);
+        let outage = incidents
+            .iter()
+            .find(|i| i.cause == FailureKind::Provider)
+            .unwrap();
+        assert_eq!(outage.task_count, 2);
+        assert_eq!(outage.agent_ids.len(), 2);
+
+        storage
+            .resolve_incident(&outage.id, "provider restored service")
+            .unwrap();
+        // A resolved incident no longer absorbs new failures.
+        let failed = fail_task(&storage, &first.id, FailureKind::Provider);
+        record_failure(&storage, &failed).unwrap();
+        assert_eq!(storage.list_incidents().unwrap().len(), 3);
+        assert!(matches!(
+            storage.resolve_incident(&outage.id, "again"),
+            Err(AppError::NotFound { .. })
+        ));
+    }
+}
@@ -0,0 +1,320 @@
+pub mod api_tokens;
+pub mod artifacts;
+pub mod backup;
+pub mod commands;
+pub mod composites;
+pub mod diagnostics;
+pub mod digest;
+pub mod email;
+pub mod encryption;
+pub mod discovery;
+pub mod error;
+pub mod feed;
+pub mod framework_config;
+pub mod health;
+pub mod incidents;
+pub mod maintenance;
+pub mod mcp;
+pub mod metrics;
+pub mod models;
+pub mod policy;
+pub mod pricing;
+pub mod providers;
+pub mod scheduler;
+pub mod scripted_mock;
+pub mod secrets;
+pub mod settings_io;
+pub mod state;
+pub mod status_page;
+pub mod storage;
+pub mod subprocess;
+pub mod task_dispatch;
+pub mod telemetry;
+pub mod templates;
+pub mod webhooks;
+pub mod windows;
+pub mod worker_pool;
+
+use std::path::{Path, PathBuf};
+
+use tauri::Manager;
+
+use error::AppResult;
+use state::{AppState, BackendHealth};
+use storage::Storage;
+
+/// Marker file in the data dir pointing at an alternate database path,
+/// written by the degraded-startup recovery flow.
+const DB_PATH_OVERRIDE_FILE: &str = "db_path_override";
+
+/// Resolve the database path for this data dir, honoring an override
+/// left by a previous recovery.
+pub fn resolve_db_path(data_dir: &Path) -> PathBuf {
+    match std::fs::read_to_string(data_dir.join(DB_PATH_OVERRIDE_FILE)) {
+        Ok(path) if !path.trim().is_empty() => PathBuf::from(path.trim()),
+        _ => data_dir.join("workspace.db"),
+    }
+}
+
+/// Persist a database path override for future startups.
+pub fn write_db_path_override(data_dir: &Path, db_path: &Path) -> AppResult<()> {
+    std::fs::write(
+        data_dir.join(DB_PATH_OVERRIDE_FILE),
+        db_path.to_string_lossy().as_bytes(),
+    )?;
+    Ok(())
+}
+
+/// Open storage and artifacts for `data_dir` and hang them on the app.
+/// Shared by normal startup and the recovery commands. Only the
+/// critical path runs here; everything else is deferred to
+/// [`init_deferred`] so the window shows fast on large databases.
+pub fn init_backend(app: &tauri::AppHandle, data_dir: &Path, db_path: &Path) -> AppResult<()> {
+    std::fs::create_dir_all(data_dir)?;
+    let key = encryption::database_key(data_dir)?;
+    let storage = Storage::open_with_key(db_path, key.as_deref())?;
+    let artifacts = artifacts::ArtifactStore::new(data_dir.join("artifacts"))?;
+    storage.set_artifacts_root(artifacts.root().to_path_buf());
+    app.manage(AppState::new(storage, artifacts));
+
+    let state = app.state::<AppState>();
+    for name in ["diagnostics", "dependency_probes", "mcp_tools"] {
+        state.readiness.pending(name);
+    }
+    let handle = app.clone();
+    let backup_dir = data_dir.to_path_buf();
+    std::thread::spawn(move || {
+        let state = handle.state::<AppState>();
+        backup::backup_loop(&state.storage, &backup_dir);
+    });
+
+    let handle = app.clone();
+    let data_dir = data_dir.to_path_buf();
+    std::thread::spawn(move || init_deferred(&handle, &data_dir));
+
+    let handle = app.clone();
+    std::thread::spawn(move || {
+        let state = handle.state::<AppState>();
+        maintenance::scheduler_loop(&state.storage);
+    });
+
+    let handle = app.clone();
+    std::thread::spawn(move || {
+        let state = handle.state::<AppState>();
+        scheduler::scheduler_loop(&state.storage, &state.workers);
+    });
+
+    let handle = app.clone();
+    std::thread::spawn(move || {
+        let state = handle.state::<AppState>();
+        status_page::publisher_loop(&state.storage);
+    });
+
+    let handle = app.clone();
+    std::thread::spawn(move || {
+        let state = handle.state::<AppState>();
+        feed::export_loop(&state.storage);
+    });
+
+    let handle = app.clone();
+    std::thread::spawn(move || {
+        let state = handle.state::<AppState>();
+        task_dispatch::escalation_loop(&state.storage);
+    });
+
+    let handle = app.clone();
+    std::thread::spawn(move || {
+        let state = handle.state::<AppState>();
+        windows::task_event_bridge(&handle, &state.storage, &state.subscriptions);
+    });
+
+    let handle = app.clone();
+    std::thread::spawn(move || {
+        let state = handle.state::<AppState>();
+        telemetry::submit_loop(&state.storage);
+    });
+
+    let handle = app.clone();
+    std::thread::spawn(move || {
+        let state = handle.state::<AppState>();
+        let enabled = matches!(
+            state.storage.get_setting(webhooks::ENABLED_SETTING),
+            Ok(Some(ref raw)) if raw == "true"
+        );
+        if enabled {
+            webhooks::serve_loop(&state.storage, &state.workers);
+        }
+    });
+    Ok(())
+}
+
+/// Non-critical subsystem init, run on a background thread after the
+/// window is up. Progress is visible through `get_health()`.
+fn init_deferred(app: &tauri::AppHandle, data_dir: &Path) {
+    let state = app.state::<AppState>();
+
+    if let Err(err) = diagnostics::run_startup(&state.storage, data_dir) {
+        tracing::warn!(%err, "startup diagnostics failed to run");
+    }
+    state.readiness.mark_ready("diagnostics");
+
+    // The execution queue only lives in memory; rebuild it from the
+    // persisted tasks before anything new is dispatched.
+    match state.workers.restore_queue(&state.storage) {
+        Ok(restored) if restored > 0 => tracing::info!(restored, "task queue restored"),
+        Ok(_) => {}
+        Err(err) => tracing::warn!(%err, "task queue restore failed"),
+    }
+
+    match state.storage.get_all_agents() {
+        Ok(agents) => {
+            for agent in &agents {
+                for url in &agent.dependencies {
+                    if let Err(err) = health::probe_cached(&state.storage, url) {
+                        tracing::debug!(url, %err, "dependency warm-up probe failed");
+                    }
+                }
+            }
+            state.readiness.mark_ready("dependency_probes");
+            for agent in &agents {
+                if let Err(err) = mcp::refresh_agent_tools(&state.storage, agent) {
+                    tracing::debug!(agent = %agent.id, %err, "MCP warm-up discovery failed");
+                }
+            }
+            state.readiness.mark_ready("mcp_tools");
+        }
+        Err(err) => tracing::warn!(%err, "deferred init could not list agents"),
+    }
+}
+
+pub fn run() {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| "info".into()),
+        )
+        .init();
+
+    tauri::Builder::default()
+        .setup(|app| {
+            let health = BackendHealth::default();
+            let data_dir = app.path().app_data_dir()?;
+            health.set_data_dir(data_dir.clone());
+            // Init failures no longer panic: the UI opens in a degraded
+            // state and offers recovery actions over IPC.
+            if let Err(err) = init_backend(app.handle(), &data_dir, &resolve_db_path(&data_dir)) {
+                tracing::error!(%err, "backend failed to initialize; starting degraded");
+                health.set_error(err.to_string());
+            }
+            app.manage(health);
+            Ok(())
+        })
+        .on_window_event(|window, event| {
+            if let tauri::WindowEvent::Destroyed = event {
+                if let Some(state) = window.try_state::<AppState>() {
+                    state.windows.unregister(window.label());
+                }
+            }
+        })
+        .invoke_handler(tauri::generate_handler![
+            commands::agents::create_agent,
+            commands::agents::get_all_agents,
+            commands::agents::delete_agent,
+            commands::agents::list_deleted_agents,
+            commands::agents::restore_agent,
+            commands::agents::purge_agent,
+            commands::agents::pause_agent,
+            commands::agents::resume_agent,
+            commands::agents::set_agent_avatar,
+            commands::agents::set_agent_min_severity,
+            commands::agents::get_agent_history,
+            commands::agents::get_agent_stats,
+            commands::agents::get_cost_breakdown,
+            commands::agents::get_framework_schema,
+            commands::agents::import_agents_csv,
+            commands::agents::discover_local_agents,
+            commands::agents::register_discovered_agent,
+            commands::agents::refresh_mcp_tools,
+            commands::agents::get_mcp_tools,
+            commands::tasks::invoke_mcp_tool,
+            commands::tasks::get_task_config_snapshot,
+            commands::tasks::dispatch,
+            commands::tasks::dispatch_tasks_batch,
+            commands::tasks::execute_task,
+            commands::tasks::cancel_task,
+            commands::tasks::retry_task,
+            commands::tasks::continue_task,
+            commands::tasks::approve_plan,
+            commands::tasks::provide_task_input,
+            commands::tasks::send_task_message,
+            commands::tasks::get_pending_tool_calls,
+            commands::tasks::resolve_tool_call,
+            commands::tasks::get_task_approvals,
+            commands::tasks::accept_result,
+            commands::tasks::request_changes,
+            commands::tasks::get_all_tasks,
+            commands::tasks::get_task_timeline,
+            commands::tasks::get_task_events,
+            commands::tasks::get_task_events_page,
+            commands::tasks::query_events,
+            commands::tasks::stream_task_events,
+            commands::tasks::read_result_range,
+            commands::tasks::move_task,
+            commands::tasks::get_board,
+            commands::tasks::upload_attachment,
+            commands::tasks::create_task_template,
+            commands::tasks::list_task_templates,
+            commands::tasks::delete_task_template,
+            commands::tasks::dispatch_from_template,
+            commands::tasks::create_schedule,
+            commands::tasks::list_schedules,
+            commands::tasks::pause_schedule,
+            commands::tasks::delete_schedule,
+            commands::settings::export_settings,
+            commands::settings::plan_import,
+            commands::settings::import_settings,
+            commands::settings::import_data,
+            commands::settings::send_test_email,
+            commands::settings::create_api_token,
+            commands::settings::list_api_tokens,
+            commands::settings::revoke_api_token,
+            commands::settings::get_secret_usage,
+            commands::settings::rotate_secret,
+            commands::settings::preview_telemetry_payload,
+            commands::settings::set_telemetry_enabled,
+            commands::settings::get_constitution,
+            commands::settings::set_constitution,
+            commands::workspace::generate_digest,
+            commands::workspace::get_activity_feed,
+            commands::workspace::search_events,
+            commands::workspace::export_events_jsonl,
+            commands::workspace::subscribe_window,
+            commands::workspace::subscribe_events,
+            commands::workspace::unsubscribe,
+            commands::workspace::query_metrics,
+            commands::workspace::get_startup_diagnostics,
+            commands::workspace::get_backend_status,
+            commands::workspace::get_health,
+            commands::workspace::query_archive,
+            commands::workspace::list_incidents,
+            commands::workspace::resolve_incident,
+            commands::workspace::run_maintenance,
+            commands::workspace::upgrade_storage,
+            commands::workspace::maintain_storage,
+            commands::workspace::repair_storage,
+            commands::workspace::publish_status_page,
+            commands::workspace::get_quick_status,
+            commands::workspace::save_composite,
+            commands::workspace::list_composites,
+            commands::workspace::delete_composite,
+            commands::workspace::run_composite,
+            commands::workspace::enable_storage_encryption,
+            commands::workspace::list_backups,
+            commands::workspace::restore_backup,
+            commands::workspace::migrate_storage,
+            commands::workspace::recover_with_db_path,
+            commands::workspace::recover_restore_backup,
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
@@ -0,0 +1,535 @@
+use std::time::Instant;
+
+use chrono::{Duration, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppResult;
+use crate::models::{AgentStatus, TaskStatus};
+use crate::storage::{Storage, MAINTENANCE_MODE_SETTING};
+
+/// Settings controlling the nightly job. The workspace-wide
+/// [`MAINTENANCE_MODE_SETTING`] flag suspends it entirely.
+pub const ENABLED_SETTING: &str = "maintenance.enabled";
+pub const HOUR_SETTING: &str = "maintenance.hour";
+pub const VACUUM_SETTING: &str = "maintenance.vacuum";
+pub const EVENT_RETENTION_SETTING: &str = "maintenance.event_retention_days";
+pub const ARCHIVE_RETENTION_SETTING: &str = "maintenance.archive_retention_days";
+pub const LAST_RUN_SETTING: &str = "maintenance.last_run_date";
+
+const DEFAULT_HOUR: u32 = 3;
+const DEFAULT_EVENT_RETENTION_DAYS: i64 = 90;
+const METRIC_RETENTION_DAYS: i64 = 30;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceSummary {
+    pub pruned_events: usize,
+    pub archived_tasks: usize,
+    pub rolled_up_metrics: usize,
+    pub wal_checkpointed: bool,
+    pub vacuumed: bool,
+    pub reclaimed_bytes: i64,
+    pub duration_ms: i64,
+}
+
+/// One full maintenance pass: prune old events for finished tasks,
+/// compact old command metrics into daily rollups, checkpoint the WAL
+/// and optionally VACUUM. Returns the summary that is also recorded as
+/// a notification.
+pub fn run_maintenance(storage: &Storage) -> AppResult<MaintenanceSummary> {
+    let started = Instant::now();
+    let size_before = storage.db_size_bytes()?;
+
+    let event_retention = storage
+        .get_setting(EVENT_RETENTION_SETTING)?
+        .and_then(|raw| raw.parse().ok())
+        .unwrap_or(DEFAULT_EVENT_RETENTION_DAYS);
+    let pruned_events =
+        storage.prune_terminal_task_events(Utc::now() - Duration::days(event_retention))?;
+    // Cold storage is opt-in: terminal tasks older than the threshold
+    // move (with their events) to the archive database instead of
+    // being deleted.
+    let archived_tasks = match storage
+        .get_setting(ARCHIVE_RETENTION_SETTING)?
+        .and_then(|raw| raw.parse::<i64>().ok())
+    {
+        Some(days) => storage.archive_tasks_before(Utc::now() - Duration::days(days))?,
+        None => 0,
+    };
+    let rolled_up_metrics =
+        storage.rollup_metrics_before(Utc::now() - Duration::days(METRIC_RETENTION_DAYS))?;
+
+    let wal_checkpointed = match storage.checkpoint_wal() {
+        Ok(()) => true,
+        Err(err) => {
+            tracing::warn!(%err, "WAL checkpoint failed");
+            false
+        }
+    };
+    let vacuumed = storage
+        .get_setting(VACUUM_SETTING)?
+        .map(|raw| raw == "true")
+        .unwrap_or(false);
+    if vacuumed {
+        storage.vacuum()?;
+    }
+
+    let summary = MaintenanceSummary {
+        pruned_events,
+        archived_tasks,
+        rolled_up_metrics,
+        wal_checkpointed,
+        vacuumed,
+        reclaimed_bytes: (size_before - storage.db_size_bytes()?).max(0),
+        duration_ms: started.elapsed().as_millis() as i64,
+    };
+    storage.add_notification(
+        "Database maintenance finished",
+        &format!(
+            "Pruned {} events, archived {} tasks, rolled up {} metric rows, reclaimed {} KB \
+             in {} ms",
+            summary.pruned_events,
+            summary.archived_tasks,
+            summary.rolled_up_metrics,
+            summary.reclaimed_bytes / 1024,
+            summary.duration_ms
+        ),
+    )?;
+    tracing::info!(?summary, "maintenance pass complete");
+    Ok(summary)
+}
+
+/// Whether the nightly pass should run right now: enabled, not in
+/// maintenance mode, at/after the configured hour and not yet run today.
+pub fn due_now(storage: &Storage) -> AppResult<bool> {
+    let enabled = storage
+        .get_setting(ENABLED_SETTING)?
+        .map(|raw| raw != "false")
+        .unwrap_or(true);
+    // Presence of the key is what counts, matching `claim_task`: the
+    // value is a free-form reason ("upgrading", "paused", ...).
+    let suspended = storage.get_setting(MAINTENANCE_MODE_SETTING)?.is_some();
+    if !enabled || suspended {
+        return Ok(false);
+    }
+    let hour = storage
+        .get_setting(HOUR_SETTING)?
+        .and_then(|raw| raw.parse().ok())
+        .unwrap_or(DEFAULT_HOUR);
+    let now = chrono::Local::now();
+    if now.hour() < hour {
+        return Ok(false);
+    }
+    let today = now.date_naive().to_string();
+    Ok(storage.get_setting(LAST_RUN_SETTING)?.as_deref() != Some(today.as_str()))
+}
+
+/// Nightly scheduler loop, run on its own thread: checks every minute
+/// and records the run date so one pass happens per day.
+pub fn scheduler_loop(storage: &Storage) {
+    loop {
+        match due_now(storage) {
+            Ok(true) => {
+                if let Err(err) = run_maintenance(storage) {
+                    tracing::warn!(%err, "maintenance pass failed");
+                }
+                let today = chrono::Local::now().date_naive().to_string();
+                if let Err(err) = storage.set_setting(LAST_RUN_SETTING, &today) {
+                    tracing::warn!(%err, "could not record maintenance run date");
+                }
+            }
+            Ok(false) => {}
+            Err(err) => tracing::warn!(%err, "maintenance due-check failed"),
+        }
+        std::thread::sleep(std::time::Duration::from_secs(60));
+    }
+}
+
+/// Settings key for how long the drain phase waits for in-flight tasks
+/// before checkpointing them.
+pub const DRAIN_TIMEOUT_SETTING: &str = "maintenance.drain_timeout_seconds";
+const DEFAULT_DRAIN_TIMEOUT_SECONDS: u64 = 30;
+const DRAIN_POLL: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// What a storage upgrade did, phase by phase.
+#[derive(Debug, Clone, Serialize)]
+pub struct UpgradeSummary {
+    /// In-flight tasks that finished during the drain window.
+    pub drained_tasks: usize,
+    /// Tasks still mid-flight at the deadline, checkpointed in place.
+    pub checkpointed_tasks: usize,
+    pub from_version: i64,
+    pub to_version: i64,
+}
+
+/// Drain-and-upgrade protocol for schema changes while tasks may be
+/// mid-flight: enter maintenance mode (claims are refused), wait for
+/// running tasks to finish, checkpoint any that outlast the drain
+/// window, apply the schema upgrade, then resume. `progress` is called
+/// at each phase so the caller can surface live progress; maintenance
+/// mode is always cleared, even when a phase fails.
+pub fn upgrade_storage(
+    storage: &Storage,
+    mut progress: impl FnMut(&str, serde_json::Value),
+) -> AppResult<UpgradeSummary> {
+    storage.set_setting(MAINTENANCE_MODE_SETTING, "upgrading")?;
+    progress("maintenance_mode", serde_json::json!({}));
+    let result = drain_and_upgrade(storage, &mut progress);
+    storage.delete_setting(MAINTENANCE_MODE_SETTING)?;
+    progress("resumed", serde_json::json!({}));
+    result
+}
+
+fn drain_and_upgrade(
+    storage: &Storage,
+    progress: &mut impl FnMut(&str, serde_json::Value),
+) -> AppResult<UpgradeSummary> {
+    let timeout = storage
+        .get_setting(DRAIN_TIMEOUT_SETTING)?
+        .and_then(|raw| raw.parse().ok())
+        .unwrap_or(DEFAULT_DRAIN_TIMEOUT_SECONDS);
+    let deadline = Instant::now() + std::time::Duration::from_secs(timeout);
+
+    let in_flight = |storage: &Storage| -> AppResult<Vec<crate::models::Task>> {
+        Ok(storage
+            .get_all_tasks()?
+            .into_iter()
+            .filter(|t| {
+                matches!(t.status, TaskStatus::Running | TaskStatus::WaitingForInput)
+            })
+            .collect())
+    };
+    let initial = in_flight(storage)?.len();
+    let mut remaining = initial;
+    while remaining > 0 && Instant::now() < deadline {
+        progress("draining", serde_json::json!({ "remaining": remaining }));
+        std::thread::sleep(DRAIN_POLL);
+        remaining = in_flight(storage)?.len();
+    }
+
+    // Whatever outlasted the drain window gets a checkpoint marker so
+    // a future executor can pick the run back up after the upgrade.
+    let stragglers = in_flight(storage)?;
+    for task in &stragglers {
+        storage.append_event(
+            &task.id,
+            "upgrade_checkpoint",
+            Some(&serde_json::json!({ "status": task.status })),
+        )?;
+    }
+    progress(
+        "checkpointed",
+        serde_json::json!({ "count": stragglers.len() }),
+    );
+
+    let (from_version, to_version) = storage.upgrade_schema()?;
+    progress(
+        "migrated",
+        serde_json::json!({ "from": from_version, "to": to_version }),
+    );
+
+    Ok(UpgradeSummary {
+        drained_tasks: initial - stragglers.len(),
+        checkpointed_tasks: stragglers.len(),
+        from_version,
+        to_version,
+    })
+}
+
+// ---- integrity repair ----
+
+/// What one [`repair_storage`] pass fixed. Every fix is also written to
+/// the matching audit trail: a `repair` task event, a `repair` agent
+/// history entry, or (for deleted orphan events, which have no parent
+/// left to write to) the summary notification.
+#[derive(Debug, Clone, Serialize)]
+pub struct RepairReport {
+    /// Non-terminal tasks whose agent was deleted, now marked Failed.
+    pub failed_orphan_tasks: Vec<String>,
+    /// Events whose task no longer existed, now removed.
+    pub deleted_orphan_events: u32,
+    /// Agents stuck Running with no in-flight task, reset to Idle.
+    pub reset_agents: Vec<String>,
+}
+
+impl RepairReport {
+    pub fn total_fixes(&self) -> u32 {
+        self.failed_orphan_tasks.len() as u32
+            + self.deleted_orphan_events
+            + self.reset_agents.len() as u32
+    }
+}
+
+/// Find and fix dangling references left behind by crashes or manual
+/// database surgery. Safe to run at any time: a clean database yields
+/// an empty report and writes nothing.
+pub fn repair_storage(storage: &Storage) -> AppResult<RepairReport> {
+    let failed_orphan_tasks = storage.find_tasks_with_missing_agents()?;
+    for task_id in &failed_orphan_tasks {
+        storage.set_task_status(
+            task_id,
+            TaskStatus::Failed,
+            None,
+            Some("agent no longer exists"),
+        )?;
+        storage.append_event(
+            task_id,
+            "repair",
+            Some(&serde_json::json!({ "fix": "orphan_task", "reason": "agent_missing" })),
+        )?;
+    }
+
+    let reset_agents = storage.find_stuck_running_agents()?;
+    for agent_id in &reset_agents {
+        storage.set_agent_status(agent_id, AgentStatus::Idle)?;
+        storage.append_agent_history(
+            agent_id,
+            "repair",
+            Some(&serde_json::json!({ "fix": "stuck_running", "reset_to": "idle" })),
+        )?;
+    }
+
+    let report = RepairReport {
+        failed_orphan_tasks,
+        deleted_orphan_events: storage.delete_orphan_events()?,
+        reset_agents,
+    };
+    if report.total_fixes() > 0 {
+        storage.add_notification(
+            "Storage repair finished",
+            &format!(
+                "Failed {} orphaned tasks, removed {} orphaned events, reset {} stuck agents",
+                report.failed_orphan_tasks.len(),
+                report.deleted_orphan_events,
+                report.reset_agents.len()
+            ),
+        )?;
+        tracing::info!(?report, "storage repair fixed inconsistencies");
+    }
+    Ok(report)
+}
+
+/// Outcome of an on-demand compaction and integrity pass.
+#[derive(Debug, Clone, Serialize)]
+pub struct StorageCheckReport {
+    /// Database size before and after VACUUM, in bytes.
+    pub size_before_bytes: i64,
+    pub size_after_bytes: i64,
+    /// `PRAGMA integrity_check` output: a single `ok`, or one line per
+    /// problem found.
+    pub integrity: Vec<String>,
+}
+
+impl StorageCheckReport {
+    pub fn is_healthy(&self) -> bool {
+        self.integrity.len() == 1 && self.integrity[0] == "ok"
+    }
+}
+
+/// Compact the database and check it for corruption: VACUUM + ANALYZE,
+/// then `PRAGMA integrity_check`. Long-lived installs accumulate dead
+/// pages from pruned events; this reclaims them and catches file-level
+/// damage before it spreads.
+pub fn maintain_storage(storage: &Storage) -> AppResult<StorageCheckReport> {
+    let size_before_bytes = storage.database_size_bytes()?;
+    // Check first: a corrupt database is better reported as-is than
+    // half-rewritten by a VACUUM that may itself fail.
+    let integrity = storage.integrity_check()?;
+    let report = if integrity.len() == 1 && integrity[0] == "ok" {
+        storage.vacuum_analyze()?;
+        StorageCheckReport {
+            size_before_bytes,
+            size_after_bytes: storage.database_size_bytes()?,
+            integrity,
+        }
+    } else {
+        StorageCheckReport {
+            size_before_bytes,
+            size_after_bytes: size_before_bytes,
+            integrity,
+        }
+    };
+    if !report.is_healthy() {
+        storage.add_notification(
+            "Storage integrity check failed",
+            &format!(
+                "integrity_check reported {} problems; restore a backup soon",
+                report.integrity.len()
+            ),
+        )?;
+        tracing::warn!(problems = report.integrity.len(), "database integrity check failed");
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Agent;
+    use crate::task_dispatch::{self, DispatchRequest};
+
+    #[test]
+    fn compaction_reports_sizes_and_a_clean_integrity_check() {
+        let storage = Storage::open_in_memory().unwrap();
+        let report = maintain_storage(&storage).unwrap();
+        assert!(report.is_healthy());
+        assert_eq!(report.integrity, vec!["ok".to_string()]);
+        assert!(report.size_before_bytes > 0);
+        // No shrink assertion: on a fresh database ANALYZE creates the
+        // sqlite_stat1 table, so the file can legitimately grow.
+        assert!(report.size_after_bytes > 0);
+    }
+
+    #[test]
+    fn prunes_old_events_and_reports_a_summary() {
+        let storage = Storage::open_in_memory().unwrap();
+        let agent = Agent::new("m", "mock");
+        storage.create_agent(&agent).unwrap();
+        let task = task_dispatch::dispatch(
+            &storage,
+            &DispatchRequest::new(&agent.id, "t", "p"),
+        )
+        .unwrap();
+        task_dispatch::execute(&storage, &task.id).unwrap();
+        storage
+            .backdate_task_events_for_test(&task.id, Utc::now() - Duration::days(400))
+            .unwrap();
+
+        let summary = run_maintenance(&storage).unwrap();
+        assert!(summary.pruned_events > 0);
+        assert!(storage.get_task_events(&task.id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn old_terminal_tasks_move_to_the_archive_database() {
+        let storage = Storage::open_in_memory().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        storage.set_archive_path(dir.path().join("archive.db"));
+        storage.set_setting(ARCHIVE_RETENTION_SETTING, "30").unwrap();
+        let agent = Agent::new("m", "mock");
+        storage.create_agent(&agent).unwrap();
+
+        let old = task_dispatch::dispatch(
+            &storage,
+            &DispatchRequest::new(&agent.id, "ancient report", "p"),
+        )
+        .unwrap();
+        task_dispatch::execute(&storage, &old.id).unwrap();
+        storage
+            .backdate_task_for_test(&old.id, Utc::now() - Duration::days(60))
+            .unwrap();
+        let fresh = task_dispatch::dispatch(
+            &storage,
+            &DispatchRequest::new(&agent.id, "recent", "p"),
+        )
+        .unwrap();
+        task_dispatch::execute(&storage, &fresh.id).unwrap();
+
+        let summary = run_maintenance(&storage).unwrap();
+        assert_eq!(summary.archived_tasks, 1);
+        // Gone from the hot database, still findable in the archive.
+        assert!(storage.get_task(&old.id).is_err());
+        assert!(storage.get_task(&fresh.id).is_ok());
+        let hits = storage.query_archive(Some("ancient"), 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].id, old.id);
+        assert!(storage.query_archive(Some("recent"), 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn repair_fixes_dangling_references_and_audits_each_one() {
+        let storage = Storage::open_in_memory().unwrap();
+        let agent = Agent::new("keeper", "mock");
+        storage.create_agent(&agent).unwrap();
+        let doomed = Agent::new("doomed", "mock");
+        storage.create_agent(&doomed).unwrap();
+
+        let orphan_task = task_dispatch::dispatch(
+            &storage,
+            &DispatchRequest::new(&doomed.id, "orphaned", "p"),
+        )
+        .unwrap();
+        let deleted_task = task_dispatch::dispatch(
+            &storage,
+            &DispatchRequest::new(&agent.id, "short-lived", "p"),
+        )
+        .unwrap();
+        storage.append_event(&deleted_task.id, "progress", None).unwrap();
+
+        // Corrupt the database the way a crash or manual SQL would;
+        // the deletes need foreign keys off or SQLite refuses them.
+        storage
+            .corrupt_for_test(&[
+                ("DELETE FROM agents WHERE id = ?1", doomed.id.as_str()),
+                ("DELETE FROM tasks WHERE id = ?1", deleted_task.id.as_str()),
+            ])
+            .unwrap();
+        storage.set_agent_status(&agent.id, AgentStatus::Running).unwrap();
+
+        let report = repair_storage(&storage).unwrap();
+        assert_eq!(report.failed_orphan_tasks, vec![orphan_task.id.clone()]);
+        assert!(report.deleted_orphan_events >= 1);
+        assert_eq!(report.reset_agents, vec![agent.id.clone()]);
+
+        let task = storage.get_task(&orphan_task.id).unwrap();
+        assert_eq!(task.status, TaskStatus::Failed);
+        let events = storage.get_task_events(&orphan_task.id).unwrap();
+        assert!(events.iter().any(|e| e.kind == "repair"));
+        let history = storage.get_agent_history(&agent.id).unwrap();
+        assert!(history.iter().any(|h| h.kind == "repair"));
+
+        // A clean database is left untouched.
+        let report = repair_storage(&storage).unwrap();
+        assert_eq!(report.total_fixes(), 0);
+    }
+
+    #[test]
+    fn maintenance_mode_suspends_the_job() {
+        let storage = Storage::open_in_memory().unwrap();
+        storage.set_setting(MAINTENANCE_MODE_SETTING, "paused").unwrap();
+        assert!(!due_now(&storage).unwrap());
+    }
+
+    #[test]
+    fn upgrades_drain_checkpoint_and_always_leave_maintenance_mode() {
+        let storage = Storage::open_in_memory().unwrap();
+        let agent = Agent::new("a", "mock");
+        storage.create_agent(&agent).unwrap();
+        let task = task_dispatch::dispatch(
+            &storage,
+            &DispatchRequest::new(&agent.id, "t", "p"),
+        )
+        .unwrap();
+        storage.claim_task(&task.id).unwrap();
+        // Make the drain window immediate so the running task gets
+        // checkpointed instead of waited on.
+        storage.set_setting(DRAIN_TIMEOUT_SETTING, "0").unwrap();
+
+        let mut phases = Vec::new();
+        let summary =
+            upgrade_storage(&storage, |phase, _| phases.push(phase.to_string())).unwrap();
+        assert_eq!(summary.checkpointed_tasks, 1);
+        assert_eq!(summary.to_version, crate::storage::SCHEMA_VERSION);
+        assert!(phases.first().unwrap() == "maintenance_mode");
+        assert!(phases.last().unwrap() == "resumed");
+        let events = storage.get_task_events(&task.id).unwrap();
+        assert!(events.iter().any(|e| e.kind == "upgrade_checkpoint"));
+
+        // Maintenance mode blocked claims mid-upgrade and is cleared
+        // afterwards.
+        let queued = task_dispatch::dispatch(
+            &storage,
+            &DispatchRequest::new(&agent.id, "later", "p"),
+        )
+        .unwrap();
+        storage
+            .set_setting(MAINTENANCE_MODE_SETTING, "upgrading")
+            .unwrap();
+        assert!(matches!(
+            storage.claim_task(&queued.id),
+            Err(crate::error::AppError::MaintenanceMode(_))
+        ));
+        storage
+            .delete_setting(MAINTENANCE_MODE_SETTING)
+            .unwrap();
+    }
+}
@@ -0,0 +1,130 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::error::{AppError, AppResult};
+use crate::models::Agent;
+use crate::storage::Storage;
+
+/// A tool discovered on an MCP server, as stored per server endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct McpTool {
+    pub server: String,
+    pub name: String,
+    pub description: String,
+    /// The tool's JSON input schema, verbatim from the server.
+    pub input_schema: Value,
+}
+
+/// Minimal MCP client speaking JSON-RPC over HTTP to one server.
+pub struct McpClient {
+    endpoint: String,
+}
+
+impl McpClient {
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        Self { endpoint: endpoint.into() }
+    }
+
+    fn rpc(&self, method: &str, params: Value) -> AppResult<Value> {
+        let body = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let response: Value = reqwest::blocking::Client::new()
+            .post(&self.endpoint)
+            .json(&body)
+            .send()
+            .and_then(|resp| resp.error_for_status())
+            .map_err(|err| AppError::Provider(format!("mcp ({}): {err}", self.endpoint)))?
+            .json()
+            .map_err(|err| AppError::Provider(format!("mcp: invalid response: {err}")))?;
+        if let Some(err) = response.get("error").filter(|e| !e.is_null()) {
+            return Err(AppError::Provider(format!("mcp: {err}")));
+        }
+        Ok(response["result"].clone())
+    }
+
+    /// `tools/list`: the tools this server exposes.
+    pub fn list_tools(&self) -> AppResult<Vec<McpTool>> {
+        let result = self.rpc("tools/list", json!({}))?;
+        let tools = result["tools"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default()
+            .into_iter()
+            .map(|tool| McpTool {
+                server: self.endpoint.clone(),
+                name: tool["name"].as_str().unwrap_or_default().to_string(),
+                description: tool["description"].as_str().unwrap_or_default().to_string(),
+                input_schema: tool["inputSchema"].clone(),
+            })
+            .collect();
+        Ok(tools)
+    }
+
+    /// `tools/call`: invoke one tool with JSON arguments.
+    pub fn call_tool(&self, name: &str, arguments: Value) -> AppResult<Value> {
+        self.rpc("tools/call", json!({ "name": name, "arguments": arguments }))
+    }
+}
+
+/// Discover and store the tools on every MCP server an agent declares.
+/// Unreachable servers are skipped with a warning so one bad server
+/// does not block the rest.
+pub fn refresh_agent_tools(storage: &Storage, agent: &Agent) -> AppResult<Vec<McpTool>> {
+    let mut all = Vec::new();
+    for server in &agent.mcp_servers {
+        match McpClient::new(server).list_tools() {
+            Ok(tools) => {
+                storage.replace_mcp_tools(server, &tools)?;
+                all.extend(tools);
+            }
+            Err(err) => {
+                tracing::warn!(server, %err, "MCP tool discovery failed");
+            }
+        }
+    }
+    Ok(all)
+}
+
+/// Invoke a discovered tool on behalf of a running task, surfacing the
+/// call and its outcome in the activity feed.
+pub fn invoke_tool(
+    storage: &Storage,
+    task_id: &str,
+    server: &str,
+    name: &str,
+    arguments: Value,
+) -> AppResult<Value> {
+    storage.get_mcp_tool(server, name)?.ok_or_else(|| {
+        AppError::not_found("mcp tool", format!("{name} on {server}"))
+    })?;
+    storage.append_event(
+        task_id,
+        "api_call",
+        Some(&json!({ "provider": "mcp", "server": server, "tool": name })),
+    )?;
+    let started = Utc::now();
+    let result = McpClient::new(server).call_tool(name, arguments);
+    let elapsed_ms = (Utc::now() - started).num_milliseconds();
+    match &result {
+        Ok(value) => {
+            storage.append_event(
+                task_id,
+                "action",
+                Some(&json!({
+                    "tool": name,
+                    "server": server,
+                    "elapsed_ms": elapsed_ms,
+                    "result": value,
+                })),
+            )?;
+        }
+        Err(err) => {
+            storage.append_event(
+                task_id,
+                "tool_error",
+                Some(&json!({ "tool": name, "server": server, "error": err.to_string() })),
+            )?;
+        }
+    }
+    result
+}
@@ -0,0 +1,274 @@
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{AppError, AppResult};
+use crate::storage::Storage;
+
+/// Calls slower than this are logged with their (redacted) parameters.
+pub const SLOW_CALL_THRESHOLD: Duration = Duration::from_millis(250);
+
+/// Keys whose values are never logged, matched as case-insensitive
+/// substrings of the parameter name.
+const SENSITIVE_KEYS: &[&str] = &["secret", "token", "password", "api_key", "apikey"];
+
+const MAX_LOGGED_STRING: usize = 120;
+
+/// Wrap an IPC command body: records its duration into the
+/// `command_metrics` table and logs slow calls with redacted parameters.
+///
+/// Every `#[tauri::command]` routes through this so janky UI can be
+/// traced back to the specific slow backend call.
+pub fn timed<T>(
+    storage: &Storage,
+    command: &'static str,
+    params: Value,
+    f: impl FnOnce() -> AppResult<T>,
+) -> AppResult<T> {
+    let start = Instant::now();
+    let result = f();
+    let elapsed = start.elapsed();
+
+    // Metrics are best-effort; never fail the command over bookkeeping.
+    if let Err(err) =
+        storage.record_command_metric(command, elapsed.as_millis() as i64, result.is_ok())
+    {
+        tracing::debug!(command, %err, "failed to record command metric");
+    }
+
+    if elapsed >= SLOW_CALL_THRESHOLD {
+        tracing::warn!(
+            command,
+            elapsed_ms = elapsed.as_millis() as u64,
+            params = %redact(params),
+            ok = result.is_ok(),
+            "slow IPC command"
+        );
+    }
+
+    result
+}
+
+/// A query over the command metrics, Grafana-style: an expression, a
+/// time range and a step width for bucketing.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MetricsQuery {
+    /// `count`, `avg_duration_ms` or `error_rate`, optionally filtered
+    /// like `avg_duration_ms{command="dispatch"}`.
+    pub expr: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub step_seconds: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricsPoint {
+    pub timestamp: DateTime<Utc>,
+    pub value: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricsSeries {
+    pub expr: String,
+    pub points: Vec<MetricsPoint>,
+}
+
+#[derive(Debug, PartialEq)]
+enum MetricKind {
+    Count,
+    AvgDurationMs,
+    ErrorRate,
+}
+
+/// Parse `metric{command="x"}` into the metric kind and optional filter.
+fn parse_expr(expr: &str) -> AppResult<(MetricKind, Option<String>)> {
+    let (name, filter) = match expr.split_once('{') {
+        Some((name, rest)) => {
+            let rest = rest
+                .strip_suffix('}')
+                .ok_or_else(|| AppError::InvalidArgument(format!("unclosed filter in {expr:?}")))?;
+            let command = rest
+                .strip_prefix("command=")
+                .map(|v| v.trim_matches('"').to_string())
+                .ok_or_else(|| {
+                    AppError::InvalidArgument(format!("unsupported filter in {expr:?}"))
+                })?;
+            (name, Some(command))
+        }
+        None => (expr, None),
+    };
+    let kind = match name.trim() {
+        "count" => MetricKind::Count,
+        "avg_duration_ms" => MetricKind::AvgDurationMs,
+        "error_rate" => MetricKind::ErrorRate,
+        other => {
+            return Err(AppError::InvalidArgument(format!(
+                "unknown metric {other:?}; expected count, avg_duration_ms or error_rate"
+            )))
+        }
+    };
+    Ok((kind, filter))
+}
+
+/// Evaluate a [`MetricsQuery`] into one bucketed series. This is the
+/// single endpoint external dashboards and the built-in charts use, so
+/// new charts do not require new endpoints.
+pub fn query_metrics(storage: &Storage, query: &MetricsQuery) -> AppResult<MetricsSeries> {
+    if query.step_seconds <= 0 {
+        return Err(AppError::InvalidArgument("step must be positive".into()));
+    }
+    if query.end <= query.start {
+        return Err(AppError::InvalidArgument("range end must be after start".into()));
+    }
+    let (kind, command) = parse_expr(&query.expr)?;
+    let rows = storage.query_command_metrics(&query.start, &query.end, command.as_deref())?;
+
+    let start_epoch = query.start.timestamp();
+    let buckets = ((query.end.timestamp() - start_epoch) + query.step_seconds - 1)
+        / query.step_seconds;
+    let mut counts = vec![0u64; buckets as usize];
+    let mut duration_sums = vec![0i64; buckets as usize];
+    let mut errors = vec![0u64; buckets as usize];
+    for (epoch, duration_ms, ok) in rows {
+        let bucket = (epoch - start_epoch) / query.step_seconds;
+        if bucket < 0 || bucket >= buckets {
+            continue;
+        }
+        let bucket = bucket as usize;
+        counts[bucket] += 1;
+        duration_sums[bucket] += duration_ms;
+        if !ok {
+            errors[bucket] += 1;
+        }
+    }
+
+    let points = (0..buckets as usize)
+        .map(|i| {
+            let value = match kind {
+                MetricKind::Count => counts[i] as f64,
+                MetricKind::AvgDurationMs if counts[i] > 0 => {
+                    duration_sums[i] as f64 / counts[i] as f64
+                }
+                MetricKind::ErrorRate if counts[i] > 0 => errors[i] as f64 / counts[i] as f64,
+                _ => 0.0,
+            };
+            MetricsPoint {
+                timestamp: Utc
+                    .timestamp_opt(start_epoch + i as i64 * query.step_seconds, 0)
+                    .single()
+                    .unwrap_or(query.start),
+                value,
+            }
+        })
+        .collect();
+
+    Ok(MetricsSeries {
+        expr: query.expr.clone(),
+        points,
+    })
+}
+
+/// Redact a parameter object for logging: sensitive keys are masked and
+/// long strings truncated, recursively.
+pub fn redact(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(key, val)| {
+                    let lower = key.to_ascii_lowercase();
+                    if SENSITIVE_KEYS.iter().any(|s| lower.contains(s)) {
+                        (key, Value::String("<redacted>".into()))
+                    } else {
+                        (key, redact(val))
+                    }
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(redact).collect()),
+        Value::String(s) if s.len() > MAX_LOGGED_STRING => {
+            let mut end = MAX_LOGGED_STRING;
+            while !s.is_char_boundary(end) {
+                end -= 1;
+            }
+            Value::String(format!("{}… ({} bytes)", &s[..end], s.len()))
+        }
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn redacts_sensitive_keys_recursively() {
+        let out = redact(json!({
+            "name": "alpha",
+            "api_key": "sk-very-secret",
+            "nested": { "Token": "abc", "count": 3 },
+        }));
+        assert_eq!(out["name"], "alpha");
+        assert_eq!(out["api_key"], "<redacted>");
+        assert_eq!(out["nested"]["Token"], "<redacted>");
+        assert_eq!(out["nested"]["count"], 3);
+    }
+
+    #[test]
+    fn truncates_long_strings() {
+        let long = "x".repeat(500);
+        let out = redact(json!({ "prompt": long }));
+        let logged = out["prompt"].as_str().unwrap();
+        assert!(logged.len() < 200);
+        assert!(logged.contains("500 bytes"));
+    }
+
+    #[test]
+    fn parses_expressions_with_and_without_filters() {
+        assert_eq!(
+            parse_expr("count").unwrap(),
+            (MetricKind::Count, None)
+        );
+        assert_eq!(
+            parse_expr("avg_duration_ms{command=\"dispatch\"}").unwrap(),
+            (MetricKind::AvgDurationMs, Some("dispatch".into()))
+        );
+        assert!(parse_expr("p99_duration_ms").is_err());
+        assert!(parse_expr("count{agent=\"x\"}").is_err());
+    }
+
+    #[test]
+    fn buckets_series_over_the_range() {
+        let storage = Storage::open_in_memory().unwrap();
+        storage.record_command_metric("dispatch", 100, true).unwrap();
+        storage.record_command_metric("dispatch", 300, false).unwrap();
+        storage.record_command_metric("other", 50, true).unwrap();
+
+        let query = MetricsQuery {
+            expr: "avg_duration_ms{command=\"dispatch\"}".into(),
+            start: Utc::now() - chrono::Duration::minutes(1),
+            end: Utc::now() + chrono::Duration::minutes(1),
+            step_seconds: 120,
+        };
+        let series = query_metrics(&storage, &query).unwrap();
+        assert_eq!(series.points.len(), 1);
+        assert!((series.points[0].value - 200.0).abs() < f64::EPSILON);
+
+        let query = MetricsQuery {
+            expr: "error_rate".into(),
+            step_seconds: 120,
+            ..query
+        };
+        let series = query_metrics(&storage, &query).unwrap();
+        assert!((series.points[0].value - (1.0 / 3.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn records_metric_per_call() {
+        let storage = Storage::open_in_memory().unwrap();
+        let out = timed(&storage, "noop", json!({}), || Ok(42));
+        assert_eq!(out.unwrap(), 42);
+    }
+}
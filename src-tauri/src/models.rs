@@ -0,0 +1,671 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentStatus {
+    Idle,
+    Running,
+    Paused,
+}
+
+impl AgentStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Idle => "idle",
+            Self::Running => "running",
+            Self::Paused => "paused",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "idle" => Some(Self::Idle),
+            "running" => Some(Self::Running),
+            "paused" => Some(Self::Paused),
+            _ => None,
+        }
+    }
+}
+
+/// Scheduling priority of a task. Ordering is by urgency: `Low` sorts
+/// before `Urgent`.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+    Urgent,
+}
+
+impl TaskPriority {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Normal => "normal",
+            Self::High => "high",
+            Self::Urgent => "urgent",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "low" => Some(Self::Low),
+            "normal" => Some(Self::Normal),
+            "high" => Some(Self::High),
+            "urgent" => Some(Self::Urgent),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Agent {
+    pub id: String,
+    pub name: String,
+    /// Model identifier the agent dispatches with, e.g. `gpt-4o`.
+    pub model: String,
+    pub status: AgentStatus,
+    /// Priority applied to this agent's dispatches when none is given.
+    #[serde(default)]
+    pub default_priority: TaskPriority,
+    /// Display color, generated deterministically from the id so every
+    /// client renders the same identity.
+    #[serde(default)]
+    pub color: String,
+    /// Path into the artifact store for a user-uploaded avatar image.
+    #[serde(default)]
+    pub avatar_path: Option<String>,
+    /// Execution backend override ("openai", "anthropic", "mock");
+    /// when unset the model name picks the backend.
+    #[serde(default)]
+    pub framework: Option<String>,
+    /// Framework-specific config section, validated against the
+    /// framework's schema (see [`crate::framework_config`]).
+    #[serde(default)]
+    pub framework_config: serde_json::Value,
+    /// External services this agent needs (URLs the health prober
+    /// pings before dispatching work to it).
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    /// Command line to spawn for subprocess-framework agents.
+    #[serde(default)]
+    pub command: Option<String>,
+    /// MCP server endpoints whose tools this agent may invoke.
+    #[serde(default)]
+    pub mcp_servers: Vec<String>,
+    /// Base URL for self-hosted backends like Ollama.
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    /// Models to fail over to, in order, when the primary call hits a
+    /// transient error (rate limit, 5xx).
+    #[serde(default)]
+    pub fallback_models: Vec<String>,
+    /// Default number of automatic retries for this agent's tasks.
+    #[serde(default)]
+    pub max_retries: u32,
+    /// Wall-clock limit for one execution; a run past it is aborted
+    /// and the task fails with a timeout error.
+    #[serde(default)]
+    pub timeout_seconds: Option<u64>,
+    /// IANA timezone (e.g. "America/New_York") the agent's schedules
+    /// are evaluated in; unset means UTC.
+    #[serde(default)]
+    pub timezone: Option<String>,
+    /// Completed tasks land in NeedsReview until a reviewer accepts
+    /// the result or sends it back with feedback.
+    #[serde(default)]
+    pub review_required: bool,
+    /// Opt this agent out of the workspace constitution guard prompt.
+    #[serde(default)]
+    pub constitution_opt_out: bool,
+    /// System prompt prepended to every completion call.
+    #[serde(default)]
+    pub system_prompt: Option<String>,
+    /// Sampling temperature passed to the provider, when set.
+    #[serde(default)]
+    pub temperature: Option<f64>,
+    /// Cumulative seconds this agent has spent Running, accrued from
+    /// status transitions in the dispatcher.
+    #[serde(default)]
+    pub runtime_seconds: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Palette used for generated agent colors.
+const AGENT_COLORS: &[&str] = &[
+    "#e06c75", "#d19a66", "#e5c07b", "#98c379", "#56b6c2", "#61afef", "#c678dd", "#be5046",
+];
+
+/// FNV-1a over arbitrary text; used for display colors and cheap
+/// content fingerprints (not cryptographic).
+pub fn fnv1a(text: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in text.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x1000_0000_01b3);
+    }
+    hash
+}
+
+/// Deterministic display color for an agent id.
+pub fn color_for_id(id: &str) -> String {
+    AGENT_COLORS[(fnv1a(id) % AGENT_COLORS.len() as u64) as usize].to_string()
+}
+
+impl Agent {
+    /// The exact execution-relevant config of this agent right now, for
+    /// attaching to tasks. The system prompt is fingerprinted rather
+    /// than copied so snapshots stay small and non-sensitive.
+    pub fn config_snapshot(&self) -> serde_json::Value {
+        serde_json::json!({
+            "model": self.model,
+            "framework": self.framework,
+            "framework_config": self.framework_config,
+            "endpoint": self.endpoint,
+            "command": self.command,
+            "temperature": self.temperature,
+            "timeout_seconds": self.timeout_seconds,
+            "system_prompt_fnv1a": self
+                .system_prompt
+                .as_deref()
+                .map(|p| format!("{:016x}", fnv1a(p))),
+            "fallback_models": self.fallback_models,
+            "dependencies": self.dependencies,
+            "mcp_servers": self.mcp_servers,
+        })
+    }
+
+    /// Fresh idle agent with a generated id and derived color.
+    pub fn new(name: impl Into<String>, model: impl Into<String>) -> Self {
+        let id = uuid::Uuid::new_v4().to_string();
+        let color = color_for_id(&id);
+        Self {
+            id,
+            name: name.into(),
+            model: model.into(),
+            status: AgentStatus::Idle,
+            default_priority: TaskPriority::default(),
+            color,
+            avatar_path: None,
+            framework: None,
+            framework_config: serde_json::Value::Null,
+            dependencies: Vec::new(),
+            command: None,
+            mcp_servers: Vec::new(),
+            endpoint: None,
+            fallback_models: Vec::new(),
+            max_retries: 0,
+            timeout_seconds: None,
+            timezone: None,
+            review_required: false,
+            constitution_opt_out: false,
+            system_prompt: None,
+            temperature: None,
+            runtime_seconds: 0,
+            created_at: Utc::now(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskStatus {
+    Queued,
+    Running,
+    /// Suspended on an `ask_user` question; resumes once the operator
+    /// provides an answer.
+    WaitingForInput,
+    /// Finished successfully, but the agent requires a reviewer to
+    /// accept the result (or send it back) before it counts as done.
+    NeedsReview,
+    Completed,
+    Failed,
+    Cancelled,
+    /// A dependency failed, so this task will never start.
+    Blocked,
+}
+
+impl TaskStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::Running => "running",
+            Self::WaitingForInput => "waiting_for_input",
+            Self::NeedsReview => "needs_review",
+            Self::Completed => "completed",
+            Self::Failed => "failed",
+            Self::Cancelled => "cancelled",
+            Self::Blocked => "blocked",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "queued" => Some(Self::Queued),
+            "running" => Some(Self::Running),
+            "waiting_for_input" => Some(Self::WaitingForInput),
+            "needs_review" => Some(Self::NeedsReview),
+            "completed" => Some(Self::Completed),
+            "failed" => Some(Self::Failed),
+            "cancelled" => Some(Self::Cancelled),
+            "blocked" => Some(Self::Blocked),
+            _ => None,
+        }
+    }
+
+    /// Terminal states admit no further transitions. NeedsReview is
+    /// terminal for the executor; only review decisions move it on.
+    pub fn is_terminal(self) -> bool {
+        matches!(
+            self,
+            Self::Completed | Self::Failed | Self::Cancelled | Self::Blocked | Self::NeedsReview
+        )
+    }
+}
+
+/// One row of the approvals audit trail: who decided what about a
+/// gated tool call, when, and why.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Approval {
+    pub id: i64,
+    pub task_id: String,
+    pub tool: String,
+    /// "approved" or "denied".
+    pub decision: String,
+    /// Operator identity, or "policy" for timeout auto-resolutions.
+    pub approver: String,
+    pub comment: Option<String>,
+    pub decided_at: DateTime<Utc>,
+}
+
+/// Typed taxonomy of why a task ended badly, stored on the task so
+/// dashboards and retry policies can treat transient and permanent
+/// failures differently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    /// The model backend errored (rate limit, 5xx, bad response).
+    Provider,
+    /// The run overran its wall-clock budget.
+    Timeout,
+    /// A tool or local executor failed (subprocess, MCP, script).
+    Tool,
+    /// A policy stopped the run (cost ceiling, constitution).
+    Guardrail,
+    /// An operator cancelled the task.
+    Cancelled,
+}
+
+impl FailureKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Provider => "provider",
+            Self::Timeout => "timeout",
+            Self::Tool => "tool",
+            Self::Guardrail => "guardrail",
+            Self::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "provider" => Some(Self::Provider),
+            "timeout" => Some(Self::Timeout),
+            "tool" => Some(Self::Tool),
+            "guardrail" => Some(Self::Guardrail),
+            "cancelled" => Some(Self::Cancelled),
+            _ => None,
+        }
+    }
+
+    /// Whether a retry of the same task could plausibly succeed.
+    /// Guardrail blocks and cancellations are deliberate outcomes, not
+    /// flakes.
+    pub fn is_transient(self) -> bool {
+        matches!(self, Self::Provider | Self::Timeout | Self::Tool)
+    }
+
+    /// Map an execution error onto the taxonomy; control-flow errors
+    /// that never fail a task classify as None.
+    pub fn classify(err: &crate::error::AppError) -> Option<Self> {
+        use crate::error::AppError;
+        match err {
+            AppError::Provider(_) => Some(Self::Provider),
+            AppError::Timeout { .. } => Some(Self::Timeout),
+            AppError::BudgetExceeded { .. } | AppError::ApprovalDenied { .. } => {
+                Some(Self::Guardrail)
+            }
+            AppError::Io(_) | AppError::InvalidArgument(_) => Some(Self::Tool),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Task {
+    pub id: String,
+    pub agent_id: String,
+    pub title: String,
+    pub prompt: String,
+    pub status: TaskStatus,
+    pub priority: TaskPriority,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Ids of tasks that must complete before this one may start; a
+    /// failed or cancelled parent blocks it instead.
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    /// Kanban board placement; persists ordering across restarts.
+    #[serde(default = "default_board_column")]
+    pub board_column: String,
+    #[serde(default)]
+    pub board_position: i64,
+    /// Monotonic dispatch order, so the execution queue can be rebuilt
+    /// in insertion order after a restart.
+    #[serde(default)]
+    pub queue_position: i64,
+    pub result: Option<String>,
+    pub error: Option<String>,
+    /// Artifact file holding the full result when it was too large to
+    /// keep inline; `result` then carries only a preview.
+    #[serde(default)]
+    pub result_artifact: Option<String>,
+    /// Abort the run (Failed) if accumulated cost crosses this ceiling.
+    #[serde(default)]
+    pub max_cost_usd: Option<f64>,
+    /// Automatic retries before the task finally fails.
+    #[serde(default)]
+    pub max_retries: u32,
+    /// Base delay for exponential retry backoff.
+    #[serde(default = "default_retry_backoff")]
+    pub retry_backoff_seconds: f64,
+    /// Earliest moment this task may start; the dispatcher promotes it
+    /// into the agent's queue once the time arrives.
+    #[serde(default)]
+    pub run_at: Option<DateTime<Utc>>,
+    /// The failed or cancelled task this one was cloned from.
+    #[serde(default)]
+    pub retry_of: Option<String>,
+    /// Step-through supervision: execution pauses after each thought
+    /// and tool call until the operator continues the task.
+    #[serde(default)]
+    pub step_mode: bool,
+    /// Why the task failed or was stopped, for terminal bad states.
+    #[serde(default)]
+    pub failure_kind: Option<FailureKind>,
+    /// Plan-then-approve supervision: the run submits a structured
+    /// plan and halts until the operator approves it.
+    #[serde(default)]
+    pub plan_mode: bool,
+    /// The approved (or pending) plan of a plan-mode run.
+    #[serde(default)]
+    pub plan: Option<Vec<PlanStep>>,
+    /// Token usage accumulated from provider responses across every
+    /// call this task made (retries and fallbacks included).
+    #[serde(default)]
+    pub prompt_tokens: i64,
+    #[serde(default)]
+    pub completion_tokens: i64,
+    /// `prompt_tokens + completion_tokens`, derived on read.
+    #[serde(default)]
+    pub total_tokens: i64,
+    /// When the task last entered Running, for runtime accounting.
+    #[serde(default)]
+    pub started_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+fn default_board_column() -> String {
+    "backlog".to_string()
+}
+
+/// Base delay in seconds for exponential retry backoff when a dispatch
+/// does not specify one. Matches the schema's column default.
+pub fn default_retry_backoff() -> f64 {
+    1.0
+}
+
+/// Aggregate token usage across an agent's tasks, from real provider
+/// usage reports rather than estimates.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentStats {
+    pub agent_id: String,
+    /// Tasks that have recorded any usage at all.
+    pub tasks_with_usage: i64,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub total_tokens: i64,
+}
+
+/// One step of the structured plan a plan-mode run submits before
+/// executing; progress events reference steps by `id`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlanStep {
+    pub id: u32,
+    pub description: String,
+}
+
+/// A saved instruction that can be dispatched in one call, with
+/// `{{date}}`-style parameters substituted at dispatch time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskTemplate {
+    pub id: String,
+    pub name: String,
+    pub title: String,
+    pub prompt: String,
+    /// Default target agent; a dispatch call may override it.
+    #[serde(default)]
+    pub agent_id: Option<String>,
+    #[serde(default)]
+    pub priority: Option<TaskPriority>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A recurring dispatch: the stored task template is dispatched to its
+/// agent whenever the cron expression matches.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Schedule {
+    pub id: String,
+    pub agent_id: String,
+    /// Template for the dispatched tasks; the title gets the fire time
+    /// appended so runs stay distinguishable.
+    pub title: String,
+    pub prompt: String,
+    /// Five-field cron expression (see [`crate::scheduler`] for the
+    /// supported subset).
+    pub cron: String,
+    #[serde(default)]
+    pub paused: bool,
+    #[serde(default)]
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Severity of a task event. The derive order makes severities compare
+/// (`Debug < Info < Warn < Error`) for minimum-severity filtering.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum EventSeverity {
+    Debug,
+    #[default]
+    Info,
+    Warn,
+    Error,
+}
+
+impl EventSeverity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Debug => "debug",
+            Self::Info => "info",
+            Self::Warn => "warn",
+            Self::Error => "error",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "debug" => Some(Self::Debug),
+            "info" => Some(Self::Info),
+            "warn" => Some(Self::Warn),
+            "error" => Some(Self::Error),
+            _ => None,
+        }
+    }
+
+    /// Default severity for an event kind: per-token and thinking
+    /// chatter is debug, warnings and failures keep their weight, and
+    /// everything else is info.
+    pub fn for_kind(kind: &str) -> Self {
+        match kind {
+            "thought_log" | "token_chunk" => Self::Debug,
+            "warning" => Self::Warn,
+            "error" | "failed" => Self::Error,
+            _ => Self::Info,
+        }
+    }
+}
+
+/// Append-only record of something that happened while a task ran.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskEvent {
+    pub id: i64,
+    pub task_id: String,
+    pub kind: String,
+    #[serde(default)]
+    pub severity: EventSeverity,
+    pub payload: Option<serde_json::Value>,
+    /// Typed view of `payload` per the kind's schema, so the frontend
+    /// can render rich cards without parsing free-form JSON. Computed
+    /// when the event is read back; unknown kinds and payloads that do
+    /// not match their schema fall back to [`EventPayload::Other`].
+    #[serde(default)]
+    pub typed: EventPayload,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Per-kind schemas for the well-known event payloads. The stored
+/// payload stays free-form JSON -- agents may attach anything -- but
+/// events whose payload matches its kind's schema come back with this
+/// typed view filled in.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventPayload {
+    Thought {
+        message: String,
+    },
+    /// A provider request (`api_call` events).
+    ApiCall {
+        provider: Option<String>,
+        model: Option<String>,
+    },
+    /// A tool invocation awaiting approval (`tool_approval_requested`).
+    ToolCall {
+        tool: String,
+        #[serde(default)]
+        args: serde_json::Value,
+    },
+    AskUser {
+        question: String,
+    },
+    Output {
+        text: String,
+    },
+    TokenChunk {
+        delta: String,
+    },
+    /// Token counts from a completed provider call (`usage` events).
+    Usage {
+        prompt_tokens: u64,
+        completion_tokens: u64,
+        #[serde(default)]
+        estimated_cost_cents: f64,
+    },
+    Warning {
+        reason: String,
+    },
+    /// Everything else, carrying the raw payload unchanged.
+    Other {
+        #[serde(default)]
+        raw: Option<serde_json::Value>,
+    },
+}
+
+impl Default for EventPayload {
+    fn default() -> Self {
+        Self::Other { raw: None }
+    }
+}
+
+impl EventPayload {
+    /// Parse a stored event into its typed schema.
+    pub fn from_event(kind: &str, payload: Option<&serde_json::Value>) -> Self {
+        fn text(payload: &serde_json::Value, key: &str) -> Option<String> {
+            payload[key].as_str().map(str::to_string)
+        }
+        let Some(p) = payload else {
+            return Self::Other { raw: None };
+        };
+        let typed = match kind {
+            "thought_log" => text(p, "message").map(|message| Self::Thought { message }),
+            "api_call" if p["provider"].is_string() || p["model"].is_string() => {
+                Some(Self::ApiCall {
+                    provider: text(p, "provider"),
+                    model: text(p, "model"),
+                })
+            }
+            "tool_approval_requested" => text(p, "tool").map(|tool| Self::ToolCall {
+                tool,
+                args: p.get("args").cloned().unwrap_or(serde_json::Value::Null),
+            }),
+            "ask_user" => text(p, "question").map(|question| Self::AskUser { question }),
+            "output" => text(p, "text").map(|text| Self::Output { text }),
+            "token_chunk" => text(p, "delta").map(|delta| Self::TokenChunk { delta }),
+            "usage" => match (p["prompt_tokens"].as_u64(), p["completion_tokens"].as_u64()) {
+                (Some(prompt_tokens), Some(completion_tokens)) => Some(Self::Usage {
+                    prompt_tokens,
+                    completion_tokens,
+                    estimated_cost_cents: p["estimated_cost_cents"].as_f64().unwrap_or(0.0),
+                }),
+                _ => None,
+            },
+            "warning" => text(p, "reason").map(|reason| Self::Warning { reason }),
+            _ => None,
+        };
+        typed.unwrap_or_else(|| Self::Other {
+            raw: Some(p.clone()),
+        })
+    }
+}
+
+/// One entry in an agent's lifecycle changelog: creation, pauses,
+/// config changes and notable incidents. Distinct from raw task events.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentHistoryEntry {
+    pub id: i64,
+    pub agent_id: String,
+    pub kind: String,
+    pub detail: Option<serde_json::Value>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// One audited access of a secret: who read which name, and when.
+/// Values are never recorded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecretUsage {
+    pub id: i64,
+    pub secret_name: String,
+    pub agent_id: Option<String>,
+    pub task_id: Option<String>,
+    pub used_at: DateTime<Utc>,
+}
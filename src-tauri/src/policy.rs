@@ -0,0 +1,371 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppResult;
+use crate::models::TaskPriority;
+use crate::storage::Storage;
+
+/// Settings key under which the workspace priority policy is stored.
+pub const PRIORITY_POLICY_KEY: &str = "priority_policy";
+
+/// Workspace-level policy capping the maximum priority a task may carry
+/// based on its tags. An empty policy caps nothing.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PriorityPolicy {
+    /// tag -> highest priority a task carrying that tag may have.
+    #[serde(default)]
+    pub caps: HashMap<String, TaskPriority>,
+}
+
+impl PriorityPolicy {
+    pub fn load(storage: &Storage) -> AppResult<Self> {
+        Ok(storage
+            .get_setting(PRIORITY_POLICY_KEY)?
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default())
+    }
+
+    pub fn save(&self, storage: &Storage) -> AppResult<()> {
+        let raw = serde_json::to_string(self).expect("policy serializes");
+        storage.set_setting(PRIORITY_POLICY_KEY, &raw)
+    }
+
+    /// Strictest (lowest) cap across the task's tags, if any tag is capped.
+    pub fn cap_for(&self, tags: &[String]) -> Option<TaskPriority> {
+        tags.iter().filter_map(|tag| self.caps.get(tag)).min().copied()
+    }
+}
+
+/// Settings key under which the budget downgrade policy is stored.
+pub const BUDGET_POLICY_KEY: &str = "budget_policy";
+
+/// Per-agent budget rule: once monthly spend crosses the limit the
+/// agent is switched to the cheaper fallback model instead of having
+/// its work hard-stopped.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BudgetRule {
+    pub monthly_limit_usd: f64,
+    pub fallback_model: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BudgetPolicy {
+    /// agent id -> rule.
+    #[serde(default)]
+    pub rules: HashMap<String, BudgetRule>,
+}
+
+/// Settings key under which the tool approval policy is stored.
+pub const APPROVAL_POLICY_KEY: &str = "tool_approval_policy";
+
+/// Per-agent list of tools that require explicit operator approval
+/// before each invocation (e.g. `file_access`, `code_execution`). An
+/// unlisted tool runs without gating.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ApprovalPolicy {
+    /// agent id -> tool names needing approval.
+    #[serde(default)]
+    pub rules: HashMap<String, Vec<String>>,
+    /// Auto-resolve approvals still pending after this long, so they
+    /// do not silently rot in the queue. None waits forever.
+    #[serde(default)]
+    pub timeout_seconds: Option<u64>,
+    /// Low-risk agents whose timed-out approvals are granted instead
+    /// of denied.
+    #[serde(default)]
+    pub auto_approve_on_timeout: Vec<String>,
+}
+
+impl ApprovalPolicy {
+    pub fn load(storage: &Storage) -> AppResult<Self> {
+        Ok(storage
+            .get_setting(APPROVAL_POLICY_KEY)?
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default())
+    }
+
+    pub fn save(&self, storage: &Storage) -> AppResult<()> {
+        let raw = serde_json::to_string(self).expect("policy serializes");
+        storage.set_setting(APPROVAL_POLICY_KEY, &raw)
+    }
+
+    /// Whether `tool` needs operator approval when run by `agent_id`.
+    pub fn requires_approval(&self, agent_id: &str, tool: &str) -> bool {
+        self.rules
+            .get(agent_id)
+            .is_some_and(|tools| tools.iter().any(|t| t == tool))
+    }
+}
+
+/// Settings key under which the dispatch confirmation policy is
+/// stored.
+pub const DISPATCH_CONFIRMATION_POLICY_KEY: &str = "dispatch_confirmation_policy";
+
+/// One confirmation rule. Set conditions are ANDed; a rule with no
+/// conditions never matches.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DispatchRule {
+    /// Short label recorded on confirmed tasks and shown in the
+    /// confirmation prompt.
+    pub name: String,
+    /// Matches dispatches at this priority or above.
+    #[serde(default)]
+    pub min_priority: Option<TaskPriority>,
+    /// Case-insensitive substring of the instruction.
+    #[serde(default)]
+    pub prompt_contains: Option<String>,
+    /// Matches when the dispatch carries any of these tags.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Matches dispatches to any of these agents.
+    #[serde(default)]
+    pub agent_ids: Vec<String>,
+}
+
+impl DispatchRule {
+    fn matches(
+        &self,
+        agent_id: &str,
+        priority: TaskPriority,
+        prompt: &str,
+        tags: &[String],
+    ) -> bool {
+        let mut conditions = 0;
+        if let Some(min) = self.min_priority {
+            if priority < min {
+                return false;
+            }
+            conditions += 1;
+        }
+        if let Some(needle) = &self.prompt_contains {
+            if !prompt.to_lowercase().contains(&needle.to_lowercase()) {
+                return false;
+            }
+            conditions += 1;
+        }
+        if !self.tags.is_empty() {
+            if !self.tags.iter().any(|t| tags.contains(t)) {
+                return false;
+            }
+            conditions += 1;
+        }
+        if !self.agent_ids.is_empty() {
+            if !self.agent_ids.iter().any(|a| a == agent_id) {
+                return false;
+            }
+            conditions += 1;
+        }
+        conditions > 0
+    }
+}
+
+/// Dispatches matching a rule are rejected with `ConfirmationRequired`
+/// until the frontend re-sends them confirmed; the triggering rule is
+/// recorded on the task.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DispatchConfirmationPolicy {
+    #[serde(default)]
+    pub rules: Vec<DispatchRule>,
+}
+
+impl DispatchConfirmationPolicy {
+    pub fn load(storage: &Storage) -> AppResult<Self> {
+        Ok(storage
+            .get_setting(DISPATCH_CONFIRMATION_POLICY_KEY)?
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default())
+    }
+
+    pub fn save(&self, storage: &Storage) -> AppResult<()> {
+        let raw = serde_json::to_string(self).expect("policy serializes");
+        storage.set_setting(DISPATCH_CONFIRMATION_POLICY_KEY, &raw)
+    }
+
+    /// First rule matching this dispatch, if any.
+    pub fn matching_rule(
+        &self,
+        agent_id: &str,
+        priority: TaskPriority,
+        prompt: &str,
+        tags: &[String],
+    ) -> Option<&DispatchRule> {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(agent_id, priority, prompt, tags))
+    }
+}
+
+/// Settings key under which the escalation policy is stored.
+pub const ESCALATION_POLICY_KEY: &str = "escalation_policy";
+
+/// When a tool approval or an input request sits unattended for too
+/// long, raise it out of the queue: emit an `escalation` event, notify
+/// the operator, and optionally pause the agent so it stops piling up
+/// work nobody is watching.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EscalationPolicy {
+    /// Minutes a wait may sit unattended before escalating. None
+    /// disables escalation.
+    #[serde(default)]
+    pub unattended_minutes: Option<u64>,
+    /// Pause the waiting task's agent as part of the escalation.
+    #[serde(default)]
+    pub auto_pause_agent: bool,
+}
+
+impl EscalationPolicy {
+    pub fn load(storage: &Storage) -> AppResult<Self> {
+        Ok(storage
+            .get_setting(ESCALATION_POLICY_KEY)?
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default())
+    }
+
+    pub fn save(&self, storage: &Storage) -> AppResult<()> {
+        let raw = serde_json::to_string(self).expect("policy serializes");
+        storage.set_setting(ESCALATION_POLICY_KEY, &raw)
+    }
+}
+
+/// Settings key under which the event sampling policy is stored.
+pub const SAMPLING_POLICY_KEY: &str = "event_sampling_policy";
+
+/// Event kinds never sampled away, regardless of policy.
+const ALWAYS_KEEP_KINDS: &[&str] = &["error", "failed", "cancelled", "budget_exceeded"];
+
+/// Per-agent write-time sampling for verbose event kinds: keep 1 in N
+/// events of a kind, so chatty agents do not flood storage. Kept events
+/// carry the rate so analytics can extrapolate counts.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SamplingPolicy {
+    /// agent id -> event kind -> keep 1 in N (N <= 1 means keep all).
+    #[serde(default)]
+    pub rules: HashMap<String, HashMap<String, u32>>,
+}
+
+impl SamplingPolicy {
+    pub fn load(storage: &Storage) -> AppResult<Self> {
+        Ok(storage
+            .get_setting(SAMPLING_POLICY_KEY)?
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default())
+    }
+
+    pub fn save(&self, storage: &Storage) -> AppResult<()> {
+        let raw = serde_json::to_string(self).expect("policy serializes");
+        storage.set_setting(SAMPLING_POLICY_KEY, &raw)
+    }
+
+    /// Error-like kinds are exempt from sampling.
+    pub fn always_keep(kind: &str) -> bool {
+        ALWAYS_KEEP_KINDS.contains(&kind) || kind.contains("error")
+    }
+
+    /// The keep-1-in-N rate for an agent and kind, when it samples at all.
+    pub fn rate_for(&self, agent_id: &str, kind: &str) -> Option<u32> {
+        if Self::always_keep(kind) {
+            return None;
+        }
+        self.rules
+            .get(agent_id)
+            .and_then(|kinds| kinds.get(kind))
+            .copied()
+            .filter(|n| *n > 1)
+    }
+}
+
+impl BudgetPolicy {
+    pub fn load(storage: &Storage) -> AppResult<Self> {
+        Ok(storage
+            .get_setting(BUDGET_POLICY_KEY)?
+            .and_then(|raw| serde_json::from_str(&raw).ok())
+            .unwrap_or_default())
+    }
+
+    pub fn save(&self, storage: &Storage) -> AppResult<()> {
+        let raw = serde_json::to_string(self).expect("policy serializes");
+        storage.set_setting(BUDGET_POLICY_KEY, &raw)
+    }
+}
+
+/// Settings keys for the workspace constitution: guard text prepended
+/// to every agent's system prompt, and a version bumped on each edit.
+pub const CONSTITUTION_TEXT_KEY: &str = "constitution_text";
+pub const CONSTITUTION_VERSION_KEY: &str = "constitution_version";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Constitution {
+    pub text: String,
+    pub version: u64,
+}
+
+impl Constitution {
+    pub fn load(storage: &Storage) -> AppResult<Option<Self>> {
+        let Some(text) = storage.get_setting(CONSTITUTION_TEXT_KEY)? else {
+            return Ok(None);
+        };
+        let version = storage
+            .get_setting(CONSTITUTION_VERSION_KEY)?
+            .and_then(|raw| raw.parse().ok())
+            .unwrap_or(1);
+        Ok(Some(Self { text, version }))
+    }
+
+    /// Store new constitution text, bumping the version so task config
+    /// snapshots can tell which rules were in effect.
+    pub fn save(storage: &Storage, text: &str) -> AppResult<Self> {
+        let version = storage
+            .get_setting(CONSTITUTION_VERSION_KEY)?
+            .and_then(|raw| raw.parse::<u64>().ok())
+            .unwrap_or(0)
+            + 1;
+        storage.set_setting(CONSTITUTION_TEXT_KEY, text)?;
+        storage.set_setting(CONSTITUTION_VERSION_KEY, &version.to_string())?;
+        Ok(Self { text: text.to_string(), version })
+    }
+}
+
+/// The system prompt an agent actually runs with: the workspace
+/// constitution (unless the agent opted out) followed by the agent's
+/// own system prompt. Returns the applied constitution version, if any.
+pub fn guarded_system_prompt(
+    storage: &Storage,
+    agent: &crate::models::Agent,
+) -> AppResult<(Option<String>, Option<u64>)> {
+    let constitution = if agent.constitution_opt_out {
+        None
+    } else {
+        Constitution::load(storage)?
+    };
+    match (&constitution, &agent.system_prompt) {
+        (None, prompt) => Ok((prompt.clone(), None)),
+        (Some(c), None) => Ok((Some(c.text.clone()), Some(c.version))),
+        (Some(c), Some(own)) => Ok((Some(format!("{}\n\n{own}", c.text)), Some(c.version))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Agent;
+
+    #[test]
+    fn constitution_prepends_and_respects_opt_out() {
+        let storage = Storage::open_in_memory().unwrap();
+        Constitution::save(&storage, "Be safe.").unwrap();
+        let saved = Constitution::save(&storage, "Be very safe.").unwrap();
+        assert_eq!(saved.version, 2);
+
+        let mut agent = Agent::new("a", "mock");
+        agent.system_prompt = Some("You are a researcher.".into());
+        let (prompt, version) = guarded_system_prompt(&storage, &agent).unwrap();
+        assert_eq!(prompt.as_deref(), Some("Be very safe.\n\nYou are a researcher."));
+        assert_eq!(version, Some(2));
+
+        agent.constitution_opt_out = true;
+        let (prompt, version) = guarded_system_prompt(&storage, &agent).unwrap();
+        assert_eq!(prompt.as_deref(), Some("You are a researcher."));
+        assert_eq!(version, None);
+    }
+}
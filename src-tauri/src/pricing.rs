@@ -0,0 +1,193 @@
+//! Model pricing registry and cost computation.
+//!
+//! Per-model input/output token rates with a built-in table for common
+//! models, overridable through the `pricing.overrides` setting so
+//! negotiated or newly launched rates do not need a release. Costs are
+//! computed from the real token usage providers report, never from
+//! character-count guesses.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::error::AppResult;
+use crate::storage::Storage;
+
+/// Settings key holding a JSON map of model-name prefix to
+/// [`ModelRates`]; the longest matching prefix wins.
+pub const OVERRIDES_SETTING: &str = "pricing.overrides";
+
+/// USD per million tokens, split by direction.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ModelRates {
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+}
+
+/// Built-in rates, matched by model-name prefix. Unknown models price
+/// at zero rather than guessing; operators can fill the gap with an
+/// override.
+pub fn builtin_rates(model: &str) -> ModelRates {
+    let (input_per_mtok, output_per_mtok) = if model.starts_with("claude-3-opus") {
+        (15.0, 75.0)
+    } else if model.starts_with("claude-3-5-sonnet") || model.starts_with("claude-3-sonnet") {
+        (3.0, 15.0)
+    } else if model.starts_with("claude-3-haiku") {
+        (0.25, 1.25)
+    } else if model.starts_with("gpt-4o-mini") {
+        (0.15, 0.6)
+    } else if model.starts_with("gpt-4o") {
+        (2.5, 10.0)
+    } else {
+        (0.0, 0.0)
+    };
+    ModelRates {
+        input_per_mtok,
+        output_per_mtok,
+    }
+}
+
+/// Effective rates for a model: the longest matching prefix from the
+/// user's overrides wins, otherwise the built-in table.
+pub fn rates_for(storage: &Storage, model: &str) -> ModelRates {
+    if let Ok(Some(raw)) = storage.get_setting(OVERRIDES_SETTING) {
+        match serde_json::from_str::<BTreeMap<String, ModelRates>>(&raw) {
+            Ok(overrides) => {
+                if let Some((_, rates)) = overrides
+                    .iter()
+                    .filter(|(prefix, _)| model.starts_with(prefix.as_str()))
+                    .max_by_key(|(prefix, _)| prefix.len())
+                {
+                    return *rates;
+                }
+            }
+            Err(err) => tracing::warn!(%err, "ignoring malformed pricing overrides"),
+        }
+    }
+    builtin_rates(model)
+}
+
+/// Cost of one completion in USD at the effective rates.
+pub fn cost_usd(
+    storage: &Storage,
+    model: &str,
+    prompt_tokens: u64,
+    completion_tokens: u64,
+) -> f64 {
+    let rates = rates_for(storage, model);
+    (prompt_tokens as f64 * rates.input_per_mtok
+        + completion_tokens as f64 * rates.output_per_mtok)
+        / 1_000_000.0
+}
+
+/// One task's share of an agent's spend.
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskCost {
+    pub task_id: String,
+    pub title: String,
+    pub total_tokens: i64,
+    pub estimated_cost_cents: f64,
+}
+
+/// An agent's spend over a period, broken down per task and sorted
+/// most expensive first.
+#[derive(Debug, Clone, Serialize)]
+pub struct CostBreakdown {
+    pub agent_id: String,
+    pub since: DateTime<Utc>,
+    pub total_cents: f64,
+    pub tasks: Vec<TaskCost>,
+}
+
+fn to_cents(usd: f64) -> f64 {
+    (usd * 100.0 * 1000.0).round() / 1000.0
+}
+
+/// Sum the recorded per-call charges for `agent_id` over the last
+/// `period_days` days and attribute them to tasks.
+pub fn cost_breakdown(
+    storage: &Storage,
+    agent_id: &str,
+    period_days: u32,
+) -> AppResult<CostBreakdown> {
+    let since = Utc::now() - Duration::days(i64::from(period_days));
+    let mut tasks = Vec::new();
+    let mut total_cents = 0.0;
+    for (task_id, amount_usd) in storage.agent_task_costs_since(agent_id, &since)? {
+        total_cents += to_cents(amount_usd);
+        let task = storage.get_task(&task_id)?;
+        tasks.push(TaskCost {
+            task_id,
+            title: task.title,
+            total_tokens: task.total_tokens,
+            estimated_cost_cents: to_cents(amount_usd),
+        });
+    }
+    tasks.sort_by(|a, b| {
+        b.estimated_cost_cents
+            .partial_cmp(&a.estimated_cost_cents)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    Ok(CostBreakdown {
+        agent_id: agent_id.to_string(),
+        since,
+        total_cents,
+        tasks,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task_dispatch::{self, DispatchRequest};
+
+    #[test]
+    fn overrides_beat_builtin_rates_by_longest_prefix() {
+        let storage = Storage::open_in_memory().unwrap();
+        assert_eq!(rates_for(&storage, "gpt-4o").input_per_mtok, 2.5);
+        // Unknown models price at zero until an override is set.
+        assert_eq!(cost_usd(&storage, "in-house-llm", 1_000_000, 0), 0.0);
+
+        storage
+            .set_setting(
+                OVERRIDES_SETTING,
+                r#"{
+                    "in-house": { "input_per_mtok": 1.0, "output_per_mtok": 2.0 },
+                    "in-house-llm": { "input_per_mtok": 4.0, "output_per_mtok": 8.0 }
+                }"#,
+            )
+            .unwrap();
+        // The longest matching prefix wins.
+        let cost = cost_usd(&storage, "in-house-llm-v2", 1_000_000, 500_000);
+        assert!((cost - 8.0).abs() < 1e-9);
+        // Malformed overrides fall back to the built-in table.
+        storage.set_setting(OVERRIDES_SETTING, "not json").unwrap();
+        assert_eq!(rates_for(&storage, "gpt-4o").output_per_mtok, 10.0);
+    }
+
+    #[test]
+    fn breakdown_attributes_spend_to_tasks_within_the_period() {
+        let storage = Storage::open_in_memory().unwrap();
+        let agent = crate::models::Agent::new("analyst", "mock");
+        storage.create_agent(&agent).unwrap();
+        let cheap = task_dispatch::dispatch(
+            &storage,
+            &DispatchRequest::new(&agent.id, "cheap", "p"),
+        )
+        .unwrap();
+        let pricey = task_dispatch::dispatch(
+            &storage,
+            &DispatchRequest::new(&agent.id, "pricey", "p"),
+        )
+        .unwrap();
+        storage.record_cost(&agent.id, Some(&cheap.id), 0.01).unwrap();
+        storage.record_cost(&agent.id, Some(&pricey.id), 0.50).unwrap();
+        storage.record_cost(&agent.id, Some(&pricey.id), 0.25).unwrap();
+
+        let breakdown = cost_breakdown(&storage, &agent.id, 7).unwrap();
+        assert_eq!(breakdown.tasks.len(), 2);
+        assert_eq!(breakdown.tasks[0].task_id, pricey.id);
+        assert!((breakdown.tasks[0].estimated_cost_cents - 75.0).abs() < 1e-6);
+        assert!((breakdown.total_cents - 76.0).abs() < 1e-6);
+    }
+}
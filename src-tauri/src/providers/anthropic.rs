@@ -0,0 +1,64 @@
+use serde_json::json;
+
+use super::{CompletionRequest, CompletionResponse, EventSink, Provider};
+use crate::error::{AppError, AppResult};
+
+const API_URL: &str = "https://api.anthropic.com/v1/messages";
+const API_VERSION: &str = "2023-06-01";
+const MAX_TOKENS: u32 = 4096;
+
+/// Backend calling the Anthropic messages API for `claude-*` models.
+pub struct AnthropicProvider;
+
+impl Provider for AnthropicProvider {
+    fn name(&self) -> &'static str {
+        "anthropic"
+    }
+
+    fn secret_name(&self) -> Option<&'static str> {
+        Some("anthropic_api_key")
+    }
+
+    fn complete(
+        &self,
+        api_key: Option<&str>,
+        request: &CompletionRequest,
+        _events: EventSink<'_>,
+    ) -> AppResult<CompletionResponse> {
+        let api_key = api_key
+            .ok_or_else(|| AppError::Provider("anthropic: no API key available".into()))?;
+
+        let mut body = json!({
+            "model": request.model,
+            "max_tokens": MAX_TOKENS,
+            "messages": [{ "role": "user", "content": request.prompt }],
+        });
+        if let Some(system) = &request.system_prompt {
+            body["system"] = json!(system);
+        }
+        if let Some(temperature) = request.temperature {
+            body["temperature"] = json!(temperature);
+        }
+
+        let response: serde_json::Value = super::http_client(request)
+            .post(API_URL)
+            .header("x-api-key", api_key)
+            .header("anthropic-version", API_VERSION)
+            .json(&body)
+            .send()
+            .and_then(|resp| resp.error_for_status())
+            .map_err(|err| AppError::Provider(format!("anthropic: {err}")))?
+            .json()
+            .map_err(|err| AppError::Provider(format!("anthropic: invalid response: {err}")))?;
+
+        let text = response["content"][0]["text"]
+            .as_str()
+            .ok_or_else(|| AppError::Provider("anthropic: response missing content".into()))?
+            .to_string();
+        Ok(CompletionResponse {
+            text,
+            prompt_tokens: response["usage"]["input_tokens"].as_u64().unwrap_or(0),
+            completion_tokens: response["usage"]["output_tokens"].as_u64().unwrap_or(0),
+        })
+    }
+}
@@ -0,0 +1,95 @@
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+use super::{CompletionRequest, CompletionResponse, EventSink, Provider};
+use crate::error::{AppError, AppResult};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Give up polling after this many attempts (~10 minutes).
+const MAX_POLLS: u32 = 300;
+
+/// Backend bridging to a CrewAI service over REST: kick off a crew run
+/// and poll its status, streaming per-crew-task updates back as events.
+pub struct CrewAiProvider;
+
+impl Provider for CrewAiProvider {
+    fn name(&self) -> &'static str {
+        "crewai"
+    }
+
+    fn secret_name(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn complete(
+        &self,
+        _api_key: Option<&str>,
+        request: &CompletionRequest,
+        events: EventSink<'_>,
+    ) -> AppResult<CompletionResponse> {
+        let endpoint = request.endpoint.as_deref().ok_or_else(|| {
+            AppError::InvalidArgument("crewai agent has no endpoint configured".into())
+        })?;
+        let base = endpoint.trim_end_matches('/');
+        let client = super::http_client(request);
+
+        let kickoff: Value = client
+            .post(format!("{base}/kickoff"))
+            .json(&json!({ "inputs": { "instruction": request.prompt } }))
+            .send()
+            .and_then(|resp| resp.error_for_status())
+            .map_err(|err| AppError::Provider(format!("crewai: {err}")))?
+            .json()
+            .map_err(|err| AppError::Provider(format!("crewai: invalid response: {err}")))?;
+        let run_id = kickoff["kickoff_id"]
+            .as_str()
+            .or_else(|| kickoff["id"].as_str())
+            .ok_or_else(|| AppError::Provider("crewai: kickoff returned no run id".into()))?
+            .to_string();
+        events("api_call", json!({ "provider": "crewai", "run_id": run_id }));
+
+        let mut last_step_count = 0;
+        for _ in 0..MAX_POLLS {
+            let status: Value = client
+                .get(format!("{base}/status/{run_id}"))
+                .send()
+                .and_then(|resp| resp.error_for_status())
+                .map_err(|err| AppError::Provider(format!("crewai: {err}")))?
+                .json()
+                .map_err(|err| AppError::Provider(format!("crewai: invalid status: {err}")))?;
+
+            // Surface newly finished crew tasks since the last poll.
+            if let Some(steps) = status["tasks"].as_array() {
+                for step in steps.iter().skip(last_step_count) {
+                    events("progress", json!({ "source": "crewai", "task": step }));
+                }
+                last_step_count = steps.len();
+            }
+
+            match status["state"].as_str().unwrap_or("") {
+                "SUCCESS" | "completed" => {
+                    let text = status["result"]
+                        .as_str()
+                        .map(str::to_string)
+                        .unwrap_or_else(|| status["result"].to_string());
+                    return Ok(CompletionResponse {
+                        text,
+                        prompt_tokens: 0,
+                        completion_tokens: 0,
+                    });
+                }
+                "FAILED" | "failed" | "error" => {
+                    return Err(AppError::Provider(format!(
+                        "crewai: run {run_id} failed: {}",
+                        status["error"].as_str().unwrap_or("unknown error")
+                    )));
+                }
+                _ => std::thread::sleep(POLL_INTERVAL),
+            }
+        }
+        Err(AppError::Provider(format!(
+            "crewai: run {run_id} did not finish within the polling window"
+        )))
+    }
+}
@@ -0,0 +1,105 @@
+use std::io::{BufRead, BufReader};
+
+use serde_json::{json, Value};
+
+use super::{CompletionRequest, CompletionResponse, EventSink, Provider};
+use crate::error::{AppError, AppResult};
+
+/// Backend POSTing instructions to a LangServe runnable. Uses the
+/// `/stream` SSE endpoint so intermediate steps surface as activity
+/// events, falling back to `/invoke` for servers without streaming.
+pub struct LangServeProvider;
+
+impl Provider for LangServeProvider {
+    fn name(&self) -> &'static str {
+        "langserve"
+    }
+
+    fn secret_name(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn complete(
+        &self,
+        _api_key: Option<&str>,
+        request: &CompletionRequest,
+        events: EventSink<'_>,
+    ) -> AppResult<CompletionResponse> {
+        let endpoint = request.endpoint.as_deref().ok_or_else(|| {
+            AppError::InvalidArgument("langserve agent has no endpoint configured".into())
+        })?;
+        let base = endpoint.trim_end_matches('/');
+        let input = json!({ "input": request.prompt });
+
+        match self.stream(request, base, &input, events) {
+            Ok(response) => Ok(response),
+            Err(err) => {
+                tracing::debug!(%err, "langserve /stream failed; falling back to /invoke");
+                self.invoke(request, base, &input)
+            }
+        }
+    }
+}
+
+impl LangServeProvider {
+    fn stream(
+        &self,
+        request: &CompletionRequest,
+        base: &str,
+        input: &Value,
+        events: EventSink<'_>,
+    ) -> AppResult<CompletionResponse> {
+        let response = super::http_client(request)
+            .post(format!("{base}/stream"))
+            .json(input)
+            .send()
+            .and_then(|resp| resp.error_for_status())
+            .map_err(|err| AppError::Provider(format!("langserve: {err}")))?;
+
+        let mut output = String::new();
+        for line in BufReader::new(response).lines() {
+            let line = line.map_err(|err| AppError::Provider(format!("langserve: {err}")))?;
+            let Some(data) = line.strip_prefix("data: ") else { continue };
+            let chunk: Value = match serde_json::from_str(data) {
+                Ok(chunk) => chunk,
+                Err(_) => continue,
+            };
+            if let Some(text) = chunk_text(&chunk) {
+                output.push_str(&text);
+            }
+            events("progress", json!({ "source": "langserve", "chunk": chunk }));
+        }
+        if output.is_empty() {
+            return Err(AppError::Provider("langserve: empty stream".into()));
+        }
+        Ok(CompletionResponse { text: output, prompt_tokens: 0, completion_tokens: 0 })
+    }
+
+    fn invoke(
+        &self,
+        request: &CompletionRequest,
+        base: &str,
+        input: &Value,
+    ) -> AppResult<CompletionResponse> {
+        let response: Value = super::http_client(request)
+            .post(format!("{base}/invoke"))
+            .json(input)
+            .send()
+            .and_then(|resp| resp.error_for_status())
+            .map_err(|err| AppError::Provider(format!("langserve: {err}")))?
+            .json()
+            .map_err(|err| AppError::Provider(format!("langserve: invalid response: {err}")))?;
+        let text = chunk_text(&response["output"])
+            .ok_or_else(|| AppError::Provider("langserve: response missing output".into()))?;
+        Ok(CompletionResponse { text, prompt_tokens: 0, completion_tokens: 0 })
+    }
+}
+
+/// LangServe chunks are either plain strings or message objects with a
+/// `content` field, depending on the runnable.
+fn chunk_text(value: &Value) -> Option<String> {
+    if let Some(text) = value.as_str() {
+        return Some(text.to_string());
+    }
+    value["content"].as_str().map(str::to_string)
+}
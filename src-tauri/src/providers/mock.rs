@@ -0,0 +1,39 @@
+use serde_json::json;
+
+use super::{CompletionRequest, CompletionResponse, EventSink, Provider};
+use crate::error::AppResult;
+
+/// Deterministic offline backend used by tests and `mock*` models.
+pub struct MockProvider;
+
+impl Provider for MockProvider {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn secret_name(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn complete(
+        &self,
+        _api_key: Option<&str>,
+        request: &CompletionRequest,
+        events: EventSink<'_>,
+    ) -> AppResult<CompletionResponse> {
+        // Rough 4-chars-per-token estimate keeps usage numbers plausible.
+        let prompt_tokens = (request.prompt.chars().count() / 4) as u64;
+        let text = format!("Simulated completion for: {}", request.prompt.lines().next().unwrap_or(""));
+        // Stream word-sized deltas so the frontend's streaming path is
+        // exercised without a live model.
+        for word in text.split_inclusive(' ') {
+            events("token_chunk", json!({ "delta": word }));
+        }
+        let completion_tokens = (text.chars().count() / 4) as u64;
+        Ok(CompletionResponse {
+            text,
+            prompt_tokens,
+            completion_tokens,
+        })
+    }
+}
@@ -0,0 +1,124 @@
+pub mod anthropic;
+pub mod crewai;
+pub mod langserve;
+pub mod mock;
+pub mod ollama;
+pub mod openai;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::AppResult;
+
+/// A single completion call to a model backend.
+#[derive(Debug, Clone)]
+pub struct CompletionRequest {
+    pub model: String,
+    pub system_prompt: Option<String>,
+    pub prompt: String,
+    pub temperature: Option<f64>,
+    /// Base URL for self-hosted backends (Ollama); ignored by hosted APIs.
+    pub endpoint: Option<String>,
+    /// Wall-clock limit applied to the HTTP call, from the agent's
+    /// configured execution timeout.
+    pub timeout_seconds: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompletionResponse {
+    pub text: String,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+/// HTTP client honoring the request's execution timeout, shared by
+/// every network-backed provider.
+pub(crate) fn http_client(request: &CompletionRequest) -> reqwest::blocking::Client {
+    let mut builder = reqwest::blocking::Client::builder();
+    if let Some(limit) = request.timeout_seconds {
+        builder = builder.timeout(std::time::Duration::from_secs(limit));
+    }
+    builder.build().unwrap_or_default()
+}
+
+/// Sink for intermediate activity a backend produces mid-completion
+/// (streamed steps, tool traces): `(event_kind, payload)`.
+pub type EventSink<'a> = &'a mut dyn FnMut(&str, Value);
+
+/// An execution backend capable of serving completion requests.
+pub trait Provider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Secret name holding this provider's API key, if it needs one.
+    fn secret_name(&self) -> Option<&'static str>;
+
+    /// Run one completion. Backends that stream intermediate steps
+    /// report them through `events`; request/response backends ignore it.
+    fn complete(
+        &self,
+        api_key: Option<&str>,
+        request: &CompletionRequest,
+        events: EventSink<'_>,
+    ) -> AppResult<CompletionResponse>;
+}
+
+/// Pick the backend for an agent. An explicit `framework` wins;
+/// otherwise the model name decides (`claude-*` routes to Anthropic,
+/// `mock*` stays on the simulated provider tests rely on).
+pub fn for_agent(framework: Option<&str>, model: &str) -> Box<dyn Provider> {
+    match framework {
+        Some("anthropic") => return Box::new(anthropic::AnthropicProvider),
+        Some("openai") => return Box::new(openai::OpenAiProvider),
+        Some("ollama") => return Box::new(ollama::OllamaProvider),
+        Some("langchain") | Some("langserve") => {
+            return Box::new(langserve::LangServeProvider)
+        }
+        Some("crewai") => return Box::new(crewai::CrewAiProvider),
+        Some("mock") => return Box::new(mock::MockProvider),
+        _ => {}
+    }
+    if model.starts_with("mock") {
+        Box::new(mock::MockProvider)
+    } else if model.starts_with("claude") {
+        Box::new(anthropic::AnthropicProvider)
+    } else if model.starts_with("ollama/") {
+        Box::new(ollama::OllamaProvider)
+    } else {
+        Box::new(openai::OpenAiProvider)
+    }
+}
+
+/// Estimated cost of one completion in USD at the built-in rates.
+/// Callers with storage at hand should prefer
+/// [`crate::pricing::cost_usd`], which honors user overrides.
+pub fn estimate_cost_usd(model: &str, prompt_tokens: u64, completion_tokens: u64) -> f64 {
+    let rates = crate::pricing::builtin_rates(model);
+    (prompt_tokens as f64 * rates.input_per_mtok
+        + completion_tokens as f64 * rates.output_per_mtok)
+        / 1_000_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn framework_overrides_model_based_routing() {
+        assert_eq!(for_agent(None, "claude-3-haiku-20240307").name(), "anthropic");
+        assert_eq!(for_agent(None, "gpt-4o").name(), "openai");
+        assert_eq!(for_agent(None, "mock-model").name(), "mock");
+        assert_eq!(for_agent(None, "ollama/llama3").name(), "ollama");
+        assert_eq!(for_agent(Some("anthropic"), "custom-tune").name(), "anthropic");
+        assert_eq!(for_agent(Some("ollama"), "llama3").name(), "ollama");
+        assert_eq!(for_agent(Some("langchain"), "gpt-4o").name(), "langserve");
+        assert_eq!(for_agent(Some("crewai"), "gpt-4o").name(), "crewai");
+        assert_eq!(for_agent(Some("mock"), "claude-3-opus").name(), "mock");
+    }
+
+    #[test]
+    fn estimates_cost_from_usage() {
+        let cost = estimate_cost_usd("claude-3-opus-20240229", 1_000_000, 0);
+        assert!((cost - 15.0).abs() < 1e-9);
+        assert_eq!(estimate_cost_usd("unknown-model", 1000, 1000), 0.0);
+    }
+}
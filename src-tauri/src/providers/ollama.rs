@@ -0,0 +1,84 @@
+use std::io::{BufRead, BufReader};
+
+use serde_json::json;
+
+use super::{CompletionRequest, CompletionResponse, EventSink, Provider};
+use crate::error::{AppError, AppResult};
+
+/// Default Ollama server when the agent has no endpoint configured.
+const DEFAULT_ENDPOINT: &str = "http://localhost:11434";
+
+/// Backend talking to a local Ollama server, for fully offline use.
+pub struct OllamaProvider;
+
+impl Provider for OllamaProvider {
+    fn name(&self) -> &'static str {
+        "ollama"
+    }
+
+    fn secret_name(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn complete(
+        &self,
+        _api_key: Option<&str>,
+        request: &CompletionRequest,
+        events: EventSink<'_>,
+    ) -> AppResult<CompletionResponse> {
+        let endpoint = request.endpoint.as_deref().unwrap_or(DEFAULT_ENDPOINT);
+        let url = format!("{}/api/chat", endpoint.trim_end_matches('/'));
+        // Model names may be prefixed to force Ollama routing.
+        let model = request.model.strip_prefix("ollama/").unwrap_or(&request.model);
+
+        let mut messages = Vec::new();
+        if let Some(system) = &request.system_prompt {
+            messages.push(json!({ "role": "system", "content": system }));
+        }
+        messages.push(json!({ "role": "user", "content": request.prompt }));
+
+        let mut body = json!({ "model": model, "messages": messages, "stream": true });
+        if let Some(temperature) = request.temperature {
+            body["options"] = json!({ "temperature": temperature });
+        }
+
+        // Streaming mode: the server sends one NDJSON chunk per token
+        // batch; deltas go to the sink and accumulate into the result.
+        let response = super::http_client(request)
+            .post(&url)
+            .json(&body)
+            .send()
+            .and_then(|resp| resp.error_for_status())
+            .map_err(|err| AppError::Provider(format!("ollama ({endpoint}): {err}")))?;
+
+        let mut text = String::new();
+        let mut prompt_tokens = 0;
+        let mut completion_tokens = 0;
+        for line in BufReader::new(response).lines() {
+            let line = line.map_err(|err| AppError::Provider(format!("ollama: {err}")))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let chunk: serde_json::Value = serde_json::from_str(&line)
+                .map_err(|err| AppError::Provider(format!("ollama: invalid chunk: {err}")))?;
+            if let Some(delta) = chunk["message"]["content"].as_str() {
+                if !delta.is_empty() {
+                    text.push_str(delta);
+                    events("token_chunk", json!({ "delta": delta }));
+                }
+            }
+            if chunk["done"].as_bool() == Some(true) {
+                prompt_tokens = chunk["prompt_eval_count"].as_u64().unwrap_or(0);
+                completion_tokens = chunk["eval_count"].as_u64().unwrap_or(0);
+            }
+        }
+        if text.is_empty() {
+            return Err(AppError::Provider("ollama: response missing content".into()));
+        }
+        Ok(CompletionResponse {
+            text,
+            prompt_tokens,
+            completion_tokens,
+        })
+    }
+}
@@ -0,0 +1,60 @@
+use serde_json::json;
+
+use super::{CompletionRequest, CompletionResponse, EventSink, Provider};
+use crate::error::{AppError, AppResult};
+
+const API_URL: &str = "https://api.openai.com/v1/chat/completions";
+
+/// Backend calling the OpenAI chat completions API.
+pub struct OpenAiProvider;
+
+impl Provider for OpenAiProvider {
+    fn name(&self) -> &'static str {
+        "openai"
+    }
+
+    fn secret_name(&self) -> Option<&'static str> {
+        Some("openai_api_key")
+    }
+
+    fn complete(
+        &self,
+        api_key: Option<&str>,
+        request: &CompletionRequest,
+        _events: EventSink<'_>,
+    ) -> AppResult<CompletionResponse> {
+        let api_key = api_key
+            .ok_or_else(|| AppError::Provider("openai: no API key available".into()))?;
+
+        let mut messages = Vec::new();
+        if let Some(system) = &request.system_prompt {
+            messages.push(json!({ "role": "system", "content": system }));
+        }
+        messages.push(json!({ "role": "user", "content": request.prompt }));
+
+        let mut body = json!({ "model": request.model, "messages": messages });
+        if let Some(temperature) = request.temperature {
+            body["temperature"] = json!(temperature);
+        }
+
+        let response: serde_json::Value = super::http_client(request)
+            .post(API_URL)
+            .bearer_auth(api_key)
+            .json(&body)
+            .send()
+            .and_then(|resp| resp.error_for_status())
+            .map_err(|err| AppError::Provider(format!("openai: {err}")))?
+            .json()
+            .map_err(|err| AppError::Provider(format!("openai: invalid response: {err}")))?;
+
+        let text = response["choices"][0]["message"]["content"]
+            .as_str()
+            .ok_or_else(|| AppError::Provider("openai: response missing content".into()))?
+            .to_string();
+        Ok(CompletionResponse {
+            text,
+            prompt_tokens: response["usage"]["prompt_tokens"].as_u64().unwrap_or(0),
+            completion_tokens: response["usage"]["completion_tokens"].as_u64().unwrap_or(0),
+        })
+    }
+}
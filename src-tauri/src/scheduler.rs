@@ -0,0 +1,253 @@
+//! Cron-style recurring dispatch.
+//!
+//! A schedule stores a task template plus a five-field cron expression
+//! (`minute hour day-of-month month day-of-week`). The supported field
+//! syntax is the common subset: `*`, `*/step`, numbers, `a-b` ranges
+//! and comma lists. A background loop fires due schedules once per
+//! matching minute.
+
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use chrono_tz::Tz;
+use serde_json::json;
+
+use crate::error::{AppError, AppResult};
+use crate::storage::Storage;
+use crate::task_dispatch::{self, DispatchRequest};
+
+/// Validate a cron expression against the supported subset.
+pub fn validate_cron(expr: &str) -> AppResult<()> {
+    let fields: Vec<&str> = expr.split_whitespace().collect();
+    if fields.len() != 5 {
+        return Err(AppError::InvalidArgument(format!(
+            "cron expression needs 5 fields, got {}: {expr:?}",
+            fields.len()
+        )));
+    }
+    let bounds = [(0, 59), (0, 23), (1, 31), (1, 12), (0, 6)];
+    for (field, (min, max)) in fields.iter().zip(bounds) {
+        parse_field(field, min, max).map_err(|reason| {
+            AppError::InvalidArgument(format!("invalid cron field {field:?}: {reason}"))
+        })?;
+    }
+    Ok(())
+}
+
+/// Check that a timezone string is a valid IANA name.
+pub fn validate_timezone(name: &str) -> AppResult<()> {
+    Tz::from_str(name).map(|_| ()).map_err(|_| {
+        AppError::InvalidArgument(format!("unknown IANA timezone {name:?}"))
+    })
+}
+
+/// Whether `expr` matches the minute containing `at`. Day-of-month and
+/// day-of-week combine with OR when both are restricted, per cron
+/// convention. Generic over the zone so schedules evaluate in their
+/// agent's local wall-clock time (DST shifts included).
+pub fn cron_matches<Z: chrono::TimeZone>(expr: &str, at: DateTime<Z>) -> bool {
+    let fields: Vec<&str> = expr.split_whitespace().collect();
+    if fields.len() != 5 {
+        return false;
+    }
+    let minute = field_matches(fields[0], 0, 59, at.minute());
+    let hour = field_matches(fields[1], 0, 23, at.hour());
+    let dom = field_matches(fields[2], 1, 31, at.day());
+    let month = field_matches(fields[3], 1, 12, at.month());
+    let dow = field_matches(fields[4], 0, 6, at.weekday().num_days_from_sunday());
+    let day = if fields[2] != "*" && fields[4] != "*" {
+        dom || dow
+    } else {
+        dom && dow
+    };
+    minute && hour && day && month
+}
+
+/// Parse one field into the values it matches, or an error reason.
+fn parse_field(field: &str, min: u32, max: u32) -> Result<Vec<u32>, String> {
+    let mut values = Vec::new();
+    for part in field.split(',') {
+        if part == "*" {
+            values.extend(min..=max);
+        } else if let Some(step) = part.strip_prefix("*/") {
+            let step: u32 = step.parse().map_err(|_| "step is not a number")?;
+            if step == 0 {
+                return Err("step of 0".into());
+            }
+            values.extend((min..=max).filter(|v| (v - min) % step == 0));
+        } else if let Some((a, b)) = part.split_once('-') {
+            let a: u32 = a.parse().map_err(|_| "range start is not a number")?;
+            let b: u32 = b.parse().map_err(|_| "range end is not a number")?;
+            if a < min || b > max || a > b {
+                return Err(format!("range {a}-{b} outside {min}-{max}"));
+            }
+            values.extend(a..=b);
+        } else {
+            let v: u32 = part.parse().map_err(|_| "not a number")?;
+            if v < min || v > max {
+                return Err(format!("{v} outside {min}-{max}"));
+            }
+            values.push(v);
+        }
+    }
+    Ok(values)
+}
+
+fn field_matches(field: &str, min: u32, max: u32, value: u32) -> bool {
+    parse_field(field, min, max)
+        .map(|values| values.contains(&value))
+        .unwrap_or(false)
+}
+
+/// Dispatch every schedule due at `now` that has not already fired in
+/// this minute. Returns the dispatched task ids (for the worker queue).
+pub fn fire_due(storage: &Storage, now: DateTime<Utc>) -> AppResult<Vec<String>> {
+    let mut dispatched = Vec::new();
+    for schedule in storage.get_schedules()? {
+        if schedule.paused {
+            continue;
+        }
+        // Evaluate the cron expression on the agent's wall clock; an
+        // unset or invalid timezone falls back to UTC.
+        let timezone = storage
+            .get_agent(&schedule.agent_id)
+            .ok()
+            .and_then(|agent| agent.timezone)
+            .and_then(|name| Tz::from_str(&name).ok());
+        let due = match timezone {
+            Some(tz) => cron_matches(&schedule.cron, now.with_timezone(&tz)),
+            None => cron_matches(&schedule.cron, now),
+        };
+        if !due {
+            continue;
+        }
+        if let Some(last) = schedule.last_run_at {
+            if last.format("%Y-%m-%d %H:%M").to_string()
+                == now.format("%Y-%m-%d %H:%M").to_string()
+            {
+                continue;
+            }
+        }
+        let request = DispatchRequest::new(
+            &schedule.agent_id,
+            format!("{} ({})", schedule.title, now.format("%Y-%m-%d %H:%M")),
+            &schedule.prompt,
+        );
+        match task_dispatch::dispatch(storage, &request) {
+            Ok(task) => {
+                storage.mark_schedule_run(&schedule.id, now)?;
+                storage.append_event(
+                    &task.id,
+                    "scheduled_dispatch",
+                    Some(&json!({ "schedule_id": schedule.id, "cron": schedule.cron })),
+                )?;
+                dispatched.push(task.id);
+            }
+            Err(err) => {
+                tracing::warn!(schedule = %schedule.id, %err, "scheduled dispatch failed");
+            }
+        }
+    }
+    Ok(dispatched)
+}
+
+/// Scheduler loop, run on its own thread: fires due schedules,
+/// promotes delayed (`run_at`) tasks whose time has arrived, and
+/// queues all of them for execution. Double promotion across sweeps is
+/// harmless: claiming is atomic, so a second attempt is rejected.
+pub fn scheduler_loop(storage: &Storage, workers: &crate::worker_pool::WorkerPool) {
+    loop {
+        match fire_due(storage, Utc::now()) {
+            Ok(task_ids) => {
+                for task_id in task_ids {
+                    workers.enqueue(&task_id);
+                }
+            }
+            Err(err) => tracing::warn!(%err, "schedule sweep failed"),
+        }
+        match storage.due_delayed_tasks(Utc::now()) {
+            Ok(due) => {
+                for task in due {
+                    workers.enqueue(&task.id);
+                }
+            }
+            Err(err) => tracing::warn!(%err, "delayed-task sweep failed"),
+        }
+        std::thread::sleep(std::time::Duration::from_secs(20));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Agent;
+    use chrono::TimeZone;
+
+    #[test]
+    fn cron_subset_parses_and_matches() {
+        validate_cron("0 * * * *").unwrap();
+        validate_cron("*/15 9-17 * * 1-5").unwrap();
+        assert!(validate_cron("61 * * * *").is_err());
+        assert!(validate_cron("* * * *").is_err());
+
+        let nine_thirty = Utc.with_ymd_and_hms(2024, 5, 6, 9, 30, 0).unwrap(); // a Monday
+        assert!(cron_matches("30 9 * * *", nine_thirty));
+        assert!(cron_matches("*/15 9-17 * * 1-5", nine_thirty));
+        assert!(!cron_matches("30 9 * * 0", nine_thirty));
+    }
+
+    #[test]
+    fn schedules_follow_the_agent_timezone_across_dst() {
+        let storage = Storage::open_in_memory().unwrap();
+        let mut agent = Agent::new("ny", "mock");
+        agent.timezone = Some("America/New_York".into());
+        storage.create_agent(&agent).unwrap();
+        storage
+            .create_schedule(&agent.id, "daily", "p", "30 9 * * *")
+            .unwrap();
+
+        // 13:30 UTC is 09:30 in New York during DST...
+        let summer = Utc.with_ymd_and_hms(2024, 7, 1, 13, 30, 0).unwrap();
+        assert_eq!(fire_due(&storage, summer).unwrap().len(), 1);
+        // ...but 08:30 in winter, so nothing fires.
+        let winter = Utc.with_ymd_and_hms(2024, 1, 8, 13, 30, 0).unwrap();
+        assert!(fire_due(&storage, winter).unwrap().is_empty());
+        // The winter fire time is an hour later in UTC.
+        let winter_due = Utc.with_ymd_and_hms(2024, 1, 8, 14, 30, 0).unwrap();
+        assert_eq!(fire_due(&storage, winter_due).unwrap().len(), 1);
+
+        assert!(validate_timezone("America/New_York").is_ok());
+        assert!(validate_timezone("Mars/Olympus_Mons").is_err());
+    }
+
+    #[test]
+    fn due_schedules_dispatch_once_per_minute() {
+        let storage = Storage::open_in_memory().unwrap();
+        let agent = Agent::new("cron", "mock");
+        storage.create_agent(&agent).unwrap();
+        let schedule = storage
+            .create_schedule(&agent.id, "health check", "check the pipeline", "* * * * *")
+            .unwrap();
+
+        let now = Utc::now();
+        let first = fire_due(&storage, now).unwrap();
+        assert_eq!(first.len(), 1);
+        // Same minute: nothing fires again.
+        assert!(fire_due(&storage, now).unwrap().is_empty());
+        let schedules = storage.get_schedules().unwrap();
+        assert!(schedules[0].last_run_at.is_some());
+        assert_eq!(schedules[0].id, schedule.id);
+    }
+
+    #[test]
+    fn paused_schedules_do_not_fire() {
+        let storage = Storage::open_in_memory().unwrap();
+        let agent = Agent::new("cron", "mock");
+        storage.create_agent(&agent).unwrap();
+        let schedule = storage
+            .create_schedule(&agent.id, "t", "p", "* * * * *")
+            .unwrap();
+        storage.set_schedule_paused(&schedule.id, true).unwrap();
+        assert!(fire_due(&storage, Utc::now()).unwrap().is_empty());
+    }
+}
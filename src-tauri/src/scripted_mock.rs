@@ -0,0 +1,516 @@
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+
+use crate::error::{AppError, AppResult};
+use crate::models::{Agent, PlanStep, Task};
+use crate::storage::Storage;
+
+/// Settings key pointing at the default script file, used when the
+/// agent itself does not name one in `command`.
+pub const SCRIPT_PATH_SETTING: &str = "mock_script_path";
+
+/// Run a scriptable mock agent (framework "scripted"): a JSON file
+/// defines the exact sequence of thoughts, tool calls, delays, token
+/// chunks and the final result (or error) for given instructions, so
+/// frontend developers can reproduce any UI state on demand.
+///
+/// Script format:
+/// ```json
+/// {
+///   "default": [ { "thought": "..." }, { "result": "..." } ],
+///   "scripts": {
+///     "instruction substring": [
+///       { "thought": "planning" },
+///       { "tool_call": { "tool": "search", "args": {} } },
+///       { "delay_ms": 250 },
+///       { "token_chunk": "partial " },
+///       { "error": "simulated failure" }
+///     ]
+///   }
+/// }
+/// ```
+pub fn run(storage: &Storage, task: &Task, agent: &Agent, prompt: &str) -> AppResult<String> {
+    let path = match &agent.command {
+        Some(path) => path.clone(),
+        None => storage.get_setting(SCRIPT_PATH_SETTING)?.ok_or_else(|| {
+            AppError::InvalidArgument(format!(
+                "scripted agent {} has no script: set `command` or the {SCRIPT_PATH_SETTING} setting",
+                agent.id
+            ))
+        })?,
+    };
+    let raw = std::fs::read_to_string(&path)?;
+    let script: Value = serde_json::from_str(&raw)
+        .map_err(|err| AppError::InvalidArgument(format!("invalid mock script {path}: {err}")))?;
+
+    let steps = select_steps(&script, prompt).ok_or_else(|| {
+        AppError::InvalidArgument(format!(
+            "mock script {path} has no entry matching this instruction and no default"
+        ))
+    })?;
+
+    let deadline = agent
+        .timeout_seconds
+        .map(|limit| Instant::now() + Duration::from_secs(limit));
+    let mut streamed = String::new();
+    // The most recent `ask_user` answer; `{{input}}` in later steps is
+    // replaced with it, so scripts can exercise the resume path.
+    let mut last_answer: Option<String> = None;
+    // Operator steering messages accumulate here and fill `{{guidance}}`.
+    let mut guidance: Vec<String> = Vec::new();
+    // Plan mode: derive one plan step per scripted action and hold at
+    // the approval gate before doing any work.
+    if task.plan_mode {
+        crate::task_dispatch::submit_plan(storage, task, &plan_from_steps(steps))?;
+    }
+    for (index, step) in steps.iter().enumerate() {
+        if task.plan_mode {
+            // Progress events reference the plan step being executed.
+            storage.append_event(
+                &task.id,
+                "plan_progress",
+                Some(&json!({ "plan_step": index as u32 + 1 })),
+            )?;
+        }
+        // A step boundary is the mock's "model turn": queued operator
+        // messages are taken up before the next action.
+        guidance.extend(crate::task_dispatch::drain_operator_messages(storage, task)?);
+        if let Some(thought) = step["thought"].as_str() {
+            let thought = fill_input(thought, &last_answer, &guidance);
+            storage.append_event(&task.id, "thought_log", Some(&json!({ "message": thought })))?;
+            crate::task_dispatch::pause_at_step(
+                storage,
+                task,
+                &json!({ "step": "thought", "message": thought }),
+            )?;
+        } else if let Some(question) = step["ask_user"].as_str() {
+            last_answer = Some(crate::task_dispatch::ask_user(storage, task, question)?);
+        } else if let Some(call) = step.get("tool_call") {
+            let tool = call["tool"].as_str().unwrap_or_default();
+            crate::task_dispatch::request_tool_approval(storage, task, tool, call)?;
+            storage.append_event(&task.id, "api_call", Some(call))?;
+            crate::task_dispatch::pause_at_step(
+                storage,
+                task,
+                &json!({ "step": "tool_call", "call": call }),
+            )?;
+        } else if let Some(ms) = step["delay_ms"].as_u64() {
+            let wait = Duration::from_millis(ms);
+            // Delays never sleep past the execution timeout; a script
+            // that would is cut short as a timed-out run.
+            match deadline {
+                Some(deadline) if Instant::now() + wait >= deadline => {
+                    std::thread::sleep(deadline.saturating_duration_since(Instant::now()));
+                    return Err(AppError::Timeout {
+                        task_id: task.id.clone(),
+                        seconds: agent.timeout_seconds.unwrap_or_default(),
+                    });
+                }
+                _ => std::thread::sleep(wait),
+            }
+        } else if let Some(delta) = step["token_chunk"].as_str() {
+            let delta = fill_input(delta, &last_answer, &guidance);
+            streamed.push_str(&delta);
+            storage.append_event(&task.id, "token_chunk", Some(&json!({ "delta": delta })))?;
+        } else if let Some(message) = step["error"].as_str() {
+            return Err(AppError::Provider(format!("scripted failure: {message}")));
+        } else if let Some(result) = step["result"].as_str() {
+            return Ok(fill_input(result, &last_answer, &guidance));
+        }
+    }
+    // A script that streams chunks but names no explicit result
+    // completes with the accumulated stream.
+    if streamed.is_empty() {
+        Err(AppError::InvalidArgument(
+            "mock script ended without a result or error step".into(),
+        ))
+    } else {
+        Ok(streamed)
+    }
+}
+
+/// Derive the structured plan of a plan-mode run: one step per
+/// scripted action, described in the operator's terms.
+fn plan_from_steps(steps: &[Value]) -> Vec<PlanStep> {
+    steps
+        .iter()
+        .enumerate()
+        .map(|(index, step)| {
+            let description = if let Some(thought) = step["thought"].as_str() {
+                format!("Think: {thought}")
+            } else if let Some(call) = step.get("tool_call") {
+                format!("Call tool {}", call["tool"].as_str().unwrap_or("unknown"))
+            } else if let Some(question) = step["ask_user"].as_str() {
+                format!("Ask the operator: {question}")
+            } else if step["error"].as_str().is_some() {
+                "Fail (scripted)".to_string()
+            } else if step["result"].as_str().is_some() {
+                "Produce the final result".to_string()
+            } else if step["delay_ms"].as_u64().is_some() {
+                "Wait".to_string()
+            } else {
+                "Stream output".to_string()
+            };
+            PlanStep { id: index as u32 + 1, description }
+        })
+        .collect()
+}
+
+/// Substitute the latest operator answer and accumulated steering
+/// messages into a step's text.
+fn fill_input(text: &str, answer: &Option<String>, guidance: &[String]) -> String {
+    let mut text = match answer {
+        Some(answer) => text.replace("{{input}}", answer),
+        None => text.to_string(),
+    };
+    if text.contains("{{guidance}}") {
+        text = text.replace("{{guidance}}", &guidance.join("; "));
+    }
+    text
+}
+
+/// Longest matching instruction-substring entry wins; `default` is the
+/// fallback.
+fn select_steps<'a>(script: &'a Value, prompt: &str) -> Option<&'a Vec<Value>> {
+    let scripts = script["scripts"].as_object();
+    let best = scripts.and_then(|map| {
+        map.iter()
+            .filter(|(needle, _)| prompt.contains(needle.as_str()))
+            .max_by_key(|(needle, _)| needle.len())
+    });
+    best.map(|(_, steps)| steps)
+        .or_else(|| Some(&script["default"]))
+        .and_then(Value::as_array)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task_dispatch::{self, DispatchRequest};
+    use std::io::Write;
+
+    fn scripted_agent(script: &str, prompt: &str) -> (Storage, Task) {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(script.as_bytes()).unwrap();
+        let (file, path) = file.keep().unwrap();
+        drop(file);
+
+        let storage = Storage::open_in_memory().unwrap();
+        let mut agent = Agent::new("scripted", "mock");
+        agent.framework = Some("scripted".into());
+        agent.command = Some(path.to_string_lossy().into_owned());
+        storage.create_agent(&agent).unwrap();
+        let task = task_dispatch::dispatch(
+            &storage,
+            &DispatchRequest::new(&agent.id, "t", prompt),
+        )
+        .unwrap();
+        (storage, task)
+    }
+
+    #[test]
+    fn replays_thoughts_chunks_and_result_for_matching_instruction() {
+        let script = r#"{
+            "default": [ { "result": "fallback" } ],
+            "scripts": {
+                "summarize": [
+                    { "thought": "reading" },
+                    { "token_chunk": "partial " },
+                    { "token_chunk": "answer" },
+                    { "result": "partial answer" }
+                ]
+            }
+        }"#;
+        let (storage, task) = scripted_agent(script, "please summarize this");
+        let done = task_dispatch::execute(&storage, &task.id).unwrap();
+        assert_eq!(done.result.as_deref(), Some("partial answer"));
+        let events = storage.get_task_events(&task.id).unwrap();
+        assert!(events.iter().any(|e| e.kind == "thought_log"));
+        assert_eq!(events.iter().filter(|e| e.kind == "token_chunk").count(), 2);
+    }
+
+    #[test]
+    fn plan_mode_halts_until_the_plan_is_approved() {
+        let script = r#"{
+            "default": [
+                { "thought": "survey" },
+                { "tool_call": { "tool": "search" } },
+                { "result": "done" }
+            ]
+        }"#;
+        let (storage, plain) = scripted_agent(script, "anything");
+        let mut request = DispatchRequest::new(&plain.agent_id, "planned", "anything");
+        request.plan_mode = true;
+        let task = task_dispatch::dispatch(&storage, &request).unwrap();
+
+        let storage = std::sync::Arc::new(storage);
+        let runner = {
+            let storage = std::sync::Arc::clone(&storage);
+            let task_id = task.id.clone();
+            std::thread::spawn(move || task_dispatch::execute(&storage, &task_id))
+        };
+
+        // The run submits its plan and holds; no work happens before
+        // the approval.
+        let deadline = std::time::Instant::now() + Duration::from_secs(10);
+        loop {
+            let events = storage.get_task_events(&task.id).unwrap();
+            if events.iter().any(|e| e.kind == "plan_submitted") {
+                break;
+            }
+            assert!(std::time::Instant::now() < deadline, "plan never submitted");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let events = storage.get_task_events(&task.id).unwrap();
+        assert!(!events.iter().any(|e| e.kind == "thought_log"));
+        let plan = storage.get_task(&task.id).unwrap().plan.expect("plan stored");
+        assert_eq!(plan.len(), 3);
+        assert_eq!(plan[0].id, 1);
+        assert!(plan[1].description.contains("search"));
+
+        task_dispatch::approve_plan(&storage, &task.id).unwrap();
+        let done = runner.join().unwrap().unwrap();
+        assert_eq!(done.result.as_deref(), Some("done"));
+        // Progress events reference the plan steps that were executed.
+        let events = storage.get_task_events(&task.id).unwrap();
+        let steps: Vec<u64> = events
+            .iter()
+            .filter(|e| e.kind == "plan_progress")
+            .filter_map(|e| e.payload.as_ref()?["plan_step"].as_u64())
+            .collect();
+        assert_eq!(steps, vec![1, 2, 3]);
+        // Plain runs never gate on a plan.
+        assert!(task_dispatch::approve_plan(&storage, &plain.id).is_err());
+    }
+
+    #[test]
+    fn step_mode_pauses_at_each_step_until_continued() {
+        let script = r#"{
+            "default": [
+                { "thought": "planning" },
+                { "tool_call": { "tool": "search" } },
+                { "result": "done" }
+            ]
+        }"#;
+        let (storage, plain) = scripted_agent(script, "anything");
+        let mut request = DispatchRequest::new(&plain.agent_id, "supervised", "anything");
+        request.step_mode = true;
+        let task = task_dispatch::dispatch(&storage, &request).unwrap();
+
+        let storage = std::sync::Arc::new(storage);
+        let runner = {
+            let storage = std::sync::Arc::clone(&storage);
+            let task_id = task.id.clone();
+            std::thread::spawn(move || task_dispatch::execute(&storage, &task_id))
+        };
+
+        // The run pauses twice (thought, then tool call); release each
+        // pause once it appears.
+        for _ in 0..2 {
+            let deadline = std::time::Instant::now() + Duration::from_secs(10);
+            loop {
+                let events = storage.get_task_events(&task.id).unwrap();
+                let paused = events.iter().filter(|e| e.kind == "paused_at_step").count();
+                let continued = events.iter().filter(|e| e.kind == "step_continued").count();
+                if paused > continued {
+                    break;
+                }
+                assert!(std::time::Instant::now() < deadline, "run never paused");
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            task_dispatch::continue_task(&storage, &task.id).unwrap();
+        }
+        let done = runner.join().unwrap().unwrap();
+        assert_eq!(done.result.as_deref(), Some("done"));
+    }
+
+    #[test]
+    fn ask_user_suspends_until_an_answer_arrives() {
+        let script = r#"{
+            "default": [
+                { "ask_user": "Which environment?" },
+                { "result": "deployed to {{input}}" }
+            ]
+        }"#;
+        let (storage, task) = scripted_agent(script, "anything");
+        let storage = std::sync::Arc::new(storage);
+        let runner = {
+            let storage = std::sync::Arc::clone(&storage);
+            let task_id = task.id.clone();
+            std::thread::spawn(move || task_dispatch::execute(&storage, &task_id))
+        };
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let status = storage.get_task(&task.id).unwrap().status;
+            if status == crate::models::TaskStatus::WaitingForInput {
+                break;
+            }
+            assert!(Instant::now() < deadline, "task never asked for input");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        // Answering out of order is rejected; only waiting tasks accept.
+        task_dispatch::provide_input(&storage, &task.id, "staging").unwrap();
+        assert!(task_dispatch::provide_input(&storage, &task.id, "again").is_err());
+
+        let done = runner.join().unwrap().unwrap();
+        assert_eq!(done.result.as_deref(), Some("deployed to staging"));
+        let events = storage.get_task_events(&task.id).unwrap();
+        assert!(events.iter().any(|e| e.kind == "ask_user"));
+        assert!(events.iter().any(|e| e.kind == "user_input"));
+    }
+
+    #[test]
+    fn gated_tools_wait_for_approval_and_denial_fails_the_run() {
+        let script = r#"{
+            "default": [
+                { "tool_call": { "tool": "file_access" } },
+                { "tool_call": { "tool": "code_execution" } },
+                { "result": "done" }
+            ]
+        }"#;
+        let (storage, task) = scripted_agent(script, "anything");
+        let mut policy = crate::policy::ApprovalPolicy::default();
+        policy.rules.insert(
+            task.agent_id.clone(),
+            vec!["file_access".into(), "code_execution".into()],
+        );
+        policy.save(&storage).unwrap();
+
+        let storage = std::sync::Arc::new(storage);
+        let runner = {
+            let storage = std::sync::Arc::clone(&storage);
+            let task_id = task.id.clone();
+            std::thread::spawn(move || task_dispatch::execute(&storage, &task_id))
+        };
+
+        let wait_for_pending = |tool: &str| {
+            let deadline = Instant::now() + Duration::from_secs(10);
+            loop {
+                let pending = task_dispatch::pending_tool_calls(&storage).unwrap();
+                if let Some(call) = pending.first() {
+                    assert_eq!(call.tool, tool);
+                    return;
+                }
+                assert!(Instant::now() < deadline, "tool call never paused");
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        };
+
+        wait_for_pending("file_access");
+        task_dispatch::resolve_tool_call(&storage, &task.id, true, "alice", None).unwrap();
+        wait_for_pending("code_execution");
+        task_dispatch::resolve_tool_call(&storage, &task.id, false, "bob", Some("too risky"))
+            .unwrap();
+
+        let done = runner.join().unwrap().unwrap();
+        assert_eq!(done.status, crate::models::TaskStatus::Failed);
+        assert_eq!(done.failure_kind, Some(crate::models::FailureKind::Guardrail));
+        let events = storage.get_task_events(&task.id).unwrap();
+        assert!(events.iter().any(|e| e.kind == "tool_approval_granted"));
+        assert!(events.iter().any(|e| e.kind == "tool_approval_denied"));
+        // There is nothing left to resolve after the run aborted.
+        assert!(
+            task_dispatch::resolve_tool_call(&storage, &task.id, true, "alice", None).is_err()
+        );
+        // Both decisions are on the audit trail with their approvers.
+        let approvals = storage.get_task_approvals(&task.id).unwrap();
+        assert_eq!(approvals.len(), 2);
+        assert_eq!(approvals[0].approver, "alice");
+        assert_eq!(approvals[0].decision, "approved");
+        assert_eq!(approvals[1].approver, "bob");
+        assert_eq!(approvals[1].comment.as_deref(), Some("too risky"));
+    }
+
+    #[test]
+    fn unanswered_approvals_time_out_per_policy() {
+        let script = r#"{
+            "default": [
+                { "tool_call": { "tool": "file_access" } },
+                { "result": "done" }
+            ]
+        }"#;
+
+        // Default: a timed-out approval is denied and fails the run.
+        let (storage, task) = scripted_agent(script, "anything");
+        let mut policy = crate::policy::ApprovalPolicy::default();
+        policy
+            .rules
+            .insert(task.agent_id.clone(), vec!["file_access".into()]);
+        policy.timeout_seconds = Some(0);
+        policy.save(&storage).unwrap();
+        let done = task_dispatch::execute(&storage, &task.id).unwrap();
+        assert_eq!(done.status, crate::models::TaskStatus::Failed);
+        assert_eq!(done.failure_kind, Some(crate::models::FailureKind::Guardrail));
+        let events = storage.get_task_events(&task.id).unwrap();
+        assert!(events.iter().any(|e| {
+            e.kind == "warning"
+                && e.payload.as_ref().is_some_and(|p| p["reason"] == "approval_timeout")
+        }));
+
+        // Low-risk agents auto-approve on timeout instead.
+        let (storage, task) = scripted_agent(script, "anything");
+        let mut policy = crate::policy::ApprovalPolicy::default();
+        policy
+            .rules
+            .insert(task.agent_id.clone(), vec!["file_access".into()]);
+        policy.timeout_seconds = Some(0);
+        policy.auto_approve_on_timeout = vec![task.agent_id.clone()];
+        policy.save(&storage).unwrap();
+        let done = task_dispatch::execute(&storage, &task.id).unwrap();
+        assert_eq!(done.result.as_deref(), Some("done"));
+    }
+
+    #[test]
+    fn operator_messages_steer_the_next_turn() {
+        let script = r#"{
+            "default": [
+                { "ask_user": "Proceed?" },
+                { "result": "guidance: {{guidance}}; answer: {{input}}" }
+            ]
+        }"#;
+        let (storage, task) = scripted_agent(script, "anything");
+        let storage = std::sync::Arc::new(storage);
+        let runner = {
+            let storage = std::sync::Arc::clone(&storage);
+            let task_id = task.id.clone();
+            std::thread::spawn(move || task_dispatch::execute(&storage, &task_id))
+        };
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            if storage.get_task(&task.id).unwrap().status
+                == crate::models::TaskStatus::WaitingForInput
+            {
+                break;
+            }
+            assert!(Instant::now() < deadline, "task never asked for input");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        // Steering is allowed while the task waits; it queues for the
+        // next turn rather than interrupting anything.
+        task_dispatch::send_message(&storage, &task.id, "keep it short").unwrap();
+        task_dispatch::provide_input(&storage, &task.id, "yes").unwrap();
+
+        let done = runner.join().unwrap().unwrap();
+        assert_eq!(
+            done.result.as_deref(),
+            Some("guidance: keep it short; answer: yes")
+        );
+        let events = storage.get_task_events(&task.id).unwrap();
+        assert!(events.iter().any(|e| e.kind == "guidance_applied"));
+        // Steering a finished task is rejected.
+        assert!(task_dispatch::send_message(&storage, &task.id, "late").is_err());
+    }
+
+    #[test]
+    fn scripted_errors_fail_the_task_reproducibly() {
+        let script = r#"{ "default": [ { "error": "boom" } ] }"#;
+        let (storage, task) = scripted_agent(script, "anything");
+        let done = task_dispatch::execute(&storage, &task.id).unwrap();
+        assert_eq!(done.status, crate::models::TaskStatus::Failed);
+        assert_eq!(done.failure_kind, Some(crate::models::FailureKind::Provider));
+        assert!(done.error.unwrap().contains("boom"));
+    }
+}
@@ -0,0 +1,121 @@
+use serde_json::json;
+
+use crate::error::{AppError, AppResult};
+use crate::storage::Storage;
+
+/// Settings key enabling live provider smoke tests during rotation.
+/// Off by default so air-gapped setups can still rotate keys.
+pub const SMOKE_TEST_SETTING: &str = "smoke_test_on_rotate";
+
+/// Rotate a secret: validate the new value, swap it atomically and
+/// record an audit entry. Running tasks read secrets through storage on
+/// each API call, so they pick up the new value without a restart.
+pub fn rotate_secret(storage: &Storage, name: &str, new_value: &str) -> AppResult<()> {
+    // The name must already be registered; rotation never creates keys.
+    match storage.get_secret(name) {
+        Ok(_) | Err(AppError::SecretNotSet(_)) => {}
+        Err(err) => return Err(err),
+    }
+
+    validate_secret_value(name, new_value)?;
+    let smoke_test = run_smoke_test(storage, name, new_value)?;
+
+    // Single upsert, so readers never observe a half-rotated state.
+    storage.set_secret(name, new_value)?;
+    storage.record_secret_rotation(name, &smoke_test)?;
+    storage.add_notification(
+        &format!("Secret {name} rotated"),
+        &format!("Smoke test: {smoke_test}. Running tasks use the new value on their next call."),
+    )?;
+    tracing::info!(secret = name, smoke_test, "secret rotated");
+    Ok(())
+}
+
+/// Static sanity checks on the new value before it replaces a working key.
+fn validate_secret_value(name: &str, value: &str) -> AppResult<()> {
+    if value.trim().is_empty() {
+        return Err(AppError::InvalidArgument(format!(
+            "new value for {name} is empty"
+        )));
+    }
+    if value.chars().any(char::is_whitespace) {
+        return Err(AppError::InvalidArgument(format!(
+            "new value for {name} contains whitespace; probably a paste error"
+        )));
+    }
+    Ok(())
+}
+
+/// Provider smoke test for well-known key names, when enabled. Returns a
+/// short outcome string for the audit entry.
+fn run_smoke_test(storage: &Storage, name: &str, value: &str) -> AppResult<String> {
+    let enabled = storage
+        .get_setting(SMOKE_TEST_SETTING)?
+        .map(|v| v == "true")
+        .unwrap_or(false);
+    if !enabled {
+        return Ok("skipped (disabled)".to_string());
+    }
+    let (url, header) = match name {
+        "openai_api_key" => ("https://api.openai.com/v1/models", "Authorization"),
+        "anthropic_api_key" => ("https://api.anthropic.com/v1/models", "x-api-key"),
+        _ => return Ok("skipped (no provider test for this name)".to_string()),
+    };
+    let client = reqwest::blocking::Client::new();
+    let request = if header == "Authorization" {
+        client.get(url).bearer_auth(value)
+    } else {
+        client.get(url).header(header, value)
+    };
+    match request.send().and_then(|resp| resp.error_for_status()) {
+        Ok(_) => Ok("passed".to_string()),
+        Err(err) => Err(AppError::InvalidArgument(format!(
+            "provider smoke test for {name} failed: {err}; secret not rotated"
+        ))),
+    }
+}
+
+/// Notification payload describing a rotation, for callers that surface
+/// it to the frontend.
+pub fn rotation_event(name: &str) -> serde_json::Value {
+    json!({ "secret": name, "kind": "rotated" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotation_swaps_value_and_records_audit_entry() {
+        let storage = Storage::open_in_memory().unwrap();
+        storage.set_secret("api_key", "old-value").unwrap();
+
+        rotate_secret(&storage, "api_key", "new-value").unwrap();
+        assert_eq!(storage.get_secret("api_key").unwrap(), "new-value");
+        let rotations = storage.get_secret_rotations("api_key").unwrap();
+        assert_eq!(rotations.len(), 1);
+    }
+
+    #[test]
+    fn rejects_unknown_names_and_bad_values() {
+        let storage = Storage::open_in_memory().unwrap();
+        storage.set_secret("api_key", "old").unwrap();
+
+        assert!(matches!(
+            rotate_secret(&storage, "nope", "value"),
+            Err(AppError::NotFound { .. })
+        ));
+        assert!(rotate_secret(&storage, "api_key", "  ").is_err());
+        assert!(rotate_secret(&storage, "api_key", "has space").is_err());
+        // The working key is untouched after failed validations.
+        assert_eq!(storage.get_secret("api_key").unwrap(), "old");
+    }
+
+    #[test]
+    fn fills_in_value_for_registered_but_unset_names() {
+        let storage = Storage::open_in_memory().unwrap();
+        storage.ensure_secret_name("api_key").unwrap();
+        rotate_secret(&storage, "api_key", "first-value").unwrap();
+        assert_eq!(storage.get_secret("api_key").unwrap(), "first-value");
+    }
+}
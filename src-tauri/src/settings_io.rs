@@ -0,0 +1,675 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{AppError, AppResult};
+use crate::models::{Agent, Task, TaskEvent};
+use crate::storage::{ImportBatch, Storage};
+
+/// Current export format version, bumped on incompatible changes.
+/// v2 added agents to the bundle; v3 added tasks and their events.
+pub const EXPORT_VERSION: u32 = 3;
+
+/// Fixed namespace for deriving stable imported-agent ids. Importing
+/// the same bundle twice (e.g. refreshed team templates) maps each
+/// source agent to the same local id, so re-imports update in place
+/// instead of multiplying agents.
+const IMPORT_NAMESPACE: uuid::Uuid = uuid::Uuid::from_u128(0x8f1f_52a3_19c4_4b0e_9d3a_6c70_25e1_77b2);
+
+/// Deterministic UUIDv5 local id for an imported entity's source id.
+fn stable_import_id(source_id: &str) -> String {
+    uuid::Uuid::new_v5(&IMPORT_NAMESPACE, source_id.as_bytes()).to_string()
+}
+
+/// Portable snapshot of workspace configuration.
+///
+/// Contains every settings key (which includes alert rules, schedules
+/// and policies — they are all stored as settings) plus the *names* of
+/// registered secrets. Secret values are deliberately never exported.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SettingsExport {
+    pub version: u32,
+    pub settings: BTreeMap<String, String>,
+    pub secret_names: Vec<String>,
+    /// Agents in the bundle (v2+); absent in v1 exports.
+    #[serde(default)]
+    pub agents: Vec<Agent>,
+    /// Tasks and their events (v3+), so a workspace can move machines
+    /// with its history intact.
+    #[serde(default)]
+    pub tasks: Vec<Task>,
+    #[serde(default)]
+    pub events: Vec<TaskEvent>,
+}
+
+/// How to handle an imported agent that matches an existing one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateResolution {
+    /// Keep the existing agent and its stats; update its config from
+    /// the import. The default, so re-imports do not create doppelgangers.
+    Merge,
+    /// Create the imported agent as a separate copy anyway.
+    Duplicate,
+    /// Leave the existing agent untouched and drop the imported one.
+    Skip,
+}
+
+/// One imported agent that matches an existing agent by name+framework,
+/// surfaced so the user can choose a [`DuplicateResolution`].
+#[derive(Debug, Clone, Serialize)]
+pub struct AgentImportConflict {
+    pub imported_id: String,
+    pub existing_id: String,
+    pub name: String,
+    pub framework: Option<String>,
+}
+
+/// Detect imported agents that collide with existing ones, without
+/// changing anything. The frontend shows these before the real import.
+pub fn plan_agent_import(
+    storage: &Storage,
+    agents: &[Agent],
+) -> AppResult<Vec<AgentImportConflict>> {
+    let existing = storage.get_all_agents()?;
+    let mut conflicts = Vec::new();
+    for agent in agents {
+        if let Some(found) = existing
+            .iter()
+            .find(|e| e.name == agent.name && e.framework == agent.framework)
+        {
+            conflicts.push(AgentImportConflict {
+                imported_id: agent.id.clone(),
+                existing_id: found.id.clone(),
+                name: agent.name.clone(),
+                framework: agent.framework.clone(),
+            });
+        }
+    }
+    Ok(conflicts)
+}
+
+/// Snapshot the current settings, secret names, agents, tasks and
+/// events. Secret values are the only thing deliberately left behind.
+pub fn export_settings(storage: &Storage) -> AppResult<SettingsExport> {
+    let mut events = Vec::new();
+    storage.stream_events(None, None, None, None, None, |event| {
+        events.push(event.clone());
+        Ok(())
+    })?;
+    Ok(SettingsExport {
+        version: EXPORT_VERSION,
+        settings: storage.list_settings()?.into_iter().collect(),
+        secret_names: storage.list_secret_names()?,
+        agents: storage.get_all_agents()?,
+        tasks: storage.get_all_tasks()?,
+        events,
+    })
+}
+
+/// Apply an exported snapshot: settings are upserted and secret names
+/// registered without values, so the user is prompted to supply them on
+/// the new machine. Kept for callers that do not care about the
+/// summary; the real work happens in [`import_data`].
+pub fn import_settings(
+    storage: &Storage,
+    export: &SettingsExport,
+    resolutions: &BTreeMap<String, DuplicateResolution>,
+) -> AppResult<()> {
+    import_data(storage, export, resolutions, false).map(|_| ())
+}
+
+/// What an import did, or -- with `dry_run` -- what it would do.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportSummary {
+    /// True when nothing was written and the counts are a preview.
+    pub dry_run: bool,
+    pub settings_added: u32,
+    pub settings_updated: u32,
+    pub secrets_registered: u32,
+    pub agents_created: u32,
+    pub agents_merged: u32,
+    pub agents_skipped: u32,
+    pub tasks_created: u32,
+    /// Tasks dropped because they were already imported or their agent
+    /// was skipped.
+    pub tasks_skipped: u32,
+    pub events_created: u32,
+}
+
+/// Reject bundles that are not self-consistent: every task must point
+/// at an agent in the bundle and every event at a task in the bundle,
+/// otherwise the import would manufacture exactly the dangling
+/// references [`crate::maintenance::repair_storage`] exists to clean up.
+fn validate_export(export: &SettingsExport) -> AppResult<()> {
+    let agent_ids: std::collections::BTreeSet<&str> =
+        export.agents.iter().map(|a| a.id.as_str()).collect();
+    let task_ids: std::collections::BTreeSet<&str> =
+        export.tasks.iter().map(|t| t.id.as_str()).collect();
+    let mut problems = Vec::new();
+    for task in &export.tasks {
+        if !agent_ids.contains(task.agent_id.as_str()) {
+            problems.push(format!(
+                "task {} references agent {} missing from the bundle",
+                task.id, task.agent_id
+            ));
+        }
+    }
+    for event in &export.events {
+        if !task_ids.contains(event.task_id.as_str()) {
+            problems.push(format!(
+                "event {} references task {} missing from the bundle",
+                event.id, event.task_id
+            ));
+        }
+    }
+    if problems.is_empty() {
+        return Ok(());
+    }
+    let shown = problems.iter().take(3).cloned().collect::<Vec<_>>().join("; ");
+    Err(AppError::InvalidArgument(format!(
+        "import bundle failed integrity validation ({} problems): {shown}",
+        problems.len()
+    )))
+}
+
+/// Validated, transactional import of an exported snapshot.
+///
+/// The whole bundle is integrity-checked and planned first; with
+/// `dry_run` the plan is returned as a summary without writing
+/// anything, otherwise it is applied in a single transaction so an
+/// error rolls everything back instead of leaving partial state.
+pub fn import_data(
+    storage: &Storage,
+    export: &SettingsExport,
+    resolutions: &BTreeMap<String, DuplicateResolution>,
+    dry_run: bool,
+) -> AppResult<ImportSummary> {
+    if export.version > EXPORT_VERSION {
+        return Err(AppError::InvalidArgument(format!(
+            "settings export version {} is newer than supported version {EXPORT_VERSION}",
+            export.version
+        )));
+    }
+    validate_export(export)?;
+
+    let mut summary = ImportSummary {
+        dry_run,
+        ..ImportSummary::default()
+    };
+    let mut batch = ImportBatch::default();
+
+    let current: BTreeMap<String, String> = storage.list_settings()?.into_iter().collect();
+    for (key, value) in &export.settings {
+        match current.get(key) {
+            None => summary.settings_added += 1,
+            Some(existing) if existing != value => summary.settings_updated += 1,
+            Some(_) => {}
+        }
+        batch.settings.push((key.clone(), value.clone()));
+    }
+    let known_secrets = storage.list_secret_names()?;
+    for name in &export.secret_names {
+        if !known_secrets.contains(name) {
+            summary.secrets_registered += 1;
+        }
+        batch.secret_names.push(name.clone());
+    }
+
+    // Decide every agent's fate up front and remember the local id its
+    // tasks must be rewritten to; `None` means the agent (and with it
+    // its tasks) was skipped.
+    let conflicts = plan_agent_import(storage, &export.agents)?;
+    let mut local_agent_ids: BTreeMap<&str, Option<String>> = BTreeMap::new();
+    for agent in &export.agents {
+        match conflicts.iter().find(|c| c.imported_id == agent.id) {
+            None => {
+                // Source ids may collide across workspaces, so imported
+                // agents get a namespaced id derived from theirs. The
+                // same source always maps to the same local agent:
+                // seen before means update in place, not a new copy.
+                let local_id = stable_import_id(&agent.id);
+                if storage.get_agent(&local_id).is_ok() {
+                    batch.agents_to_merge.push((local_id.clone(), agent.clone()));
+                    summary.agents_merged += 1;
+                } else {
+                    let mut fresh = agent.clone();
+                    fresh.id = local_id.clone();
+                    fresh.status = crate::models::AgentStatus::Idle;
+                    fresh.runtime_seconds = 0;
+                    batch.agents_to_create.push(fresh);
+                    summary.agents_created += 1;
+                }
+                local_agent_ids.insert(&agent.id, Some(local_id));
+            }
+            Some(conflict) => {
+                let resolution = resolutions
+                    .get(&agent.id)
+                    .copied()
+                    .unwrap_or(DuplicateResolution::Merge);
+                match resolution {
+                    DuplicateResolution::Merge => {
+                        batch
+                            .agents_to_merge
+                            .push((conflict.existing_id.clone(), agent.clone()));
+                        summary.agents_merged += 1;
+                        local_agent_ids.insert(&agent.id, Some(conflict.existing_id.clone()));
+                    }
+                    DuplicateResolution::Duplicate => {
+                        let mut copy = agent.clone();
+                        copy.id = uuid::Uuid::new_v4().to_string();
+                        copy.name = format!("{} (imported)", agent.name);
+                        copy.status = crate::models::AgentStatus::Idle;
+                        copy.runtime_seconds = 0;
+                        local_agent_ids.insert(&agent.id, Some(copy.id.clone()));
+                        batch.agents_to_create.push(copy);
+                        summary.agents_created += 1;
+                    }
+                    DuplicateResolution::Skip => {
+                        summary.agents_skipped += 1;
+                        local_agent_ids.insert(&agent.id, None);
+                    }
+                }
+            }
+        }
+    }
+
+    // Tasks get deterministic local ids just like agents, so re-importing
+    // the same bundle never duplicates history.
+    let mut imported_task_ids: BTreeMap<&str, String> = BTreeMap::new();
+    for task in &export.tasks {
+        let Some(Some(local_agent)) = local_agent_ids.get(task.agent_id.as_str()) else {
+            summary.tasks_skipped += 1;
+            continue;
+        };
+        let local_id = stable_import_id(&task.id);
+        if storage.get_task(&local_id).is_ok() {
+            summary.tasks_skipped += 1;
+            continue;
+        }
+        let mut local = task.clone();
+        local.id = local_id.clone();
+        local.agent_id = local_agent.clone();
+        local.depends_on = task.depends_on.iter().map(|id| stable_import_id(id)).collect();
+        local.retry_of = task.retry_of.as_deref().map(stable_import_id);
+        imported_task_ids.insert(&task.id, local_id);
+        batch.tasks.push(local);
+        summary.tasks_created += 1;
+    }
+    for event in &export.events {
+        let Some(local_task) = imported_task_ids.get(event.task_id.as_str()) else {
+            continue;
+        };
+        let mut local = event.clone();
+        local.task_id = local_task.clone();
+        batch.events.push(local);
+        summary.events_created += 1;
+    }
+
+    if !dry_run {
+        storage.apply_import(&batch)?;
+    }
+    Ok(summary)
+}
+
+/// Outcome of one CSV row in a bulk agent import: the created agent's
+/// id, or the validation error for that row.
+#[derive(Debug, Clone, Serialize)]
+pub struct CsvImportRow {
+    /// 1-based line number in the file (the header is line 1).
+    pub line: usize,
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Columns a fleet CSV may carry; `name` and `model` are required.
+const CSV_COLUMNS: &[&str] = &[
+    "name",
+    "model",
+    "default_priority",
+    "framework",
+    "command",
+    "endpoint",
+    "timezone",
+    "max_retries",
+    "timeout_seconds",
+    "color",
+    "system_prompt",
+];
+
+/// Bulk-import a fleet of agents from a CSV file, for teams migrating
+/// dozens of agents at once. The header row maps columns to agent
+/// fields; every row is validated, and all valid rows are created in
+/// one transaction so a crash cannot leave half a fleet behind. Rows
+/// that fail validation are reported alongside the successes rather
+/// than aborting the rest.
+pub fn import_agents_csv(storage: &Storage, path: &str) -> AppResult<Vec<CsvImportRow>> {
+    let raw = std::fs::read_to_string(path)?;
+    let mut lines = raw.lines().enumerate();
+    let header = lines
+        .next()
+        .map(|(_, line)| parse_csv_line(line))
+        .ok_or_else(|| AppError::InvalidArgument("CSV file is empty".into()))?;
+    for column in &header {
+        if !CSV_COLUMNS.contains(&column.as_str()) {
+            return Err(AppError::InvalidArgument(format!(
+                "unknown CSV column \"{column}\" (expected a subset of {CSV_COLUMNS:?})"
+            )));
+        }
+    }
+    for required in ["name", "model"] {
+        if !header.iter().any(|c| c == required) {
+            return Err(AppError::InvalidArgument(format!(
+                "CSV is missing the required \"{required}\" column"
+            )));
+        }
+    }
+
+    let mut report = Vec::new();
+    let mut roster = Vec::new();
+    for (index, line) in lines {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields = parse_csv_line(line);
+        match agent_from_csv_row(&header, &fields) {
+            Ok(agent) => {
+                report.push(CsvImportRow {
+                    line: index + 1,
+                    name: Some(agent.name.clone()),
+                    agent_id: Some(agent.id.clone()),
+                    error: None,
+                });
+                roster.push(agent);
+            }
+            Err(err) => report.push(CsvImportRow {
+                line: index + 1,
+                name: csv_field(&header, &fields, "name").map(str::to_string),
+                agent_id: None,
+                error: Some(err.to_string()),
+            }),
+        }
+    }
+    storage.create_agents(&roster)?;
+    Ok(report)
+}
+
+/// The trimmed, non-empty value of `column` in one parsed row.
+fn csv_field<'a>(header: &[String], fields: &'a [String], column: &str) -> Option<&'a str> {
+    header
+        .iter()
+        .position(|c| c == column)
+        .and_then(|i| fields.get(i))
+        .map(|v| v.trim())
+        .filter(|v| !v.is_empty())
+}
+
+/// Build and validate one agent from a parsed CSV row.
+fn agent_from_csv_row(header: &[String], fields: &[String]) -> AppResult<Agent> {
+    let row = |column: &str| csv_field(header, fields, column);
+    let name = row("name")
+        .ok_or_else(|| AppError::InvalidArgument("name is required".into()))?;
+    let model = row("model")
+        .ok_or_else(|| AppError::InvalidArgument("model is required".into()))?;
+    let mut agent = Agent::new(name, model);
+    if let Some(priority) = row("default_priority") {
+        agent.default_priority = crate::models::TaskPriority::parse(priority).ok_or_else(|| {
+            AppError::InvalidArgument(format!("invalid default_priority \"{priority}\""))
+        })?;
+    }
+    if let Some(tz) = row("timezone") {
+        crate::scheduler::validate_timezone(tz)?;
+        agent.timezone = Some(tz.to_string());
+    }
+    if let Some(retries) = row("max_retries") {
+        agent.max_retries = retries.parse().map_err(|_| {
+            AppError::InvalidArgument(format!("invalid max_retries \"{retries}\""))
+        })?;
+    }
+    if let Some(timeout) = row("timeout_seconds") {
+        agent.timeout_seconds = Some(timeout.parse().map_err(|_| {
+            AppError::InvalidArgument(format!("invalid timeout_seconds \"{timeout}\""))
+        })?);
+    }
+    agent.framework = row("framework").map(str::to_string);
+    agent.command = row("command").map(str::to_string);
+    agent.endpoint = row("endpoint").map(str::to_string);
+    if let Some(color) = row("color") {
+        agent.color = color.to_string();
+    }
+    agent.system_prompt = row("system_prompt").map(str::to_string);
+    Ok(agent)
+}
+
+/// Minimal CSV field splitter: commas separate fields, double quotes
+/// protect embedded commas, and `""` inside a quoted field is a
+/// literal quote. Fields never span lines, which matches what
+/// spreadsheet exports of agent rosters look like.
+fn parse_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_settings_and_secret_names_without_values() {
+        let source = Storage::open_in_memory().unwrap();
+        source.set_setting("alert_rules", "[]").unwrap();
+        source.set_setting("theme", "dark").unwrap();
+        source.set_secret("openai_api_key", "sk-live-value").unwrap();
+
+        let export = export_settings(&source).unwrap();
+        assert_eq!(export.secret_names, vec!["openai_api_key"]);
+        // Values must never appear anywhere in the serialized export.
+        let raw = serde_json::to_string(&export).unwrap();
+        assert!(!raw.contains("sk-live-value"));
+
+        let target = Storage::open_in_memory().unwrap();
+        import_settings(&target, &export, &BTreeMap::new()).unwrap();
+        assert_eq!(target.get_setting("theme").unwrap().as_deref(), Some("dark"));
+        assert_eq!(target.list_secret_names().unwrap(), vec!["openai_api_key"]);
+        // The name is registered but the value must be absent.
+        assert!(matches!(
+            target.get_secret("openai_api_key"),
+            Err(AppError::SecretNotSet(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_exports_from_a_newer_version() {
+        let storage = Storage::open_in_memory().unwrap();
+        let export = SettingsExport {
+            version: EXPORT_VERSION + 1,
+            settings: BTreeMap::new(),
+            secret_names: Vec::new(),
+            agents: Vec::new(),
+            tasks: Vec::new(),
+            events: Vec::new(),
+        };
+        assert!(matches!(
+            import_settings(&storage, &export, &BTreeMap::new()),
+            Err(AppError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn matching_agents_merge_instead_of_duplicating() {
+        let source = Storage::open_in_memory().unwrap();
+        let mut agent = Agent::new("researcher", "gpt-4o");
+        agent.temperature = Some(0.2);
+        source.create_agent(&agent).unwrap();
+        let export = export_settings(&source).unwrap();
+
+        let target = Storage::open_in_memory().unwrap();
+        let mut existing = Agent::new("researcher", "gpt-3.5");
+        existing.runtime_seconds = 777;
+        target.create_agent(&existing).unwrap();
+
+        assert_eq!(plan_agent_import(&target, &export.agents).unwrap().len(), 1);
+        import_settings(&target, &export, &BTreeMap::new()).unwrap();
+
+        let agents = target.get_all_agents().unwrap();
+        assert_eq!(agents.len(), 1);
+        // Config updated, stats kept.
+        assert_eq!(agents[0].model, "gpt-4o");
+        assert_eq!(agents[0].temperature, Some(0.2));
+        assert_eq!(agents[0].runtime_seconds, 777);
+    }
+
+    #[test]
+    fn re_imports_map_source_ids_to_stable_local_agents() {
+        let source = Storage::open_in_memory().unwrap();
+        let mut agent = Agent::new("analyst", "gpt-4o");
+        source.create_agent(&agent).unwrap();
+        let export = export_settings(&source).unwrap();
+
+        let target = Storage::open_in_memory().unwrap();
+        import_settings(&target, &export, &BTreeMap::new()).unwrap();
+        let first = target.get_all_agents().unwrap();
+        assert_eq!(first.len(), 1);
+        // The local id is derived, not the source's and not random.
+        assert_ne!(first[0].id, agent.id);
+        assert_eq!(first[0].id, stable_import_id(&agent.id));
+
+        // A refreshed bundle updates the same agent in place.
+        agent.model = "gpt-4o-mini".into();
+        source.merge_agent_config(&agent.id, &agent).unwrap();
+        let refreshed = export_settings(&source).unwrap();
+        import_settings(&target, &refreshed, &BTreeMap::new()).unwrap();
+        let agents = target.get_all_agents().unwrap();
+        assert_eq!(agents.len(), 1);
+        assert_eq!(agents[0].model, "gpt-4o-mini");
+    }
+
+    #[test]
+    fn explicit_duplicate_resolution_creates_a_copy() {
+        let source = Storage::open_in_memory().unwrap();
+        let agent = Agent::new("researcher", "gpt-4o");
+        source.create_agent(&agent).unwrap();
+        let export = export_settings(&source).unwrap();
+
+        let target = Storage::open_in_memory().unwrap();
+        target.create_agent(&Agent::new("researcher", "gpt-4o")).unwrap();
+        let mut resolutions = BTreeMap::new();
+        resolutions.insert(agent.id.clone(), DuplicateResolution::Duplicate);
+        import_settings(&target, &export, &resolutions).unwrap();
+        assert_eq!(target.get_all_agents().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn dry_run_previews_the_import_without_writing_anything() {
+        let source = Storage::open_in_memory().unwrap();
+        let agent = Agent::new("researcher", "gpt-4o");
+        source.create_agent(&agent).unwrap();
+        let task = crate::task_dispatch::dispatch(
+            &source,
+            &crate::task_dispatch::DispatchRequest::new(&agent.id, "t", "p"),
+        )
+        .unwrap();
+        source.append_event(&task.id, "output", None).unwrap();
+        source.set_setting("theme", "dark").unwrap();
+        let export = export_settings(&source).unwrap();
+
+        let target = Storage::open_in_memory().unwrap();
+        let preview = import_data(&target, &export, &BTreeMap::new(), true).unwrap();
+        assert!(preview.dry_run);
+        assert_eq!(preview.agents_created, 1);
+        assert_eq!(preview.tasks_created, 1);
+        assert!(preview.events_created > 0);
+        assert_eq!(preview.settings_added, 1);
+        // Nothing actually landed.
+        assert!(target.get_all_agents().unwrap().is_empty());
+        assert!(target.get_setting("theme").unwrap().is_none());
+
+        let applied = import_data(&target, &export, &BTreeMap::new(), false).unwrap();
+        assert!(!applied.dry_run);
+        assert_eq!(applied.tasks_created, 1);
+        let local_task = target.get_task(&stable_import_id(&task.id)).unwrap();
+        assert_eq!(local_task.agent_id, stable_import_id(&agent.id));
+        let (events, total) = target
+            .get_task_events_bounded(&local_task.id, 50, 0)
+            .unwrap();
+        assert_eq!(total as u32, applied.events_created);
+        assert!(events.iter().any(|e| e.kind == "output"));
+
+        // Re-importing the same bundle duplicates nothing.
+        let again = import_data(&target, &export, &BTreeMap::new(), false).unwrap();
+        assert_eq!(again.tasks_created, 0);
+        assert_eq!(again.tasks_skipped, 1);
+        assert_eq!(again.events_created, 0);
+    }
+
+    #[test]
+    fn inconsistent_bundles_are_rejected_before_any_write() {
+        let source = Storage::open_in_memory().unwrap();
+        let agent = Agent::new("researcher", "gpt-4o");
+        source.create_agent(&agent).unwrap();
+        let task = crate::task_dispatch::dispatch(
+            &source,
+            &crate::task_dispatch::DispatchRequest::new(&agent.id, "t", "p"),
+        )
+        .unwrap();
+        let mut export = export_settings(&source).unwrap();
+        export.settings.insert("theme".into(), "dark".into());
+        // Corrupt the bundle: the task's agent goes missing.
+        export.agents.clear();
+
+        let target = Storage::open_in_memory().unwrap();
+        let err = import_data(&target, &export, &BTreeMap::new(), false).unwrap_err();
+        assert!(matches!(err, AppError::InvalidArgument(_)));
+        assert!(err.to_string().contains(&task.id));
+        // Validation runs before anything is written, settings included.
+        assert!(target.get_setting("theme").unwrap().is_none());
+    }
+
+    #[test]
+    fn csv_fleet_import_reports_per_row_and_creates_valid_agents() {
+        use std::io::Write;
+        let storage = Storage::open_in_memory().unwrap();
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "name,model,default_priority,timezone").unwrap();
+        writeln!(file, "researcher,gpt-4o,high,America/New_York").unwrap();
+        writeln!(file, "\"writer, senior\",claude,normal,").unwrap();
+        writeln!(file, "broken,claude,someday,").unwrap();
+        writeln!(file, ",claude,,").unwrap();
+
+        let report =
+            import_agents_csv(&storage, file.path().to_str().unwrap()).unwrap();
+        assert_eq!(report.len(), 4);
+        assert!(report[0].agent_id.is_some());
+        assert_eq!(report[1].name.as_deref(), Some("writer, senior"));
+        assert!(report[2].error.as_deref().unwrap().contains("default_priority"));
+        assert!(report[3].error.as_deref().unwrap().contains("name"));
+
+        let agents = storage.get_all_agents().unwrap();
+        assert_eq!(agents.len(), 2);
+        let researcher = agents.iter().find(|a| a.name == "researcher").unwrap();
+        assert_eq!(researcher.timezone.as_deref(), Some("America/New_York"));
+
+        // An unknown column aborts before anything is created.
+        let mut bad = tempfile::NamedTempFile::new().unwrap();
+        writeln!(bad, "name,model,api_key").unwrap();
+        assert!(import_agents_csv(&storage, bad.path().to_str().unwrap()).is_err());
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::artifacts::ArtifactStore;
+use crate::storage::Storage;
+use crate::windows::{SubscriptionRegistry, WindowRegistry};
+use crate::worker_pool::WorkerPool;
+
+/// Shared application state managed by Tauri and handed to every command.
+pub struct AppState {
+    pub storage: Arc<Storage>,
+    pub artifacts: ArtifactStore,
+    pub windows: WindowRegistry,
+    pub subscriptions: SubscriptionRegistry,
+    pub readiness: Readiness,
+    pub workers: WorkerPool,
+}
+
+impl AppState {
+    pub fn new(storage: Storage, artifacts: ArtifactStore) -> Self {
+        let storage = Arc::new(storage);
+        let workers = WorkerPool::new(Arc::clone(&storage));
+        Self {
+            storage,
+            artifacts,
+            windows: WindowRegistry::default(),
+            subscriptions: SubscriptionRegistry::default(),
+            readiness: Readiness::default(),
+            workers,
+        }
+    }
+}
+
+/// Tracks which deferred subsystems have finished initializing, so the
+/// frontend can show the window immediately and reflect readiness as
+/// background init completes.
+#[derive(Default)]
+pub struct Readiness(Mutex<HashMap<String, bool>>);
+
+impl Readiness {
+    /// Register a subsystem as pending (shown as not-ready until marked).
+    pub fn pending(&self, name: &str) {
+        self.0.lock().unwrap().entry(name.to_string()).or_insert(false);
+    }
+
+    pub fn mark_ready(&self, name: &str) {
+        self.0.lock().unwrap().insert(name.to_string(), true);
+    }
+
+    pub fn snapshot(&self) -> HashMap<String, bool> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+/// Health of the storage backend, managed from startup onwards even
+/// when [`AppState`] could not be initialized. The frontend polls this
+/// to decide between the normal UI and the degraded-startup screen.
+#[derive(Default)]
+pub struct BackendHealth {
+    error: Mutex<Option<String>>,
+    data_dir: Mutex<Option<PathBuf>>,
+}
+
+impl BackendHealth {
+    pub fn set_data_dir(&self, dir: PathBuf) {
+        *self.data_dir.lock().unwrap() = Some(dir);
+    }
+
+    pub fn data_dir(&self) -> Option<PathBuf> {
+        self.data_dir.lock().unwrap().clone()
+    }
+
+    pub fn set_error(&self, message: String) {
+        *self.error.lock().unwrap() = Some(message);
+    }
+
+    pub fn clear_error(&self) {
+        *self.error.lock().unwrap() = None;
+    }
+
+    pub fn error(&self) -> Option<String> {
+        self.error.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,342 @@
+//! Static status page snapshots.
+//!
+//! An optional interval job renders the workspace state -- agents,
+//! queue depth, recent incidents -- to `status.json` plus a small
+//! self-contained `index.html` under a local path, and can PUT the
+//! JSON to an upload URL (e.g. a pre-signed S3 URL) so a team can host
+//! a read-only status page without exposing the workspace itself.
+
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppResult;
+use crate::models::TaskStatus;
+use crate::storage::Storage;
+
+/// Local directory the snapshot is written into; publishing is off
+/// until this is set.
+pub const PATH_SETTING: &str = "status_page.path";
+/// Optional HTTP PUT target for `status.json` (a pre-signed S3 URL
+/// works; credentials never live in settings).
+pub const UPLOAD_URL_SETTING: &str = "status_page.upload_url";
+pub const INTERVAL_SETTING: &str = "status_page.interval_seconds";
+const DEFAULT_INTERVAL_SECONDS: u64 = 300;
+const MAX_INCIDENTS: usize = 10;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentSummary {
+    pub name: String,
+    pub model: String,
+    pub status: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Incident {
+    pub title: String,
+    pub error: Option<String>,
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusSnapshot {
+    pub generated_at: DateTime<Utc>,
+    pub agents: Vec<AgentSummary>,
+    pub queued: usize,
+    pub running: usize,
+    pub completed_today: usize,
+    pub incidents: Vec<Incident>,
+}
+
+/// Assemble the current snapshot. Task ids, prompts and results stay
+/// out of it: the page is meant to be publishable.
+pub fn snapshot(storage: &Storage) -> AppResult<StatusSnapshot> {
+    let agents = storage
+        .get_all_agents()?
+        .into_iter()
+        .map(|agent| AgentSummary {
+            name: agent.name,
+            model: agent.model,
+            status: agent.status.as_str().to_string(),
+        })
+        .collect();
+    let tasks = storage.get_all_tasks()?;
+    let today = Utc::now().date_naive();
+    let mut incidents: Vec<Incident> = tasks
+        .iter()
+        .filter(|t| t.status == TaskStatus::Failed)
+        .map(|t| Incident {
+            title: t.title.clone(),
+            error: t.error.clone(),
+            at: t.updated_at,
+        })
+        .collect();
+    incidents.sort_by_key(|i| std::cmp::Reverse(i.at));
+    incidents.truncate(MAX_INCIDENTS);
+
+    Ok(StatusSnapshot {
+        generated_at: Utc::now(),
+        queued: tasks.iter().filter(|t| t.status == TaskStatus::Queued).count(),
+        running: tasks.iter().filter(|t| t.status == TaskStatus::Running).count(),
+        completed_today: tasks
+            .iter()
+            .filter(|t| t.status == TaskStatus::Completed && t.updated_at.date_naive() == today)
+            .count(),
+        agents,
+        incidents,
+    })
+}
+
+/// Every task status, in queue-lifecycle order, so counts render with
+/// a stable layout even when a bucket is empty.
+const ALL_STATUSES: &[TaskStatus] = &[
+    TaskStatus::Queued,
+    TaskStatus::Running,
+    TaskStatus::WaitingForInput,
+    TaskStatus::NeedsReview,
+    TaskStatus::Completed,
+    TaskStatus::Failed,
+    TaskStatus::Cancelled,
+    TaskStatus::Blocked,
+];
+
+/// How many agents the activity leaderboard keeps.
+const TOP_AGENTS: usize = 5;
+/// Window for the activity leaderboard.
+const ACTIVITY_WINDOW_HOURS: i64 = 24;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusCount {
+    pub status: TaskStatus,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AgentActivity {
+    pub agent_id: String,
+    pub name: String,
+    /// Tasks touched inside the activity window.
+    pub recent_tasks: usize,
+}
+
+/// Structured workspace status for the command palette: per-status
+/// counts, the busiest agents, and the sorted roster. Replaces the old
+/// habit of concatenating every agent into one string, which became
+/// unreadable on large fleets.
+#[derive(Debug, Clone, Serialize)]
+pub struct QuickStatus {
+    pub generated_at: DateTime<Utc>,
+    /// One bucket per task status, zeros included.
+    pub task_counts: Vec<StatusCount>,
+    /// Top [`TOP_AGENTS`] agents by tasks touched in the last day.
+    pub top_agents: Vec<AgentActivity>,
+    /// Full roster in display order.
+    pub agent_names: Vec<String>,
+}
+
+/// Unicode case-insensitive collation key with the raw name as a
+/// stable tiebreak, so "alpha", "Beta" and "\u{00e9}clair" interleave
+/// the way a directory listing would rather than by code point.
+fn collation_key(name: &str) -> (String, String) {
+    (name.to_lowercase(), name.to_string())
+}
+
+/// Assemble the palette's quick status.
+pub fn quick_status(storage: &Storage) -> AppResult<QuickStatus> {
+    let tasks = storage.get_all_tasks()?;
+    let task_counts = ALL_STATUSES
+        .iter()
+        .map(|&status| StatusCount {
+            status,
+            count: tasks.iter().filter(|t| t.status == status).count(),
+        })
+        .collect();
+
+    let since = Utc::now() - chrono::Duration::hours(ACTIVITY_WINDOW_HOURS);
+    let mut agents = storage.get_all_agents()?;
+    agents.sort_by_key(|a| collation_key(&a.name));
+    let mut top_agents: Vec<AgentActivity> = agents
+        .iter()
+        .map(|agent| AgentActivity {
+            agent_id: agent.id.clone(),
+            name: agent.name.clone(),
+            recent_tasks: tasks
+                .iter()
+                .filter(|t| t.agent_id == agent.id && t.updated_at >= since)
+                .count(),
+        })
+        .collect();
+    // Busiest first; the roster's collation order (already applied)
+    // breaks ties, keeping the leaderboard stable between refreshes.
+    top_agents.sort_by_key(|a| std::cmp::Reverse(a.recent_tasks));
+    top_agents.truncate(TOP_AGENTS);
+
+    Ok(QuickStatus {
+        generated_at: Utc::now(),
+        task_counts,
+        top_agents,
+        agent_names: agents.into_iter().map(|a| a.name).collect(),
+    })
+}
+
+/// Minimal self-contained HTML rendering of a snapshot.
+pub fn render_html(snapshot: &StatusSnapshot) -> String {
+    let mut agents = String::new();
+    for agent in &snapshot.agents {
+        agents.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td class=\"{}\">{}</td></tr>",
+            escape(&agent.name),
+            escape(&agent.model),
+            agent.status,
+            agent.status
+        ));
+    }
+    let mut incidents = String::new();
+    for incident in &snapshot.incidents {
+        incidents.push_str(&format!(
+            "<li><strong>{}</strong> ({}): {}</li>",
+            escape(&incident.title),
+            incident.at.format("%Y-%m-%d %H:%M UTC"),
+            escape(incident.error.as_deref().unwrap_or("no error recorded"))
+        ));
+    }
+    format!(
+        "<!doctype html><html><head><meta charset=\"utf-8\">\
+         <title>Workspace status</title>\
+         <style>body{{font-family:sans-serif;margin:2rem}}table{{border-collapse:collapse}}\
+         td{{padding:.25rem .75rem;border:1px solid #ccc}}.idle{{color:green}}\
+         .running{{color:orange}}.paused{{color:gray}}</style></head><body>\
+         <h1>Workspace status</h1><p>Generated {generated}</p>\
+         <p>{queued} queued &middot; {running} running &middot; {completed} completed today</p>\
+         <h2>Agents</h2><table>{agents}</table>\
+         <h2>Recent incidents</h2><ul>{incidents}</ul></body></html>",
+        generated = snapshot.generated_at.format("%Y-%m-%d %H:%M UTC"),
+        queued = snapshot.queued,
+        running = snapshot.running,
+        completed = snapshot.completed_today,
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Render and publish one snapshot to the configured targets.
+pub fn publish(storage: &Storage) -> AppResult<StatusSnapshot> {
+    let snapshot = snapshot(storage)?;
+    let json = serde_json::to_string_pretty(&snapshot)
+        .unwrap_or_else(|_| "{}".to_string());
+
+    if let Some(dir) = storage.get_setting(PATH_SETTING)? {
+        let dir = Path::new(&dir);
+        std::fs::create_dir_all(dir)?;
+        std::fs::write(dir.join("status.json"), &json)?;
+        std::fs::write(dir.join("index.html"), render_html(&snapshot))?;
+    }
+    if let Some(url) = storage.get_setting(UPLOAD_URL_SETTING)? {
+        let result = reqwest::blocking::Client::new()
+            .put(&url)
+            .header("content-type", "application/json")
+            .body(json)
+            .send();
+        if let Err(err) = result {
+            tracing::warn!(%err, "status page upload failed");
+        }
+    }
+    Ok(snapshot)
+}
+
+/// Interval loop, spawned at startup; idle until a target is set.
+pub fn publisher_loop(storage: &Storage) {
+    loop {
+        let configured = matches!(storage.get_setting(PATH_SETTING), Ok(Some(_)))
+            || matches!(storage.get_setting(UPLOAD_URL_SETTING), Ok(Some(_)));
+        if configured {
+            if let Err(err) = publish(storage) {
+                tracing::warn!(%err, "status page publish failed");
+            }
+        }
+        let interval = storage
+            .get_setting(INTERVAL_SETTING)
+            .ok()
+            .flatten()
+            .and_then(|raw| raw.parse().ok())
+            .unwrap_or(DEFAULT_INTERVAL_SECONDS);
+        std::thread::sleep(std::time::Duration::from_secs(interval.max(30)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Agent;
+    use crate::task_dispatch::{self, DispatchRequest};
+
+    #[test]
+    fn snapshot_counts_queue_and_lists_incidents_without_task_details() {
+        let storage = Storage::open_in_memory().unwrap();
+        let agent = Agent::new("a", "mock");
+        storage.create_agent(&agent).unwrap();
+        let ok = task_dispatch::dispatch(&storage, &DispatchRequest::new(&agent.id, "fine", "p"))
+            .unwrap();
+        task_dispatch::execute(&storage, &ok.id).unwrap();
+        let queued =
+            task_dispatch::dispatch(&storage, &DispatchRequest::new(&agent.id, "waiting", "p"))
+                .unwrap();
+        storage
+            .set_task_status(&queued.id, TaskStatus::Failed, None, Some("boom"))
+            .unwrap();
+
+        let snapshot = snapshot(&storage).unwrap();
+        assert_eq!(snapshot.completed_today, 1);
+        assert_eq!(snapshot.incidents.len(), 1);
+        assert_eq!(snapshot.incidents[0].error.as_deref(), Some("boom"));
+
+        let html = render_html(&snapshot);
+        assert!(html.contains("Workspace status"));
+        assert!(html.contains("boom"));
+        assert!(!html.contains(&queued.id));
+    }
+
+    #[test]
+    fn quick_status_buckets_counts_and_orders_names_case_insensitively() {
+        let storage = Storage::open_in_memory().unwrap();
+        for name in ["zeta", "Alpha", "beta"] {
+            storage.create_agent(&Agent::new(name, "mock")).unwrap();
+        }
+        let busy = storage.get_all_agents().unwrap()[0].id.clone();
+        for i in 0..3 {
+            task_dispatch::dispatch(
+                &storage,
+                &DispatchRequest::new(&busy, format!("t{i}"), "p"),
+            )
+            .unwrap();
+        }
+
+        let status = quick_status(&storage).unwrap();
+        // Every status bucket is present, zeros included.
+        assert_eq!(status.task_counts.len(), ALL_STATUSES.len());
+        let queued = status
+            .task_counts
+            .iter()
+            .find(|c| c.status == TaskStatus::Queued)
+            .unwrap();
+        assert_eq!(queued.count, 3);
+        assert_eq!(status.agent_names, vec!["Alpha", "beta", "zeta"]);
+        assert_eq!(status.top_agents[0].recent_tasks, 3);
+    }
+
+    #[test]
+    fn publish_writes_the_local_snapshot_files() {
+        let storage = Storage::open_in_memory().unwrap();
+        let dir = std::env::temp_dir().join(format!("status-page-{}", uuid::Uuid::new_v4()));
+        storage
+            .set_setting(PATH_SETTING, dir.to_str().unwrap())
+            .unwrap();
+        publish(&storage).unwrap();
+        assert!(dir.join("status.json").exists());
+        assert!(dir.join("index.html").exists());
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,3593 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+use crate::error::{AppError, AppResult};
+use crate::policy::SamplingPolicy;
+use crate::models::{
+    Agent, AgentHistoryEntry, AgentStats, AgentStatus, Approval, EventSeverity, FailureKind,
+    PlanStep, Schedule, SecretUsage, Task, TaskEvent, TaskPriority, TaskStatus, TaskTemplate,
+};
+
+const AGENT_COLUMNS: &str = "id, name, model, status, default_priority, color, avatar_path, \
+                             framework, framework_config, dependencies, command, mcp_servers, endpoint, \
+                             fallback_models, max_retries, timeout_seconds, timezone, \
+                             review_required, constitution_opt_out, system_prompt, temperature, \
+                             runtime_seconds, created_at";
+const INCIDENT_COLUMNS: &str = "id, cause, started_at, last_seen_at, resolved_at, agent_ids, \
+                                task_count, resolution";
+const TASK_COLUMNS: &str = "id, agent_id, title, prompt, status, priority, tags, depends_on, \
+                            result, error, result_artifact, max_cost_usd, max_retries, \
+                            retry_backoff_seconds, run_at, retry_of, started_at, created_at, \
+                            updated_at, board_column, board_position, queue_position, \
+                            step_mode, failure_kind, plan_mode, plan, prompt_tokens, \
+                            completion_tokens";
+
+/// Settings key set while a storage upgrade drains the workspace; its
+/// value describes the phase. Claims are refused while it is present.
+pub const MAINTENANCE_MODE_SETTING: &str = "maintenance.mode";
+
+/// Prefix of the per-agent minimum stored event severity settings; the
+/// agent id is appended to form the full key.
+pub const MIN_SEVERITY_SETTING_PREFIX: &str = "events.min_severity.";
+/// Version the schema DDL in [`Storage::init_schema`] produces; stored
+/// in SQLite's `user_version` so upgrades know where a database stands.
+/// Always equals the last entry of [`MIGRATIONS`].
+pub const SCHEMA_VERSION: i64 = MIGRATIONS.len() as i64;
+
+/// One step of the versioned schema migration chain. Databases at
+/// version `n` run every migration with a higher version, in order;
+/// each step is idempotent so a database whose tables were created
+/// fresh (and so already have the new columns) passes through cleanly.
+struct Migration {
+    version: i64,
+    description: &'static str,
+    apply: fn(&Connection) -> AppResult<()>,
+}
+
+/// Everything one validated import writes, applied atomically by
+/// [`Storage::apply_import`]. Ids are already remapped to local ones.
+#[derive(Debug, Default)]
+pub struct ImportBatch {
+    pub settings: Vec<(String, String)>,
+    pub secret_names: Vec<String>,
+    pub agents_to_create: Vec<Agent>,
+    /// `(existing local id, imported config to merge into it)`.
+    pub agents_to_merge: Vec<(String, Agent)>,
+    pub tasks: Vec<Task>,
+    pub events: Vec<TaskEvent>,
+}
+
+/// Ordered migration chain. Add new entries at the end -- never edit or
+/// reorder released ones -- and bump nothing else: [`SCHEMA_VERSION`]
+/// follows the list length.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "tasks: plan_mode and plan columns for plan-then-approve",
+        apply: |conn| {
+            add_column_if_missing(conn, "tasks", "plan_mode", "INTEGER NOT NULL DEFAULT 0")?;
+            add_column_if_missing(conn, "tasks", "plan", "TEXT")
+        },
+    },
+    Migration {
+        version: 2,
+        description: "task_events: severity column for minimum-severity filtering",
+        apply: |conn| {
+            add_column_if_missing(conn, "task_events", "severity", "TEXT NOT NULL DEFAULT 'info'")
+        },
+    },
+    Migration {
+        version: 3,
+        description: "agents: deleted_at column for soft delete",
+        apply: |conn| add_column_if_missing(conn, "agents", "deleted_at", "TEXT"),
+    },
+    Migration {
+        version: 4,
+        description: "tasks: prompt/completion token columns for usage accounting",
+        apply: |conn| {
+            add_column_if_missing(conn, "tasks", "prompt_tokens", "INTEGER NOT NULL DEFAULT 0")?;
+            add_column_if_missing(
+                conn,
+                "tasks",
+                "completion_tokens",
+                "INTEGER NOT NULL DEFAULT 0",
+            )
+        },
+    },
+];
+
+/// `ALTER TABLE ... ADD COLUMN`, skipped when the column already
+/// exists, so migrations stay idempotent across fresh and upgraded
+/// databases.
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    declaration: &str,
+) -> AppResult<()> {
+    let exists = conn
+        .prepare(&format!("PRAGMA table_info({table})"))?
+        .query_map([], |row| row.get::<_, String>(1))?
+        .filter_map(Result::ok)
+        .any(|name| name == column);
+    if !exists {
+        conn.execute_batch(&format!("ALTER TABLE {table} ADD COLUMN {column} {declaration}"))?;
+    }
+    Ok(())
+}
+
+/// How long a connection waits on a held lock before giving up with
+/// [`AppError::Busy`].
+const BUSY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Results larger than this are offloaded to an artifact file; the
+/// tasks table keeps only a preview plus the file reference.
+pub const RESULT_INLINE_LIMIT: usize = 64 * 1024;
+/// Size of the inline preview kept for offloaded results.
+pub const RESULT_PREVIEW_CHARS: usize = 4_096;
+
+/// SQLite-backed persistence for agents, tasks and task events.
+///
+/// A single connection is shared behind a mutex; all access goes through
+/// the methods here so callers never touch SQL directly.
+pub struct Storage {
+    conn: Mutex<Connection>,
+    /// Where offloaded task results are written; `None` for in-memory
+    /// databases, which keep results inline.
+    artifacts_root: Mutex<Option<PathBuf>>,
+    /// Cold-storage database for archived tasks; `None` disables
+    /// archiving (in-memory databases).
+    archive_path: Mutex<Option<PathBuf>>,
+}
+
+impl Storage {
+    pub fn open(path: &Path) -> AppResult<Self> {
+        Self::open_with_key(path, None)
+    }
+
+    /// Open a database that may be SQLCipher-encrypted. The key must be
+    /// applied before any other statement touches the file; on builds
+    /// without the `sqlcipher` feature the pragma is a harmless no-op,
+    /// so this path is not feature-gated.
+    pub fn open_with_key(path: &Path, key: Option<&str>) -> AppResult<Self> {
+        let conn = Connection::open(path)?;
+        if let Some(key) = key {
+            conn.pragma_update(None, "key", key)?;
+        }
+        Self::configure_connection(&conn)?;
+        let storage = Self {
+            conn: Mutex::new(conn),
+            // Large results land next to the database by default; the
+            // app repoints this at its artifact store during init.
+            artifacts_root: Mutex::new(path.parent().map(|dir| dir.join("artifacts"))),
+            archive_path: Mutex::new(Some(path.with_extension("archive.db"))),
+        };
+        storage.init_schema()?;
+        Ok(storage)
+    }
+
+    /// Connection-level pragmas every on-disk database runs with:
+    /// WAL journaling so readers never block the writer, NORMAL
+    /// synchronous (safe under WAL, much cheaper than FULL) and a busy
+    /// timeout so a briefly held lock waits instead of failing. Locks
+    /// still held past the timeout surface as [`AppError::Busy`], which
+    /// callers may retry.
+    fn configure_connection(conn: &Connection) -> AppResult<()> {
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             PRAGMA synchronous = NORMAL;",
+        )?;
+        Ok(())
+    }
+
+    /// Current database size in bytes from page accounting, so it also
+    /// works for in-memory databases.
+    pub fn database_size_bytes(&self) -> AppResult<i64> {
+        self.with_conn(|conn| {
+            let pages: i64 = conn.query_row("PRAGMA page_count", [], |row| row.get(0))?;
+            let page_size: i64 = conn.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+            Ok(pages * page_size)
+        })
+    }
+
+    /// Rebuild the database file and refresh the query planner's
+    /// statistics. Reclaims the space deleted rows leave behind.
+    pub fn vacuum_analyze(&self) -> AppResult<()> {
+        self.with_conn(|conn| {
+            conn.execute_batch("VACUUM; ANALYZE;")?;
+            Ok(())
+        })
+    }
+
+    /// `PRAGMA integrity_check`: a single `ok` when the database is
+    /// healthy, otherwise one line per problem found.
+    pub fn integrity_check(&self) -> AppResult<Vec<String>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+            let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    /// Copy the whole database into a new file at `target` via `VACUUM
+    /// INTO`, which yields a consistent snapshot even with writers
+    /// active under WAL.
+    pub fn snapshot_to(&self, target: &Path) -> AppResult<()> {
+        self.with_conn(|conn| {
+            conn.execute("VACUUM INTO ?1", params![target.to_string_lossy()])?;
+            Ok(())
+        })
+    }
+
+    /// Rewrite the whole database, encrypted with `key`, into a new file
+    /// at `target` via SQLCipher's export. Only meaningful on builds
+    /// with the `sqlcipher` feature; plain SQLite has no
+    /// `sqlcipher_export` and the call fails cleanly.
+    pub fn export_encrypted(&self, target: &Path, key: &str) -> AppResult<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "ATTACH DATABASE ?1 AS encrypted KEY ?2",
+                params![target.to_string_lossy(), key],
+            )?;
+            let exported =
+                conn.query_row("SELECT sqlcipher_export('encrypted')", [], |_| Ok(()));
+            conn.execute_batch("DETACH DATABASE encrypted")?;
+            exported?;
+            Ok(())
+        })
+    }
+
+    /// Point cold-storage archiving at a specific file (used by tests;
+    /// `open` defaults to a sibling of the main database).
+    pub fn set_archive_path(&self, path: PathBuf) {
+        *self.archive_path.lock().unwrap() = Some(path);
+    }
+
+    /// Redirect large-result offloading (used by the app so results and
+    /// uploads share one artifact directory, and by tests).
+    pub fn set_artifacts_root(&self, root: PathBuf) {
+        *self.artifacts_root.lock().unwrap() = Some(root);
+    }
+
+    fn offload_result(&self, task_id: &str, full: &str) -> AppResult<String> {
+        let root = self.artifacts_root.lock().unwrap().clone().ok_or_else(|| {
+            AppError::InvalidArgument("no artifacts directory configured".into())
+        })?;
+        std::fs::create_dir_all(&root)?;
+        let name = format!("result-{task_id}.txt");
+        std::fs::write(root.join(&name), full)?;
+        Ok(name)
+    }
+
+    /// The absolute path of an offloaded result file.
+    pub fn result_artifact_path(&self, artifact: &str) -> AppResult<PathBuf> {
+        let root = self.artifacts_root.lock().unwrap().clone().ok_or_else(|| {
+            AppError::InvalidArgument("no artifacts directory configured".into())
+        })?;
+        Ok(root.join(artifact))
+    }
+
+    /// In-memory database, used by tests.
+    pub fn open_in_memory() -> AppResult<Self> {
+        let conn = Connection::open_in_memory()?;
+        let storage = Self {
+            conn: Mutex::new(conn),
+            artifacts_root: Mutex::new(None),
+            archive_path: Mutex::new(None),
+        };
+        storage.init_schema()?;
+        Ok(storage)
+    }
+
+    fn init_schema(&self) -> AppResult<()> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS agents (
+                 id          TEXT PRIMARY KEY,
+                 name        TEXT NOT NULL,
+                 model       TEXT NOT NULL,
+                 status      TEXT NOT NULL,
+                 default_priority TEXT NOT NULL DEFAULT 'normal',
+                 color       TEXT NOT NULL DEFAULT '',
+                 avatar_path TEXT,
+                 framework TEXT,
+                 framework_config TEXT NOT NULL DEFAULT 'null',
+                 dependencies TEXT NOT NULL DEFAULT '[]',
+                 command TEXT,
+                 mcp_servers TEXT NOT NULL DEFAULT '[]',
+                 endpoint TEXT,
+                 fallback_models TEXT NOT NULL DEFAULT '[]',
+                 max_retries INTEGER NOT NULL DEFAULT 0,
+                 timeout_seconds INTEGER,
+                 timezone TEXT,
+                 review_required INTEGER NOT NULL DEFAULT 0,
+                 constitution_opt_out INTEGER NOT NULL DEFAULT 0,
+                 system_prompt TEXT,
+                 temperature REAL,
+                 runtime_seconds INTEGER NOT NULL DEFAULT 0,
+                 created_at  TEXT NOT NULL,
+                 deleted_at  TEXT
+             );
+             CREATE TABLE IF NOT EXISTS tasks (
+                 id          TEXT PRIMARY KEY,
+                 agent_id    TEXT NOT NULL REFERENCES agents(id),
+                 title       TEXT NOT NULL,
+                 prompt      TEXT NOT NULL,
+                 status      TEXT NOT NULL,
+                 priority    TEXT NOT NULL DEFAULT 'normal',
+                 tags        TEXT NOT NULL DEFAULT '[]',
+                 depends_on  TEXT NOT NULL DEFAULT '[]',
+                 board_column TEXT NOT NULL DEFAULT 'backlog',
+                 board_position INTEGER NOT NULL DEFAULT 0,
+                 queue_position INTEGER NOT NULL DEFAULT 0,
+                 result      TEXT,
+                 error       TEXT,
+                 result_artifact TEXT,
+                 max_retries INTEGER NOT NULL DEFAULT 0,
+                 retry_backoff_seconds REAL NOT NULL DEFAULT 1.0,
+                 max_cost_usd REAL,
+                 run_at      TEXT,
+                 retry_of    TEXT,
+                 step_mode   INTEGER NOT NULL DEFAULT 0,
+                 failure_kind TEXT,
+                 plan_mode   INTEGER NOT NULL DEFAULT 0,
+                 plan        TEXT,
+                 prompt_tokens INTEGER NOT NULL DEFAULT 0,
+                 completion_tokens INTEGER NOT NULL DEFAULT 0,
+                 started_at  TEXT,
+                 created_at  TEXT NOT NULL,
+                 updated_at  TEXT NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS task_events (
+                 id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                 task_id     TEXT NOT NULL REFERENCES tasks(id),
+                 kind        TEXT NOT NULL,
+                 payload     TEXT,
+                 severity    TEXT NOT NULL DEFAULT 'info',
+                 created_at  TEXT NOT NULL
+             );
+             CREATE INDEX IF NOT EXISTS idx_tasks_agent ON tasks(agent_id);
+             CREATE INDEX IF NOT EXISTS idx_task_events_task ON task_events(task_id);
+             CREATE TABLE IF NOT EXISTS agent_history (
+                 id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                 agent_id    TEXT NOT NULL REFERENCES agents(id),
+                 kind        TEXT NOT NULL,
+                 detail      TEXT,
+                 created_at  TEXT NOT NULL
+             );
+             CREATE INDEX IF NOT EXISTS idx_agent_history_agent ON agent_history(agent_id);
+             CREATE TABLE IF NOT EXISTS settings (
+                 key         TEXT PRIMARY KEY,
+                 value       TEXT NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS secrets (
+                 name        TEXT PRIMARY KEY,
+                 value       TEXT,
+                 updated_at  TEXT NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS attachments (
+                 artifact    TEXT PRIMARY KEY,
+                 filename    TEXT NOT NULL,
+                 extracted_text TEXT NOT NULL,
+                 truncated   INTEGER NOT NULL DEFAULT 0,
+                 created_at  TEXT NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS task_attachments (
+                 task_id     TEXT NOT NULL REFERENCES tasks(id),
+                 artifact    TEXT NOT NULL REFERENCES attachments(artifact),
+                 PRIMARY KEY (task_id, artifact)
+             );
+             CREATE TABLE IF NOT EXISTS task_costs (
+                 id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                 agent_id    TEXT NOT NULL REFERENCES agents(id),
+                 task_id     TEXT REFERENCES tasks(id),
+                 amount_usd  REAL NOT NULL,
+                 recorded_at TEXT NOT NULL
+             );
+             CREATE INDEX IF NOT EXISTS idx_task_costs_agent ON task_costs(agent_id);
+             CREATE TABLE IF NOT EXISTS notifications (
+                 id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                 title       TEXT NOT NULL,
+                 body        TEXT NOT NULL,
+                 read        INTEGER NOT NULL DEFAULT 0,
+                 created_at  TEXT NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS secret_usage (
+                 id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                 secret_name TEXT NOT NULL,
+                 agent_id    TEXT,
+                 task_id     TEXT,
+                 used_at     TEXT NOT NULL
+             );
+             CREATE INDEX IF NOT EXISTS idx_secret_usage_name ON secret_usage(secret_name);
+             CREATE TABLE IF NOT EXISTS secret_rotations (
+                 id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                 secret_name TEXT NOT NULL,
+                 smoke_test  TEXT NOT NULL,
+                 rotated_at  TEXT NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS api_tokens (
+                id         TEXT PRIMARY KEY,
+                name       TEXT NOT NULL,
+                token_hash TEXT NOT NULL UNIQUE,
+                scopes     TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                revoked_at TEXT
+            );
+
+            CREATE TABLE IF NOT EXISTS task_templates (
+                id         TEXT PRIMARY KEY,
+                name       TEXT NOT NULL UNIQUE,
+                title      TEXT NOT NULL,
+                prompt     TEXT NOT NULL,
+                agent_id   TEXT,
+                priority   TEXT,
+                tags       TEXT NOT NULL DEFAULT '[]',
+                created_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS schedules (
+                id          TEXT PRIMARY KEY,
+                agent_id    TEXT NOT NULL REFERENCES agents(id),
+                title       TEXT NOT NULL,
+                prompt      TEXT NOT NULL,
+                cron        TEXT NOT NULL,
+                paused      INTEGER NOT NULL DEFAULT 0,
+                last_run_at TEXT,
+                created_at  TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS approvals (
+                id         INTEGER PRIMARY KEY AUTOINCREMENT,
+                task_id    TEXT NOT NULL REFERENCES tasks(id),
+                tool       TEXT NOT NULL,
+                decision   TEXT NOT NULL,
+                approver   TEXT NOT NULL,
+                comment    TEXT,
+                decided_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS incidents (
+                id           TEXT PRIMARY KEY,
+                cause        TEXT NOT NULL,
+                started_at   TEXT NOT NULL,
+                last_seen_at TEXT NOT NULL,
+                resolved_at  TEXT,
+                agent_ids    TEXT NOT NULL DEFAULT '[]',
+                task_count   INTEGER NOT NULL DEFAULT 0,
+                resolution   TEXT
+            );
+
+            CREATE TABLE IF NOT EXISTS command_metrics_rollup (
+                day TEXT NOT NULL,
+                command TEXT NOT NULL,
+                calls INTEGER NOT NULL,
+                total_duration_ms INTEGER NOT NULL,
+                errors INTEGER NOT NULL,
+                PRIMARY KEY (day, command)
+            );
+
+            CREATE TABLE IF NOT EXISTS task_config_snapshots (
+                task_id TEXT PRIMARY KEY REFERENCES tasks(id),
+                snapshot TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS mcp_tools (
+                server TEXT NOT NULL,
+                name TEXT NOT NULL,
+                description TEXT NOT NULL DEFAULT '',
+                input_schema TEXT NOT NULL DEFAULT 'null',
+                discovered_at TEXT NOT NULL,
+                PRIMARY KEY (server, name)
+            );
+
+            CREATE TABLE IF NOT EXISTS service_status (
+                url TEXT PRIMARY KEY,
+                healthy INTEGER NOT NULL,
+                detail TEXT NOT NULL,
+                checked_at TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS event_sample_counters (
+                task_id TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                seen INTEGER NOT NULL DEFAULT 0,
+                PRIMARY KEY (task_id, kind)
+            );
+
+            CREATE TABLE IF NOT EXISTS command_metrics (
+                 id          INTEGER PRIMARY KEY AUTOINCREMENT,
+                 command     TEXT NOT NULL,
+                 duration_ms INTEGER NOT NULL,
+                 ok          INTEGER NOT NULL,
+                 recorded_at TEXT NOT NULL
+             );
+
+            CREATE VIRTUAL TABLE IF NOT EXISTS event_search
+                USING fts5(kind, content);
+            CREATE TRIGGER IF NOT EXISTS task_events_fts_insert
+                AFTER INSERT ON task_events BEGIN
+                    INSERT INTO event_search(rowid, kind, content)
+                    VALUES (new.id, new.kind, COALESCE(new.payload, ''));
+                END;
+            CREATE TRIGGER IF NOT EXISTS task_events_fts_delete
+                AFTER DELETE ON task_events BEGIN
+                    DELETE FROM event_search WHERE rowid = old.id;
+                END;",
+        )?;
+        // Backfill the search index for databases created before it
+        // existed; a no-op on fresh and already-indexed databases.
+        conn.execute(
+            "INSERT INTO event_search(rowid, kind, content)
+             SELECT id, kind, COALESCE(payload, '') FROM task_events
+             WHERE id NOT IN (SELECT rowid FROM event_search)",
+            [],
+        )?;
+        Ok(())
+    }
+
+    /// Run `f` with the shared connection. Kept private so SQL stays in
+    /// this module.
+    fn with_conn<T>(&self, f: impl FnOnce(&Connection) -> AppResult<T>) -> AppResult<T> {
+        let conn = self.conn.lock().unwrap();
+        f(&conn)
+    }
+
+    /// Run `f` inside an IMMEDIATE transaction, committing on success.
+    /// Run a closure against this storage on the blocking thread pool.
+    ///
+    /// IPC handlers run on the async runtime while rusqlite is
+    /// synchronous behind a mutex; a long query executed inline would
+    /// park the runtime and stall every other command. Heavy reads and
+    /// exports go through here instead.
+    pub async fn run<T, F>(self: &Arc<Self>, f: F) -> AppResult<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Storage) -> AppResult<T> + Send + 'static,
+    {
+        let storage = Arc::clone(self);
+        tauri::async_runtime::spawn_blocking(move || f(&storage))
+            .await
+            .map_err(|err| AppError::Internal(format!("blocking storage task failed: {err}")))?
+    }
+
+    pub fn transaction<T>(
+        &self,
+        f: impl FnOnce(&rusqlite::Transaction<'_>) -> AppResult<T>,
+    ) -> AppResult<T> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+        let out = f(&tx)?;
+        tx.commit()?;
+        Ok(out)
+    }
+
+    // ---- agents ----
+
+    pub fn create_agent(&self, agent: &Agent) -> AppResult<()> {
+        self.with_conn(|conn| create_agent_conn(conn, agent))
+    }
+
+    /// Create a whole roster atomically: either every agent lands or
+    /// none do (used by bulk imports).
+    pub fn create_agents(&self, agents: &[Agent]) -> AppResult<()> {
+        self.transaction(|tx| {
+            for agent in agents {
+                create_agent_conn(tx, agent)?;
+            }
+            Ok(())
+        })
+    }
+
+    pub fn get_agent(&self, id: &str) -> AppResult<Agent> {
+        self.with_conn(|conn| get_agent_conn(conn, id))
+    }
+
+    pub fn get_all_agents(&self) -> AppResult<Vec<Agent>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn
+                .prepare(&format!(
+                    "SELECT {AGENT_COLUMNS} FROM agents WHERE deleted_at IS NULL \
+                     ORDER BY created_at"
+                ))?;
+            let rows = stmt.query_map([], agent_from_row)?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    /// Offset-paged agent listing with the total count, for rosters too
+    /// large to ship to the UI in one response.
+    pub fn get_agents_page(&self, limit: i64, offset: i64) -> AppResult<(Vec<Agent>, i64)> {
+        self.with_conn(|conn| {
+            let total: i64 = conn.query_row(
+                "SELECT COUNT(*) FROM agents WHERE deleted_at IS NULL",
+                [],
+                |row| row.get(0),
+            )?;
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {AGENT_COLUMNS} FROM agents WHERE deleted_at IS NULL \
+                 ORDER BY created_at LIMIT ?1 OFFSET ?2"
+            ))?;
+            let rows = stmt.query_map(params![limit, offset], agent_from_row)?;
+            let agents = rows.collect::<Result<Vec<_>, _>>()?;
+            Ok((agents, total))
+        })
+    }
+
+    /// Apply a validated import in one transaction: either the whole
+    /// bundle lands or none of it does. The batch is assembled (and
+    /// integrity-checked) by [`crate::settings_io::import_data`];
+    /// storage only guarantees atomicity.
+    pub fn apply_import(&self, batch: &ImportBatch) -> AppResult<()> {
+        self.transaction(|tx| {
+            for (key, value) in &batch.settings {
+                tx.execute(
+                    "INSERT INTO settings (key, value) VALUES (?1, ?2)
+                     ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+                    params![key, value],
+                )?;
+            }
+            for name in &batch.secret_names {
+                tx.execute(
+                    "INSERT OR IGNORE INTO secrets (name, value, updated_at)
+                     VALUES (?1, NULL, ?2)",
+                    params![name, Utc::now().to_rfc3339()],
+                )?;
+            }
+            for agent in &batch.agents_to_create {
+                create_agent_conn(tx, agent)?;
+            }
+            for (existing_id, imported) in &batch.agents_to_merge {
+                merge_agent_config_conn(tx, existing_id, imported)?;
+            }
+            for task in &batch.tasks {
+                create_task_conn(tx, task)?;
+            }
+            for event in &batch.events {
+                tx.execute(
+                    "INSERT INTO task_events (task_id, kind, payload, severity, created_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![
+                        event.task_id,
+                        event.kind,
+                        event.payload.as_ref().map(|p| p.to_string()),
+                        event.severity.as_str(),
+                        event.created_at.to_rfc3339(),
+                    ],
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Move an agent to the trash. Nothing else is touched -- its
+    /// tasks, events and history stay intact so a restore brings the
+    /// whole activity history back.
+    pub fn soft_delete_agent(&self, id: &str) -> AppResult<()> {
+        self.with_conn(|conn| {
+            let changed = conn.execute(
+                "UPDATE agents SET deleted_at = ?2 WHERE id = ?1 AND deleted_at IS NULL",
+                params![id, Utc::now().to_rfc3339()],
+            )?;
+            if changed == 0 {
+                return Err(AppError::not_found("agent", id));
+            }
+            Ok(())
+        })
+    }
+
+    /// Bring a trashed agent back into the roster.
+    pub fn restore_agent(&self, id: &str) -> AppResult<()> {
+        self.with_conn(|conn| {
+            let changed = conn.execute(
+                "UPDATE agents SET deleted_at = NULL WHERE id = ?1 AND deleted_at IS NOT NULL",
+                params![id],
+            )?;
+            if changed == 0 {
+                return Err(AppError::not_found("agent", id));
+            }
+            Ok(())
+        })
+    }
+
+    /// Trashed agents with when they were deleted, newest first.
+    pub fn list_deleted_agents(&self) -> AppResult<Vec<(Agent, DateTime<Utc>)>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {AGENT_COLUMNS}, deleted_at FROM agents \
+                 WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC"
+            ))?;
+            let rows = stmt.query_map([], |row| {
+                Ok((agent_from_row(row)?, row.get::<_, String>(23)?))
+            })?;
+            rows.map(|row| {
+                row.map(|(agent, deleted_at)| (agent, parse_datetime(deleted_at)))
+                    .map_err(Into::into)
+            })
+            .collect()
+        })
+    }
+
+    /// Permanently remove a trashed agent and everything that hangs off
+    /// it: events, approvals, costs, attachments links, config
+    /// snapshots, tasks and history. Refuses agents not in the trash so
+    /// a stray id cannot skip the soft-delete stage.
+    pub fn purge_agent(&self, id: &str) -> AppResult<()> {
+        self.transaction(|tx| {
+            let trashed: i64 = tx.query_row(
+                "SELECT COUNT(*) FROM agents WHERE id = ?1 AND deleted_at IS NOT NULL",
+                params![id],
+                |row| row.get(0),
+            )?;
+            if trashed == 0 {
+                return Err(AppError::not_found("agent", id));
+            }
+            for sql in [
+                "DELETE FROM task_events WHERE task_id IN (SELECT id FROM tasks WHERE agent_id = ?1)",
+                "DELETE FROM approvals WHERE task_id IN (SELECT id FROM tasks WHERE agent_id = ?1)",
+                "DELETE FROM task_attachments WHERE task_id IN (SELECT id FROM tasks WHERE agent_id = ?1)",
+                "DELETE FROM task_config_snapshots WHERE task_id IN (SELECT id FROM tasks WHERE agent_id = ?1)",
+                "DELETE FROM task_costs WHERE agent_id = ?1",
+                "DELETE FROM tasks WHERE agent_id = ?1",
+                "DELETE FROM agent_history WHERE agent_id = ?1",
+                "DELETE FROM agents WHERE id = ?1",
+            ] {
+                tx.execute(sql, params![id])?;
+            }
+            Ok(())
+        })
+    }
+
+    pub fn set_agent_status(&self, id: &str, status: AgentStatus) -> AppResult<()> {
+        self.with_conn(|conn| {
+            let changed = conn.execute(
+                "UPDATE agents SET status = ?2 WHERE id = ?1",
+                params![id, status.as_str()],
+            )?;
+            if changed == 0 {
+                return Err(AppError::not_found("agent", id));
+            }
+            Ok(())
+        })
+    }
+
+    pub fn set_agent_avatar(&self, id: &str, avatar_path: Option<&str>) -> AppResult<()> {
+        self.with_conn(|conn| {
+            let changed = conn.execute(
+                "UPDATE agents SET avatar_path = ?2 WHERE id = ?1",
+                params![id, avatar_path],
+            )?;
+            if changed == 0 {
+                return Err(AppError::not_found("agent", id));
+            }
+            Ok(())
+        })
+    }
+
+    // ---- tasks ----
+
+    pub fn create_task(&self, task: &Task) -> AppResult<()> {
+        self.with_conn(|conn| create_task_conn(conn, task))
+    }
+
+    pub fn get_task(&self, id: &str) -> AppResult<Task> {
+        self.with_conn(|conn| get_task_conn(conn, id))
+    }
+
+    /// Accumulate provider-reported token usage onto a task. Called
+    /// once per completed call, so retries and fallbacks add up.
+    pub fn add_task_usage(
+        &self,
+        id: &str,
+        prompt_tokens: i64,
+        completion_tokens: i64,
+    ) -> AppResult<()> {
+        self.with_conn(|conn| {
+            let changed = conn.execute(
+                "UPDATE tasks SET prompt_tokens = prompt_tokens + ?2,
+                        completion_tokens = completion_tokens + ?3
+                 WHERE id = ?1",
+                params![id, prompt_tokens, completion_tokens],
+            )?;
+            if changed == 0 {
+                return Err(AppError::not_found("task", id));
+            }
+            Ok(())
+        })
+    }
+
+    /// Token usage summed over an agent's tasks.
+    pub fn get_agent_stats(&self, agent_id: &str) -> AppResult<AgentStats> {
+        self.with_conn(|conn| {
+            get_agent_conn(conn, agent_id)?;
+            conn.query_row(
+                "SELECT COUNT(*) FILTER (WHERE prompt_tokens + completion_tokens > 0),
+                        COALESCE(SUM(prompt_tokens), 0),
+                        COALESCE(SUM(completion_tokens), 0)
+                 FROM tasks WHERE agent_id = ?1",
+                params![agent_id],
+                |row| {
+                    let prompt_tokens: i64 = row.get(1)?;
+                    let completion_tokens: i64 = row.get(2)?;
+                    Ok(AgentStats {
+                        agent_id: agent_id.to_string(),
+                        tasks_with_usage: row.get(0)?,
+                        prompt_tokens,
+                        completion_tokens,
+                        total_tokens: prompt_tokens + completion_tokens,
+                    })
+                },
+            )
+            .map_err(Into::into)
+        })
+    }
+
+    /// Tasks whose last update falls inside `[start, end)`.
+    pub fn get_tasks_updated_between(
+        &self,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+    ) -> AppResult<Vec<Task>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {TASK_COLUMNS} FROM tasks
+                 WHERE updated_at >= ?1 AND updated_at < ?2
+                 ORDER BY updated_at"
+            ))?;
+            let rows = stmt.query_map(
+                params![start.to_rfc3339(), end.to_rfc3339()],
+                task_from_row,
+            )?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    pub fn get_all_tasks(&self) -> AppResult<Vec<Task>> {
+        self.with_conn(|conn| {
+            let mut stmt =
+                conn.prepare(&format!("SELECT {TASK_COLUMNS} FROM tasks ORDER BY created_at"))?;
+            let rows = stmt.query_map([], task_from_row)?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    /// Offset-paged task listing with the total count; newest first,
+    /// since the UI leads with recent work.
+    pub fn get_tasks_page(&self, limit: i64, offset: i64) -> AppResult<(Vec<Task>, i64)> {
+        self.with_conn(|conn| {
+            let total: i64 = conn.query_row("SELECT COUNT(*) FROM tasks", [], |row| row.get(0))?;
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {TASK_COLUMNS} FROM tasks ORDER BY created_at DESC LIMIT ?1 OFFSET ?2"
+            ))?;
+            let rows = stmt.query_map(params![limit, offset], task_from_row)?;
+            let tasks = rows.collect::<Result<Vec<_>, _>>()?;
+            Ok((tasks, total))
+        })
+    }
+
+    pub fn set_task_status(
+        &self,
+        id: &str,
+        status: TaskStatus,
+        result: Option<&str>,
+        error: Option<&str>,
+    ) -> AppResult<()> {
+        self.with_conn(|conn| {
+            let changed = conn.execute(
+                "UPDATE tasks SET status = ?2, result = ?3, error = ?4, updated_at = ?5
+                 WHERE id = ?1",
+                params![
+                    id,
+                    status.as_str(),
+                    result,
+                    error,
+                    Utc::now().to_rfc3339()
+                ],
+            )?;
+            if changed == 0 {
+                return Err(AppError::not_found("task", id));
+            }
+            Ok(())
+        })
+    }
+
+    /// Atomically claim a queued task for execution.
+    ///
+    /// The task must be Queued and its agent must not already be Running;
+    /// both are checked and updated inside one IMMEDIATE transaction so
+    /// concurrent `execute_task` calls cannot double-run an agent.
+    pub fn claim_task(&self, task_id: &str) -> AppResult<Task> {
+        self.transaction(|tx| {
+            // Upgrades drain the workspace: nothing new starts while
+            // maintenance mode is set.
+            if let Some(phase) = tx
+                .query_row(
+                    "SELECT value FROM settings WHERE key = ?1",
+                    params![MAINTENANCE_MODE_SETTING],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()?
+            {
+                return Err(AppError::MaintenanceMode(phase));
+            }
+            let task = get_task_conn(tx, task_id)?;
+            if task.status != TaskStatus::Queued {
+                return Err(AppError::InvalidTransition {
+                    task_id: task_id.to_string(),
+                    status: task.status.as_str().to_string(),
+                    requested: TaskStatus::Running.as_str().to_string(),
+                });
+            }
+            let agent = get_agent_conn(tx, &task.agent_id)?;
+            match agent.status {
+                AgentStatus::Running => return Err(AppError::AgentBusy(agent.id)),
+                // Paused is a hard gate: nothing enters Running for a
+                // paused agent, no matter how the task was queued.
+                AgentStatus::Paused => return Err(AppError::AgentPaused(agent.id)),
+                AgentStatus::Idle => {}
+            }
+            let now = Utc::now().to_rfc3339();
+            tx.execute(
+                "UPDATE tasks SET status = 'running', started_at = ?2, updated_at = ?2
+                 WHERE id = ?1",
+                params![task_id, now],
+            )?;
+            tx.execute(
+                "UPDATE agents SET status = 'running' WHERE id = ?1",
+                params![task.agent_id],
+            )?;
+            append_event_conn(tx, task_id, "started", None)?;
+            get_task_conn(tx, task_id)
+        })
+    }
+
+    /// Move a Running task to a terminal state and release its agent.
+    ///
+    /// The status update is a compare-and-set on `status = 'running'`, so
+    /// a concurrent cancellation wins cleanly instead of being silently
+    /// overwritten.
+    pub fn finish_task(
+        &self,
+        task_id: &str,
+        status: TaskStatus,
+        result: Option<&str>,
+        error: Option<&str>,
+        failure: Option<FailureKind>,
+    ) -> AppResult<Task> {
+        debug_assert!(status.is_terminal());
+        // Huge results go to a file; the row keeps a preview and the
+        // file reference so IPC payloads stay small.
+        let mut inline = result.map(str::to_string);
+        let mut artifact = None;
+        if let Some(full) = result {
+            if full.len() > RESULT_INLINE_LIMIT {
+                match self.offload_result(task_id, full) {
+                    Ok(name) => {
+                        inline = Some(full.chars().take(RESULT_PREVIEW_CHARS).collect());
+                        artifact = Some(name);
+                    }
+                    Err(err) => {
+                        tracing::warn!(task_id, %err, "result offload failed; keeping inline");
+                    }
+                }
+            }
+        }
+        self.transaction(|tx| {
+            let task = get_task_conn(tx, task_id)?;
+            let changed = tx.execute(
+                "UPDATE tasks SET status = ?2, result = ?3, error = ?4, result_artifact = ?5,
+                        updated_at = ?6, failure_kind = ?7
+                 WHERE id = ?1 AND status = 'running'",
+                params![
+                    task_id,
+                    status.as_str(),
+                    inline,
+                    error,
+                    artifact,
+                    Utc::now().to_rfc3339(),
+                    failure.map(FailureKind::as_str),
+                ],
+            )?;
+            if changed == 0 {
+                return Err(AppError::InvalidTransition {
+                    task_id: task_id.to_string(),
+                    status: task.status.as_str().to_string(),
+                    requested: status.as_str().to_string(),
+                });
+            }
+            tx.execute(
+                "UPDATE agents SET status = 'idle',
+                        runtime_seconds = runtime_seconds + ?2
+                 WHERE id = ?1 AND status = 'running'",
+                params![task.agent_id, elapsed_seconds(task.started_at, Utc::now())],
+            )?;
+            append_event_conn(tx, task_id, status.as_str(), None)?;
+            if status == TaskStatus::Failed {
+                append_agent_history_conn(
+                    tx,
+                    &task.agent_id,
+                    "task_failed",
+                    Some(&serde_json::json!({ "task_id": task_id, "error": error })),
+                )?;
+            }
+            get_task_conn(tx, task_id)
+        })
+    }
+
+    /// Cancel a non-terminal task, releasing its agent if it was running.
+    pub fn cancel_task(&self, task_id: &str) -> AppResult<Task> {
+        self.transaction(|tx| {
+            let task = get_task_conn(tx, task_id)?;
+            if task.status.is_terminal() {
+                return Err(AppError::InvalidTransition {
+                    task_id: task_id.to_string(),
+                    status: task.status.as_str().to_string(),
+                    requested: TaskStatus::Cancelled.as_str().to_string(),
+                });
+            }
+            tx.execute(
+                "UPDATE tasks SET status = 'cancelled', failure_kind = 'cancelled',
+                        updated_at = ?2
+                 WHERE id = ?1 AND status = ?3",
+                params![task_id, Utc::now().to_rfc3339(), task.status.as_str()],
+            )?;
+            if matches!(
+                task.status,
+                TaskStatus::Running | TaskStatus::WaitingForInput
+            ) {
+                tx.execute(
+                    "UPDATE agents SET status = 'idle',
+                            runtime_seconds = runtime_seconds + ?2
+                     WHERE id = ?1 AND status = 'running'",
+                    params![task.agent_id, elapsed_seconds(task.started_at, Utc::now())],
+                )?;
+            }
+            append_event_conn(tx, task_id, "cancelled", None)?;
+            get_task_conn(tx, task_id)
+        })
+    }
+
+    /// Suspend a Running task on an operator question. The executor
+    /// thread stays alive and polls for the matching answer.
+    pub fn suspend_for_input(&self, task_id: &str, question: &str) -> AppResult<()> {
+        self.transaction(|tx| {
+            let task = get_task_conn(tx, task_id)?;
+            if task.status != TaskStatus::Running {
+                return Err(AppError::InvalidTransition {
+                    task_id: task_id.to_string(),
+                    status: task.status.as_str().to_string(),
+                    requested: TaskStatus::WaitingForInput.as_str().to_string(),
+                });
+            }
+            tx.execute(
+                "UPDATE tasks SET status = 'waiting_for_input', updated_at = ?2
+                 WHERE id = ?1",
+                params![task_id, Utc::now().to_rfc3339()],
+            )?;
+            append_event_conn(
+                tx,
+                task_id,
+                "ask_user",
+                Some(&serde_json::json!({ "question": question })),
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Answer a suspended task's question and put it back to Running.
+    pub fn resume_from_input(&self, task_id: &str, answer: &str) -> AppResult<()> {
+        self.transaction(|tx| {
+            let task = get_task_conn(tx, task_id)?;
+            if task.status != TaskStatus::WaitingForInput {
+                return Err(AppError::InvalidTransition {
+                    task_id: task_id.to_string(),
+                    status: task.status.as_str().to_string(),
+                    requested: TaskStatus::Running.as_str().to_string(),
+                });
+            }
+            tx.execute(
+                "UPDATE tasks SET status = 'running', updated_at = ?2
+                 WHERE id = ?1",
+                params![task_id, Utc::now().to_rfc3339()],
+            )?;
+            append_event_conn(
+                tx,
+                task_id,
+                "user_input",
+                Some(&serde_json::json!({ "answer": answer })),
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Queued tasks listing `task_id` among their dependencies.
+    pub fn get_dependents(&self, task_id: &str) -> AppResult<Vec<Task>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {TASK_COLUMNS} FROM tasks
+                 WHERE status = 'queued' AND depends_on LIKE '%' || ?1 || '%'"
+            ))?;
+            let rows = stmt.query_map(params![task_id], task_from_row)?;
+            // LIKE is only a prefilter; the JSON array decides.
+            Ok(rows
+                .collect::<Result<Vec<_>, _>>()?
+                .into_iter()
+                .filter(|t| t.depends_on.iter().any(|dep| dep == task_id))
+                .collect())
+        })
+    }
+
+    /// Dependents of `task_id` whose parents have now all completed,
+    /// i.e. the tasks the dispatcher should auto-start.
+    pub fn ready_dependents(&self, task_id: &str) -> AppResult<Vec<Task>> {
+        let mut ready = Vec::new();
+        for dependent in self.get_dependents(task_id)? {
+            let mut all_done = true;
+            for parent_id in &dependent.depends_on {
+                if self.get_task(parent_id)?.status != TaskStatus::Completed {
+                    all_done = false;
+                    break;
+                }
+            }
+            if all_done {
+                ready.push(dependent);
+            }
+        }
+        Ok(ready)
+    }
+
+    /// Mark a still-queued task Blocked because `parent_id` will never
+    /// complete, recording a `blocked` event.
+    pub fn block_task(
+        &self,
+        task_id: &str,
+        parent_id: &str,
+        parent_status: TaskStatus,
+    ) -> AppResult<()> {
+        let reason = format!("blocked: dependency {parent_id} {}", parent_status.as_str());
+        let changed = self.with_conn(|conn| {
+            Ok(conn.execute(
+                "UPDATE tasks SET status = 'blocked', error = ?2, updated_at = ?3
+                 WHERE id = ?1 AND status = 'queued'",
+                params![task_id, reason, Utc::now().to_rfc3339()],
+            )?)
+        })?;
+        if changed > 0 {
+            self.append_event(
+                task_id,
+                "blocked",
+                Some(&serde_json::json!({
+                    "dependency": parent_id,
+                    "dependency_status": parent_status.as_str(),
+                })),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Cascade a failed/cancelled task through its dependency graph:
+    /// every queued descendant becomes Blocked. Returns the blocked ids.
+    pub fn block_dependents(&self, task_id: &str) -> AppResult<Vec<String>> {
+        let root_status = self.get_task(task_id)?.status;
+        let mut blocked = Vec::new();
+        let mut frontier = vec![(task_id.to_string(), root_status)];
+        while let Some((parent_id, parent_status)) = frontier.pop() {
+            for dependent in self.get_dependents(&parent_id)? {
+                self.block_task(&dependent.id, &parent_id, parent_status)?;
+                frontier.push((dependent.id.clone(), TaskStatus::Blocked));
+                blocked.push(dependent.id);
+            }
+        }
+        Ok(blocked)
+    }
+
+    /// Move a task to (`column`, `position`) on the kanban board,
+    /// shifting neighbours so positions stay dense in both columns.
+    pub fn move_task(&self, task_id: &str, column: &str, position: i64) -> AppResult<Task> {
+        self.transaction(|tx| {
+            let task = get_task_conn(tx, task_id)?;
+            // Close the gap left in the old column.
+            tx.execute(
+                "UPDATE tasks SET board_position = board_position - 1
+                 WHERE board_column = ?1 AND board_position > ?2",
+                params![task.board_column, task.board_position],
+            )?;
+            // Clamp into the target column and make room.
+            let count: i64 = tx.query_row(
+                "SELECT COUNT(*) FROM tasks WHERE board_column = ?1 AND id != ?2",
+                params![column, task_id],
+                |row| row.get(0),
+            )?;
+            let position = position.clamp(0, count);
+            tx.execute(
+                "UPDATE tasks SET board_position = board_position + 1
+                 WHERE board_column = ?1 AND board_position >= ?2 AND id != ?3",
+                params![column, position, task_id],
+            )?;
+            tx.execute(
+                "UPDATE tasks SET board_column = ?2, board_position = ?3, updated_at = ?4
+                 WHERE id = ?1",
+                params![task_id, column, position, Utc::now().to_rfc3339()],
+            )?;
+            get_task_conn(tx, task_id)
+        })
+    }
+
+    /// Tasks in board order: by column, then position.
+    pub fn get_board(&self) -> AppResult<Vec<Task>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {TASK_COLUMNS} FROM tasks ORDER BY board_column, board_position"
+            ))?;
+            let rows = stmt.query_map([], task_from_row)?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    // ---- agent history ----
+
+    pub fn append_agent_history(
+        &self,
+        agent_id: &str,
+        kind: &str,
+        detail: Option<&serde_json::Value>,
+    ) -> AppResult<()> {
+        self.with_conn(|conn| append_agent_history_conn(conn, agent_id, kind, detail))
+    }
+
+    /// Chronological lifecycle feed for one agent.
+    pub fn get_agent_history(&self, agent_id: &str) -> AppResult<Vec<AgentHistoryEntry>> {
+        // Ensure a NotFound for unknown agents rather than an empty feed.
+        self.get_agent(agent_id)?;
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, agent_id, kind, detail, created_at
+                 FROM agent_history WHERE agent_id = ?1 ORDER BY id",
+            )?;
+            let rows = stmt.query_map(params![agent_id], |row| {
+                let detail: Option<String> = row.get(3)?;
+                Ok(AgentHistoryEntry {
+                    id: row.get(0)?,
+                    agent_id: row.get(1)?,
+                    kind: row.get(2)?,
+                    detail: detail.and_then(|d| serde_json::from_str(&d).ok()),
+                    created_at: parse_datetime(row.get(4)?),
+                })
+            })?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    // ---- events ----
+
+    /// Append an event, subject to the write-time sampling policy and
+    /// the agent's minimum-severity setting. Returns 0 when the event
+    /// was sampled or filtered away; kept events under a sampling rule
+    /// carry a `sample_rate` field in their payload.
+    pub fn append_event(
+        &self,
+        task_id: &str,
+        kind: &str,
+        payload: Option<&serde_json::Value>,
+    ) -> AppResult<i64> {
+        self.with_conn(|conn| {
+            if EventSeverity::for_kind(kind) < min_severity_conn(conn, task_id)? {
+                return Ok(0);
+            }
+            match sampling_rate_conn(conn, task_id, kind)? {
+                SamplingDecision::Keep => append_event_conn(conn, task_id, kind, payload),
+                SamplingDecision::Drop => Ok(0),
+                SamplingDecision::KeepSampled(rate) => {
+                    let mut merged = payload.cloned().unwrap_or_else(|| serde_json::json!({}));
+                    if let Some(obj) = merged.as_object_mut() {
+                        obj.insert("sample_rate".into(), serde_json::json!(rate));
+                    }
+                    append_event_conn(conn, task_id, kind, Some(&merged))
+                }
+            }
+        })
+    }
+
+    /// Highest event id written so far; the live bridge starts its
+    /// cursor here so restarts do not replay history.
+    pub fn latest_event_id(&self) -> AppResult<i64> {
+        self.with_conn(|conn| {
+            conn.query_row("SELECT COALESCE(MAX(id), 0) FROM task_events", [], |row| {
+                row.get(0)
+            })
+            .map_err(Into::into)
+        })
+    }
+
+    /// Every event appended after `cursor`, across all tasks, oldest
+    /// first.
+    pub fn get_events_after(&self, cursor: i64) -> AppResult<Vec<TaskEvent>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, task_id, kind, payload, severity, created_at FROM task_events
+                 WHERE id > ?1 ORDER BY id",
+            )?;
+            let rows = stmt.query_map(params![cursor], event_from_row)?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    pub fn get_task_events(&self, task_id: &str) -> AppResult<Vec<TaskEvent>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, task_id, kind, payload, severity, created_at
+                 FROM task_events WHERE task_id = ?1 ORDER BY id",
+            )?;
+            let rows = stmt.query_map(params![task_id], event_from_row)?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    /// The most recent `limit` events for a task (ascending order), plus
+    /// total-count metadata for responsible pagination.
+    pub fn get_task_events_bounded(
+        &self,
+        task_id: &str,
+        limit: i64,
+        offset: i64,
+    ) -> AppResult<(Vec<TaskEvent>, i64)> {
+        self.with_conn(|conn| {
+            let total: i64 = conn.query_row(
+                "SELECT COUNT(*) FROM task_events WHERE task_id = ?1",
+                params![task_id],
+                |row| row.get(0),
+            )?;
+            let mut stmt = conn.prepare(
+                "SELECT id, task_id, kind, payload, severity, created_at
+                 FROM task_events WHERE task_id = ?1 ORDER BY id LIMIT ?2 OFFSET ?3",
+            )?;
+            let rows = stmt.query_map(params![task_id, limit, offset], event_from_row)?;
+            let events = rows.collect::<Result<Vec<_>, _>>()?;
+            Ok((events, total))
+        })
+    }
+
+    /// One cursor page of a task's events, newest first. `before_id`
+    /// and `before` are exclusive upper bounds (id and timestamp); pass
+    /// the last row of the previous page to scroll further back. Over-
+    /// fetches one row so callers can tell whether more pages exist.
+    pub fn get_task_events_page(
+        &self,
+        task_id: &str,
+        before_id: Option<i64>,
+        before: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> AppResult<Vec<TaskEvent>> {
+        self.with_conn(|conn| {
+            let mut sql = String::from(
+                "SELECT id, task_id, kind, payload, severity, created_at
+                 FROM task_events WHERE task_id = ?",
+            );
+            let mut args: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(task_id.to_string())];
+            if let Some(before_id) = before_id {
+                sql.push_str(" AND id < ?");
+                args.push(Box::new(before_id));
+            }
+            if let Some(before) = before {
+                sql.push_str(" AND created_at < ?");
+                args.push(Box::new(before.to_rfc3339()));
+            }
+            sql.push_str(" ORDER BY id DESC LIMIT ?");
+            args.push(Box::new(limit));
+            let mut stmt = conn.prepare(&sql)?;
+            let rows = stmt.query_map(rusqlite::params_from_iter(args), event_from_row)?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    /// Full-text search across the whole activity history. The query is
+    /// treated as plain words (quoted per token, so "openapi.yaml" or
+    /// "rate limit" need no FTS syntax knowledge); results come back
+    /// best-match first.
+    pub fn search_events(&self, query: &str, limit: i64) -> AppResult<Vec<TaskEvent>> {
+        let expression = query
+            .split_whitespace()
+            .map(|token| format!("\"{}\"", token.replace('"', "\"\"")))
+            .collect::<Vec<_>>()
+            .join(" ");
+        if expression.is_empty() {
+            return Ok(Vec::new());
+        }
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT e.id, e.task_id, e.kind, e.payload, e.severity, e.created_at
+                 FROM event_search s JOIN task_events e ON e.id = s.rowid
+                 WHERE event_search MATCH ?1
+                 ORDER BY rank LIMIT ?2",
+            )?;
+            let rows = stmt.query_map(params![expression, limit], event_from_row)?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    /// Filtered event query, with every filter pushed down into SQL so
+    /// the frontend never pages a giant unfiltered list: event kind,
+    /// task, agent, and a `[start, end)` time window, all optional.
+    /// Ascending id order, capped at `limit` rows.
+    pub fn query_events(
+        &self,
+        kind: Option<&str>,
+        task_id: Option<&str>,
+        agent_id: Option<&str>,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        limit: i64,
+    ) -> AppResult<Vec<TaskEvent>> {
+        self.with_conn(|conn| {
+            let mut sql = String::from(
+                "SELECT e.id, e.task_id, e.kind, e.payload, e.severity, e.created_at
+                 FROM task_events e JOIN tasks t ON t.id = e.task_id
+                 WHERE 1 = 1",
+            );
+            let mut args: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+            if let Some(kind) = kind {
+                sql.push_str(" AND e.kind = ?");
+                args.push(Box::new(kind.to_string()));
+            }
+            if let Some(task_id) = task_id {
+                sql.push_str(" AND e.task_id = ?");
+                args.push(Box::new(task_id.to_string()));
+            }
+            if let Some(agent_id) = agent_id {
+                sql.push_str(" AND t.agent_id = ?");
+                args.push(Box::new(agent_id.to_string()));
+            }
+            if let Some(start) = start {
+                sql.push_str(" AND e.created_at >= ?");
+                args.push(Box::new(start.to_rfc3339()));
+            }
+            if let Some(end) = end {
+                sql.push_str(" AND e.created_at < ?");
+                args.push(Box::new(end.to_rfc3339()));
+            }
+            sql.push_str(" ORDER BY e.id LIMIT ?");
+            args.push(Box::new(limit));
+            let mut stmt = conn.prepare(&sql)?;
+            let rows = stmt.query_map(rusqlite::params_from_iter(args), event_from_row)?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    /// Stream every event matching the same filters as [`query_events`]
+    /// through `f`, in id order and without a limit. Used by bulk
+    /// exports so large histories never materialize in memory at once.
+    #[allow(clippy::too_many_arguments)]
+    pub fn stream_events(
+        &self,
+        kind: Option<&str>,
+        task_id: Option<&str>,
+        agent_id: Option<&str>,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+        mut f: impl FnMut(&TaskEvent) -> AppResult<()>,
+    ) -> AppResult<u64> {
+        self.with_conn(|conn| {
+            let mut sql = String::from(
+                "SELECT e.id, e.task_id, e.kind, e.payload, e.severity, e.created_at
+                 FROM task_events e JOIN tasks t ON t.id = e.task_id
+                 WHERE 1 = 1",
+            );
+            let mut args: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+            if let Some(kind) = kind {
+                sql.push_str(" AND e.kind = ?");
+                args.push(Box::new(kind.to_string()));
+            }
+            if let Some(task_id) = task_id {
+                sql.push_str(" AND e.task_id = ?");
+                args.push(Box::new(task_id.to_string()));
+            }
+            if let Some(agent_id) = agent_id {
+                sql.push_str(" AND t.agent_id = ?");
+                args.push(Box::new(agent_id.to_string()));
+            }
+            if let Some(start) = start {
+                sql.push_str(" AND e.created_at >= ?");
+                args.push(Box::new(start.to_rfc3339()));
+            }
+            if let Some(end) = end {
+                sql.push_str(" AND e.created_at < ?");
+                args.push(Box::new(end.to_rfc3339()));
+            }
+            sql.push_str(" ORDER BY e.id");
+            let mut stmt = conn.prepare(&sql)?;
+            let mut rows = stmt.query(rusqlite::params_from_iter(args))?;
+            let mut streamed = 0;
+            while let Some(row) = rows.next()? {
+                f(&event_from_row(row)?)?;
+                streamed += 1;
+            }
+            Ok(streamed)
+        })
+    }
+
+    /// Newest-first event rows joined with task and agent, for the
+    /// activity feed. `before_id` is an exclusive cursor.
+    pub fn query_feed_events(
+        &self,
+        before_id: Option<i64>,
+        limit: i64,
+        agent_id: Option<&str>,
+        kinds: Option<&[String]>,
+    ) -> AppResult<Vec<crate::feed::FeedEventRow>> {
+        self.with_conn(|conn| {
+            let mut sql = String::from(
+                "SELECT e.id, e.task_id, t.title, a.id, a.name, e.kind, e.payload, e.created_at
+                 FROM task_events e
+                 JOIN tasks t ON t.id = e.task_id
+                 JOIN agents a ON a.id = t.agent_id
+                 WHERE 1 = 1",
+            );
+            let mut args: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+            if let Some(before) = before_id {
+                sql.push_str(" AND e.id < ?");
+                args.push(Box::new(before));
+            }
+            if let Some(agent) = agent_id {
+                sql.push_str(" AND a.id = ?");
+                args.push(Box::new(agent.to_string()));
+            }
+            if let Some(kinds) = kinds {
+                if !kinds.is_empty() {
+                    let marks = vec!["?"; kinds.len()].join(", ");
+                    sql.push_str(&format!(" AND e.kind IN ({marks})"));
+                    for kind in kinds {
+                        args.push(Box::new(kind.clone()));
+                    }
+                }
+            }
+            sql.push_str(" ORDER BY e.id DESC LIMIT ?");
+            args.push(Box::new(limit));
+
+            let mut stmt = conn.prepare(&sql)?;
+            let params: Vec<&dyn rusqlite::ToSql> = args.iter().map(|a| a.as_ref()).collect();
+            let rows = stmt.query_map(params.as_slice(), |row| {
+                Ok(crate::feed::FeedEventRow {
+                    event_id: row.get(0)?,
+                    task_id: row.get(1)?,
+                    task_title: row.get(2)?,
+                    agent_id: row.get(3)?,
+                    agent_name: row.get(4)?,
+                    kind: row.get(5)?,
+                    payload: row
+                        .get::<_, Option<String>>(6)?
+                        .and_then(|raw| serde_json::from_str(&raw).ok()),
+                    created_at: parse_datetime(row.get(7)?),
+                })
+            })?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    /// Cached health-probe result for a dependency URL, if any.
+    pub fn get_service_status(&self, url: &str) -> AppResult<Option<crate::health::ServiceStatus>> {
+        self.with_conn(|conn| {
+            conn.query_row(
+                "SELECT url, healthy, detail, checked_at FROM service_status WHERE url = ?1",
+                params![url],
+                |row| {
+                    Ok(crate::health::ServiceStatus {
+                        url: row.get(0)?,
+                        healthy: row.get::<_, i64>(1)? != 0,
+                        detail: row.get(2)?,
+                        checked_at: parse_datetime(row.get(3)?),
+                    })
+                },
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+    }
+
+    pub fn set_service_status(&self, status: &crate::health::ServiceStatus) -> AppResult<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO service_status (url, healthy, detail, checked_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    status.url,
+                    status.healthy as i64,
+                    status.detail,
+                    status.checked_at.to_rfc3339(),
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Replace the stored tool list for one MCP server after discovery.
+    pub fn replace_mcp_tools(&self, server: &str, tools: &[crate::mcp::McpTool]) -> AppResult<()> {
+        self.transaction(|conn| {
+            conn.execute("DELETE FROM mcp_tools WHERE server = ?1", params![server])?;
+            for tool in tools {
+                conn.execute(
+                    "INSERT INTO mcp_tools (server, name, description, input_schema, discovered_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![
+                        server,
+                        tool.name,
+                        tool.description,
+                        tool.input_schema.to_string(),
+                        Utc::now().to_rfc3339(),
+                    ],
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    pub fn get_mcp_tool(&self, server: &str, name: &str) -> AppResult<Option<crate::mcp::McpTool>> {
+        self.with_conn(|conn| {
+            conn.query_row(
+                "SELECT server, name, description, input_schema
+                 FROM mcp_tools WHERE server = ?1 AND name = ?2",
+                params![server, name],
+                mcp_tool_from_row,
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+    }
+
+    /// Tools discovered across an agent's declared MCP servers.
+    pub fn get_mcp_tools(&self, servers: &[String]) -> AppResult<Vec<crate::mcp::McpTool>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT server, name, description, input_schema
+                 FROM mcp_tools ORDER BY server, name",
+            )?;
+            let rows = stmt.query_map([], mcp_tool_from_row)?;
+            let all: Vec<_> = rows.collect::<Result<Vec<_>, _>>()?;
+            Ok(all
+                .into_iter()
+                .filter(|tool: &crate::mcp::McpTool| servers.contains(&tool.server))
+                .collect())
+        })
+    }
+
+    /// Attach the agent-config snapshot in effect for this run. Kept
+    /// once per task: re-executions overwrite with the current config.
+    pub fn set_task_config_snapshot(
+        &self,
+        task_id: &str,
+        snapshot: &serde_json::Value,
+    ) -> AppResult<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO task_config_snapshots (task_id, snapshot, created_at)
+                 VALUES (?1, ?2, ?3)",
+                params![task_id, snapshot.to_string(), Utc::now().to_rfc3339()],
+            )?;
+            Ok(())
+        })
+    }
+
+    pub fn get_task_config_snapshot(&self, task_id: &str) -> AppResult<Option<serde_json::Value>> {
+        self.with_conn(|conn| {
+            conn.query_row(
+                "SELECT snapshot FROM task_config_snapshots WHERE task_id = ?1",
+                params![task_id],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()
+            .map(|raw| raw.and_then(|raw| serde_json::from_str(&raw).ok()))
+            .map_err(Into::into)
+        })
+    }
+
+    /// Update an existing agent's config from an imported agent,
+    /// keeping its identity, status and accumulated stats.
+    pub fn merge_agent_config(&self, existing_id: &str, imported: &Agent) -> AppResult<()> {
+        self.transaction(|tx| merge_agent_config_conn(tx, existing_id, imported))
+    }
+
+    /// Reset tasks left Running by a previous process (a crash or hard
+    /// exit) back to Queued, releasing their agents, and record what
+    /// happened on each task. Returns the requeued ids.
+    pub fn requeue_orphaned_running(&self) -> AppResult<Vec<String>> {
+        let ids = self.transaction(|tx| {
+            let mut stmt = tx.prepare(
+                "UPDATE tasks SET status = 'queued', started_at = NULL, updated_at = ?1
+                 WHERE status = 'running' RETURNING id",
+            )?;
+            let ids = stmt
+                .query_map(params![Utc::now().to_rfc3339()], |row| row.get::<_, String>(0))?
+                .collect::<Result<Vec<_>, _>>()?;
+            drop(stmt);
+            tx.execute("UPDATE agents SET status = 'idle' WHERE status = 'running'", [])?;
+            Ok(ids)
+        })?;
+        for id in &ids {
+            self.append_event(id, "requeued_after_restart", None)?;
+        }
+        Ok(ids)
+    }
+
+    /// Queued tasks in execution order -- most urgent first, then
+    /// dispatch order -- skipping delayed tasks that are not yet due.
+    /// Dependency gating is the dispatcher's job.
+    pub fn queued_tasks_in_order(&self, now: DateTime<Utc>) -> AppResult<Vec<Task>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {TASK_COLUMNS} FROM tasks
+                 WHERE status = 'queued' AND (run_at IS NULL OR run_at <= ?1)
+                 ORDER BY CASE priority
+                              WHEN 'urgent' THEN 0
+                              WHEN 'high' THEN 1
+                              WHEN 'normal' THEN 2
+                              ELSE 3
+                          END,
+                          queue_position"
+            ))?;
+            let rows = stmt.query_map(params![now.to_rfc3339()], task_from_row)?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    /// Queued tasks whose `run_at` has arrived, for promotion into the
+    /// worker queue.
+    pub fn due_delayed_tasks(&self, now: DateTime<Utc>) -> AppResult<Vec<Task>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {TASK_COLUMNS} FROM tasks
+                 WHERE status = 'queued' AND run_at IS NOT NULL AND run_at <= ?1"
+            ))?;
+            let rows = stmt.query_map(params![now.to_rfc3339()], task_from_row)?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    // ---- API tokens ----
+
+    /// Persist an issued token. Only its hash arrives here; callers
+    /// keep the plaintext out of storage and logs.
+    pub fn insert_api_token(
+        &self,
+        record: &crate::api_tokens::ApiToken,
+        token_hash: &str,
+    ) -> AppResult<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO api_tokens (id, name, token_hash, scopes, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    record.id,
+                    record.name,
+                    token_hash,
+                    serde_json::to_string(&record.scopes).unwrap_or_else(|_| "[]".into()),
+                    record.created_at.to_rfc3339(),
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    pub fn find_api_token_by_hash(
+        &self,
+        token_hash: &str,
+    ) -> AppResult<Option<crate::api_tokens::ApiToken>> {
+        self.with_conn(|conn| {
+            conn.query_row(
+                "SELECT id, name, scopes, created_at, revoked_at
+                 FROM api_tokens WHERE token_hash = ?1",
+                params![token_hash],
+                api_token_from_row,
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+    }
+
+    pub fn get_api_tokens(&self) -> AppResult<Vec<crate::api_tokens::ApiToken>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, name, scopes, created_at, revoked_at
+                 FROM api_tokens ORDER BY created_at",
+            )?;
+            let rows = stmt.query_map([], api_token_from_row)?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    pub fn revoke_api_token(&self, id: &str) -> AppResult<()> {
+        self.with_conn(|conn| {
+            let changed = conn.execute(
+                "UPDATE api_tokens SET revoked_at = ?2 WHERE id = ?1 AND revoked_at IS NULL",
+                params![id, Utc::now().to_rfc3339()],
+            )?;
+            if changed == 0 {
+                return Err(AppError::not_found("api token", id));
+            }
+            Ok(())
+        })
+    }
+
+    // ---- task templates ----
+
+    pub fn create_task_template(&self, template: &TaskTemplate) -> AppResult<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO task_templates (id, name, title, prompt, agent_id, priority,
+                                             tags, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    template.id,
+                    template.name,
+                    template.title,
+                    template.prompt,
+                    template.agent_id,
+                    template.priority.map(TaskPriority::as_str),
+                    serde_json::to_string(&template.tags).unwrap_or_else(|_| "[]".into()),
+                    template.created_at.to_rfc3339(),
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    pub fn get_task_template(&self, id: &str) -> AppResult<TaskTemplate> {
+        self.with_conn(|conn| {
+            conn.query_row(
+                "SELECT id, name, title, prompt, agent_id, priority, tags, created_at
+                 FROM task_templates WHERE id = ?1",
+                params![id],
+                task_template_from_row,
+            )
+            .optional()?
+            .ok_or_else(|| AppError::not_found("task template", id))
+        })
+    }
+
+    pub fn get_task_templates(&self) -> AppResult<Vec<TaskTemplate>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, name, title, prompt, agent_id, priority, tags, created_at
+                 FROM task_templates ORDER BY name",
+            )?;
+            let rows = stmt.query_map([], task_template_from_row)?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    pub fn delete_task_template(&self, id: &str) -> AppResult<()> {
+        self.with_conn(|conn| {
+            let changed =
+                conn.execute("DELETE FROM task_templates WHERE id = ?1", params![id])?;
+            if changed == 0 {
+                return Err(AppError::not_found("task template", id));
+            }
+            Ok(())
+        })
+    }
+
+    // ---- schedules ----
+
+    pub fn create_schedule(
+        &self,
+        agent_id: &str,
+        title: &str,
+        prompt: &str,
+        cron: &str,
+    ) -> AppResult<Schedule> {
+        let schedule = Schedule {
+            id: uuid::Uuid::new_v4().to_string(),
+            agent_id: agent_id.to_string(),
+            title: title.to_string(),
+            prompt: prompt.to_string(),
+            cron: cron.to_string(),
+            paused: false,
+            last_run_at: None,
+            created_at: Utc::now(),
+        };
+        self.with_conn(|conn| {
+            // The agent must exist; a dangling schedule would fail on
+            // every fire.
+            get_agent_conn(conn, agent_id)?;
+            conn.execute(
+                "INSERT INTO schedules (id, agent_id, title, prompt, cron, paused,
+                                        last_run_at, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, 0, NULL, ?6)",
+                params![
+                    schedule.id,
+                    schedule.agent_id,
+                    schedule.title,
+                    schedule.prompt,
+                    schedule.cron,
+                    schedule.created_at.to_rfc3339(),
+                ],
+            )?;
+            Ok(())
+        })?;
+        Ok(schedule)
+    }
+
+    pub fn get_schedules(&self) -> AppResult<Vec<Schedule>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, agent_id, title, prompt, cron, paused, last_run_at, created_at
+                 FROM schedules ORDER BY created_at",
+            )?;
+            let rows = stmt.query_map([], schedule_from_row)?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    pub fn set_schedule_paused(&self, id: &str, paused: bool) -> AppResult<()> {
+        self.with_conn(|conn| {
+            let changed = conn.execute(
+                "UPDATE schedules SET paused = ?2 WHERE id = ?1",
+                params![id, paused as i64],
+            )?;
+            if changed == 0 {
+                return Err(AppError::not_found("schedule", id));
+            }
+            Ok(())
+        })
+    }
+
+    pub fn delete_schedule(&self, id: &str) -> AppResult<()> {
+        self.with_conn(|conn| {
+            let changed = conn.execute("DELETE FROM schedules WHERE id = ?1", params![id])?;
+            if changed == 0 {
+                return Err(AppError::not_found("schedule", id));
+            }
+            Ok(())
+        })
+    }
+
+    pub fn mark_schedule_run(&self, id: &str, at: DateTime<Utc>) -> AppResult<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "UPDATE schedules SET last_run_at = ?2 WHERE id = ?1",
+                params![id, at.to_rfc3339()],
+            )?;
+            Ok(())
+        })
+    }
+
+    // ---- approvals ----
+
+    /// Append one approval decision to the audit trail.
+    pub fn record_approval(
+        &self,
+        task_id: &str,
+        tool: &str,
+        decision: &str,
+        approver: &str,
+        comment: Option<&str>,
+    ) -> AppResult<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO approvals (task_id, tool, decision, approver, comment, decided_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    task_id,
+                    tool,
+                    decision,
+                    approver,
+                    comment,
+                    Utc::now().to_rfc3339()
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Every approval decision recorded for a task, oldest first, for
+    /// compliance review.
+    pub fn get_task_approvals(&self, task_id: &str) -> AppResult<Vec<Approval>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, task_id, tool, decision, approver, comment, decided_at
+                 FROM approvals WHERE task_id = ?1 ORDER BY id",
+            )?;
+            let rows = stmt.query_map(params![task_id], |row| {
+                Ok(Approval {
+                    id: row.get(0)?,
+                    task_id: row.get(1)?,
+                    tool: row.get(2)?,
+                    decision: row.get(3)?,
+                    approver: row.get(4)?,
+                    comment: row.get(5)?,
+                    decided_at: parse_datetime(row.get(6)?),
+                })
+            })?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    // ---- incidents ----
+
+    pub fn create_incident(&self, incident: &crate::incidents::Incident) -> AppResult<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO incidents (id, cause, started_at, last_seen_at, resolved_at,
+                                        agent_ids, task_count, resolution)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    incident.id,
+                    incident.cause.as_str(),
+                    incident.started_at.to_rfc3339(),
+                    incident.last_seen_at.to_rfc3339(),
+                    incident.resolved_at.map(|t| t.to_rfc3339()),
+                    serde_json::to_string(&incident.agent_ids).unwrap_or_else(|_| "[]".into()),
+                    incident.task_count,
+                    incident.resolution,
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// The unresolved incident with this cause last active at or after
+    /// `since`, if one is open.
+    pub fn find_open_incident(
+        &self,
+        cause: FailureKind,
+        since: DateTime<Utc>,
+    ) -> AppResult<Option<crate::incidents::Incident>> {
+        self.with_conn(|conn| {
+            conn.query_row(
+                &format!(
+                    "SELECT {INCIDENT_COLUMNS} FROM incidents
+                     WHERE resolved_at IS NULL AND cause = ?1 AND last_seen_at >= ?2
+                     ORDER BY last_seen_at DESC LIMIT 1"
+                ),
+                params![cause.as_str(), since.to_rfc3339()],
+                incident_from_row,
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+    }
+
+    /// Fold one more failure into an open incident: bump the activity
+    /// time and counter, and add the agent if it is new to the set.
+    pub fn attach_to_incident(
+        &self,
+        incident_id: &str,
+        agent_id: &str,
+        at: DateTime<Utc>,
+    ) -> AppResult<()> {
+        self.transaction(|tx| {
+            let incident = tx.query_row(
+                &format!("SELECT {INCIDENT_COLUMNS} FROM incidents WHERE id = ?1"),
+                params![incident_id],
+                incident_from_row,
+            )?;
+            let mut agent_ids = incident.agent_ids;
+            if !agent_ids.iter().any(|id| id == agent_id) {
+                agent_ids.push(agent_id.to_string());
+            }
+            tx.execute(
+                "UPDATE incidents SET last_seen_at = ?2, agent_ids = ?3,
+                        task_count = task_count + 1
+                 WHERE id = ?1",
+                params![
+                    incident_id,
+                    at.to_rfc3339(),
+                    serde_json::to_string(&agent_ids).unwrap_or_else(|_| "[]".into()),
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    pub fn list_incidents(&self) -> AppResult<Vec<crate::incidents::Incident>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {INCIDENT_COLUMNS} FROM incidents ORDER BY started_at DESC"
+            ))?;
+            let rows = stmt.query_map([], incident_from_row)?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    /// Close an incident with an operator's resolution note.
+    pub fn resolve_incident(&self, incident_id: &str, resolution: &str) -> AppResult<()> {
+        self.with_conn(|conn| {
+            let changed = conn.execute(
+                "UPDATE incidents SET resolved_at = ?2, resolution = ?3
+                 WHERE id = ?1 AND resolved_at IS NULL",
+                params![incident_id, Utc::now().to_rfc3339(), resolution],
+            )?;
+            if changed == 0 {
+                return Err(AppError::not_found("incident", incident_id));
+            }
+            Ok(())
+        })
+    }
+
+    // ---- archive ----
+
+    /// Ensure the attached archive database has the mirror tables.
+    fn init_archive_schema(conn: &Connection) -> AppResult<()> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS archive.tasks AS
+                 SELECT * FROM main.tasks WHERE 0;
+             CREATE TABLE IF NOT EXISTS archive.task_events AS
+                 SELECT * FROM main.task_events WHERE 0;",
+        )?;
+        Ok(())
+    }
+
+    /// Move terminal tasks last touched before `cutoff`, with their
+    /// events, into the archive database. The hot tables shrink; the
+    /// rows stay queryable through [`Storage::query_archive`]. Returns
+    /// how many tasks moved; a no-op when archiving is disabled.
+    pub fn archive_tasks_before(&self, cutoff: DateTime<Utc>) -> AppResult<usize> {
+        let Some(path) = self.archive_path.lock().unwrap().clone() else {
+            return Ok(0);
+        };
+        self.with_conn(|conn| {
+            conn.execute(
+                "ATTACH DATABASE ?1 AS archive",
+                params![path.to_string_lossy().into_owned()],
+            )?;
+            let moved = (|| -> AppResult<usize> {
+                Self::init_archive_schema(conn)?;
+                conn.execute_batch("BEGIN IMMEDIATE")?;
+                let moved = conn.execute(
+                    "INSERT INTO archive.tasks
+                     SELECT * FROM main.tasks
+                     WHERE status IN ('completed', 'failed', 'cancelled', 'blocked')
+                       AND updated_at < ?1",
+                    params![cutoff.to_rfc3339()],
+                )?;
+                conn.execute(
+                    "INSERT INTO archive.task_events
+                     SELECT e.* FROM main.task_events e
+                     WHERE e.task_id IN (SELECT id FROM archive.tasks)",
+                    [],
+                )?;
+                conn.execute(
+                    "DELETE FROM main.task_events
+                     WHERE task_id IN (SELECT id FROM archive.tasks)",
+                    [],
+                )?;
+                // The remaining per-task rows (config snapshots,
+                // attachments, approvals, costs) are operational detail
+                // that is not worth archiving, but they reference
+                // main.tasks and would fail the delete below if left
+                // behind.
+                for table in [
+                    "task_config_snapshots",
+                    "task_attachments",
+                    "approvals",
+                    "task_costs",
+                ] {
+                    conn.execute(
+                        &format!(
+                            "DELETE FROM main.{table}
+                             WHERE task_id IN (SELECT id FROM archive.tasks)"
+                        ),
+                        [],
+                    )?;
+                }
+                conn.execute(
+                    "DELETE FROM main.tasks WHERE id IN (SELECT id FROM archive.tasks)",
+                    [],
+                )?;
+                conn.execute_batch("COMMIT")?;
+                Ok(moved)
+            })();
+            if moved.is_err() {
+                let _ = conn.execute_batch("ROLLBACK");
+            }
+            let _ = conn.execute("DETACH DATABASE archive", []);
+            moved
+        })
+    }
+
+    /// Occasional lookups against archived tasks, newest first. An
+    /// optional title substring narrows the result.
+    pub fn query_archive(
+        &self,
+        title_contains: Option<&str>,
+        limit: u32,
+    ) -> AppResult<Vec<Task>> {
+        let Some(path) = self.archive_path.lock().unwrap().clone() else {
+            return Ok(Vec::new());
+        };
+        self.with_conn(|conn| {
+            conn.execute(
+                "ATTACH DATABASE ?1 AS archive",
+                params![path.to_string_lossy().into_owned()],
+            )?;
+            let rows = (|| -> AppResult<Vec<Task>> {
+                Self::init_archive_schema(conn)?;
+                let mut stmt = conn.prepare(&format!(
+                    "SELECT {TASK_COLUMNS} FROM archive.tasks
+                     WHERE ?1 IS NULL OR title LIKE '%' || ?1 || '%'
+                     ORDER BY updated_at DESC LIMIT ?2"
+                ))?;
+                let rows = stmt.query_map(params![title_contains, limit], task_from_row)?;
+                rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+            })();
+            let _ = conn.execute("DETACH DATABASE archive", []);
+            rows
+        })
+    }
+
+    /// Test hook: age a task's `updated_at` so archive retention can be
+    /// exercised without waiting.
+    #[doc(hidden)]
+    pub fn backdate_task_for_test(&self, task_id: &str, to: DateTime<Utc>) -> AppResult<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "UPDATE tasks SET updated_at = ?2 WHERE id = ?1",
+                params![task_id, to.to_rfc3339()],
+            )?;
+            Ok(())
+        })
+    }
+
+    // ---- maintenance ----
+
+    /// Delete events older than `cutoff` belonging to finished tasks;
+    /// running/queued tasks keep their full history.
+    pub fn prune_terminal_task_events(
+        &self,
+        cutoff: DateTime<Utc>,
+    ) -> AppResult<usize> {
+        self.with_conn(|conn| {
+            let deleted = conn.execute(
+                "DELETE FROM task_events
+                 WHERE created_at < ?1
+                   AND task_id IN (SELECT id FROM tasks
+                                   WHERE status IN ('completed', 'failed', 'cancelled'))",
+                params![cutoff.to_rfc3339()],
+            )?;
+            Ok(deleted)
+        })
+    }
+
+    /// Compact command metrics older than `cutoff` into daily rollups
+    /// and drop the raw rows. Returns how many raw rows were compacted.
+    pub fn rollup_metrics_before(&self, cutoff: DateTime<Utc>) -> AppResult<usize> {
+        self.transaction(|tx| {
+            tx.execute(
+                "INSERT INTO command_metrics_rollup (day, command, calls, total_duration_ms, errors)
+                 SELECT date(recorded_at), command, COUNT(*), SUM(duration_ms),
+                        SUM(CASE WHEN ok THEN 0 ELSE 1 END)
+                 FROM command_metrics WHERE recorded_at < ?1
+                 GROUP BY date(recorded_at), command
+                 ON CONFLICT(day, command) DO UPDATE SET
+                     calls = calls + excluded.calls,
+                     total_duration_ms = total_duration_ms + excluded.total_duration_ms,
+                     errors = errors + excluded.errors",
+                params![cutoff.to_rfc3339()],
+            )?;
+            let deleted = tx.execute(
+                "DELETE FROM command_metrics WHERE recorded_at < ?1",
+                params![cutoff.to_rfc3339()],
+            )?;
+            Ok(deleted)
+        })
+    }
+
+    pub fn checkpoint_wal(&self) -> AppResult<()> {
+        self.with_conn(|conn| {
+            conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+            Ok(())
+        })
+    }
+
+    pub fn vacuum(&self) -> AppResult<()> {
+        self.with_conn(|conn| {
+            conn.execute_batch("VACUUM")?;
+            Ok(())
+        })
+    }
+
+    /// Current database size from page accounting.
+    pub fn db_size_bytes(&self) -> AppResult<i64> {
+        self.with_conn(|conn| {
+            let pages: i64 = conn.query_row("PRAGMA page_count", [], |row| row.get(0))?;
+            let page_size: i64 = conn.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+            Ok(pages * page_size)
+        })
+    }
+
+    /// Test hook: age a task's events so retention logic can be
+    /// exercised without waiting.
+    #[doc(hidden)]
+    pub fn backdate_task_events_for_test(
+        &self,
+        task_id: &str,
+        to: DateTime<Utc>,
+    ) -> AppResult<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "UPDATE task_events SET created_at = ?2 WHERE task_id = ?1",
+                params![task_id, to.to_rfc3339()],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Test hook: run deletes with foreign key enforcement off, to
+    /// plant the dangling references repair looks for. The pragma
+    /// cannot change inside a transaction, so the statements run bare.
+    #[doc(hidden)]
+    pub fn corrupt_for_test(&self, statements: &[(&str, &str)]) -> AppResult<()> {
+        self.with_conn(|conn| {
+            conn.pragma_update(None, "foreign_keys", "OFF")?;
+            let result = (|| -> AppResult<()> {
+                for (sql, id) in statements {
+                    conn.execute(sql, params![id])?;
+                }
+                Ok(())
+            })();
+            conn.pragma_update(None, "foreign_keys", "ON")?;
+            result
+        })
+    }
+
+    // ---- attachments ----
+
+    /// Register an uploaded attachment's metadata and extracted text.
+    pub fn register_attachment(
+        &self,
+        artifact: &str,
+        filename: &str,
+        extracted_text: &str,
+        truncated: bool,
+    ) -> AppResult<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO attachments
+                     (artifact, filename, extracted_text, truncated, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    artifact,
+                    filename,
+                    extracted_text,
+                    truncated as i64,
+                    Utc::now().to_rfc3339()
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Store the structured plan a plan-mode run submitted for
+    /// approval.
+    pub fn set_task_plan(&self, task_id: &str, steps: &[PlanStep]) -> AppResult<()> {
+        self.with_conn(|conn| {
+            let changed = conn.execute(
+                "UPDATE tasks SET plan = ?2, updated_at = ?3 WHERE id = ?1",
+                params![
+                    task_id,
+                    serde_json::to_string(steps).unwrap_or_else(|_| "[]".into()),
+                    Utc::now().to_rfc3339(),
+                ],
+            )?;
+            if changed == 0 {
+                return Err(AppError::not_found("task", task_id));
+            }
+            Ok(())
+        })
+    }
+
+    pub fn set_task_retry_of(&self, task_id: &str, original: &str) -> AppResult<()> {
+        self.with_conn(|conn| {
+            let changed = conn.execute(
+                "UPDATE tasks SET retry_of = ?2 WHERE id = ?1",
+                params![task_id, original],
+            )?;
+            if changed == 0 {
+                return Err(AppError::not_found("task", task_id));
+            }
+            Ok(())
+        })
+    }
+
+    /// Carry one task's attachment links over to another (used when a
+    /// failed task is retried).
+    pub fn copy_attachments(&self, from_task: &str, to_task: &str) -> AppResult<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT OR IGNORE INTO task_attachments (task_id, artifact)
+                 SELECT ?2, artifact FROM task_attachments WHERE task_id = ?1",
+                params![from_task, to_task],
+            )?;
+            Ok(())
+        })
+    }
+
+    pub fn link_attachment(&self, task_id: &str, artifact: &str) -> AppResult<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT OR IGNORE INTO task_attachments (task_id, artifact) VALUES (?1, ?2)",
+                params![task_id, artifact],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// `(filename, extracted_text, truncated)` for each attachment on a task.
+    pub fn get_task_attachments(&self, task_id: &str) -> AppResult<Vec<(String, String, bool)>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT a.filename, a.extracted_text, a.truncated
+                 FROM task_attachments ta JOIN attachments a ON a.artifact = ta.artifact
+                 WHERE ta.task_id = ?1 ORDER BY a.filename",
+            )?;
+            let rows = stmt.query_map(params![task_id], |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get::<_, i64>(2)? != 0,
+                ))
+            })?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    // ---- costs ----
+
+    pub fn record_cost(
+        &self,
+        agent_id: &str,
+        task_id: Option<&str>,
+        amount_usd: f64,
+    ) -> AppResult<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO task_costs (agent_id, task_id, amount_usd, recorded_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![agent_id, task_id, amount_usd, Utc::now().to_rfc3339()],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Per-task spend for an agent since `since`, summed per task.
+    /// Charges not tied to a task (budget probes) are excluded.
+    pub fn agent_task_costs_since(
+        &self,
+        agent_id: &str,
+        since: &DateTime<Utc>,
+    ) -> AppResult<Vec<(String, f64)>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT task_id, SUM(amount_usd) FROM task_costs
+                 WHERE agent_id = ?1 AND task_id IS NOT NULL AND recorded_at >= ?2
+                 GROUP BY task_id",
+            )?;
+            let rows = stmt.query_map(params![agent_id, since.to_rfc3339()], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    /// Total spend for an agent since the start of the current month.
+    pub fn monthly_cost_for_agent(&self, agent_id: &str) -> AppResult<f64> {
+        let month_start = Utc::now().format("%Y-%m-01T00:00:00+00:00").to_string();
+        self.with_conn(|conn| {
+            conn.query_row(
+                "SELECT COALESCE(SUM(amount_usd), 0.0) FROM task_costs
+                 WHERE agent_id = ?1 AND recorded_at >= ?2",
+                params![agent_id, month_start],
+                |row| row.get(0),
+            )
+            .map_err(Into::into)
+        })
+    }
+
+    pub fn set_agent_model(&self, id: &str, model: &str) -> AppResult<()> {
+        self.with_conn(|conn| {
+            let changed = conn.execute(
+                "UPDATE agents SET model = ?2 WHERE id = ?1",
+                params![id, model],
+            )?;
+            if changed == 0 {
+                return Err(AppError::not_found("agent", id));
+            }
+            Ok(())
+        })
+    }
+
+    // ---- notifications ----
+
+    // ---- integrity repair ----
+
+    /// Non-terminal tasks whose agent row no longer exists. Such tasks
+    /// can never be claimed and would sit in the queue forever.
+    pub fn find_tasks_with_missing_agents(&self) -> AppResult<Vec<String>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id FROM tasks
+                 WHERE agent_id NOT IN (SELECT id FROM agents)
+                   AND status IN ('queued', 'running', 'waiting_for_input')
+                 ORDER BY created_at",
+            )?;
+            let rows = stmt.query_map([], |row| row.get(0))?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    /// Delete events whose task row no longer exists; returns how many
+    /// were removed. The search-index trigger cleans up alongside.
+    pub fn delete_orphan_events(&self) -> AppResult<u32> {
+        self.with_conn(|conn| {
+            let deleted = conn.execute(
+                "DELETE FROM task_events
+                 WHERE task_id NOT IN (SELECT id FROM tasks)",
+                [],
+            )?;
+            Ok(deleted as u32)
+        })
+    }
+
+    /// Agents marked Running with no in-flight task to show for it --
+    /// typically the residue of a crash mid-execution.
+    pub fn find_stuck_running_agents(&self) -> AppResult<Vec<String>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id FROM agents
+                 WHERE status = 'running'
+                   AND id NOT IN (SELECT agent_id FROM tasks
+                                  WHERE status IN ('running', 'waiting_for_input'))
+                 ORDER BY name",
+            )?;
+            let rows = stmt.query_map([], |row| row.get(0))?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    pub fn add_notification(&self, title: &str, body: &str) -> AppResult<i64> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO notifications (title, body, created_at) VALUES (?1, ?2, ?3)",
+                params![title, body, Utc::now().to_rfc3339()],
+            )?;
+            Ok(conn.last_insert_rowid())
+        })
+    }
+
+    // ---- settings ----
+
+    pub fn get_setting(&self, key: &str) -> AppResult<Option<String>> {
+        self.with_conn(|conn| {
+            conn.query_row(
+                "SELECT value FROM settings WHERE key = ?1",
+                params![key],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(Into::into)
+        })
+    }
+
+    pub fn delete_setting(&self, key: &str) -> AppResult<()> {
+        self.with_conn(|conn| {
+            conn.execute("DELETE FROM settings WHERE key = ?1", params![key])?;
+            Ok(())
+        })
+    }
+
+    /// The database's recorded schema version (SQLite `user_version`).
+    pub fn schema_version(&self) -> AppResult<i64> {
+        self.with_conn(|conn| {
+            conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+                .map_err(Into::into)
+        })
+    }
+
+    /// Bring the schema up to [`SCHEMA_VERSION`]: re-applies the
+    /// idempotent baseline DDL, then runs every [`MIGRATIONS`] entry
+    /// past the database's recorded version, stamping `user_version`
+    /// after each step so a failure partway resumes where it stopped.
+    /// Returns the (from, to) versions. Callers are expected to have
+    /// drained running work first; see `maintenance::upgrade_storage`.
+    pub fn upgrade_schema(&self) -> AppResult<(i64, i64)> {
+        let from = self.schema_version()?;
+        self.init_schema()?;
+        self.with_conn(|conn| {
+            for migration in MIGRATIONS.iter().filter(|m| m.version > from) {
+                (migration.apply)(conn)?;
+                conn.execute_batch(&format!("PRAGMA user_version = {}", migration.version))?;
+                tracing::info!(
+                    version = migration.version,
+                    description = migration.description,
+                    "schema migration applied"
+                );
+            }
+            Ok(())
+        })?;
+        Ok((from, SCHEMA_VERSION))
+    }
+
+    pub fn set_setting(&self, key: &str, value: &str) -> AppResult<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO settings (key, value) VALUES (?1, ?2)
+                 ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+                params![key, value],
+            )?;
+            Ok(())
+        })
+    }
+
+    // ---- secrets ----
+
+    /// Store (or replace) a secret value under `name`.
+    pub fn set_secret(&self, name: &str, value: &str) -> AppResult<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO secrets (name, value, updated_at) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(name) DO UPDATE SET value = excluded.value,
+                                                 updated_at = excluded.updated_at",
+                params![name, value, Utc::now().to_rfc3339()],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Fetch a secret value. A registered name with no value yet (e.g.
+    /// right after an import) yields `SecretNotSet`.
+    pub fn get_secret(&self, name: &str) -> AppResult<String> {
+        self.with_conn(|conn| {
+            let value: Option<Option<String>> = conn
+                .query_row(
+                    "SELECT value FROM secrets WHERE name = ?1",
+                    params![name],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            match value {
+                None => Err(AppError::not_found("secret", name)),
+                Some(None) => Err(AppError::SecretNotSet(name.to_string())),
+                Some(Some(v)) => Ok(v),
+            }
+        })
+    }
+
+    /// Fetch a secret on behalf of an agent/task, recording the access
+    /// in the usage audit. Executors use this instead of `get_secret` so
+    /// operators can tell whether a key is safe to rotate.
+    pub fn get_secret_audited(
+        &self,
+        name: &str,
+        agent_id: Option<&str>,
+        task_id: Option<&str>,
+    ) -> AppResult<String> {
+        let value = self.get_secret(name)?;
+        self.record_secret_usage(name, agent_id, task_id)?;
+        Ok(value)
+    }
+
+    pub fn record_secret_usage(
+        &self,
+        name: &str,
+        agent_id: Option<&str>,
+        task_id: Option<&str>,
+    ) -> AppResult<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO secret_usage (secret_name, agent_id, task_id, used_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![name, agent_id, task_id, Utc::now().to_rfc3339()],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Audit trail for one secret name, most recent first.
+    pub fn get_secret_usage(&self, name: &str) -> AppResult<Vec<SecretUsage>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, secret_name, agent_id, task_id, used_at
+                 FROM secret_usage WHERE secret_name = ?1 ORDER BY id DESC",
+            )?;
+            let rows = stmt.query_map(params![name], |row| {
+                Ok(SecretUsage {
+                    id: row.get(0)?,
+                    secret_name: row.get(1)?,
+                    agent_id: row.get(2)?,
+                    task_id: row.get(3)?,
+                    used_at: parse_datetime(row.get(4)?),
+                })
+            })?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    pub fn record_secret_rotation(&self, name: &str, smoke_test: &str) -> AppResult<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO secret_rotations (secret_name, smoke_test, rotated_at)
+                 VALUES (?1, ?2, ?3)",
+                params![name, smoke_test, Utc::now().to_rfc3339()],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// `(smoke_test, rotated_at)` audit entries for one secret name.
+    pub fn get_secret_rotations(&self, name: &str) -> AppResult<Vec<(String, DateTime<Utc>)>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT smoke_test, rotated_at FROM secret_rotations
+                 WHERE secret_name = ?1 ORDER BY id DESC",
+            )?;
+            let rows = stmt.query_map(params![name], |row| {
+                Ok((row.get(0)?, row.get::<_, String>(1)?))
+            })?;
+            rows.map(|r| r.map(|(t, at)| (t, parse_datetime(at))))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(Into::into)
+        })
+    }
+
+    /// Register a secret name without a value, if not already present.
+    pub fn ensure_secret_name(&self, name: &str) -> AppResult<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT OR IGNORE INTO secrets (name, value, updated_at)
+                 VALUES (?1, NULL, ?2)",
+                params![name, Utc::now().to_rfc3339()],
+            )?;
+            Ok(())
+        })
+    }
+
+    pub fn list_secret_names(&self) -> AppResult<Vec<String>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT name FROM secrets ORDER BY name")?;
+            let rows = stmt.query_map([], |row| row.get(0))?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    pub fn list_settings(&self) -> AppResult<Vec<(String, String)>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT key, value FROM settings ORDER BY key")?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    // ---- metrics ----
+
+    /// Raw metric rows `(epoch_seconds, duration_ms, ok)` in `[start, end)`,
+    /// optionally filtered to one command.
+    pub fn query_command_metrics(
+        &self,
+        start: &DateTime<Utc>,
+        end: &DateTime<Utc>,
+        command: Option<&str>,
+    ) -> AppResult<Vec<(i64, i64, bool)>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT strftime('%s', recorded_at), duration_ms, ok FROM command_metrics
+                 WHERE recorded_at >= ?1 AND recorded_at < ?2
+                   AND (?3 IS NULL OR command = ?3)
+                 ORDER BY recorded_at",
+            )?;
+            let rows = stmt.query_map(
+                params![start.to_rfc3339(), end.to_rfc3339(), command],
+                |row| {
+                    let epoch: String = row.get(0)?;
+                    Ok((
+                        epoch.parse::<i64>().unwrap_or(0),
+                        row.get::<_, i64>(1)?,
+                        row.get::<_, i64>(2)? != 0,
+                    ))
+                },
+            )?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    /// Per-command call and error counts since `start`, for telemetry
+    /// aggregation. Only counts -- no parameters or durations.
+    pub fn command_usage_since(
+        &self,
+        start: &DateTime<Utc>,
+    ) -> AppResult<Vec<(String, i64, i64)>> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT command, COUNT(*), SUM(CASE WHEN ok = 0 THEN 1 ELSE 0 END)
+                 FROM command_metrics WHERE recorded_at >= ?1
+                 GROUP BY command ORDER BY command",
+            )?;
+            let rows = stmt.query_map(params![start.to_rfc3339()], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(Into::into)
+        })
+    }
+
+    pub fn record_command_metric(
+        &self,
+        command: &str,
+        duration_ms: i64,
+        ok: bool,
+    ) -> AppResult<()> {
+        self.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO command_metrics (command, duration_ms, ok, recorded_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![command, duration_ms, ok as i64, Utc::now().to_rfc3339()],
+            )?;
+            Ok(())
+        })
+    }
+}
+
+fn get_agent_conn(conn: &Connection, id: &str) -> AppResult<Agent> {
+    conn.query_row(
+        &format!("SELECT {AGENT_COLUMNS} FROM agents WHERE id = ?1 AND deleted_at IS NULL"),
+        params![id],
+        agent_from_row,
+    )
+    .optional()?
+    .ok_or_else(|| AppError::not_found("agent", id))
+}
+
+fn get_task_conn(conn: &Connection, id: &str) -> AppResult<Task> {
+    conn.query_row(
+        &format!("SELECT {TASK_COLUMNS} FROM tasks WHERE id = ?1"),
+        params![id],
+        task_from_row,
+    )
+    .optional()?
+    .ok_or_else(|| AppError::not_found("task", id))
+}
+
+enum SamplingDecision {
+    Keep,
+    KeepSampled(u32),
+    Drop,
+}
+
+/// Apply the per-agent sampling policy for one prospective event write.
+/// Counts every sighting (kept or dropped) so keep-1-in-N stays exact.
+fn sampling_rate_conn(conn: &Connection, task_id: &str, kind: &str) -> AppResult<SamplingDecision> {
+    let policy: SamplingPolicy = match conn
+        .query_row(
+            "SELECT value FROM settings WHERE key = 'event_sampling_policy'",
+            [],
+            |row| row.get::<_, String>(0),
+        )
+        .optional()?
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+    {
+        Some(policy) => policy,
+        None => return Ok(SamplingDecision::Keep),
+    };
+    let agent_id: Option<String> = conn
+        .query_row(
+            "SELECT agent_id FROM tasks WHERE id = ?1",
+            params![task_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let rate = match agent_id.and_then(|agent| policy.rate_for(&agent, kind)) {
+        Some(rate) => rate,
+        None => return Ok(SamplingDecision::Keep),
+    };
+    let seen: i64 = conn.query_row(
+        "INSERT INTO event_sample_counters (task_id, kind, seen) VALUES (?1, ?2, 1)
+         ON CONFLICT(task_id, kind) DO UPDATE SET seen = seen + 1
+         RETURNING seen",
+        params![task_id, kind],
+        |row| row.get(0),
+    )?;
+    if (seen - 1) % i64::from(rate) == 0 {
+        Ok(SamplingDecision::KeepSampled(rate))
+    } else {
+        Ok(SamplingDecision::Drop)
+    }
+}
+
+fn merge_agent_config_conn(conn: &Connection, existing_id: &str, imported: &Agent) -> AppResult<()> {
+    let changed = conn.execute(
+        "UPDATE agents SET model = ?2, default_priority = ?3, framework = ?4,
+                framework_config = ?5, dependencies = ?6, command = ?7,
+                mcp_servers = ?8, endpoint = ?9, fallback_models = ?10,
+                max_retries = ?11, timeout_seconds = ?12, timezone = ?13,
+                review_required = ?14, constitution_opt_out = ?15,
+                system_prompt = ?16, temperature = ?17
+         WHERE id = ?1",
+        params![
+            existing_id,
+            imported.model,
+            imported.default_priority.as_str(),
+            imported.framework,
+            serde_json::to_string(&imported.framework_config)
+                .unwrap_or_else(|_| "null".into()),
+            serde_json::to_string(&imported.dependencies).unwrap_or_else(|_| "[]".into()),
+            imported.command,
+            serde_json::to_string(&imported.mcp_servers).unwrap_or_else(|_| "[]".into()),
+            imported.endpoint,
+            serde_json::to_string(&imported.fallback_models)
+                .unwrap_or_else(|_| "[]".into()),
+            imported.max_retries,
+            imported.timeout_seconds,
+            imported.timezone,
+            imported.review_required as i64,
+            imported.constitution_opt_out as i64,
+            imported.system_prompt,
+            imported.temperature,
+        ],
+    )?;
+    if changed == 0 {
+        return Err(AppError::not_found("agent", existing_id));
+    }
+    append_agent_history_conn(
+        conn,
+        existing_id,
+        "config_merged_from_import",
+        Some(&serde_json::json!({ "imported_agent_id": imported.id })),
+    )
+}
+
+fn create_task_conn(conn: &Connection, task: &Task) -> AppResult<()> {
+    conn.execute(
+        "INSERT INTO tasks (id, agent_id, title, prompt, status, priority, tags,
+                            depends_on, result, error, max_cost_usd, max_retries,
+                            retry_backoff_seconds, run_at, retry_of, started_at,
+                            created_at, updated_at, board_column, board_position,
+                            queue_position, step_mode, failure_kind, plan_mode,
+                            plan, prompt_tokens, completion_tokens)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15,
+                 ?16, ?17, ?18, ?19,
+                 COALESCE((SELECT MAX(board_position) + 1 FROM tasks
+                           WHERE board_column = ?18), 0),
+                 COALESCE((SELECT MAX(queue_position) + 1 FROM tasks), 0),
+                 ?20, ?21, ?22, ?23, ?24, ?25)",
+        params![
+            task.id,
+            task.agent_id,
+            task.title,
+            task.prompt,
+            task.status.as_str(),
+            task.priority.as_str(),
+            serde_json::to_string(&task.tags).unwrap_or_else(|_| "[]".into()),
+            serde_json::to_string(&task.depends_on).unwrap_or_else(|_| "[]".into()),
+            task.result,
+            task.error,
+            task.max_cost_usd,
+            task.max_retries,
+            task.retry_backoff_seconds,
+            task.run_at.map(|t| t.to_rfc3339()),
+            task.retry_of,
+            task.started_at.map(|t| t.to_rfc3339()),
+            task.created_at.to_rfc3339(),
+            task.updated_at.to_rfc3339(),
+            task.board_column,
+            task.step_mode as i64,
+            task.failure_kind.map(FailureKind::as_str),
+            task.plan_mode as i64,
+            task.plan
+                .as_ref()
+                .map(|plan| serde_json::to_string(plan).unwrap_or_else(|_| "[]".into())),
+            task.prompt_tokens,
+            task.completion_tokens,
+        ],
+    )?;
+    Ok(())
+}
+
+fn append_event_conn(
+    conn: &Connection,
+    task_id: &str,
+    kind: &str,
+    payload: Option<&serde_json::Value>,
+) -> AppResult<i64> {
+    conn.execute(
+        "INSERT INTO task_events (task_id, kind, payload, severity, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            task_id,
+            kind,
+            payload.map(|p| p.to_string()),
+            EventSeverity::for_kind(kind).as_str(),
+            Utc::now().to_rfc3339(),
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// The minimum severity stored for a task's agent, from the
+/// `events.min_severity.<agent_id>` setting; Debug (store everything)
+/// when unset.
+fn min_severity_conn(conn: &Connection, task_id: &str) -> AppResult<EventSeverity> {
+    let value: Option<String> = conn
+        .query_row(
+            "SELECT value FROM settings
+             WHERE key = ?1 || (SELECT agent_id FROM tasks WHERE id = ?2)",
+            params![MIN_SEVERITY_SETTING_PREFIX, task_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(value
+        .and_then(|raw| EventSeverity::parse(&raw))
+        .unwrap_or(EventSeverity::Debug))
+}
+
+fn append_agent_history_conn(
+    conn: &Connection,
+    agent_id: &str,
+    kind: &str,
+    detail: Option<&serde_json::Value>,
+) -> AppResult<()> {
+    conn.execute(
+        "INSERT INTO agent_history (agent_id, kind, detail, created_at)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![
+            agent_id,
+            kind,
+            detail.map(|d| d.to_string()),
+            Utc::now().to_rfc3339(),
+        ],
+    )?;
+    Ok(())
+}
+
+/// Whole seconds between a task's start and `now`, clamped to zero so
+/// clock skew can never subtract runtime.
+fn elapsed_seconds(started_at: Option<DateTime<Utc>>, now: DateTime<Utc>) -> i64 {
+    started_at
+        .map(|start| (now - start).num_seconds().max(0))
+        .unwrap_or(0)
+}
+
+fn parse_datetime(s: String) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(&s)
+        .map(|dt| dt.with_timezone(&Utc))
+        .unwrap_or_else(|_| Utc::now())
+}
+
+fn agent_from_row(row: &Row<'_>) -> rusqlite::Result<Agent> {
+    Ok(Agent {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        model: row.get(2)?,
+        status: AgentStatus::parse(&row.get::<_, String>(3)?).unwrap_or(AgentStatus::Idle),
+        default_priority: TaskPriority::parse(&row.get::<_, String>(4)?)
+            .unwrap_or_default(),
+        color: row.get(5)?,
+        avatar_path: row.get(6)?,
+        framework: row.get(7)?,
+        framework_config: serde_json::from_str(&row.get::<_, String>(8)?)
+            .unwrap_or(serde_json::Value::Null),
+        dependencies: serde_json::from_str(&row.get::<_, String>(9)?).unwrap_or_default(),
+        command: row.get(10)?,
+        mcp_servers: serde_json::from_str(&row.get::<_, String>(11)?).unwrap_or_default(),
+        endpoint: row.get(12)?,
+        fallback_models: serde_json::from_str(&row.get::<_, String>(13)?).unwrap_or_default(),
+        max_retries: row.get(14)?,
+        timeout_seconds: row.get(15)?,
+        timezone: row.get(16)?,
+        review_required: row.get::<_, i64>(17)? != 0,
+        constitution_opt_out: row.get::<_, i64>(18)? != 0,
+        system_prompt: row.get(19)?,
+        temperature: row.get(20)?,
+        runtime_seconds: row.get(21)?,
+        created_at: parse_datetime(row.get(22)?),
+    })
+}
+
+fn incident_from_row(row: &Row<'_>) -> rusqlite::Result<crate::incidents::Incident> {
+    Ok(crate::incidents::Incident {
+        id: row.get(0)?,
+        cause: FailureKind::parse(&row.get::<_, String>(1)?).unwrap_or(FailureKind::Provider),
+        started_at: parse_datetime(row.get(2)?),
+        last_seen_at: parse_datetime(row.get(3)?),
+        resolved_at: row.get::<_, Option<String>>(4)?.map(parse_datetime),
+        agent_ids: serde_json::from_str(&row.get::<_, String>(5)?).unwrap_or_default(),
+        task_count: row.get(6)?,
+        resolution: row.get(7)?,
+    })
+}
+
+fn api_token_from_row(row: &Row<'_>) -> rusqlite::Result<crate::api_tokens::ApiToken> {
+    Ok(crate::api_tokens::ApiToken {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        scopes: serde_json::from_str(&row.get::<_, String>(2)?).unwrap_or_default(),
+        created_at: parse_datetime(row.get(3)?),
+        revoked_at: row.get::<_, Option<String>>(4)?.map(parse_datetime),
+    })
+}
+
+fn task_template_from_row(row: &Row<'_>) -> rusqlite::Result<TaskTemplate> {
+    Ok(TaskTemplate {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        title: row.get(2)?,
+        prompt: row.get(3)?,
+        agent_id: row.get(4)?,
+        priority: row
+            .get::<_, Option<String>>(5)?
+            .and_then(|raw| TaskPriority::parse(&raw)),
+        tags: serde_json::from_str(&row.get::<_, String>(6)?).unwrap_or_default(),
+        created_at: parse_datetime(row.get(7)?),
+    })
+}
+
+fn schedule_from_row(row: &Row<'_>) -> rusqlite::Result<Schedule> {
+    Ok(Schedule {
+        id: row.get(0)?,
+        agent_id: row.get(1)?,
+        title: row.get(2)?,
+        prompt: row.get(3)?,
+        cron: row.get(4)?,
+        paused: row.get::<_, i64>(5)? != 0,
+        last_run_at: row.get::<_, Option<String>>(6)?.map(parse_datetime),
+        created_at: parse_datetime(row.get(7)?),
+    })
+}
+
+fn create_agent_conn(conn: &Connection, agent: &Agent) -> AppResult<()> {
+    conn.execute(
+        "INSERT INTO agents (id, name, model, status, default_priority, color,
+                             avatar_path, framework, framework_config, dependencies,
+                             command, mcp_servers, endpoint, fallback_models,
+                             max_retries, timeout_seconds, timezone, review_required,
+                             constitution_opt_out, system_prompt, temperature,
+                             runtime_seconds, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15,
+                 ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23)",
+        params![
+            agent.id,
+            agent.name,
+            agent.model,
+            agent.status.as_str(),
+            agent.default_priority.as_str(),
+            agent.color,
+            agent.avatar_path,
+            agent.framework,
+            serde_json::to_string(&agent.framework_config).unwrap_or_else(|_| "null".into()),
+            serde_json::to_string(&agent.dependencies).unwrap_or_else(|_| "[]".into()),
+            agent.command,
+            serde_json::to_string(&agent.mcp_servers).unwrap_or_else(|_| "[]".into()),
+            agent.endpoint,
+            serde_json::to_string(&agent.fallback_models).unwrap_or_else(|_| "[]".into()),
+            agent.max_retries,
+            agent.timeout_seconds,
+            agent.timezone,
+            agent.review_required as i64,
+            agent.constitution_opt_out as i64,
+            agent.system_prompt,
+            agent.temperature,
+            agent.runtime_seconds,
+            agent.created_at.to_rfc3339(),
+        ],
+    )?;
+    append_agent_history_conn(conn, &agent.id, "created", None)?;
+    Ok(())
+}
+
+fn task_from_row(row: &Row<'_>) -> rusqlite::Result<Task> {
+    let tags: String = row.get(6)?;
+    Ok(Task {
+        id: row.get(0)?,
+        agent_id: row.get(1)?,
+        title: row.get(2)?,
+        prompt: row.get(3)?,
+        status: TaskStatus::parse(&row.get::<_, String>(4)?).unwrap_or(TaskStatus::Queued),
+        priority: TaskPriority::parse(&row.get::<_, String>(5)?).unwrap_or_default(),
+        tags: serde_json::from_str(&tags).unwrap_or_default(),
+        depends_on: serde_json::from_str(&row.get::<_, String>(7)?).unwrap_or_default(),
+        result: row.get(8)?,
+        error: row.get(9)?,
+        result_artifact: row.get(10)?,
+        max_cost_usd: row.get(11)?,
+        max_retries: row.get(12)?,
+        retry_backoff_seconds: row.get(13)?,
+        run_at: row.get::<_, Option<String>>(14)?.map(parse_datetime),
+        retry_of: row.get(15)?,
+        started_at: row.get::<_, Option<String>>(16)?.map(parse_datetime),
+        created_at: parse_datetime(row.get(17)?),
+        updated_at: parse_datetime(row.get(18)?),
+        board_column: row.get(19)?,
+        board_position: row.get(20)?,
+        queue_position: row.get(21)?,
+        step_mode: row.get::<_, i64>(22)? != 0,
+        failure_kind: row
+            .get::<_, Option<String>>(23)?
+            .as_deref()
+            .and_then(FailureKind::parse),
+        plan_mode: row.get::<_, i64>(24)? != 0,
+        plan: row
+            .get::<_, Option<String>>(25)?
+            .as_deref()
+            .and_then(|raw| serde_json::from_str(raw).ok()),
+        prompt_tokens: row.get(26)?,
+        completion_tokens: row.get(27)?,
+        total_tokens: row.get::<_, i64>(26)? + row.get::<_, i64>(27)?,
+    })
+}
+
+fn mcp_tool_from_row(row: &Row<'_>) -> rusqlite::Result<crate::mcp::McpTool> {
+    Ok(crate::mcp::McpTool {
+        server: row.get(0)?,
+        name: row.get(1)?,
+        description: row.get(2)?,
+        input_schema: serde_json::from_str(&row.get::<_, String>(3)?)
+            .unwrap_or(serde_json::Value::Null),
+    })
+}
+
+fn event_from_row(row: &Row<'_>) -> rusqlite::Result<TaskEvent> {
+    let kind: String = row.get(2)?;
+    let payload: Option<serde_json::Value> = row
+        .get::<_, Option<String>>(3)?
+        .and_then(|p| serde_json::from_str(&p).ok());
+    Ok(TaskEvent {
+        id: row.get(0)?,
+        task_id: row.get(1)?,
+        typed: crate::models::EventPayload::from_event(&kind, payload.as_ref()),
+        kind,
+        payload,
+        severity: row
+            .get::<_, String>(4)
+            .ok()
+            .and_then(|raw| EventSeverity::parse(&raw))
+            .unwrap_or_default(),
+        created_at: parse_datetime(row.get(5)?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task_dispatch::{self, DispatchRequest};
+
+    fn storage_with_tasks(n: usize) -> (Storage, Vec<String>) {
+        let storage = Storage::open_in_memory().unwrap();
+        let agent = Agent::new("board", "mock");
+        storage.create_agent(&agent).unwrap();
+        let ids = (0..n)
+            .map(|i| {
+                task_dispatch::dispatch(
+                    &storage,
+                    &DispatchRequest::new(&agent.id, format!("t{i}"), "p"),
+                )
+                .unwrap()
+                .id
+            })
+            .collect();
+        (storage, ids)
+    }
+
+    #[test]
+    fn trashed_agents_disappear_from_the_roster_but_survive_until_purged() {
+        let (storage, ids) = storage_with_tasks(1);
+        let agent_id = storage.get_task(&ids[0]).unwrap().agent_id;
+
+        storage.soft_delete_agent(&agent_id).unwrap();
+        assert!(storage.get_agent(&agent_id).is_err());
+        assert!(storage.get_all_agents().unwrap().is_empty());
+        // The history is untouched: the task and its events are still there.
+        assert!(storage.get_task(&ids[0]).is_ok());
+
+        let trash = storage.list_deleted_agents().unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].0.id, agent_id);
+
+        storage.restore_agent(&agent_id).unwrap();
+        assert!(storage.get_agent(&agent_id).is_ok());
+        assert!(storage.list_deleted_agents().unwrap().is_empty());
+
+        // Purge refuses live agents, then removes everything once trashed.
+        assert!(storage.purge_agent(&agent_id).is_err());
+        storage.soft_delete_agent(&agent_id).unwrap();
+        storage.purge_agent(&agent_id).unwrap();
+        assert!(storage.get_task(&ids[0]).is_err());
+        assert!(storage.list_deleted_agents().unwrap().is_empty());
+    }
+
+    #[test]
+    fn paged_listings_carry_totals_and_respect_offsets() {
+        let (storage, _ids) = storage_with_tasks(5);
+
+        let (tasks, total) = storage.get_tasks_page(2, 0).unwrap();
+        assert_eq!(total, 5);
+        assert_eq!(tasks.len(), 2);
+        let (rest, total) = storage.get_tasks_page(10, 4).unwrap();
+        assert_eq!(total, 5);
+        assert_eq!(rest.len(), 1);
+
+        let (agents, total) = storage.get_agents_page(10, 0).unwrap();
+        assert_eq!(total, 1);
+        assert_eq!(agents.len(), 1);
+        let (agents, _) = storage.get_agents_page(10, 1).unwrap();
+        assert!(agents.is_empty());
+    }
+
+    #[test]
+    fn full_text_search_finds_phrases_and_filenames_in_payloads() {
+        let (storage, ids) = storage_with_tasks(1);
+        storage
+            .append_event(
+                &ids[0],
+                "error",
+                Some(&serde_json::json!({ "message": "provider hit a rate limit" })),
+            )
+            .unwrap();
+        storage
+            .append_event(
+                &ids[0],
+                "output",
+                Some(&serde_json::json!({ "text": "wrote openapi.yaml to disk" })),
+            )
+            .unwrap();
+
+        let hits = storage.search_events("rate limit", 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].kind, "error");
+        let hits = storage.search_events("openapi.yaml", 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].kind, "output");
+        assert!(storage.search_events("unrelated", 10).unwrap().is_empty());
+        assert!(storage.search_events("", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn event_queries_filter_by_kind_task_agent_and_window() {
+        let (storage, ids) = storage_with_tasks(2);
+        storage.append_event(&ids[0], "warning", None).unwrap();
+        storage.append_event(&ids[1], "warning", None).unwrap();
+        storage.append_event(&ids[1], "output", None).unwrap();
+
+        let warnings = storage
+            .query_events(Some("warning"), None, None, None, None, 100)
+            .unwrap();
+        assert_eq!(warnings.len(), 2);
+        let scoped = storage
+            .query_events(Some("warning"), Some(&ids[0]), None, None, None, 100)
+            .unwrap();
+        assert_eq!(scoped.len(), 1);
+        assert_eq!(scoped[0].task_id, ids[0]);
+
+        let agent_id = storage.get_task(&ids[0]).unwrap().agent_id;
+        let by_agent = storage
+            .query_events(None, None, Some(&agent_id), None, None, 100)
+            .unwrap();
+        assert!(!by_agent.is_empty());
+
+        // A window ending in the past matches nothing.
+        let stale = storage
+            .query_events(None, None, None, None, Some(Utc::now() - chrono::Duration::hours(1)), 100)
+            .unwrap();
+        assert!(stale.is_empty());
+    }
+
+    #[test]
+    fn migrations_bring_old_databases_forward_idempotently() {
+        let (storage, ids) = storage_with_tasks(1);
+        assert_eq!(storage.schema_version().unwrap(), 0);
+
+        // Simulate a database from before the migration chain existed:
+        // drop a column the chain is responsible for adding.
+        storage
+            .with_conn(|conn| {
+                conn.execute_batch("ALTER TABLE task_events DROP COLUMN severity")?;
+                Ok(())
+            })
+            .unwrap();
+
+        let (from, to) = storage.upgrade_schema().unwrap();
+        assert_eq!((from, to), (0, SCHEMA_VERSION));
+        // The dropped column is back and usable.
+        assert!(storage.append_event(&ids[0], "warning", None).unwrap() > 0);
+
+        // Running again from the latest version is a no-op.
+        let (from, to) = storage.upgrade_schema().unwrap();
+        assert_eq!((from, to), (SCHEMA_VERSION, SCHEMA_VERSION));
+    }
+
+    #[test]
+    fn min_severity_setting_filters_low_value_chatter_per_agent() {
+        let (storage, ids) = storage_with_tasks(1);
+        // Default: everything is stored, with its kind-derived severity.
+        assert!(storage.append_event(&ids[0], "thought_log", None).unwrap() > 0);
+
+        let agent_id = storage.get_task(&ids[0]).unwrap().agent_id;
+        storage
+            .set_setting(&format!("{MIN_SEVERITY_SETTING_PREFIX}{agent_id}"), "info")
+            .unwrap();
+        assert_eq!(storage.append_event(&ids[0], "thought_log", None).unwrap(), 0);
+        assert!(storage.append_event(&ids[0], "warning", None).unwrap() > 0);
+
+        let events = storage.get_task_events(&ids[0]).unwrap();
+        assert_eq!(
+            events.iter().filter(|e| e.kind == "thought_log").count(),
+            1
+        );
+        let warning = events.iter().find(|e| e.kind == "warning").unwrap();
+        assert_eq!(warning.severity, crate::models::EventSeverity::Warn);
+    }
+
+    #[test]
+    fn events_come_back_with_typed_payload_views() {
+        use crate::models::EventPayload;
+
+        let (storage, ids) = storage_with_tasks(1);
+        storage
+            .append_event(
+                &ids[0],
+                "usage",
+                Some(&serde_json::json!({ "prompt_tokens": 10, "completion_tokens": 4 })),
+            )
+            .unwrap();
+        storage
+            .append_event(&ids[0], "custom_kind", Some(&serde_json::json!({ "x": 1 })))
+            .unwrap();
+
+        let events = storage.get_task_events(&ids[0]).unwrap();
+        let usage = events.iter().find(|e| e.kind == "usage").unwrap();
+        assert_eq!(
+            usage.typed,
+            EventPayload::Usage {
+                prompt_tokens: 10,
+                completion_tokens: 4,
+                estimated_cost_cents: 0.0,
+            }
+        );
+        let custom = events.iter().find(|e| e.kind == "custom_kind").unwrap();
+        assert_eq!(
+            custom.typed,
+            EventPayload::Other { raw: Some(serde_json::json!({ "x": 1 })) }
+        );
+    }
+
+    #[test]
+    fn event_pages_scroll_backwards_from_a_cursor() {
+        let (storage, ids) = storage_with_tasks(1);
+        for i in 0..7 {
+            storage
+                .append_event(&ids[0], &format!("kind_{i}"), None)
+                .unwrap();
+        }
+
+        let first = storage.get_task_events_page(&ids[0], None, None, 3).unwrap();
+        assert_eq!(first.len(), 3);
+        assert!(first.windows(2).all(|w| w[0].id > w[1].id));
+
+        let cursor = first.last().unwrap().id;
+        let second = storage
+            .get_task_events_page(&ids[0], Some(cursor), None, 100)
+            .unwrap();
+        assert!(second.iter().all(|e| e.id < cursor));
+        // Timestamp cursors bound the page the same way.
+        let recent = storage
+            .get_task_events_page(&ids[0], None, Some(Utc::now()), 100)
+            .unwrap();
+        assert_eq!(recent.len(), first.len() + second.len());
+    }
+
+    #[test]
+    fn sampling_policy_thins_verbose_kinds_but_keeps_errors() {
+        let storage = Storage::open_in_memory().unwrap();
+        let agent = Agent::new("chatty", "mock");
+        storage.create_agent(&agent).unwrap();
+        let task = task_dispatch::dispatch(
+            &storage,
+            &DispatchRequest::new(&agent.id, "t", "p"),
+        )
+        .unwrap();
+
+        let mut policy = crate::policy::SamplingPolicy::default();
+        policy
+            .rules
+            .entry(agent.id.clone())
+            .or_default()
+            .insert("thought_log".into(), 10);
+        policy.save(&storage).unwrap();
+
+        for _ in 0..20 {
+            storage.append_event(&task.id, "thought_log", None).unwrap();
+        }
+        for _ in 0..3 {
+            storage.append_event(&task.id, "tool_error", None).unwrap();
+        }
+
+        let events = storage.get_task_events(&task.id).unwrap();
+        let thoughts: Vec<_> = events.iter().filter(|e| e.kind == "thought_log").collect();
+        assert_eq!(thoughts.len(), 2);
+        // Kept events record the rate so analytics can extrapolate.
+        assert_eq!(thoughts[0].payload.as_ref().unwrap()["sample_rate"], 10);
+        assert_eq!(events.iter().filter(|e| e.kind == "tool_error").count(), 3);
+    }
+
+    fn column_order(storage: &Storage, column: &str) -> Vec<String> {
+        let mut tasks: Vec<_> = storage
+            .get_board()
+            .unwrap()
+            .into_iter()
+            .filter(|t| t.board_column == column)
+            .collect();
+        tasks.sort_by_key(|t| t.board_position);
+        tasks.into_iter().map(|t| t.id).collect()
+    }
+
+    #[test]
+    fn runtime_accrues_from_running_transitions() {
+        use chrono::Duration;
+        let now = Utc::now();
+        assert_eq!(elapsed_seconds(Some(now - Duration::seconds(42)), now), 42);
+        // Missing start or clock skew never subtracts runtime.
+        assert_eq!(elapsed_seconds(None, now), 0);
+        assert_eq!(elapsed_seconds(Some(now + Duration::seconds(5)), now), 0);
+
+        let (storage, ids) = storage_with_tasks(1);
+        let task = storage.claim_task(&ids[0]).unwrap();
+        assert!(task.started_at.is_some());
+        storage
+            .finish_task(&ids[0], TaskStatus::Completed, None, None, None)
+            .unwrap();
+        let agent = storage.get_agent(&task.agent_id).unwrap();
+        assert!(agent.runtime_seconds >= 0);
+    }
+
+    #[test]
+    fn secret_access_is_audited_without_values() {
+        let (storage, ids) = storage_with_tasks(1);
+        let task = storage.get_task(&ids[0]).unwrap();
+        storage.set_secret("api_key", "sk-value").unwrap();
+
+        let value = storage
+            .get_secret_audited("api_key", Some(&task.agent_id), Some(&task.id))
+            .unwrap();
+        assert_eq!(value, "sk-value");
+
+        let usage = storage.get_secret_usage("api_key").unwrap();
+        assert_eq!(usage.len(), 1);
+        assert_eq!(usage[0].agent_id.as_deref(), Some(task.agent_id.as_str()));
+        assert_eq!(usage[0].task_id.as_deref(), Some(task.id.as_str()));
+        // A failed read (unknown name) is not recorded as usage.
+        assert!(storage.get_secret_audited("missing", None, None).is_err());
+        assert!(storage.get_secret_usage("missing").unwrap().is_empty());
+    }
+
+    #[test]
+    fn new_tasks_append_to_backlog_in_order() {
+        let (storage, ids) = storage_with_tasks(3);
+        assert_eq!(column_order(&storage, "backlog"), ids);
+    }
+
+    #[test]
+    fn move_task_reorders_within_and_across_columns() {
+        let (storage, ids) = storage_with_tasks(3);
+
+        // Move the last task to the front of the backlog.
+        storage.move_task(&ids[2], "backlog", 0).unwrap();
+        assert_eq!(
+            column_order(&storage, "backlog"),
+            vec![ids[2].clone(), ids[0].clone(), ids[1].clone()]
+        );
+
+        // Move one across columns; both columns stay dense.
+        storage.move_task(&ids[0], "in_progress", 0).unwrap();
+        assert_eq!(
+            column_order(&storage, "backlog"),
+            vec![ids[2].clone(), ids[1].clone()]
+        );
+        assert_eq!(column_order(&storage, "in_progress"), vec![ids[0].clone()]);
+
+        // Out-of-range positions clamp to the end of the column.
+        storage.move_task(&ids[0], "backlog", 99).unwrap();
+        assert_eq!(
+            column_order(&storage, "backlog"),
+            vec![ids[2].clone(), ids[1].clone(), ids[0].clone()]
+        );
+    }
+}
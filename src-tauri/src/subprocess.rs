@@ -0,0 +1,166 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+use serde_json::json;
+
+use crate::error::{AppError, AppResult};
+use crate::models::{Agent, Task};
+use crate::storage::Storage;
+
+/// Run a CLI-framework agent: spawn its configured command, feed the
+/// instruction over stdin and stream stdout/stderr lines into the
+/// task's event stream as `thought_log`/`progress` events. The exit
+/// code decides Completed vs Failed via the returned result.
+pub fn run(storage: &Storage, task: &Task, agent: &Agent, prompt: &str) -> AppResult<String> {
+    let command_line = agent.command.as_deref().ok_or_else(|| {
+        AppError::InvalidArgument(format!(
+            "agent {} has framework \"subprocess\" but no command configured",
+            agent.id
+        ))
+    })?;
+    let mut parts = command_line.split_whitespace();
+    let program = parts.next().ok_or_else(|| {
+        AppError::InvalidArgument("subprocess command is empty".into())
+    })?;
+
+    let mut child = Command::new(program)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| AppError::Provider(format!("subprocess ({program}): {err}")))?;
+
+    // A watchdog kills the child once the agent's execution timeout
+    // elapses; the stdout loop below then unblocks on the closed pipe.
+    let watchdog = agent.timeout_seconds.map(|limit| {
+        let (done_tx, done_rx) = mpsc::channel::<()>();
+        let timed_out = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&timed_out);
+        #[cfg_attr(not(unix), allow(unused_variables))]
+        let pid = child.id();
+        std::thread::spawn(move || {
+            if done_rx.recv_timeout(Duration::from_secs(limit)).is_err() {
+                flag.store(true, Ordering::SeqCst);
+                #[cfg(unix)]
+                unsafe {
+                    libc::kill(pid as i32, libc::SIGKILL);
+                }
+            }
+        });
+        (done_tx, timed_out)
+    });
+
+    // Write the instruction and close stdin so line-oriented tools exit.
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(prompt.as_bytes())?;
+    }
+
+    // Stderr is drained on a thread so a chatty tool cannot deadlock on
+    // full pipes; its lines land as progress events afterwards.
+    let stderr = child.stderr.take().expect("stderr piped");
+    let stderr_lines = std::thread::spawn(move || {
+        BufReader::new(stderr)
+            .lines()
+            .map_while(Result::ok)
+            .collect::<Vec<_>>()
+    });
+
+    let mut output = String::new();
+    let stdout = child.stdout.take().expect("stdout piped");
+    for line in BufReader::new(stdout).lines() {
+        let line = line?;
+        storage.append_event(&task.id, "thought_log", Some(&json!({ "message": line })))?;
+        output.push_str(&line);
+        output.push('\n');
+    }
+    for line in stderr_lines.join().unwrap_or_default() {
+        storage.append_event(&task.id, "progress", Some(&json!({ "message": line })))?;
+    }
+
+    let status = child
+        .wait()
+        .map_err(|err| AppError::Provider(format!("subprocess ({program}): {err}")))?;
+    if let Some((done_tx, timed_out)) = watchdog {
+        let _ = done_tx.send(());
+        if timed_out.load(Ordering::SeqCst) {
+            return Err(AppError::Timeout {
+                task_id: task.id.clone(),
+                seconds: agent.timeout_seconds.unwrap_or_default(),
+            });
+        }
+    }
+    if status.success() {
+        Ok(output.trim_end().to_string())
+    } else {
+        Err(AppError::Provider(format!(
+            "subprocess ({program}) exited with {status}"
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task_dispatch::{self, DispatchRequest};
+
+    fn subprocess_agent(command: &str) -> (Storage, Task) {
+        let storage = Storage::open_in_memory().unwrap();
+        let mut agent = Agent::new("cli", "mock");
+        agent.framework = Some("subprocess".into());
+        agent.command = Some(command.into());
+        storage.create_agent(&agent).unwrap();
+        let task = task_dispatch::dispatch(
+            &storage,
+            &DispatchRequest::new(&agent.id, "echo", "hello subprocess"),
+        )
+        .unwrap();
+        (storage, task)
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn stdout_streams_as_thought_log_and_maps_exit_zero_to_completed() {
+        let (storage, task) = subprocess_agent("cat");
+        let done = task_dispatch::execute(&storage, &task.id).unwrap();
+        assert_eq!(done.status, crate::models::TaskStatus::Completed);
+        assert_eq!(done.result.as_deref(), Some("hello subprocess"));
+        let events = storage.get_task_events(&task.id).unwrap();
+        assert!(events.iter().any(|e| e.kind == "thought_log"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn nonzero_exit_fails_the_task() {
+        let (storage, task) = subprocess_agent("false");
+        let done = task_dispatch::execute(&storage, &task.id).unwrap();
+        assert_eq!(done.status, crate::models::TaskStatus::Failed);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn timeout_kills_a_hung_subprocess_and_fails_the_task() {
+        let storage = Storage::open_in_memory().unwrap();
+        let mut agent = Agent::new("cli", "mock");
+        agent.framework = Some("subprocess".into());
+        agent.command = Some("sleep 30".into());
+        agent.timeout_seconds = Some(1);
+        storage.create_agent(&agent).unwrap();
+        let task = task_dispatch::dispatch(
+            &storage,
+            &DispatchRequest::new(&agent.id, "hang", "never answered"),
+        )
+        .unwrap();
+
+        let done = task_dispatch::execute(&storage, &task.id).unwrap();
+        assert_eq!(done.status, crate::models::TaskStatus::Failed);
+        assert!(done.error.unwrap().contains("timed out"));
+        let agent = storage.get_agent(&agent.id).unwrap();
+        assert_eq!(agent.status, crate::models::AgentStatus::Idle);
+        let events = storage.get_task_events(&task.id).unwrap();
+        assert!(events.iter().any(|e| e.kind == "warning"));
+    }
+}
@@ -0,0 +1,1726 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::error::{AppError, AppResult};
+use crate::health;
+use crate::models::{Agent, AgentStatus, FailureKind, PlanStep, Task, TaskPriority, TaskStatus};
+use crate::policy::{
+    ApprovalPolicy, BudgetPolicy, DispatchConfirmationPolicy, EscalationPolicy, PriorityPolicy,
+};
+use crate::providers::{self, CompletionRequest};
+use crate::templates;
+use crate::storage::Storage;
+
+/// Parameters for dispatching a task to an agent.
+#[derive(Debug, Clone, Deserialize)]
+pub struct DispatchRequest {
+    pub agent_id: String,
+    pub title: String,
+    pub prompt: String,
+    /// Explicit priority; falls back to the agent's default when absent.
+    #[serde(default)]
+    pub priority: Option<TaskPriority>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Abort the run once accumulated cost crosses this ceiling.
+    #[serde(default)]
+    pub max_cost_usd: Option<f64>,
+    /// Override the agent's default automatic retry count.
+    #[serde(default)]
+    pub max_retries: Option<u32>,
+    /// Base delay for exponential retry backoff, in seconds.
+    #[serde(default)]
+    pub retry_backoff_seconds: Option<f64>,
+    /// Artifact names of previously uploaded attachments to include in
+    /// the executor prompt.
+    #[serde(default)]
+    pub attachments: Vec<String>,
+    /// Tasks that must complete before this one runs; failed parents
+    /// cascade a Blocked status instead.
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    /// Earliest start time; omit to allow immediate execution.
+    #[serde(default)]
+    pub run_at: Option<DateTime<Utc>>,
+    /// Pause after every thought/tool call and wait for `continue_task`.
+    #[serde(default)]
+    pub step_mode: bool,
+    /// Submit a structured plan first and wait for `approve_plan`
+    /// before executing it.
+    #[serde(default)]
+    pub plan_mode: bool,
+    /// Acknowledges a `ConfirmationRequired` rejection; set by the
+    /// frontend after the user confirms the flagged dispatch.
+    #[serde(default)]
+    pub confirmed: bool,
+}
+
+impl DispatchRequest {
+    pub fn new(
+        agent_id: impl Into<String>,
+        title: impl Into<String>,
+        prompt: impl Into<String>,
+    ) -> Self {
+        Self {
+            agent_id: agent_id.into(),
+            title: title.into(),
+            prompt: prompt.into(),
+            priority: None,
+            tags: Vec::new(),
+            max_cost_usd: None,
+            max_retries: None,
+            retry_backoff_seconds: None,
+            attachments: Vec::new(),
+            depends_on: Vec::new(),
+            run_at: None,
+            step_mode: false,
+            plan_mode: false,
+            confirmed: false,
+        }
+    }
+}
+
+/// Create a new queued task for an agent.
+///
+/// The effective priority is the requested one (or the agent's default),
+/// clamped by the workspace [`PriorityPolicy`] for the task's tags. The
+/// resolution is recorded as a `priority_resolved` event so the decision
+/// stays traceable.
+pub fn dispatch(storage: &Storage, request: &DispatchRequest) -> AppResult<Task> {
+    // Save-time template validation: referenced settings/secrets must
+    // exist before the task is accepted.
+    templates::validate(storage, &request.prompt)?;
+    // Dependencies must exist up front; dangling ids would hold the
+    // task forever.
+    for dep in &request.depends_on {
+        storage.get_task(dep)?;
+    }
+    let agent = apply_budget_policy(storage, storage.get_agent(&request.agent_id)?)?;
+
+    let requested = request.priority.unwrap_or(agent.default_priority);
+    let cap = PriorityPolicy::load(storage)?.cap_for(&request.tags);
+    let effective = match cap {
+        Some(cap) => requested.min(cap),
+        None => requested,
+    };
+
+    // Guarded dispatches (critical priority, destructive wording,
+    // protected tags) bounce until the user confirms; the rule that
+    // fired is recorded on the task below.
+    let confirmation_rule = DispatchConfirmationPolicy::load(storage)?
+        .matching_rule(&request.agent_id, effective, &request.prompt, &request.tags)
+        .map(|rule| rule.name.clone());
+    if let Some(rule) = &confirmation_rule {
+        if !request.confirmed {
+            return Err(AppError::ConfirmationRequired { rule: rule.clone() });
+        }
+    }
+
+    let now = Utc::now();
+    let task = Task {
+        id: Uuid::new_v4().to_string(),
+        agent_id: request.agent_id.clone(),
+        title: request.title.clone(),
+        prompt: request.prompt.clone(),
+        status: TaskStatus::Queued,
+        priority: effective,
+        tags: request.tags.clone(),
+        depends_on: request.depends_on.clone(),
+        run_at: request.run_at,
+        retry_of: None,
+        step_mode: request.step_mode,
+        failure_kind: None,
+        plan_mode: request.plan_mode,
+        plan: None,
+        prompt_tokens: 0,
+        completion_tokens: 0,
+        total_tokens: 0,
+        board_column: "backlog".to_string(),
+        board_position: 0,
+        queue_position: 0,
+        result: None,
+        error: None,
+        result_artifact: None,
+        max_cost_usd: request.max_cost_usd,
+        max_retries: request.max_retries.unwrap_or(agent.max_retries),
+        retry_backoff_seconds: request
+            .retry_backoff_seconds
+            .unwrap_or_else(crate::models::default_retry_backoff),
+        started_at: None,
+        created_at: now,
+        updated_at: now,
+    };
+    storage.create_task(&task)?;
+    for artifact in &request.attachments {
+        storage.link_attachment(&task.id, artifact)?;
+    }
+    storage.append_event(
+        &task.id,
+        "dispatched",
+        Some(&json!({ "agent_id": request.agent_id })),
+    )?;
+    if let Some(run_at) = request.run_at {
+        storage.append_event(
+            &task.id,
+            "scheduled_for",
+            Some(&json!({ "run_at": run_at })),
+        )?;
+    }
+    if let Some(rule) = &confirmation_rule {
+        storage.append_event(
+            &task.id,
+            "dispatch_confirmed",
+            Some(&json!({ "rule": rule })),
+        )?;
+    }
+    storage.append_event(
+        &task.id,
+        "priority_resolved",
+        Some(&json!({
+            "requested": request.priority,
+            "agent_default": agent.default_priority,
+            "cap": cap,
+            "effective": effective,
+        })),
+    )?;
+    // A parent that already failed blocks the new task immediately;
+    // there is no later event to cascade from.
+    for dep in &task.depends_on {
+        let parent = storage.get_task(dep)?;
+        // NeedsReview is not doomed, just not accepted yet; dependents
+        // keep waiting for the review decision.
+        if parent.status.is_terminal()
+            && !matches!(parent.status, TaskStatus::Completed | TaskStatus::NeedsReview)
+        {
+            storage.block_task(&task.id, dep, parent.status)?;
+            return storage.get_task(&task.id);
+        }
+    }
+    Ok(task)
+}
+
+/// Outcome of one request in a batch dispatch: either the created task
+/// or the error message for that item.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct BatchDispatchResult {
+    pub index: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task: Option<Task>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Dispatch a whole list of requests in one call, reporting per-item
+/// success/failure. Items are independent: one invalid request does
+/// not abort the rest. The storage mutex serializes the batch into a
+/// single writer window, so interleaved IPC traffic cannot split it.
+pub fn dispatch_batch(
+    storage: &Storage,
+    requests: &[DispatchRequest],
+) -> Vec<BatchDispatchResult> {
+    requests
+        .iter()
+        .enumerate()
+        .map(|(index, request)| match dispatch(storage, request) {
+            Ok(task) => BatchDispatchResult {
+                index,
+                task: Some(task),
+                error: None,
+            },
+            Err(err) => BatchDispatchResult {
+                index,
+                task: None,
+                error: Some(err.to_string()),
+            },
+        })
+        .collect()
+}
+
+/// Downgrade the agent to its configured fallback model if its monthly
+/// spend has crossed the budget limit, recording the switch in the
+/// agent's history and the user's notifications.
+fn apply_budget_policy(storage: &Storage, agent: crate::models::Agent) -> AppResult<crate::models::Agent> {
+    let policy = BudgetPolicy::load(storage)?;
+    let Some(rule) = policy.rules.get(&agent.id) else {
+        return Ok(agent);
+    };
+    if agent.model == rule.fallback_model {
+        return Ok(agent);
+    }
+    let spent = storage.monthly_cost_for_agent(&agent.id)?;
+    if spent < rule.monthly_limit_usd {
+        return Ok(agent);
+    }
+
+    storage.set_agent_model(&agent.id, &rule.fallback_model)?;
+    storage.append_agent_history(
+        &agent.id,
+        "model_downgraded",
+        Some(&json!({
+            "from": agent.model,
+            "to": rule.fallback_model,
+            "monthly_spend_usd": spent,
+            "limit_usd": rule.monthly_limit_usd,
+        })),
+    )?;
+    storage.add_notification(
+        &format!("Agent {} switched to {}", agent.name, rule.fallback_model),
+        &format!(
+            "Monthly spend ${spent:.2} crossed the ${:.2} budget; new dispatches use the \
+             cheaper model instead of stopping work.",
+            rule.monthly_limit_usd
+        ),
+    )?;
+    storage.get_agent(&agent.id)
+}
+
+/// Tracks cost accumulated by an executor mid-run and trips once the
+/// task's max-cost ceiling is crossed, so one runaway task cannot burn
+/// an unbounded amount.
+pub struct CostGuard<'a> {
+    storage: &'a Storage,
+    task_id: String,
+    agent_id: String,
+    limit: Option<f64>,
+    spent: f64,
+}
+
+impl<'a> CostGuard<'a> {
+    pub fn new(storage: &'a Storage, task: &Task) -> Self {
+        Self {
+            storage,
+            task_id: task.id.clone(),
+            agent_id: task.agent_id.clone(),
+            limit: task.max_cost_usd,
+            spent: 0.0,
+        }
+    }
+
+    /// Record `amount_usd` of spend; errors with `BudgetExceeded` once
+    /// the accumulated total crosses the ceiling.
+    pub fn charge(&mut self, amount_usd: f64) -> AppResult<()> {
+        self.spent += amount_usd;
+        self.storage
+            .record_cost(&self.agent_id, Some(&self.task_id), amount_usd)?;
+        match self.limit {
+            Some(limit) if self.spent > limit => Err(AppError::BudgetExceeded {
+                task_id: self.task_id.clone(),
+                spent: self.spent,
+                limit,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn spent(&self) -> f64 {
+        self.spent
+    }
+}
+
+/// Run a queued task to completion.
+///
+/// Claiming the task (Queued -> Running, agent Idle -> Running) happens
+/// in one transaction via [`Storage::claim_task`], so concurrent callers
+/// cannot double-run an agent. Execution is currently simulated.
+pub fn execute(storage: &Storage, task_id: &str) -> AppResult<Task> {
+    // Hold the task (it stays Queued) when a declared dependency is
+    // down, instead of claiming it and failing mid-run.
+    let queued = storage.get_task(task_id)?;
+    // A delayed task refuses to start early; the dispatcher's timer
+    // promotes it once `run_at` arrives.
+    if let Some(run_at) = queued.run_at {
+        if run_at > Utc::now() {
+            storage.append_event(
+                task_id,
+                "not_due",
+                Some(&json!({ "run_at": run_at })),
+            )?;
+            return Err(AppError::InvalidTransition {
+                task_id: task_id.to_string(),
+                status: queued.status.as_str().to_string(),
+                requested: TaskStatus::Running.as_str().to_string(),
+            });
+        }
+    }
+    // Graph gating: parents must all be Completed. A doomed parent has
+    // already blocked this task (or does so now); unfinished parents
+    // leave it queued for the auto-start on their completion.
+    for parent_id in &queued.depends_on {
+        let parent = storage.get_task(parent_id)?;
+        match parent.status {
+            TaskStatus::Completed => {}
+            status if status.is_terminal() && status != TaskStatus::NeedsReview => {
+                storage.block_task(task_id, parent_id, status)?;
+                return storage.get_task(task_id);
+            }
+            _ => {
+                storage.append_event(
+                    task_id,
+                    "waiting_on_dependency",
+                    Some(&json!({ "dependency": parent_id })),
+                )?;
+                return Err(AppError::InvalidTransition {
+                    task_id: task_id.to_string(),
+                    status: queued.status.as_str().to_string(),
+                    requested: TaskStatus::Running.as_str().to_string(),
+                });
+            }
+        }
+    }
+    let agent = storage.get_agent(&queued.agent_id)?;
+    if let Err(err) = health::check_agent_dependencies(storage, &agent) {
+        if let AppError::DependencyDown { service, reason, .. } = &err {
+            storage.append_event(
+                task_id,
+                "held",
+                Some(&json!({ "service": service, "reason": reason })),
+            )?;
+        }
+        return Err(err);
+    }
+
+    let task = storage.claim_task(task_id)?;
+    // Freeze the agent config in effect for this run, so later "why did
+    // this output change" investigations can diff snapshots.
+    storage.set_task_config_snapshot(task_id, &agent.config_snapshot())?;
+    let mut costs = CostGuard::new(storage, &task);
+
+    // A budget trip mid-run aborts the task as Failed rather than
+    // surfacing as a command error: the run happened, it just went over.
+    // Other failures retry with exponential backoff up to the task's
+    // budgeted attempts; only exhaustion finally fails the task.
+    let mut attempt: u32 = 0;
+    let run = loop {
+        attempt += 1;
+        match run_provider(storage, &task, &mut costs) {
+            Ok(result) => break Ok(result),
+            Err(err @ AppError::BudgetExceeded { .. }) => break Err(err),
+            // Retrying a run that already burned its wall-clock budget
+            // would only multiply the damage.
+            Err(err @ AppError::Timeout { .. }) => break Err(err),
+            // An operator already said no; asking again is not a retry.
+            Err(err @ AppError::ApprovalDenied { .. }) => break Err(err),
+            Err(err) if attempt <= task.max_retries => {
+                let delay = task.retry_backoff_seconds * f64::from(1u32 << (attempt - 1).min(16));
+                storage.append_event(
+                    task_id,
+                    "retry",
+                    Some(&json!({
+                        "attempt": attempt,
+                        "next_delay_seconds": delay,
+                        "error": err.to_string(),
+                    })),
+                )?;
+                std::thread::sleep(std::time::Duration::from_secs_f64(delay.max(0.0)));
+            }
+            Err(err) => break Err(err),
+        }
+    };
+    match run {
+        Ok(result) => {
+            // Review-gated agents park successful runs in NeedsReview;
+            // accept_result promotes them to Completed.
+            let done = if agent.review_required {
+                TaskStatus::NeedsReview
+            } else {
+                TaskStatus::Completed
+            };
+            match storage.finish_task(task_id, done, Some(&result), None, None) {
+                Ok(task) => Ok(task),
+                // Cancelled while executing: report the actual final state.
+                Err(AppError::InvalidTransition { .. }) => storage.get_task(task_id),
+                Err(err) => Err(err),
+            }
+        }
+        Err(err @ AppError::Timeout { .. }) => {
+            storage.append_event(
+                task_id,
+                "warning",
+                Some(&json!({ "reason": "timeout", "error": err.to_string() })),
+            )?;
+            let task = storage.finish_task(
+                task_id,
+                TaskStatus::Failed,
+                None,
+                Some(&err.to_string()),
+                FailureKind::classify(&err),
+            )?;
+            crate::incidents::record_failure(storage, &task)?;
+            storage.block_dependents(task_id)?;
+            Ok(task)
+        }
+        Err(err @ AppError::BudgetExceeded { .. })
+        | Err(err @ AppError::Provider(_))
+        | Err(err @ AppError::ApprovalDenied { .. }) => {
+            let task = storage.finish_task(
+                task_id,
+                TaskStatus::Failed,
+                None,
+                Some(&err.to_string()),
+                FailureKind::classify(&err),
+            )?;
+            crate::incidents::record_failure(storage, &task)?;
+            storage.block_dependents(task_id)?;
+            Ok(task)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Assemble the prompt an executor sees: the task prompt followed by
+/// the extracted text of each attachment, so "summarize this file"
+/// tasks work end-to-end. Truncated extractions are surfaced as
+/// `attachment_truncated` events.
+pub fn build_prompt(storage: &Storage, task: &Task) -> AppResult<String> {
+    let attachments = storage.get_task_attachments(&task.id)?;
+    if attachments.is_empty() {
+        return Ok(task.prompt.clone());
+    }
+    let mut prompt = task.prompt.clone();
+    for (filename, text, truncated) in attachments {
+        prompt.push_str(&format!("\n\n--- Attachment: {filename} ---\n{text}"));
+        if truncated {
+            storage.append_event(
+                &task.id,
+                "attachment_truncated",
+                Some(&json!({
+                    "filename": filename,
+                    "limit_chars": crate::artifacts::MAX_EXTRACTED_CHARS,
+                })),
+            )?;
+        }
+    }
+    Ok(prompt)
+}
+
+/// Execute the task against the agent's model backend.
+///
+/// Task results and token counts come from the real provider response;
+/// only `mock*` models stay on the offline simulator.
+fn run_provider(
+    storage: &Storage,
+    task: &Task,
+    costs: &mut CostGuard<'_>,
+) -> AppResult<String> {
+    let agent = storage.get_agent(&task.agent_id)?;
+    // Scriptable mock agents replay a JSON-defined run for frontend work.
+    if agent.framework.as_deref() == Some("scripted") {
+        let prompt =
+            templates::render(storage, &build_prompt(storage, task)?, Some(&agent.id), Some(&task.id))?;
+        let result = crate::scripted_mock::run(storage, task, &agent, &prompt)?;
+        storage.append_event(&task.id, "output", Some(&json!({ "text": result })))?;
+        costs.charge(0.0)?;
+        return Ok(result);
+    }
+    // CLI-framework agents bypass the HTTP providers entirely.
+    if agent.framework.as_deref() == Some("subprocess") {
+        let prompt =
+            templates::render(storage, &build_prompt(storage, task)?, Some(&agent.id), Some(&task.id))?;
+        let result = crate::subprocess::run(storage, task, &agent, &prompt)?;
+        storage.append_event(&task.id, "output", Some(&json!({ "text": result })))?;
+        costs.charge(0.0)?;
+        return Ok(result);
+    }
+    let prompt = templates::render(
+        storage,
+        &build_prompt(storage, task)?,
+        Some(&agent.id),
+        Some(&task.id),
+    )?;
+
+    let (system_prompt, constitution_version) = crate::policy::guarded_system_prompt(storage, &agent)?;
+    if let Some(version) = constitution_version {
+        storage.append_event(
+            &task.id,
+            "guard_prompt_applied",
+            Some(&json!({ "constitution_version": version })),
+        )?;
+    }
+
+    // Try the primary model first, then each fallback in order.
+    // Fallbacks only engage on transient failures (rate limits, 5xx);
+    // anything else aborts immediately.
+    let mut last_err = None;
+    for (attempt, model) in std::iter::once(agent.model.as_str())
+        .chain(agent.fallback_models.iter().map(String::as_str))
+        .enumerate()
+    {
+        // The configured framework applies to the primary; fallbacks
+        // route by their own model name.
+        let framework = if attempt == 0 { agent.framework.as_deref() } else { None };
+        match try_model(
+            storage,
+            task,
+            &agent,
+            framework,
+            model,
+            system_prompt.as_deref(),
+            &prompt,
+            costs,
+        ) {
+            Ok(text) => return Ok(text),
+            Err(err @ AppError::BudgetExceeded { .. }) => return Err(err),
+            Err(err) if is_transient(&err) => {
+                tracing::warn!(model, %err, "provider call failed; trying next in chain");
+                last_err = Some((model.to_string(), err));
+            }
+            Err(err) => return Err(err),
+        }
+        if let Some((failed_model, err)) = &last_err {
+            storage.append_event(
+                &task.id,
+                "failover",
+                Some(&json!({ "from_model": failed_model, "error": err.to_string() })),
+            )?;
+        }
+    }
+    Err(last_err.map(|(_, err)| err).unwrap_or_else(|| {
+        AppError::Provider("provider chain is empty".into())
+    }))
+}
+
+/// One completion attempt against a specific model in the chain.
+#[allow(clippy::too_many_arguments)]
+fn try_model(
+    storage: &Storage,
+    task: &Task,
+    agent: &Agent,
+    framework: Option<&str>,
+    model: &str,
+    system_prompt: Option<&str>,
+    prompt: &str,
+    costs: &mut CostGuard<'_>,
+) -> AppResult<String> {
+    let provider = providers::for_agent(framework, model);
+    let api_key = match provider.secret_name() {
+        Some(name) => Some(storage.get_secret_audited(name, Some(&agent.id), Some(&task.id))?),
+        None => None,
+    };
+
+    let request = CompletionRequest {
+        model: model.to_string(),
+        system_prompt: system_prompt.map(str::to_string),
+        prompt: prompt.to_string(),
+        temperature: agent.temperature,
+        endpoint: agent.endpoint.clone(),
+        timeout_seconds: agent.timeout_seconds,
+    };
+    storage.append_event(
+        &task.id,
+        "api_call",
+        Some(&json!({ "provider": provider.name(), "model": request.model })),
+    )?;
+
+    let mut sink = |kind: &str, payload: serde_json::Value| {
+        if let Err(err) = storage.append_event(&task.id, kind, Some(&payload)) {
+            tracing::debug!(%err, kind, "failed to record streamed provider event");
+        }
+    };
+    let started = std::time::Instant::now();
+    let response = match provider.complete(api_key.as_deref(), &request, &mut sink) {
+        Ok(response) => response,
+        // A call that failed after the deadline is reported as a
+        // timeout, not a generic provider error, so it neither retries
+        // nor fails over.
+        Err(err) => match agent.timeout_seconds {
+            Some(limit) if started.elapsed().as_secs() >= limit => {
+                return Err(AppError::Timeout {
+                    task_id: task.id.clone(),
+                    seconds: limit,
+                });
+            }
+            _ => return Err(err),
+        },
+    };
+    let cost_usd = crate::pricing::cost_usd(
+        storage,
+        &request.model,
+        response.prompt_tokens,
+        response.completion_tokens,
+    );
+    storage.append_event(
+        &task.id,
+        "usage",
+        Some(&json!({
+            "prompt_tokens": response.prompt_tokens,
+            "completion_tokens": response.completion_tokens,
+            "estimated_cost_cents": (cost_usd * 100.0 * 1000.0).round() / 1000.0,
+        })),
+    )?;
+    storage.add_task_usage(
+        &task.id,
+        response.prompt_tokens as i64,
+        response.completion_tokens as i64,
+    )?;
+    costs.charge(cost_usd)?;
+    storage.append_event(&task.id, "output", Some(&json!({ "text": response.text })))?;
+    Ok(response.text)
+}
+
+/// Whether a provider failure is worth retrying on the next model in
+/// the chain (rate limits and server-side errors).
+fn is_transient(err: &AppError) -> bool {
+    let AppError::Provider(message) = err else { return false };
+    ["429", "500", "502", "503", "504", "rate limit", "timed out", "connect"]
+        .iter()
+        .any(|needle| message.contains(needle))
+}
+
+/// How often a paused step-mode run polls for its acknowledgement.
+const STEP_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(25);
+
+/// Gate one supervision step: no-op unless the task runs in step mode,
+/// otherwise emit a `paused_at_step` event and block until a matching
+/// `step_continued` acknowledgement arrives via [`continue_task`].
+/// Cancelling the task while paused aborts the run.
+pub fn pause_at_step(storage: &Storage, task: &Task, step: &serde_json::Value) -> AppResult<()> {
+    if !task.step_mode {
+        return Ok(());
+    }
+    storage.append_event(&task.id, "paused_at_step", Some(step))?;
+    loop {
+        let events = storage.get_task_events(&task.id)?;
+        let paused = events.iter().filter(|e| e.kind == "paused_at_step").count();
+        let continued = events.iter().filter(|e| e.kind == "step_continued").count();
+        if continued >= paused {
+            return Ok(());
+        }
+        if storage.get_task(&task.id)?.status == TaskStatus::Cancelled {
+            return Err(AppError::InvalidTransition {
+                task_id: task.id.clone(),
+                status: TaskStatus::Cancelled.as_str().to_string(),
+                requested: "continue".to_string(),
+            });
+        }
+        std::thread::sleep(STEP_POLL_INTERVAL);
+    }
+}
+
+/// Acknowledge the current step-mode pause so the run proceeds to the
+/// next thought or tool call.
+pub fn continue_task(storage: &Storage, task_id: &str) -> AppResult<()> {
+    let task = storage.get_task(task_id)?;
+    if !task.step_mode {
+        return Err(AppError::InvalidArgument(format!(
+            "task {task_id} is not running in step mode"
+        )));
+    }
+    if task.status != TaskStatus::Running {
+        return Err(AppError::InvalidTransition {
+            task_id: task_id.to_string(),
+            status: task.status.as_str().to_string(),
+            requested: "continue".to_string(),
+        });
+    }
+    storage.append_event(task_id, "step_continued", None)?;
+    Ok(())
+}
+
+/// Submit the structured plan of a plan-mode run and block until the
+/// operator signs it off via [`approve_plan`]: the plan is stored on
+/// the task, a `plan_submitted` event is emitted, and the run holds at
+/// the gate. No-op for tasks not dispatched in plan mode. Cancelling
+/// the task while it waits aborts the run.
+pub fn submit_plan(storage: &Storage, task: &Task, steps: &[PlanStep]) -> AppResult<()> {
+    if !task.plan_mode {
+        return Ok(());
+    }
+    storage.set_task_plan(&task.id, steps)?;
+    storage.append_event(&task.id, "plan_submitted", Some(&json!({ "steps": steps })))?;
+    loop {
+        let events = storage.get_task_events(&task.id)?;
+        if events.iter().any(|e| e.kind == "plan_approved") {
+            return Ok(());
+        }
+        if storage.get_task(&task.id)?.status == TaskStatus::Cancelled {
+            return Err(AppError::InvalidTransition {
+                task_id: task.id.clone(),
+                status: TaskStatus::Cancelled.as_str().to_string(),
+                requested: "approve_plan".to_string(),
+            });
+        }
+        std::thread::sleep(STEP_POLL_INTERVAL);
+    }
+}
+
+/// Approve a submitted plan so the waiting run proceeds to execute its
+/// steps.
+pub fn approve_plan(storage: &Storage, task_id: &str) -> AppResult<()> {
+    let task = storage.get_task(task_id)?;
+    if !task.plan_mode || task.plan.is_none() {
+        return Err(AppError::InvalidArgument(format!(
+            "task {task_id} has no plan awaiting approval"
+        )));
+    }
+    if task.status != TaskStatus::Running {
+        return Err(AppError::InvalidTransition {
+            task_id: task_id.to_string(),
+            status: task.status.as_str().to_string(),
+            requested: "approve_plan".to_string(),
+        });
+    }
+    storage.append_event(task_id, "plan_approved", None)?;
+    Ok(())
+}
+
+/// A tool call waiting on operator approval, surfaced over IPC.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct PendingToolCall {
+    pub task_id: String,
+    pub agent_id: String,
+    pub tool: String,
+    pub args: serde_json::Value,
+    pub requested_at: DateTime<Utc>,
+}
+
+/// Gate one tool invocation behind the workspace [`ApprovalPolicy`]:
+/// tools not listed for the agent run immediately; gated ones emit a
+/// `tool_approval_requested` event and block until the operator
+/// resolves it via [`resolve_tool_call`]. A denial aborts the run as a
+/// guardrail failure.
+pub fn request_tool_approval(
+    storage: &Storage,
+    task: &Task,
+    tool: &str,
+    args: &serde_json::Value,
+) -> AppResult<()> {
+    let policy = ApprovalPolicy::load(storage)?;
+    if !policy.requires_approval(&task.agent_id, tool) {
+        return Ok(());
+    }
+    storage.append_event(
+        &task.id,
+        "tool_approval_requested",
+        Some(&json!({ "tool": tool, "args": args })),
+    )?;
+    let deadline = policy
+        .timeout_seconds
+        .map(|limit| std::time::Instant::now() + std::time::Duration::from_secs(limit));
+    loop {
+        let events = storage.get_task_events(&task.id)?;
+        let requested = events
+            .iter()
+            .filter(|e| e.kind == "tool_approval_requested")
+            .count();
+        let granted = events.iter().filter(|e| e.kind == "tool_approval_granted").count();
+        let denied = events.iter().filter(|e| e.kind == "tool_approval_denied").count();
+        // Gated calls are serialized within a run, so the latest
+        // resolution always answers the latest request.
+        if granted + denied >= requested {
+            return if events
+                .iter()
+                .rev()
+                .find(|e| {
+                    e.kind == "tool_approval_granted" || e.kind == "tool_approval_denied"
+                })
+                .is_some_and(|e| e.kind == "tool_approval_granted")
+            {
+                Ok(())
+            } else {
+                Err(AppError::ApprovalDenied {
+                    task_id: task.id.clone(),
+                    tool: tool.to_string(),
+                })
+            };
+        }
+        if storage.get_task(&task.id)?.status == TaskStatus::Cancelled {
+            return Err(AppError::InvalidTransition {
+                task_id: task.id.clone(),
+                status: TaskStatus::Cancelled.as_str().to_string(),
+                requested: "tool_approval".to_string(),
+            });
+        }
+        // Nobody answered in time: resolve it ourselves so the run does
+        // not hang forever. Low-risk agents auto-approve; everything
+        // else is denied.
+        if deadline.is_some_and(|d| std::time::Instant::now() >= d) {
+            let approve = policy
+                .auto_approve_on_timeout
+                .iter()
+                .any(|id| id == &task.agent_id);
+            storage.append_event(
+                &task.id,
+                "warning",
+                Some(&json!({
+                    "reason": "approval_timeout",
+                    "tool": tool,
+                    "auto_approved": approve,
+                })),
+            )?;
+            storage.add_notification(
+                &format!("Tool approval timed out for task {}", task.title),
+                &format!(
+                    "Call to {tool} waited {}s without a decision and was {}.",
+                    policy.timeout_seconds.unwrap_or_default(),
+                    if approve { "auto-approved" } else { "auto-denied" },
+                ),
+            )?;
+            let kind = if approve {
+                "tool_approval_granted"
+            } else {
+                "tool_approval_denied"
+            };
+            storage.append_event(&task.id, kind, Some(&json!({ "auto": true })))?;
+            storage.record_approval(
+                &task.id,
+                tool,
+                if approve { "approved" } else { "denied" },
+                "policy",
+                Some("auto-resolved after approval timeout"),
+            )?;
+            return if approve {
+                Ok(())
+            } else {
+                Err(AppError::ApprovalDenied {
+                    task_id: task.id.clone(),
+                    tool: tool.to_string(),
+                })
+            };
+        }
+        std::thread::sleep(STEP_POLL_INTERVAL);
+    }
+}
+
+/// Tool calls across all running tasks still waiting on an operator
+/// decision.
+pub fn pending_tool_calls(storage: &Storage) -> AppResult<Vec<PendingToolCall>> {
+    let mut pending = Vec::new();
+    for task in storage.get_all_tasks()? {
+        if task.status != TaskStatus::Running {
+            continue;
+        }
+        let events = storage.get_task_events(&task.id)?;
+        let requested: Vec<_> = events
+            .iter()
+            .filter(|e| e.kind == "tool_approval_requested")
+            .collect();
+        let resolved = events
+            .iter()
+            .filter(|e| e.kind == "tool_approval_granted" || e.kind == "tool_approval_denied")
+            .count();
+        if requested.len() > resolved {
+            let event = requested[requested.len() - 1];
+            pending.push(PendingToolCall {
+                task_id: task.id.clone(),
+                agent_id: task.agent_id.clone(),
+                tool: event
+                    .payload
+                    .as_ref()
+                    .and_then(|p| p["tool"].as_str())
+                    .unwrap_or_default()
+                    .to_string(),
+                args: event
+                    .payload
+                    .as_ref()
+                    .map(|p| p["args"].clone())
+                    .unwrap_or(serde_json::Value::Null),
+                requested_at: event.created_at,
+            });
+        }
+    }
+    Ok(pending)
+}
+
+/// How often the background escalation sweep re-checks for unattended
+/// waits.
+const ESCALATION_SWEEP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Escalate unattended waits per the workspace [`EscalationPolicy`]:
+/// pending tool approvals and `WaitingForInput` tasks older than the
+/// configured threshold get an `escalation` event plus a notification,
+/// and the agent is optionally paused. Each wait escalates once;
+/// returns the number of escalations raised.
+pub fn escalate_unattended(storage: &Storage) -> AppResult<u32> {
+    let policy = EscalationPolicy::load(storage)?;
+    let Some(minutes) = policy.unattended_minutes else {
+        return Ok(0);
+    };
+    let cutoff = Utc::now() - chrono::Duration::minutes(minutes as i64);
+    let mut raised = 0;
+
+    for call in pending_tool_calls(storage)? {
+        if call.requested_at <= cutoff {
+            raised += escalate(
+                storage,
+                &policy,
+                &call.task_id,
+                call.requested_at,
+                &json!({
+                    "reason": "unattended_approval",
+                    "tool": call.tool,
+                    "unattended_minutes": minutes,
+                }),
+            )?;
+        }
+    }
+    for task in storage.get_all_tasks()? {
+        if task.status != TaskStatus::WaitingForInput {
+            continue;
+        }
+        let events = storage.get_task_events(&task.id)?;
+        let Some(asked) = events.iter().rev().find(|e| e.kind == "ask_user") else {
+            continue;
+        };
+        if asked.created_at <= cutoff {
+            raised += escalate(
+                storage,
+                &policy,
+                &task.id,
+                asked.created_at,
+                &json!({ "reason": "unattended_input", "unattended_minutes": minutes }),
+            )?;
+        }
+    }
+    Ok(raised)
+}
+
+/// Raise one escalation unless this wait already has one; returns how
+/// many were raised (0 or 1).
+fn escalate(
+    storage: &Storage,
+    policy: &EscalationPolicy,
+    task_id: &str,
+    waiting_since: DateTime<Utc>,
+    detail: &serde_json::Value,
+) -> AppResult<u32> {
+    let events = storage.get_task_events(task_id)?;
+    if events
+        .iter()
+        .any(|e| e.kind == "escalation" && e.created_at >= waiting_since)
+    {
+        return Ok(0);
+    }
+    let task = storage.get_task(task_id)?;
+    storage.append_event(task_id, "escalation", Some(detail))?;
+    let reason = detail["reason"].as_str().unwrap_or("unattended");
+    let body = format!(
+        "Task \"{}\" has been waiting on the operator since {} ({reason}).",
+        task.title,
+        waiting_since.to_rfc3339(),
+    );
+    storage.add_notification(&format!("Escalation: {}", task.title), &body)?;
+    crate::email::notify(storage, Some(task_id), "Unattended agent task", &body);
+    if policy.auto_pause_agent {
+        storage.set_agent_status(&task.agent_id, AgentStatus::Paused)?;
+        storage.append_agent_history(
+            &task.agent_id,
+            "paused",
+            Some(&json!({ "reason": "escalation_policy" })),
+        )?;
+    }
+    Ok(1)
+}
+
+/// Background loop running the escalation sweep; idles cheaply while
+/// no policy is configured.
+pub fn escalation_loop(storage: &Storage) {
+    loop {
+        if let Err(err) = escalate_unattended(storage) {
+            tracing::warn!(%err, "escalation sweep failed");
+        }
+        std::thread::sleep(ESCALATION_SWEEP_INTERVAL);
+    }
+}
+
+/// Record the operator's decision on a task's pending tool call; the
+/// blocked executor picks it up and either proceeds or aborts. Every
+/// decision lands in the approvals audit trail with the approver's
+/// identity and optional comment.
+pub fn resolve_tool_call(
+    storage: &Storage,
+    task_id: &str,
+    approved: bool,
+    approver: &str,
+    comment: Option<&str>,
+) -> AppResult<()> {
+    let events = storage.get_task_events(task_id)?;
+    let requested: Vec<_> = events
+        .iter()
+        .filter(|e| e.kind == "tool_approval_requested")
+        .collect();
+    let resolved = events
+        .iter()
+        .filter(|e| e.kind == "tool_approval_granted" || e.kind == "tool_approval_denied")
+        .count();
+    if requested.len() <= resolved {
+        return Err(AppError::InvalidArgument(format!(
+            "task {task_id} has no tool call awaiting approval"
+        )));
+    }
+    let tool = requested[requested.len() - 1]
+        .payload
+        .as_ref()
+        .and_then(|p| p["tool"].as_str())
+        .unwrap_or_default()
+        .to_string();
+    let kind = if approved {
+        "tool_approval_granted"
+    } else {
+        "tool_approval_denied"
+    };
+    storage.append_event(task_id, kind, Some(&json!({ "approver": approver })))?;
+    storage.record_approval(
+        task_id,
+        &tool,
+        if approved { "approved" } else { "denied" },
+        approver,
+        comment,
+    )?;
+    Ok(())
+}
+
+/// Suspend the run on a question for the operator and block until an
+/// answer arrives via [`provide_input`]. The task sits in
+/// `WaitingForInput` while suspended; cancelling it aborts the run.
+/// Returns the operator's answer for injection into the executor's
+/// context.
+pub fn ask_user(storage: &Storage, task: &Task, question: &str) -> AppResult<String> {
+    storage.suspend_for_input(&task.id, question)?;
+    loop {
+        match storage.get_task(&task.id)?.status {
+            TaskStatus::WaitingForInput => {}
+            TaskStatus::Running => {
+                let events = storage.get_task_events(&task.id)?;
+                // Steering messages share the user_input kind; only an
+                // event carrying an answer resolves the question.
+                let answer = events
+                    .iter()
+                    .rev()
+                    .find(|e| {
+                        e.kind == "user_input"
+                            && e.payload.as_ref().is_some_and(|p| p["answer"].is_string())
+                    })
+                    .and_then(|e| e.payload.as_ref()?["answer"].as_str().map(str::to_string));
+                return answer.ok_or_else(|| {
+                    AppError::InvalidArgument(format!(
+                        "task {} resumed without a recorded answer",
+                        task.id
+                    ))
+                });
+            }
+            status => {
+                return Err(AppError::InvalidTransition {
+                    task_id: task.id.clone(),
+                    status: status.as_str().to_string(),
+                    requested: TaskStatus::Running.as_str().to_string(),
+                });
+            }
+        }
+        std::thread::sleep(STEP_POLL_INTERVAL);
+    }
+}
+
+/// Answer a task's pending `ask_user` question; execution resumes with
+/// the reply injected into the executor's context.
+pub fn provide_input(storage: &Storage, task_id: &str, answer: &str) -> AppResult<()> {
+    storage.resume_from_input(task_id, answer)
+}
+
+/// Clone a failed or cancelled task into a fresh dispatch, optionally
+/// with an edited prompt, linked to the original through `retry_of`.
+/// Config (priority, tags, budgets, attachments) carries over;
+/// dependencies do not -- they were satisfied or are moot by now.
+pub fn retry(
+    storage: &Storage,
+    task_id: &str,
+    edited_prompt: Option<&str>,
+) -> AppResult<Task> {
+    let original = storage.get_task(task_id)?;
+    if !matches!(
+        original.status,
+        TaskStatus::Failed | TaskStatus::Cancelled
+    ) {
+        return Err(AppError::InvalidTransition {
+            task_id: task_id.to_string(),
+            status: original.status.as_str().to_string(),
+            requested: "retry".to_string(),
+        });
+    }
+
+    let mut request = DispatchRequest::new(
+        &original.agent_id,
+        &original.title,
+        edited_prompt.unwrap_or(&original.prompt),
+    );
+    request.priority = Some(original.priority);
+    request.tags = original.tags.clone();
+    request.max_cost_usd = original.max_cost_usd;
+    request.max_retries = Some(original.max_retries);
+    request.retry_backoff_seconds = Some(original.retry_backoff_seconds);
+    let retry = dispatch(storage, &request)?;
+
+    storage.copy_attachments(task_id, &retry.id)?;
+    storage.set_task_retry_of(&retry.id, task_id)?;
+    storage.append_event(
+        &retry.id,
+        "retry_dispatched",
+        Some(&json!({ "retry_of": task_id, "prompt_edited": edited_prompt.is_some() })),
+    )?;
+    storage.append_event(
+        task_id,
+        "retried_as",
+        Some(&json!({ "task_id": retry.id })),
+    )?;
+    storage.get_task(&retry.id)
+}
+
+/// Queue operator guidance for a running task. The message is logged
+/// as a `user_input` event and handed to the executor at its next
+/// turn boundary, so an agent can be course-corrected without being
+/// cancelled.
+pub fn send_message(storage: &Storage, task_id: &str, message: &str) -> AppResult<()> {
+    let task = storage.get_task(task_id)?;
+    if !matches!(
+        task.status,
+        TaskStatus::Running | TaskStatus::WaitingForInput
+    ) {
+        return Err(AppError::InvalidTransition {
+            task_id: task_id.to_string(),
+            status: task.status.as_str().to_string(),
+            requested: "steer".to_string(),
+        });
+    }
+    storage.append_event(
+        task_id,
+        "user_input",
+        Some(&json!({ "message": message, "source": "operator" })),
+    )?;
+    Ok(())
+}
+
+/// Collect operator messages queued since the executor last drained
+/// them, marking them consumed with a `guidance_applied` event.
+pub fn drain_operator_messages(storage: &Storage, task: &Task) -> AppResult<Vec<String>> {
+    let events = storage.get_task_events(&task.id)?;
+    let consumed_through = events
+        .iter()
+        .rev()
+        .find(|e| e.kind == "guidance_applied")
+        .and_then(|e| e.payload.as_ref()?["through"].as_i64())
+        .unwrap_or(0);
+    let fresh: Vec<_> = events
+        .iter()
+        .filter(|e| {
+            e.id > consumed_through
+                && e.kind == "user_input"
+                && e.payload.as_ref().is_some_and(|p| p["message"].is_string())
+        })
+        .collect();
+    if fresh.is_empty() {
+        return Ok(Vec::new());
+    }
+    let through = fresh.last().map(|e| e.id).unwrap_or(consumed_through);
+    let messages: Vec<String> = fresh
+        .iter()
+        .filter_map(|e| e.payload.as_ref()?["message"].as_str().map(str::to_string))
+        .collect();
+    storage.append_event(
+        &task.id,
+        "guidance_applied",
+        Some(&json!({ "through": through, "count": messages.len() })),
+    )?;
+    Ok(messages)
+}
+
+/// Accept a reviewed result: the task moves from NeedsReview to
+/// Completed and its dependents become eligible to run.
+pub fn accept_result(storage: &Storage, task_id: &str) -> AppResult<Task> {
+    let task = storage.get_task(task_id)?;
+    if task.status != TaskStatus::NeedsReview {
+        return Err(AppError::InvalidTransition {
+            task_id: task_id.to_string(),
+            status: task.status.as_str().to_string(),
+            requested: TaskStatus::Completed.as_str().to_string(),
+        });
+    }
+    storage.set_task_status(
+        task_id,
+        TaskStatus::Completed,
+        task.result.as_deref(),
+        None,
+    )?;
+    storage.append_event(task_id, "result_accepted", None)?;
+    storage.get_task(task_id)
+}
+
+/// Send a reviewed result back: the original is closed as cancelled
+/// and a fresh dispatch runs with the feedback appended to the
+/// instruction, linked through `retry_of`.
+pub fn request_changes(storage: &Storage, task_id: &str, feedback: &str) -> AppResult<Task> {
+    let original = storage.get_task(task_id)?;
+    if original.status != TaskStatus::NeedsReview {
+        return Err(AppError::InvalidTransition {
+            task_id: task_id.to_string(),
+            status: original.status.as_str().to_string(),
+            requested: "request_changes".to_string(),
+        });
+    }
+
+    let mut request = DispatchRequest::new(
+        &original.agent_id,
+        &original.title,
+        format!("{}
+
+Reviewer feedback:
+{feedback}", original.prompt),
+    );
+    request.priority = Some(original.priority);
+    request.tags = original.tags.clone();
+    request.max_cost_usd = original.max_cost_usd;
+    request.max_retries = Some(original.max_retries);
+    request.retry_backoff_seconds = Some(original.retry_backoff_seconds);
+    let revision = dispatch(storage, &request)?;
+
+    storage.copy_attachments(task_id, &revision.id)?;
+    storage.set_task_retry_of(&revision.id, task_id)?;
+    storage.set_task_status(
+        task_id,
+        TaskStatus::Cancelled,
+        original.result.as_deref(),
+        None,
+    )?;
+    storage.append_event(
+        task_id,
+        "changes_requested",
+        Some(&json!({ "feedback": feedback, "revision": revision.id })),
+    )?;
+    storage.append_event(
+        &revision.id,
+        "revision_dispatched",
+        Some(&json!({ "revision_of": task_id })),
+    )?;
+    storage.get_task(&revision.id)
+}
+
+/// Cancel a task that has not yet finished, blocking anything queued
+/// behind it in the dependency graph.
+pub fn cancel(storage: &Storage, task_id: &str) -> AppResult<Task> {
+    let task = storage.cancel_task(task_id)?;
+    storage.block_dependents(task_id)?;
+    Ok(task)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{AgentStatus, TaskPriority};
+
+    fn storage_with_agent() -> (Storage, String) {
+        let storage = Storage::open_in_memory().unwrap();
+        let agent = crate::models::Agent::new("a", "mock");
+        storage.create_agent(&agent).unwrap();
+        (storage, agent.id)
+    }
+
+    #[test]
+    fn priority_falls_back_to_agent_default_and_respects_tag_caps() {
+        let (storage, agent_id) = storage_with_agent();
+
+        // No explicit priority: the agent default (Normal) applies.
+        let task = dispatch(&storage, &DispatchRequest::new(&agent_id, "t", "p")).unwrap();
+        assert_eq!(task.priority, TaskPriority::Normal);
+
+        // Workspace policy caps `background`-tagged tasks at Low.
+        let mut policy = PriorityPolicy::default();
+        policy.caps.insert("background".into(), TaskPriority::Low);
+        policy.save(&storage).unwrap();
+
+        let mut request = DispatchRequest::new(&agent_id, "t", "p");
+        request.priority = Some(TaskPriority::Urgent);
+        request.tags = vec!["background".into()];
+        let task = dispatch(&storage, &request).unwrap();
+        assert_eq!(task.priority, TaskPriority::Low);
+
+        // The clamping decision is traceable through the event log.
+        let events = storage.get_task_events(&task.id).unwrap();
+        assert!(events.iter().any(|e| e.kind == "priority_resolved"));
+    }
+
+    #[test]
+    fn streaming_deltas_accumulate_into_the_final_result() {
+        let (storage, agent_id) = storage_with_agent();
+        let task = dispatch(&storage, &DispatchRequest::new(&agent_id, "t", "p")).unwrap();
+        let done = execute(&storage, &task.id).unwrap();
+
+        let events = storage.get_task_events(&task.id).unwrap();
+        let streamed: String = events
+            .iter()
+            .filter(|e| e.kind == "token_chunk")
+            .filter_map(|e| e.payload.as_ref()?["delta"].as_str().map(str::to_string))
+            .collect();
+        assert!(!streamed.is_empty());
+        assert_eq!(done.result.as_deref(), Some(streamed.as_str()));
+    }
+
+    #[test]
+    fn execution_records_api_call_and_usage_events() {
+        let (storage, agent_id) = storage_with_agent();
+        let task = dispatch(&storage, &DispatchRequest::new(&agent_id, "t", "p")).unwrap();
+        execute(&storage, &task.id).unwrap();
+
+        let events = storage.get_task_events(&task.id).unwrap();
+        let kinds: Vec<_> = events.iter().map(|e| e.kind.as_str()).collect();
+        assert!(kinds.contains(&"api_call"));
+        assert!(kinds.contains(&"usage"));
+        assert!(kinds.contains(&"output"));
+    }
+
+    #[test]
+    fn token_usage_accumulates_on_the_task_and_agent_stats() {
+        let (storage, agent_id) = storage_with_agent();
+        let task = dispatch(
+            &storage,
+            &DispatchRequest::new(&agent_id, "t", "summarize the weekly report in detail"),
+        )
+        .unwrap();
+        execute(&storage, &task.id).unwrap();
+
+        let done = storage.get_task(&task.id).unwrap();
+        assert!(done.prompt_tokens > 0);
+        assert!(done.completion_tokens > 0);
+        assert_eq!(done.total_tokens, done.prompt_tokens + done.completion_tokens);
+
+        let stats = storage.get_agent_stats(&agent_id).unwrap();
+        assert_eq!(stats.tasks_with_usage, 1);
+        assert_eq!(stats.total_tokens, done.total_tokens);
+    }
+
+    #[test]
+    fn prompts_include_extracted_attachment_text() {
+        let (storage, agent_id) = storage_with_agent();
+        storage
+            .register_attachment("att-1.txt", "notes.txt", "important context", false)
+            .unwrap();
+        storage
+            .register_attachment("att-2.txt", "big.txt", "cut short", true)
+            .unwrap();
+
+        let mut request = DispatchRequest::new(&agent_id, "summarize", "Summarize the notes.");
+        request.attachments = vec!["att-1.txt".into(), "att-2.txt".into()];
+        let task = dispatch(&storage, &request).unwrap();
+
+        let prompt = build_prompt(&storage, &task).unwrap();
+        assert!(prompt.contains("Summarize the notes."));
+        assert!(prompt.contains("Attachment: notes.txt"));
+        assert!(prompt.contains("important context"));
+
+        // Truncated extractions leave a trace in the event log.
+        let events = storage.get_task_events(&task.id).unwrap();
+        assert!(events.iter().any(|e| e.kind == "attachment_truncated"));
+    }
+
+    #[test]
+    fn cost_guard_trips_once_ceiling_is_crossed() {
+        let (storage, agent_id) = storage_with_agent();
+        let mut request = DispatchRequest::new(&agent_id, "t", "p");
+        request.max_cost_usd = Some(0.05);
+        let task = dispatch(&storage, &request).unwrap();
+
+        let mut guard = CostGuard::new(&storage, &task);
+        guard.charge(0.02).unwrap();
+        guard.charge(0.02).unwrap();
+        match guard.charge(0.02) {
+            Err(AppError::BudgetExceeded { spent, limit, .. }) => {
+                assert!(spent > limit);
+            }
+            other => panic!("expected BudgetExceeded, got {other:?}"),
+        }
+        // Every charge is still recorded against the agent's spend.
+        assert!(storage.monthly_cost_for_agent(&agent_id).unwrap() > 0.059);
+    }
+
+    #[test]
+    fn over_budget_agent_downgrades_to_fallback_model() {
+        let (storage, agent_id) = storage_with_agent();
+        let mut policy = BudgetPolicy::default();
+        policy.rules.insert(
+            agent_id.clone(),
+            crate::policy::BudgetRule {
+                monthly_limit_usd: 10.0,
+                fallback_model: "mock-mini".into(),
+            },
+        );
+        policy.save(&storage).unwrap();
+
+        // Under budget: model untouched.
+        storage.record_cost(&agent_id, None, 4.0).unwrap();
+        dispatch(&storage, &DispatchRequest::new(&agent_id, "t", "p")).unwrap();
+        assert_eq!(storage.get_agent(&agent_id).unwrap().model, "mock");
+
+        // Crossing the limit downgrades on the next dispatch.
+        storage.record_cost(&agent_id, None, 7.0).unwrap();
+        dispatch(&storage, &DispatchRequest::new(&agent_id, "t", "p")).unwrap();
+        assert_eq!(storage.get_agent(&agent_id).unwrap().model, "mock-mini");
+        let history = storage.get_agent_history(&agent_id).unwrap();
+        assert!(history.iter().any(|h| h.kind == "model_downgraded"));
+    }
+
+    #[test]
+    fn paused_agent_rejects_execution_until_resumed() {
+        let (storage, agent_id) = storage_with_agent();
+        let task = dispatch(&storage, &DispatchRequest::new(&agent_id, "t", "p")).unwrap();
+
+        storage
+            .set_agent_status(&agent_id, AgentStatus::Paused)
+            .unwrap();
+        match execute(&storage, &task.id) {
+            Err(AppError::AgentPaused(id)) => assert_eq!(id, agent_id),
+            other => panic!("expected AgentPaused, got {other:?}"),
+        }
+        // The queued task must not have been touched by the rejected run.
+        assert_eq!(storage.get_task(&task.id).unwrap().status, TaskStatus::Queued);
+
+        storage
+            .set_agent_status(&agent_id, AgentStatus::Idle)
+            .unwrap();
+        assert_eq!(
+            execute(&storage, &task.id).unwrap().status,
+            TaskStatus::Completed
+        );
+    }
+
+    #[test]
+    fn dependent_task_waits_for_its_parent_then_becomes_ready() {
+        let (storage, agent_id) = storage_with_agent();
+        let parent = dispatch(&storage, &DispatchRequest::new(&agent_id, "parent", "p")).unwrap();
+        let mut request = DispatchRequest::new(&agent_id, "child", "p");
+        request.depends_on = vec![parent.id.clone()];
+        let child = dispatch(&storage, &request).unwrap();
+
+        // The child cannot start while the parent is unfinished.
+        assert!(matches!(
+            execute(&storage, &child.id),
+            Err(AppError::InvalidTransition { .. })
+        ));
+        assert_eq!(storage.get_task(&child.id).unwrap().status, TaskStatus::Queued);
+
+        execute(&storage, &parent.id).unwrap();
+        let ready = storage.ready_dependents(&parent.id).unwrap();
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].id, child.id);
+        assert_eq!(
+            execute(&storage, &child.id).unwrap().status,
+            TaskStatus::Completed
+        );
+    }
+
+    #[test]
+    fn cancelled_parent_cascades_blocked_through_the_graph() {
+        let (storage, agent_id) = storage_with_agent();
+        let parent = dispatch(&storage, &DispatchRequest::new(&agent_id, "parent", "p")).unwrap();
+        let mut request = DispatchRequest::new(&agent_id, "child", "p");
+        request.depends_on = vec![parent.id.clone()];
+        let child = dispatch(&storage, &request).unwrap();
+        let mut request = DispatchRequest::new(&agent_id, "grandchild", "p");
+        request.depends_on = vec![child.id.clone()];
+        let grandchild = dispatch(&storage, &request).unwrap();
+
+        cancel(&storage, &parent.id).unwrap();
+        for id in [&child.id, &grandchild.id] {
+            let task = storage.get_task(id).unwrap();
+            assert_eq!(task.status, TaskStatus::Blocked);
+            assert!(task.error.unwrap().starts_with("blocked: dependency"));
+        }
+        let events = storage.get_task_events(&child.id).unwrap();
+        assert!(events.iter().any(|e| e.kind == "blocked"));
+    }
+
+    #[test]
+    fn batch_dispatch_reports_per_item_outcomes() {
+        let (storage, agent_id) = storage_with_agent();
+        let requests = vec![
+            DispatchRequest::new(&agent_id, "ok", "p"),
+            DispatchRequest::new("no-such-agent", "bad", "p"),
+            DispatchRequest::new(&agent_id, "also ok", "p"),
+        ];
+        let results = dispatch_batch(&storage, &requests);
+        assert_eq!(results.len(), 3);
+        assert!(results[0].task.is_some() && results[0].error.is_none());
+        assert!(results[1].task.is_none());
+        assert!(results[1].error.as_deref().unwrap().contains("not found"));
+        assert!(results[2].task.is_some());
+        // The failed item created nothing.
+        assert_eq!(storage.get_all_tasks().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn delayed_tasks_wait_for_run_at_then_become_due() {
+        let (storage, agent_id) = storage_with_agent();
+        let mut request = DispatchRequest::new(&agent_id, "later", "p");
+        request.run_at = Some(Utc::now() + chrono::Duration::hours(1));
+        let task = dispatch(&storage, &request).unwrap();
+
+        assert!(matches!(
+            execute(&storage, &task.id),
+            Err(AppError::InvalidTransition { .. })
+        ));
+        assert!(storage.due_delayed_tasks(Utc::now()).unwrap().is_empty());
+        let due = storage
+            .due_delayed_tasks(Utc::now() + chrono::Duration::hours(2))
+            .unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].id, task.id);
+
+        // A run_at in the past is immediately executable.
+        let mut request = DispatchRequest::new(&agent_id, "now", "p");
+        request.run_at = Some(Utc::now() - chrono::Duration::minutes(1));
+        let task = dispatch(&storage, &request).unwrap();
+        assert_eq!(execute(&storage, &task.id).unwrap().status, TaskStatus::Completed);
+    }
+
+    #[test]
+    fn retry_clones_a_cancelled_task_and_links_it_to_the_original() {
+        let (storage, agent_id) = storage_with_agent();
+        let mut request = DispatchRequest::new(&agent_id, "flaky", "original prompt");
+        request.priority = Some(TaskPriority::High);
+        let original = dispatch(&storage, &request).unwrap();
+
+        // A queued task cannot be retried; only terminal failures can.
+        assert!(matches!(
+            retry(&storage, &original.id, None),
+            Err(AppError::InvalidTransition { .. })
+        ));
+
+        cancel(&storage, &original.id).unwrap();
+        let retried = retry(&storage, &original.id, Some("fixed prompt")).unwrap();
+        assert_eq!(retried.retry_of.as_deref(), Some(original.id.as_str()));
+        assert_eq!(retried.prompt, "fixed prompt");
+        assert_eq!(retried.priority, TaskPriority::High);
+        assert_eq!(retried.status, TaskStatus::Queued);
+        let events = storage.get_task_events(&original.id).unwrap();
+        assert!(events.iter().any(|e| e.kind == "retried_as"));
+    }
+
+    #[test]
+    fn failures_carry_a_classified_kind() {
+        let (storage, agent_id) = storage_with_agent();
+        let task = dispatch(&storage, &DispatchRequest::new(&agent_id, "t", "p")).unwrap();
+        cancel(&storage, &task.id).unwrap();
+        assert_eq!(
+            storage.get_task(&task.id).unwrap().failure_kind,
+            Some(FailureKind::Cancelled)
+        );
+        // Deliberate stops are permanent; infrastructure flakes are not.
+        assert!(!FailureKind::Cancelled.is_transient());
+        assert!(FailureKind::Provider.is_transient());
+        assert_eq!(
+            FailureKind::classify(&AppError::BudgetExceeded {
+                task_id: "t".into(),
+                spent: 1.0,
+                limit: 0.5,
+            }),
+            Some(FailureKind::Guardrail)
+        );
+    }
+
+    #[test]
+    fn guarded_dispatches_require_confirmation_and_record_the_rule() {
+        let storage = Storage::open_in_memory().unwrap();
+        let agent = Agent::new("prod", "mock");
+        storage.create_agent(&agent).unwrap();
+        DispatchConfirmationPolicy {
+            rules: vec![crate::policy::DispatchRule {
+                name: "destructive-instruction".into(),
+                prompt_contains: Some("delete".into()),
+                ..Default::default()
+            }],
+        }
+        .save(&storage)
+        .unwrap();
+
+        // Harmless dispatches pass untouched.
+        dispatch(&storage, &DispatchRequest::new(&agent.id, "list", "list files")).unwrap();
+
+        let mut request = DispatchRequest::new(&agent.id, "cleanup", "Delete stale rows");
+        assert!(matches!(
+            dispatch(&storage, &request),
+            Err(AppError::ConfirmationRequired { .. })
+        ));
+        request.confirmed = true;
+        let task = dispatch(&storage, &request).unwrap();
+        let events = storage.get_task_events(&task.id).unwrap();
+        let confirmed = events.iter().find(|e| e.kind == "dispatch_confirmed").unwrap();
+        assert_eq!(
+            confirmed.payload.as_ref().unwrap()["rule"],
+            "destructive-instruction"
+        );
+    }
+
+    #[test]
+    fn unattended_waits_escalate_once_and_can_pause_the_agent() {
+        let storage = Storage::open_in_memory().unwrap();
+        let agent = Agent::new("slow", "mock");
+        storage.create_agent(&agent).unwrap();
+        let task = dispatch(&storage, &DispatchRequest::new(&agent.id, "t", "p")).unwrap();
+        storage.claim_task(&task.id).unwrap();
+        storage.suspend_for_input(&task.id, "which env?").unwrap();
+
+        // No policy configured: the sweep is a no-op.
+        assert_eq!(escalate_unattended(&storage).unwrap(), 0);
+
+        EscalationPolicy {
+            unattended_minutes: Some(0),
+            auto_pause_agent: true,
+        }
+        .save(&storage)
+        .unwrap();
+        assert_eq!(escalate_unattended(&storage).unwrap(), 1);
+        // The same wait never escalates twice.
+        assert_eq!(escalate_unattended(&storage).unwrap(), 0);
+
+        let events = storage.get_task_events(&task.id).unwrap();
+        let escalation = events.iter().find(|e| e.kind == "escalation").unwrap();
+        assert_eq!(
+            escalation.payload.as_ref().unwrap()["reason"],
+            "unattended_input"
+        );
+        assert_eq!(
+            storage.get_agent(&agent.id).unwrap().status,
+            AgentStatus::Paused
+        );
+    }
+
+    #[test]
+    fn review_gated_results_wait_for_acceptance_or_revision() {
+        let storage = Storage::open_in_memory().unwrap();
+        let mut agent = crate::models::Agent::new("reviewed", "mock");
+        agent.review_required = true;
+        storage.create_agent(&agent).unwrap();
+
+        let task = dispatch(&storage, &DispatchRequest::new(&agent.id, "t", "p")).unwrap();
+        let done = execute(&storage, &task.id).unwrap();
+        assert_eq!(done.status, TaskStatus::NeedsReview);
+        assert!(done.result.is_some());
+
+        // Accepting promotes to Completed and keeps the result.
+        let accepted = accept_result(&storage, &task.id).unwrap();
+        assert_eq!(accepted.status, TaskStatus::Completed);
+        assert_eq!(accepted.result, done.result);
+        assert!(accept_result(&storage, &task.id).is_err());
+
+        // Requesting changes re-dispatches with the feedback appended.
+        let task = dispatch(&storage, &DispatchRequest::new(&agent.id, "t2", "p2")).unwrap();
+        execute(&storage, &task.id).unwrap();
+        let revision = request_changes(&storage, &task.id, "needs more detail").unwrap();
+        assert_eq!(revision.retry_of.as_deref(), Some(task.id.as_str()));
+        assert!(revision.prompt.contains("p2"));
+        assert!(revision.prompt.contains("needs more detail"));
+        assert_eq!(storage.get_task(&task.id).unwrap().status, TaskStatus::Cancelled);
+        let events = storage.get_task_events(&task.id).unwrap();
+        assert!(events.iter().any(|e| e.kind == "changes_requested"));
+    }
+
+    #[test]
+    fn dispatching_against_an_already_failed_parent_blocks_immediately() {
+        let (storage, agent_id) = storage_with_agent();
+        let parent = dispatch(&storage, &DispatchRequest::new(&agent_id, "parent", "p")).unwrap();
+        cancel(&storage, &parent.id).unwrap();
+
+        let mut request = DispatchRequest::new(&agent_id, "child", "p");
+        request.depends_on = vec![parent.id.clone()];
+        let child = dispatch(&storage, &request).unwrap();
+        assert_eq!(child.status, TaskStatus::Blocked);
+    }
+}
@@ -0,0 +1,197 @@
+//! Strictly opt-in, anonymized usage telemetry.
+//!
+//! Nothing leaves the machine unless the operator turns telemetry on,
+//! and the payload carries only feature usage counts, error rates and
+//! coarse workspace shape -- never prompt content, task titles, agent
+//! names or secret values. [`preview_payload`] renders the exact
+//! payload that would be sent, so the decision can be made with the
+//! data in front of you.
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::error::AppResult;
+use crate::storage::Storage;
+
+/// Settings key for the opt-in flag; absent means disabled.
+pub const ENABLED_SETTING: &str = "telemetry.enabled";
+/// Settings key for the submission endpoint; unset means nothing is
+/// sent even when telemetry is enabled.
+pub const ENDPOINT_SETTING: &str = "telemetry.endpoint";
+/// Settings key for the random install identifier. Minted on opt-in,
+/// discarded on opt-out, so re-enabling starts a fresh identity.
+pub const INSTALL_ID_SETTING: &str = "telemetry.install_id";
+
+/// Shown as the install id in previews taken before opting in, when no
+/// identifier has been minted yet.
+pub const UNASSIGNED_INSTALL_ID: &str = "(assigned on opt-in)";
+
+/// How far back feature usage is aggregated.
+const WINDOW_DAYS: i64 = 7;
+
+const SUBMIT_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+const SUBMIT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Aggregated calls and failures for one IPC command.
+#[derive(Debug, Clone, Serialize)]
+pub struct FeatureUsage {
+    pub calls: i64,
+    pub errors: i64,
+}
+
+/// Everything a telemetry submission contains. This struct is the
+/// contract: fields not on it are not sent.
+#[derive(Debug, Clone, Serialize)]
+pub struct TelemetryPayload {
+    pub install_id: String,
+    pub generated_at: DateTime<Utc>,
+    pub app_version: String,
+    pub window_days: i64,
+    /// Per-command call and error counts over the window.
+    pub feature_usage: BTreeMap<String, FeatureUsage>,
+    /// Task counts bucketed by status name.
+    pub task_counts: BTreeMap<String, u64>,
+    pub agent_count: u64,
+}
+
+pub fn is_enabled(storage: &Storage) -> bool {
+    matches!(
+        storage.get_setting(ENABLED_SETTING),
+        Ok(Some(ref raw)) if raw == "true"
+    )
+}
+
+/// Flip the opt-in flag. Enabling mints the install identifier;
+/// disabling discards it, so nothing persists that could tie a later
+/// opt-in back to this one.
+pub fn set_enabled(storage: &Storage, enabled: bool) -> AppResult<()> {
+    storage.set_setting(ENABLED_SETTING, if enabled { "true" } else { "false" })?;
+    if enabled {
+        if storage.get_setting(INSTALL_ID_SETTING)?.is_none() {
+            storage.set_setting(INSTALL_ID_SETTING, &Uuid::new_v4().to_string())?;
+        }
+    } else {
+        storage.delete_setting(INSTALL_ID_SETTING)?;
+    }
+    Ok(())
+}
+
+/// Build the payload exactly as a submission would, using the real
+/// install id when one exists and a placeholder before opt-in.
+pub fn preview_payload(storage: &Storage) -> AppResult<TelemetryPayload> {
+    let install_id = storage
+        .get_setting(INSTALL_ID_SETTING)?
+        .unwrap_or_else(|| UNASSIGNED_INSTALL_ID.to_string());
+    build_payload(storage, install_id)
+}
+
+fn build_payload(storage: &Storage, install_id: String) -> AppResult<TelemetryPayload> {
+    let since = Utc::now() - chrono::Duration::days(WINDOW_DAYS);
+    let feature_usage = storage
+        .command_usage_since(&since)?
+        .into_iter()
+        .map(|(command, calls, errors)| (command, FeatureUsage { calls, errors }))
+        .collect();
+
+    let mut task_counts: BTreeMap<String, u64> = BTreeMap::new();
+    for task in storage.get_all_tasks()? {
+        let key = serde_json::to_value(task.status)
+            .ok()
+            .and_then(|v| v.as_str().map(str::to_string))
+            .unwrap_or_else(|| "unknown".into());
+        *task_counts.entry(key).or_default() += 1;
+    }
+
+    Ok(TelemetryPayload {
+        install_id,
+        generated_at: Utc::now(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        window_days: WINDOW_DAYS,
+        feature_usage,
+        task_counts,
+        agent_count: storage.get_all_agents()?.len() as u64,
+    })
+}
+
+/// Send one payload if telemetry is enabled and an endpoint is
+/// configured. Returns whether anything was sent.
+pub fn submit(storage: &Storage) -> AppResult<bool> {
+    if !is_enabled(storage) {
+        return Ok(false);
+    }
+    let Some(endpoint) = storage.get_setting(ENDPOINT_SETTING)? else {
+        return Ok(false);
+    };
+    let Some(install_id) = storage.get_setting(INSTALL_ID_SETTING)? else {
+        return Ok(false);
+    };
+    let payload = build_payload(storage, install_id)?;
+    reqwest::blocking::Client::builder()
+        .timeout(SUBMIT_TIMEOUT)
+        .build()
+        .and_then(|client| client.post(&endpoint).json(&payload).send())
+        .and_then(|resp| resp.error_for_status())
+        .map_err(|err| crate::error::AppError::Provider(format!("telemetry: {err}")))?;
+    Ok(true)
+}
+
+/// Background loop submitting once per interval; idles cheaply while
+/// telemetry is disabled.
+pub fn submit_loop(storage: &Storage) {
+    loop {
+        match submit(storage) {
+            Ok(true) => tracing::debug!("telemetry payload submitted"),
+            Ok(false) => {}
+            Err(err) => tracing::warn!(%err, "telemetry submission failed"),
+        }
+        std::thread::sleep(SUBMIT_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Agent;
+    use crate::task_dispatch::{self, DispatchRequest};
+
+    #[test]
+    fn payloads_count_features_but_never_carry_content() {
+        let storage = Storage::open_in_memory().unwrap();
+        let agent = Agent::new("super-secret-agent-name", "mock");
+        storage.create_agent(&agent).unwrap();
+        task_dispatch::dispatch(
+            &storage,
+            &DispatchRequest::new(&agent.id, "Private title", "confidential prompt text"),
+        )
+        .unwrap();
+        storage.record_command_metric("dispatch", 12, true).unwrap();
+        storage.record_command_metric("dispatch", 80, false).unwrap();
+
+        assert!(!is_enabled(&storage));
+        let preview = preview_payload(&storage).unwrap();
+        assert_eq!(preview.install_id, UNASSIGNED_INSTALL_ID);
+        let usage = &preview.feature_usage["dispatch"];
+        assert_eq!((usage.calls, usage.errors), (2, 1));
+        assert_eq!(preview.task_counts.values().sum::<u64>(), 1);
+        assert_eq!(preview.agent_count, 1);
+
+        let raw = serde_json::to_string(&preview).unwrap();
+        for leaked in ["confidential", "Private title", "super-secret-agent-name", &agent.id] {
+            assert!(!raw.contains(leaked), "payload leaked {leaked:?}");
+        }
+
+        // Opt-in mints an id; opt-out discards it.
+        set_enabled(&storage, true).unwrap();
+        let minted = preview_payload(&storage).unwrap().install_id;
+        assert_ne!(minted, UNASSIGNED_INSTALL_ID);
+        set_enabled(&storage, false).unwrap();
+        assert_eq!(
+            preview_payload(&storage).unwrap().install_id,
+            UNASSIGNED_INSTALL_ID
+        );
+    }
+}
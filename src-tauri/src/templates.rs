@@ -0,0 +1,159 @@
+use crate::error::{AppError, AppResult};
+use crate::storage::Storage;
+
+/// One `{{namespace.key}}` reference found in a template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateRef {
+    Setting(String),
+    Secret(String),
+}
+
+/// Scan a template for `{{settings.*}}` and `{{secrets.*}}` references.
+/// Anything else inside braces is left alone (prompts legitimately
+/// contain braces).
+pub fn find_refs(text: &str) -> Vec<TemplateRef> {
+    let mut refs = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        rest = &rest[start + 2..];
+        let Some(end) = rest.find("}}") else { break };
+        let inner = rest[..end].trim();
+        if let Some(key) = inner.strip_prefix("settings.") {
+            refs.push(TemplateRef::Setting(key.to_string()));
+        } else if let Some(name) = inner.strip_prefix("secrets.") {
+            refs.push(TemplateRef::Secret(name.to_string()));
+        }
+        rest = &rest[end + 2..];
+    }
+    refs
+}
+
+/// Save-time validation: every referenced setting must exist and every
+/// referenced secret name must be registered (a value is not required
+/// until execution).
+pub fn validate(storage: &Storage, text: &str) -> AppResult<()> {
+    for reference in find_refs(text) {
+        match reference {
+            TemplateRef::Setting(key) => {
+                if storage.get_setting(&key)?.is_none() {
+                    return Err(AppError::InvalidArgument(format!(
+                        "template references unknown setting {key:?}"
+                    )));
+                }
+            }
+            TemplateRef::Secret(name) => {
+                if !storage.list_secret_names()?.contains(&name) {
+                    return Err(AppError::InvalidArgument(format!(
+                        "template references unregistered secret {name:?}"
+                    )));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Substitute dispatch-time parameters: `{{date}}` and `{{time}}`
+/// built-ins plus any caller-supplied key. `{{settings.*}}` and
+/// `{{secrets.*}}` references pass through untouched for the
+/// execution-time [`render`] pass.
+pub fn substitute_params(
+    text: &str,
+    params: &std::collections::BTreeMap<String, String>,
+) -> String {
+    let now = chrono::Utc::now();
+    let mut out = text.to_string();
+    out = out.replace("{{date}}", &now.format("%Y-%m-%d").to_string());
+    out = out.replace("{{time}}", &now.format("%H:%M").to_string());
+    for (key, value) in params {
+        out = out.replace(&format!("{{{{{key}}}}}"), value);
+    }
+    out
+}
+
+/// Execution-time rendering. Secret values are read through the audited
+/// path and only ever exist in the in-flight prompt; the stored task
+/// keeps the raw template.
+pub fn render(
+    storage: &Storage,
+    text: &str,
+    agent_id: Option<&str>,
+    task_id: Option<&str>,
+) -> AppResult<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start + 2..].find("}}") else { break };
+        let inner = rest[start + 2..start + 2 + end].trim();
+        let replacement = if let Some(key) = inner.strip_prefix("settings.") {
+            Some(storage.get_setting(key)?.ok_or_else(|| {
+                AppError::InvalidArgument(format!("template references unknown setting {key:?}"))
+            })?)
+        } else if let Some(name) = inner.strip_prefix("secrets.") {
+            Some(storage.get_secret_audited(name, agent_id, task_id)?)
+        } else {
+            None
+        };
+        match replacement {
+            Some(value) => {
+                out.push_str(&rest[..start]);
+                out.push_str(&value);
+            }
+            // Not a template ref: keep the braces verbatim.
+            None => out.push_str(&rest[..start + 2 + end + 2]),
+        }
+        rest = &rest[start + 2 + end + 2..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_and_validates_references() {
+        let storage = Storage::open_in_memory().unwrap();
+        storage.set_setting("company_name", "Acme").unwrap();
+
+        assert!(validate(&storage, "Hi {{settings.company_name}}").is_ok());
+        assert!(validate(&storage, "Hi {{settings.missing}}").is_err());
+        assert!(validate(&storage, "Use {{secrets.api_key}}").is_err());
+        storage.ensure_secret_name("api_key").unwrap();
+        assert!(validate(&storage, "Use {{secrets.api_key}}").is_ok());
+        // Non-template braces are not references.
+        assert!(validate(&storage, "JSON like {{\"a\": 1}} passes").is_ok());
+    }
+
+    #[test]
+    fn renders_settings_and_audited_secrets_at_execution_time() {
+        let storage = Storage::open_in_memory().unwrap();
+        storage.set_setting("company_name", "Acme").unwrap();
+        storage.set_secret("api_key", "sk-123").unwrap();
+
+        let rendered = render(
+            &storage,
+            "Report for {{settings.company_name}} using {{secrets.api_key}}",
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(rendered, "Report for Acme using sk-123");
+        // The secret read shows up in the audit trail.
+        assert_eq!(storage.get_secret_usage("api_key").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn parameter_substitution_fills_builtins_and_leaves_refs_alone() {
+        let mut params = std::collections::BTreeMap::new();
+        params.insert("env".to_string(), "staging".to_string());
+        let out = substitute_params(
+            "Check {{env}} on {{date}} with {{settings.company_name}}",
+            &params,
+        );
+        assert!(out.starts_with("Check staging on 2"));
+        assert!(out.ends_with("{{settings.company_name}}"));
+        assert!(!out.contains("{{date}}"));
+    }
+}
@@ -0,0 +1,290 @@
+//! Inbound webhooks: `POST /hooks/dispatch` on a localhost listener.
+//!
+//! External systems (CI, alerting) post a JSON event; a per-source
+//! mapping in settings names the HMAC secret and the target agent, so
+//! a verified event becomes a dispatched task. Sources are rate
+//! limited so a misfiring integration cannot flood the queue.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+
+use std::net::{TcpListener, TcpStream};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use crate::error::{AppError, AppResult};
+use crate::storage::Storage;
+use crate::task_dispatch::{self, DispatchRequest};
+
+/// Settings key holding the source map, a JSON object:
+/// `{ "<source>": { "secret_name": "...", "agent_id": "..." } }`.
+pub const MAPPINGS_SETTING: &str = "webhooks.mappings";
+pub const ENABLED_SETTING: &str = "webhooks.enabled";
+pub const PORT_SETTING: &str = "webhooks.port";
+const DEFAULT_PORT: u16 = 8787;
+/// Accepted deliveries per source per minute.
+const RATE_LIMIT_PER_MINUTE: usize = 10;
+const MAX_BODY_BYTES: usize = 256 * 1024;
+
+/// Where a source's events go and how they are authenticated.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SourceMapping {
+    /// Registered secret name holding the shared HMAC key. The value
+    /// is read through the audited path and never logged.
+    pub secret_name: String,
+    pub agent_id: String,
+}
+
+/// Sliding per-source delivery counter.
+#[derive(Default)]
+pub struct RateLimiter {
+    hits: Mutex<HashMap<String, Vec<Instant>>>,
+}
+
+impl RateLimiter {
+    /// Record a delivery attempt; false once the source is over its
+    /// per-minute budget.
+    pub fn allow(&self, source: &str) -> bool {
+        let mut hits = self.hits.lock().unwrap();
+        let window = hits.entry(source.to_string()).or_default();
+        let cutoff = Instant::now() - Duration::from_secs(60);
+        window.retain(|t| *t > cutoff);
+        if window.len() >= RATE_LIMIT_PER_MINUTE {
+            return false;
+        }
+        window.push(Instant::now());
+        true
+    }
+}
+
+/// HMAC-SHA256 over `body`, hex encoded. Implemented directly on the
+/// hash so the shared key never leaves this function.
+pub fn hmac_sha256_hex(key: &[u8], body: &[u8]) -> String {
+    const BLOCK: usize = 64;
+    let mut key_block = [0u8; BLOCK];
+    if key.len() > BLOCK {
+        key_block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        key_block[..key.len()].copy_from_slice(key);
+    }
+    let ipad: Vec<u8> = key_block.iter().map(|b| b ^ 0x36).collect();
+    let opad: Vec<u8> = key_block.iter().map(|b| b ^ 0x5c).collect();
+    let inner = Sha256::digest([ipad.as_slice(), body].concat());
+    format!("{:x}", Sha256::digest([opad.as_slice(), &inner].concat()))
+}
+
+/// Handle one verified-or-rejected delivery: look up the source
+/// mapping, check the signature and rate limit, then dispatch a task
+/// to the mapped agent. Returns the created task id.
+pub fn handle_delivery(
+    storage: &Storage,
+    limiter: &RateLimiter,
+    source: &str,
+    signature: &str,
+    body: &[u8],
+) -> AppResult<String> {
+    let mappings: HashMap<String, SourceMapping> = storage
+        .get_setting(MAPPINGS_SETTING)?
+        .and_then(|raw| serde_json::from_str(&raw).ok())
+        .unwrap_or_default();
+    let mapping = mappings
+        .get(source)
+        .ok_or_else(|| AppError::InvalidArgument(format!("unknown webhook source {source:?}")))?;
+
+    if !limiter.allow(source) {
+        return Err(AppError::InvalidArgument(format!(
+            "webhook source {source:?} is over its rate limit"
+        )));
+    }
+
+    let key = storage.get_secret_audited(&mapping.secret_name, None, None)?;
+    let expected = hmac_sha256_hex(key.as_bytes(), body);
+    let presented = signature.trim().trim_start_matches("sha256=");
+    if !constant_time_eq(presented.as_bytes(), expected.as_bytes()) {
+        return Err(AppError::InvalidArgument(format!(
+            "webhook signature mismatch for source {source:?}"
+        )));
+    }
+
+    let event: serde_json::Value = serde_json::from_slice(body)
+        .map_err(|err| AppError::InvalidArgument(format!("webhook body is not JSON: {err}")))?;
+    let title = event["title"]
+        .as_str()
+        .unwrap_or("External event")
+        .to_string();
+    let prompt = event["prompt"]
+        .as_str()
+        .map(str::to_string)
+        .unwrap_or_else(|| event.to_string());
+
+    let request = DispatchRequest::new(&mapping.agent_id, format!("[{source}] {title}"), prompt);
+    let task = task_dispatch::dispatch(storage, &request)?;
+    storage.append_event(
+        &task.id,
+        "webhook_received",
+        Some(&json!({ "source": source })),
+    )?;
+    Ok(task.id)
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Listener loop bound to localhost; spawned at startup when
+/// `webhooks.enabled` is set. One thread is plenty for a local inbox.
+pub fn serve_loop(storage: &Storage, workers: &crate::worker_pool::WorkerPool) {
+    let port = storage
+        .get_setting(PORT_SETTING)
+        .ok()
+        .flatten()
+        .and_then(|raw| raw.parse().ok())
+        .unwrap_or(DEFAULT_PORT);
+    let listener = match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => listener,
+        Err(err) => {
+            tracing::error!(port, %err, "webhook listener failed to bind");
+            return;
+        }
+    };
+    let limiter = RateLimiter::default();
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else { continue };
+        if let Err(err) = handle_connection(storage, workers, &limiter, stream) {
+            tracing::debug!(%err, "webhook connection failed");
+        }
+    }
+}
+
+fn handle_connection(
+    storage: &Storage,
+    workers: &crate::worker_pool::WorkerPool,
+    limiter: &RateLimiter,
+    stream: TcpStream,
+) -> AppResult<()> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+
+    let mut source = String::new();
+    let mut signature = String::new();
+    let mut content_length = 0usize;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            match name.to_ascii_lowercase().as_str() {
+                "x-webhook-source" => source = value.trim().to_string(),
+                "x-webhook-signature" => signature = value.trim().to_string(),
+                "content-length" => content_length = value.trim().parse().unwrap_or(0),
+                _ => {}
+            }
+        }
+    }
+
+    if !request_line.starts_with("POST /hooks/dispatch") {
+        return respond(&mut reader.into_inner(), 404, "not found");
+    }
+    if content_length == 0 || content_length > MAX_BODY_BYTES {
+        return respond(&mut reader.into_inner(), 400, "bad content length");
+    }
+    // The body must come through the same buffered reader that parsed
+    // the headers, or bytes it read ahead would be lost.
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body)?;
+    let mut stream = reader.into_inner();
+
+    match handle_delivery(storage, limiter, &source, &signature, &body) {
+        Ok(task_id) => {
+            workers.enqueue(&task_id);
+            respond(&mut stream, 200, &task_id)
+        }
+        Err(err) => respond(&mut stream, 403, &err.to_string()),
+    }
+}
+
+fn respond(stream: &mut TcpStream, status: u16, body: &str) -> AppResult<()> {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        _ => "Not Found",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status} {reason}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Agent;
+
+    fn storage_with_mapping() -> (Storage, String) {
+        let storage = Storage::open_in_memory().unwrap();
+        let agent = Agent::new("ci-watcher", "mock");
+        storage.create_agent(&agent).unwrap();
+        storage.set_secret("ci_webhook_key", "shared-key").unwrap();
+        storage
+            .set_setting(
+                MAPPINGS_SETTING,
+                &serde_json::json!({
+                    "ci": { "secret_name": "ci_webhook_key", "agent_id": agent.id }
+                })
+                .to_string(),
+            )
+            .unwrap();
+        (storage, agent.id)
+    }
+
+    #[test]
+    fn verified_deliveries_dispatch_to_the_mapped_agent() {
+        let (storage, agent_id) = storage_with_mapping();
+        let limiter = RateLimiter::default();
+        let body = br#"{"title": "build failed", "prompt": "investigate build 42"}"#;
+        let signature = hmac_sha256_hex(b"shared-key", body);
+
+        let task_id =
+            handle_delivery(&storage, &limiter, "ci", &signature, body).unwrap();
+        let task = storage.get_task(&task_id).unwrap();
+        assert_eq!(task.agent_id, agent_id);
+        assert_eq!(task.title, "[ci] build failed");
+        let events = storage.get_task_events(&task_id).unwrap();
+        assert!(events.iter().any(|e| e.kind == "webhook_received"));
+    }
+
+    #[test]
+    fn bad_signatures_and_unknown_sources_are_rejected() {
+        let (storage, _) = storage_with_mapping();
+        let limiter = RateLimiter::default();
+        let body = br#"{"title": "x"}"#;
+        assert!(handle_delivery(&storage, &limiter, "ci", "sha256=wrong", body).is_err());
+        let good = hmac_sha256_hex(b"shared-key", body);
+        assert!(handle_delivery(&storage, &limiter, "unknown", &good, body).is_err());
+    }
+
+    #[test]
+    fn sources_are_rate_limited_per_minute() {
+        let limiter = RateLimiter::default();
+        for _ in 0..RATE_LIMIT_PER_MINUTE {
+            assert!(limiter.allow("ci"));
+        }
+        assert!(!limiter.allow("ci"));
+        // Other sources keep their own budget.
+        assert!(limiter.allow("alerts"));
+    }
+}
@@ -0,0 +1,352 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use serde_json::Value;
+
+use crate::error::{AppError, AppResult};
+
+/// Event name update pushes are emitted under.
+pub const UPDATE_EVENT: &str = "workspace://update";
+
+/// Registry of open windows and the update topics each subscribes to.
+///
+/// Mutating commands broadcast a topic-scoped payload to every other
+/// subscribed window, so multiple windows stay in sync without polling.
+#[derive(Default)]
+pub struct WindowRegistry {
+    sessions: Mutex<HashMap<String, HashSet<String>>>,
+    /// Monotonic revision per entity key, used to arbitrate conflicting
+    /// edits coming from different windows.
+    revisions: Mutex<HashMap<String, u64>>,
+}
+
+impl WindowRegistry {
+    /// Register (or re-register) a window with its topic subscriptions.
+    pub fn subscribe(&self, label: &str, topics: Vec<String>) {
+        self.sessions
+            .lock()
+            .unwrap()
+            .insert(label.to_string(), topics.into_iter().collect());
+    }
+
+    pub fn unregister(&self, label: &str) {
+        self.sessions.lock().unwrap().remove(label);
+    }
+
+    /// Labels of windows subscribed to `topic`, excluding the source
+    /// window so it does not receive an echo of its own mutation.
+    pub fn targets_for(&self, topic: &str, exclude: Option<&str>) -> Vec<String> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(label, topics)| {
+                Some(label.as_str()) != exclude && topics.contains(topic)
+            })
+            .map(|(label, _)| label.clone())
+            .collect()
+    }
+
+    /// Compare-and-bump revision arbitration.
+    ///
+    /// A window editing entity `key` passes the revision it last saw;
+    /// if another window has bumped it since, the edit is rejected with
+    /// `EditConflict` and the frontend refetches. Passing `None` skips
+    /// the check (used for create-style mutations).
+    pub fn check_and_bump(&self, key: &str, expected: Option<u64>) -> AppResult<u64> {
+        let mut revisions = self.revisions.lock().unwrap();
+        let current = revisions.entry(key.to_string()).or_insert(0);
+        if let Some(expected) = expected {
+            if expected != *current {
+                return Err(AppError::EditConflict {
+                    entity: key.to_string(),
+                    expected,
+                    current: *current,
+                });
+            }
+        }
+        *current += 1;
+        Ok(*current)
+    }
+}
+
+/// Emit a topic-scoped update to every other subscribed window.
+pub fn broadcast(
+    window: &tauri::Window,
+    registry: &WindowRegistry,
+    topic: &str,
+    payload: Value,
+) {
+    emit_to_targets(window, registry.targets_for(topic, Some(window.label())), topic, payload);
+}
+
+fn emit_to_targets(
+    window: &tauri::Window,
+    targets: Vec<String>,
+    topic: &str,
+    payload: Value,
+) {
+    use tauri::{Emitter, Manager};
+    for label in targets {
+        if let Err(err) = window.app_handle().emit_to(
+            &label,
+            UPDATE_EVENT,
+            serde_json::json!({ "topic": topic, "payload": payload }),
+        ) {
+            tracing::debug!(%label, topic, %err, "failed to push window update");
+        }
+    }
+}
+
+/// Prefix of the live task event bridge's Tauri event names.
+pub const TASK_EVENT_PREFIX: &str = "task://";
+/// How often the bridge polls storage for newly appended events.
+const BRIDGE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// Channel a task event kind is relayed under: terminal outcomes and
+/// warnings keep their own names so the UI can subscribe narrowly;
+/// everything else lands on `progress`.
+fn bridge_channel(kind: &str) -> &str {
+    match kind {
+        "completed" | "failed" | "cancelled" | "blocked" | "warning" => kind,
+        _ => "progress",
+    }
+}
+
+/// Relay newly appended task events to the webview as Tauri events
+/// (`task://progress`, `task://completed`, ...) so the UI updates live
+/// without polling the event log. Runs forever on a background thread;
+/// the cursor starts at the current head so restarts do not replay
+/// history.
+pub fn task_event_bridge(
+    app: &tauri::AppHandle,
+    storage: &crate::storage::Storage,
+    subscriptions: &SubscriptionRegistry,
+) {
+    use tauri::Emitter;
+    let mut cursor = storage.latest_event_id().unwrap_or(0);
+    loop {
+        match storage.get_events_after(cursor) {
+            Ok(events) => {
+                for event in events {
+                    cursor = cursor.max(event.id);
+                    let name = format!("{TASK_EVENT_PREFIX}{}", bridge_channel(&event.kind));
+                    if let Err(err) = app.emit(&name, &event) {
+                        tracing::debug!(%err, kind = %event.kind, "failed to relay task event");
+                    }
+                    // Live-tail subscriptions get a narrower push: only
+                    // the events their filter selects, on their own
+                    // channel.
+                    for id in subscriptions.matching(&event) {
+                        let name = format!("{SUBSCRIPTION_EVENT_PREFIX}{id}");
+                        if let Err(err) = app.emit(&name, &event) {
+                            tracing::debug!(%err, %id, "failed to push subscribed event");
+                        }
+                    }
+                }
+            }
+            Err(err) => tracing::warn!(%err, "task event bridge poll failed"),
+        }
+        std::thread::sleep(BRIDGE_POLL_INTERVAL);
+    }
+}
+
+/// Prefix of per-subscription live-tail event names: a subscription
+/// with id `abc` receives its events as `subscription://abc`.
+pub const SUBSCRIPTION_EVENT_PREFIX: &str = "subscription://";
+
+/// Filter for a live-tail subscription. Unset fields match everything,
+/// so an empty filter tails the whole event stream.
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+pub struct EventFilter {
+    #[serde(default)]
+    pub task_id: Option<String>,
+    /// Event kinds to include; empty means all kinds.
+    #[serde(default)]
+    pub kinds: Vec<String>,
+}
+
+impl EventFilter {
+    fn matches(&self, event: &crate::models::TaskEvent) -> bool {
+        if let Some(task_id) = &self.task_id {
+            if event.task_id != *task_id {
+                return false;
+            }
+        }
+        self.kinds.is_empty() || self.kinds.iter().any(|k| k == &event.kind)
+    }
+}
+
+/// Active live-tail subscriptions, keyed by the id handed back from
+/// `subscribe_events`. The bridge pushes each new event to every
+/// subscription whose filter matches.
+#[derive(Default)]
+pub struct SubscriptionRegistry {
+    subscriptions: Mutex<HashMap<String, EventFilter>>,
+}
+
+impl SubscriptionRegistry {
+    /// Register a filter and return the subscription id its events will
+    /// be delivered under.
+    pub fn subscribe(&self, filter: EventFilter) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .insert(id.clone(), filter);
+        id
+    }
+
+    pub fn unsubscribe(&self, id: &str) -> AppResult<()> {
+        match self.subscriptions.lock().unwrap().remove(id) {
+            Some(_) => Ok(()),
+            None => Err(AppError::not_found("subscription", id)),
+        }
+    }
+
+    /// Ids of subscriptions whose filter matches `event`.
+    fn matching(&self, event: &crate::models::TaskEvent) -> Vec<String> {
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, filter)| filter.matches(event))
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+}
+
+/// Topic optimistic-update reconciliations are broadcast under.
+pub const RECONCILE_TOPIC: &str = "reconcile";
+
+/// Envelope returned by write commands that support optimistic updates:
+/// the committed value plus the token the frontend keyed its optimistic
+/// change on.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Mutation<T> {
+    pub mutation_token: String,
+    pub value: T,
+}
+
+/// Run a storage write on behalf of an optimistic frontend mutation.
+///
+/// The frontend applies the change locally under `mutation_token` (its
+/// own uuid; omitted tokens are minted here for callers that skipped
+/// the optimistic step). Once the write commits, every subscribed
+/// window -- including the source -- receives a `reconcile` broadcast
+/// confirming the token with the authoritative value; on failure the
+/// broadcast carries `rolled_back` and the error so the window reverts
+/// without a refetch.
+pub fn reconcile<T: serde::Serialize>(
+    window: &tauri::Window,
+    registry: &WindowRegistry,
+    mutation_token: Option<String>,
+    write: impl FnOnce() -> AppResult<T>,
+) -> AppResult<Mutation<T>> {
+    let mutation_token =
+        mutation_token.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    match write() {
+        Ok(value) => {
+            emit_to_targets(
+                window,
+                registry.targets_for(RECONCILE_TOPIC, None),
+                RECONCILE_TOPIC,
+                serde_json::json!({
+                    "mutation_token": mutation_token,
+                    "status": "confirmed",
+                    "value": serde_json::to_value(&value).unwrap_or(Value::Null),
+                }),
+            );
+            Ok(Mutation { mutation_token, value })
+        }
+        Err(err) => {
+            emit_to_targets(
+                window,
+                registry.targets_for(RECONCILE_TOPIC, None),
+                RECONCILE_TOPIC,
+                serde_json::json!({
+                    "mutation_token": mutation_token,
+                    "status": "rolled_back",
+                    "error": err.to_string(),
+                }),
+            );
+            Err(err)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn targets_are_scoped_by_subscription_and_exclude_the_source() {
+        let registry = WindowRegistry::default();
+        registry.subscribe("main", vec!["tasks".into(), "agents".into()]);
+        registry.subscribe("board", vec!["tasks".into()]);
+        registry.subscribe("metrics", vec!["metrics".into()]);
+
+        let mut targets = registry.targets_for("tasks", Some("main"));
+        targets.sort();
+        assert_eq!(targets, vec!["board"]);
+
+        registry.unregister("board");
+        assert!(registry.targets_for("tasks", Some("main")).is_empty());
+    }
+
+    #[test]
+    fn bridge_channels_keep_terminal_kinds_and_fold_the_rest() {
+        assert_eq!(bridge_channel("completed"), "completed");
+        assert_eq!(bridge_channel("warning"), "warning");
+        assert_eq!(bridge_channel("token_chunk"), "progress");
+        assert_eq!(bridge_channel("thought_log"), "progress");
+    }
+
+    #[test]
+    fn subscription_filters_scope_the_tail() {
+        use crate::models::TaskEvent;
+
+        let event = |task_id: &str, kind: &str| TaskEvent {
+            id: 1,
+            task_id: task_id.into(),
+            kind: kind.into(),
+            severity: Default::default(),
+            payload: None,
+            typed: Default::default(),
+            created_at: chrono::Utc::now(),
+        };
+
+        let registry = SubscriptionRegistry::default();
+        let all = registry.subscribe(EventFilter::default());
+        let narrow = registry.subscribe(EventFilter {
+            task_id: Some("t1".into()),
+            kinds: vec!["output".into()],
+        });
+
+        let mut hits = registry.matching(&event("t1", "output"));
+        hits.sort();
+        let mut expected = vec![all.clone(), narrow.clone()];
+        expected.sort();
+        assert_eq!(hits, expected);
+
+        assert_eq!(registry.matching(&event("t1", "progress")), vec![all.clone()]);
+        assert_eq!(registry.matching(&event("t2", "output")), vec![all.clone()]);
+
+        registry.unsubscribe(&narrow).unwrap();
+        assert!(registry.unsubscribe(&narrow).is_err());
+    }
+
+    #[test]
+    fn stale_edits_are_rejected() {
+        let registry = WindowRegistry::default();
+        let rev = registry.check_and_bump("task:1", None).unwrap();
+        assert_eq!(rev, 1);
+        // Second window edits based on the same revision it saw (0).
+        assert!(matches!(
+            registry.check_and_bump("task:1", Some(0)),
+            Err(AppError::EditConflict { current: 1, .. })
+        ));
+        // Editing from the latest revision succeeds.
+        assert_eq!(registry.check_and_bump("task:1", Some(1)).unwrap(), 2);
+    }
+}
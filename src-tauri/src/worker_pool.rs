@@ -0,0 +1,215 @@
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::error::AppError;
+use crate::storage::Storage;
+
+/// Settings key for the global worker count; workers are sized once at
+/// startup.
+pub const CONCURRENCY_SETTING: &str = "dispatch.global_concurrency";
+const DEFAULT_WORKERS: usize = 4;
+/// How long a worker backs off before requeueing a busy agent's task.
+const BUSY_RETRY_DELAY: Duration = Duration::from_millis(250);
+/// Give up requeueing after this many busy retries (~1 minute).
+const MAX_BUSY_RETRIES: u32 = 240;
+
+struct Job {
+    task_id: String,
+    busy_retries: u32,
+}
+
+/// Fixed pool of executor threads draining a shared queue, so IPC
+/// dispatch calls return immediately and multiple agents run
+/// concurrently. Per-agent concurrency stays at one: claim_task
+/// refuses a second task for a Running agent, and busy jobs are
+/// requeued with a backoff.
+pub struct WorkerPool {
+    sender: Sender<Job>,
+}
+
+impl WorkerPool {
+    pub fn new(storage: Arc<Storage>) -> Self {
+        let workers = storage
+            .get_setting(CONCURRENCY_SETTING)
+            .ok()
+            .flatten()
+            .and_then(|raw| raw.parse().ok())
+            .filter(|n| *n > 0)
+            .unwrap_or(DEFAULT_WORKERS);
+
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..workers {
+            let storage = Arc::clone(&storage);
+            let receiver = Arc::clone(&receiver);
+            let requeue = sender.clone();
+            std::thread::spawn(move || loop {
+                let job = match receiver.lock().unwrap().recv() {
+                    Ok(job) => job,
+                    Err(_) => return,
+                };
+                match crate::task_dispatch::execute(&storage, &job.task_id) {
+                    // A completed parent releases its dependents: any
+                    // task whose whole dependency set is now done gets
+                    // queued without user action.
+                    Ok(task) if task.status == crate::models::TaskStatus::Completed => {
+                        match storage.ready_dependents(&task.id) {
+                            Ok(ready) => {
+                                for dependent in ready {
+                                    let _ = requeue.send(Job {
+                                        task_id: dependent.id,
+                                        busy_retries: 0,
+                                    });
+                                }
+                            }
+                            Err(err) => {
+                                tracing::warn!(task = %job.task_id, %err, "dependent lookup failed");
+                            }
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(AppError::AgentBusy(_)) if job.busy_retries < MAX_BUSY_RETRIES => {
+                        std::thread::sleep(BUSY_RETRY_DELAY);
+                        let _ = requeue.send(Job {
+                            task_id: job.task_id,
+                            busy_retries: job.busy_retries + 1,
+                        });
+                    }
+                    Err(err) => {
+                        tracing::warn!(task = %job.task_id, %err, "worker execution failed");
+                    }
+                }
+            });
+        }
+        Self { sender }
+    }
+
+    /// Rebuild the in-memory queue from storage after a restart:
+    /// orphaned Running tasks are requeued, then every runnable queued
+    /// task is enqueued most-urgent-first in dispatch order. Tasks
+    /// still waiting on dependencies or a future `run_at` are left for
+    /// their usual triggers. Returns how many tasks were enqueued.
+    pub fn restore_queue(&self, storage: &Storage) -> crate::error::AppResult<usize> {
+        let orphaned = storage.requeue_orphaned_running()?;
+        let mut restored = 0;
+        for task in storage.queued_tasks_in_order(chrono::Utc::now())? {
+            let mut parents_done = true;
+            for parent_id in &task.depends_on {
+                if storage.get_task(parent_id)?.status
+                    != crate::models::TaskStatus::Completed
+                {
+                    parents_done = false;
+                    break;
+                }
+            }
+            if parents_done {
+                self.enqueue(&task.id);
+                restored += 1;
+            }
+        }
+        if restored > 0 || !orphaned.is_empty() {
+            storage.add_notification(
+                "Task queue restored",
+                &format!(
+                    "Requeued {} interrupted and {} pending task(s) after restart",
+                    orphaned.len(),
+                    restored
+                ),
+            )?;
+        }
+        Ok(restored)
+    }
+
+    /// Queue a task for execution; returns immediately.
+    pub fn enqueue(&self, task_id: &str) {
+        let _ = self.sender.send(Job {
+            task_id: task_id.to_string(),
+            busy_retries: 0,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Agent, TaskStatus};
+    use crate::task_dispatch::{self, DispatchRequest};
+    use std::time::Instant;
+
+    #[test]
+    fn runs_tasks_for_multiple_agents_concurrently() {
+        let storage = Arc::new(Storage::open_in_memory().unwrap());
+        let pool = WorkerPool::new(Arc::clone(&storage));
+
+        let mut task_ids = Vec::new();
+        for i in 0..3 {
+            let agent = Agent::new(format!("worker{i}"), "mock");
+            storage.create_agent(&agent).unwrap();
+            for j in 0..2 {
+                let task = task_dispatch::dispatch(
+                    &storage,
+                    &DispatchRequest::new(&agent.id, format!("t{i}-{j}"), "p"),
+                )
+                .unwrap();
+                pool.enqueue(&task.id);
+                task_ids.push(task.id);
+            }
+        }
+
+        // Busy agents requeue, so everything settles terminal eventually.
+        let deadline = Instant::now() + Duration::from_secs(30);
+        loop {
+            let done = task_ids
+                .iter()
+                .all(|id| storage.get_task(id).unwrap().status == TaskStatus::Completed);
+            if done {
+                break;
+            }
+            assert!(Instant::now() < deadline, "worker pool did not drain the queue");
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn restore_queue_requeues_interrupted_work_in_priority_order() {
+        let storage = Arc::new(Storage::open_in_memory().unwrap());
+        let agent = Agent::new("restart", "mock");
+        storage.create_agent(&agent).unwrap();
+
+        // A task claimed by a previous process that never finished.
+        let interrupted =
+            task_dispatch::dispatch(&storage, &DispatchRequest::new(&agent.id, "a", "p"))
+                .unwrap();
+        storage.claim_task(&interrupted.id).unwrap();
+
+        let mut urgent = DispatchRequest::new(&agent.id, "b", "p");
+        urgent.priority = Some(crate::models::TaskPriority::Urgent);
+        let urgent = task_dispatch::dispatch(&storage, &urgent).unwrap();
+
+        // The urgent task outranks the earlier normal one on restore.
+        storage.requeue_orphaned_running().unwrap();
+        let order = storage.queued_tasks_in_order(chrono::Utc::now()).unwrap();
+        assert_eq!(
+            order.iter().map(|t| t.id.as_str()).collect::<Vec<_>>(),
+            vec![urgent.id.as_str(), interrupted.id.as_str()]
+        );
+        assert_eq!(storage.get_agent(&agent.id).unwrap().status, crate::models::AgentStatus::Idle);
+        let events = storage.get_task_events(&interrupted.id).unwrap();
+        assert!(events.iter().any(|e| e.kind == "requeued_after_restart"));
+
+        let pool = WorkerPool::new(Arc::clone(&storage));
+        assert_eq!(pool.restore_queue(&storage).unwrap(), 2);
+        let deadline = Instant::now() + Duration::from_secs(30);
+        loop {
+            let done = [&interrupted.id, &urgent.id]
+                .iter()
+                .all(|id| storage.get_task(id).unwrap().status == TaskStatus::Completed);
+            if done {
+                break;
+            }
+            assert!(Instant::now() < deadline, "restored queue did not drain");
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+}